@@ -7,6 +7,7 @@
 mod acpi;
 mod framebuffer;
 mod memory_map;
+mod smp;
 mod stack;
 
 use microdragon_interface::ModuleInterface;
@@ -16,10 +17,13 @@ microdragon_interface::macros::include_runner!();
 /// Entrypoint for the kernel.
 /// - Creates the module interface.
 /// - Runs the module runner.
+/// - Starts the application processors, which run the per-CPU module runner.
 /// - Starts the service stack.
+/// - Shuts the machine down, if built for automated runs.
 fn kernel_main() -> ! {
     let interface = ModuleInterface {
-        stack_info: stack::get_stack_info(),
+        stack_info: stack::get_stack_info(0),
+        smp_info: smp::get_smp_info(),
         rsdp_address: acpi::get_rsdp_address(),
         framebuffer_info: framebuffer::get_framebuffer_info(),
         memory_map_info: memory_map::get_memory_map_info(),
@@ -27,15 +31,24 @@ fn kernel_main() -> ! {
     };
 
     run_modules(&interface);
+    smp::start_application_processors(&interface);
+
+    if option_env!("MICRODRAGON_EXIT").is_some() {
+        common::power::shutdown();
+    }
 
     loop {
         core::hint::spin_loop();
     }
 }
 
+/// Halts the machine, or exits the VM with a failure if built for automated runs.
 #[panic_handler]
 fn panic_handler(_: &core::panic::PanicInfo) -> ! {
-    loop {
-        core::hint::spin_loop();
+    if option_env!("MICRODRAGON_EXIT").is_some() {
+        common::power::exit_qemu(common::power::ExitCode::Failure);
+        common::power::reboot();
     }
+
+    common::power::halt()
 }
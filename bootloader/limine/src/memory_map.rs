@@ -3,20 +3,26 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 use limine::paging::Mode;
-use limine::request::{MemoryMapRequest, PagingModeRequest};
+use limine::request::{HhdmRequest, MemoryMapRequest, PagingModeRequest};
 use microdragon_interface::memory::{MemoryInfo, MemoryMapInfo, MemoryMapType};
 
 static MEMORY_MAP_REQUEST: MemoryMapRequest = MemoryMapRequest::new();
 
+static HHDM_REQUEST: HhdmRequest = HhdmRequest::new();
+
 pub fn get_memory_map_info() -> MemoryMapInfo {
     let response = MEMORY_MAP_REQUEST
         .get_response()
         .expect("No memory map provided by the bootloader");
+    let hhdm = HHDM_REQUEST
+        .get_response()
+        .expect("No higher half direct mapping provided by the bootloader");
 
     MemoryMapInfo {
         memory_map: response.entries().as_ptr() as u64,
         memory_map_count: response.entries().len(),
         memory_map_type: MemoryMapType::Limine,
+        physical_memory_offset: hhdm.offset(),
     }
 }
 
@@ -0,0 +1,188 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::stack;
+use core::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+use limine::request::SmpRequest;
+use limine::response::SmpResponse;
+use limine::smp::Cpu;
+use microdragon_interface::smp::{CpuInfo, SmpInfo, MAX_CPUS};
+use microdragon_interface::stack::StackInfo;
+use microdragon_interface::ModuleInterface;
+
+static SMP_REQUEST: SmpRequest = SmpRequest::new();
+
+const EMPTY_CPU: CpuInfo = CpuInfo {
+    index: 0,
+    hardware_id: 0,
+    stack_info: StackInfo {
+        primary_stack: 0,
+        secondary_stack: 0,
+    },
+};
+
+/// The processors started by the kernel, indexed by processor index.
+static mut CPUS: [CpuInfo; MAX_CPUS] = [EMPTY_CPU; MAX_CPUS];
+
+/// The module interface, shared with the application processors.
+static INTERFACE: AtomicPtr<ModuleInterface> = AtomicPtr::new(core::ptr::null_mut());
+
+/// The number of application processors which ran their constructors.
+static ONLINE: AtomicU64 = AtomicU64::new(0);
+
+/// Creates the [`SmpInfo`] struct for the module interface.
+/// Processors beyond [`MAX_CPUS`] are left parked by the bootloader.
+pub fn get_smp_info() -> SmpInfo {
+    let response = SMP_REQUEST.get_response();
+    let bsp_id = response.map(bsp_hardware_id).unwrap_or_default();
+    let application_processors = response
+        .iter()
+        .flat_map(|x| x.cpus())
+        .filter(|x| hardware_id(x) != bsp_id)
+        .take(MAX_CPUS - 1);
+
+    unsafe {
+        CPUS[0] = CpuInfo {
+            index: 0,
+            hardware_id: bsp_id,
+            stack_info: stack::get_stack_info(0),
+        };
+
+        let mut count = 1;
+        for cpu in application_processors {
+            CPUS[count] = CpuInfo {
+                index: count as u64,
+                hardware_id: hardware_id(cpu),
+                stack_info: stack::get_stack_info(count),
+            };
+            count += 1;
+        }
+
+        SmpInfo {
+            cpu_count: count as u64,
+            cpus: &raw const CPUS as u64,
+        }
+    }
+}
+
+/// Starts all application processors, which run the per-CPU constructors, and waits until they're done.
+/// The `interface` has to live as long as the kernel, so this may only be called from [`crate::kernel_main`].
+pub fn start_application_processors(interface: &ModuleInterface) {
+    let Some(response) = SMP_REQUEST.get_response() else {
+        return;
+    };
+
+    INTERFACE.store(interface as *const _ as *mut _, Ordering::Release);
+
+    let started = interface.smp_info.cpus()[1..]
+        .iter()
+        .filter_map(|x| {
+            response
+                .cpus()
+                .iter()
+                .find(|y| hardware_id(y) == x.hardware_id)
+        })
+        .map(|x| x.goto_address.write(application_processor_entry))
+        .count() as u64;
+
+    while ONLINE.load(Ordering::Acquire) < started {
+        core::hint::spin_loop();
+    }
+}
+
+/// Entrypoint of the application processors, which switches to our own stack and calls [`application_processor_main`].
+unsafe extern "C" fn application_processor_entry(cpu: &Cpu) -> ! {
+    let interface = &*INTERFACE.load(Ordering::Acquire);
+    let index = interface
+        .smp_info
+        .cpus()
+        .iter()
+        .position(|x| x.hardware_id == hardware_id(cpu))
+        .expect("Started processor not in the processor list");
+
+    switch_stack(index);
+}
+
+/// Switches to the primary stack of the processor with the index and calls [`application_processor_main`].
+#[cfg(target_arch = "x86_64")]
+unsafe fn switch_stack(index: usize) -> ! {
+    core::arch::asm!(
+        "MOV RSP, {}",
+        "XOR EBP, EBP",
+        "CALL {}",
+        in(reg) stack::get_primary_stack_top(index),
+        sym application_processor_main,
+        in("rdi") index,
+        options(noreturn)
+    );
+}
+
+#[cfg(target_arch = "aarch64")]
+unsafe fn switch_stack(index: usize) -> ! {
+    core::arch::asm!(
+        "MOV SP, {}",
+        "MOV X29, XZR",
+        "BL {}",
+        in(reg) stack::get_primary_stack_top(index),
+        sym application_processor_main,
+        in("x0") index,
+        options(noreturn)
+    );
+}
+
+#[cfg(target_arch = "riscv64")]
+unsafe fn switch_stack(index: usize) -> ! {
+    core::arch::asm!(
+        "mv sp, {}",
+        "mv s0, zero",
+        "call {}",
+        in(reg) stack::get_primary_stack_top(index),
+        sym application_processor_main,
+        in("a0") index,
+        options(noreturn)
+    );
+}
+
+/// Returns the id the hardware assigned to the processor, its local APIC id on x86_64.
+#[cfg(target_arch = "x86_64")]
+fn hardware_id(cpu: &Cpu) -> u64 {
+    cpu.lapic_id as u64
+}
+
+#[cfg(target_arch = "aarch64")]
+fn hardware_id(cpu: &Cpu) -> u64 {
+    cpu.mpidr
+}
+
+#[cfg(target_arch = "riscv64")]
+fn hardware_id(cpu: &Cpu) -> u64 {
+    cpu.hartid
+}
+
+/// Returns the hardware id of the bootstrap processor.
+#[cfg(target_arch = "x86_64")]
+fn bsp_hardware_id(response: &SmpResponse) -> u64 {
+    response.bsp_lapic_id() as u64
+}
+
+#[cfg(target_arch = "aarch64")]
+fn bsp_hardware_id(response: &SmpResponse) -> u64 {
+    response.bsp_mpidr()
+}
+
+#[cfg(target_arch = "riscv64")]
+fn bsp_hardware_id(response: &SmpResponse) -> u64 {
+    response.bsp_hartid()
+}
+
+/// Runs the per-CPU constructors on the application processor with the index.
+extern "C" fn application_processor_main(index: usize) -> ! {
+    let interface = unsafe { &*INTERFACE.load(Ordering::Acquire) };
+    crate::run_cpu_modules(interface, &interface.smp_info.cpus()[index]);
+
+    ONLINE.fetch_add(1, Ordering::Release);
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -2,24 +2,34 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use microdragon_interface::smp::MAX_CPUS;
 use microdragon_interface::stack::{StackInfo, PRIMARY_STACK_SIZE, SECONDARY_STACK_SIZE};
 
-/// The kernel's primary stack for the bootstrap processor.
-static mut BOOTSTRAP_PRIMARY_STACK: &mut [u8] = &mut [0; PRIMARY_STACK_SIZE];
+/// A stack of `N` bytes, aligned so its top can be used as the stack pointer.
+#[repr(C, align(16))]
+struct Stack<const N: usize>([u8; N]);
 
-/// The kernel's secondary stack for the bootstrap processor.
-static mut BOOTSTRAP_SECONDARY_STACK: &mut [u8] = &mut [0; SECONDARY_STACK_SIZE];
+/// The kernel's primary stacks, indexed by processor index.
+static mut PRIMARY_STACKS: [Stack<PRIMARY_STACK_SIZE>; MAX_CPUS] =
+    [const { Stack([0; PRIMARY_STACK_SIZE]) }; MAX_CPUS];
 
-unsafe fn get_bootstrap_primary_stack() -> *const u8 {
-    BOOTSTRAP_PRIMARY_STACK.as_ptr().add(PRIMARY_STACK_SIZE)
+/// The kernel's secondary stacks, indexed by processor index.
+static mut SECONDARY_STACKS: [Stack<SECONDARY_STACK_SIZE>; MAX_CPUS] =
+    [const { Stack([0; SECONDARY_STACK_SIZE]) }; MAX_CPUS];
+
+/// Returns the top of the primary stack of the processor.
+pub unsafe fn get_primary_stack_top(index: usize) -> *const u8 {
+    (&raw const PRIMARY_STACKS[index].0)
+        .cast::<u8>()
+        .add(PRIMARY_STACK_SIZE)
 }
 
-/// Creates the [`StackInfo`] struct for the module interface.
-pub fn get_stack_info() -> StackInfo {
+/// Creates the [`StackInfo`] struct of the processor.
+pub fn get_stack_info(index: usize) -> StackInfo {
     unsafe {
         StackInfo {
-            primary_stack: BOOTSTRAP_PRIMARY_STACK.as_ptr() as u64,
-            secondary_stack: BOOTSTRAP_SECONDARY_STACK.as_ptr() as u64,
+            primary_stack: &raw const PRIMARY_STACKS[index] as u64,
+            secondary_stack: &raw const SECONDARY_STACKS[index] as u64,
         }
     }
 }
@@ -31,7 +41,7 @@ extern "C" fn _start() -> ! {
         core::arch::asm!(
             "MOV RSP, {}",
             "MOV RBP, RSP",
-            in(reg) get_bootstrap_primary_stack()
+            in(reg) get_primary_stack_top(0)
         );
     }
 
@@ -8,8 +8,10 @@
 mod acpi;
 mod framebuffer;
 mod memory;
+mod smp;
 mod stack;
 
+use bootloader_api::config::Mapping;
 use bootloader_api::{entry_point, BootInfo, BootloaderConfig};
 use core::arch::asm;
 use microdragon_interface::stack::PRIMARY_STACK_SIZE;
@@ -19,7 +21,7 @@ static BOOTLOADER_CONFIG: BootloaderConfig = {
     let mut config = BootloaderConfig::new_default();
 
     config.kernel_stack_size = PRIMARY_STACK_SIZE as u64;
-    //config.mappings.physical_memory = Some(Mapping::FixedAddress(0));
+    config.mappings.physical_memory = Some(Mapping::Dynamic);
     config.mappings.aslr = cfg!(not(debug_assertions));
 
     config
@@ -39,8 +41,10 @@ pub fn kernel_main(info: &'static mut BootInfo) -> ! {
         )
     };
 
+    let smp_info = smp::get_smp_info(info, stack::get_stack_info(stack_top));
     let interface = ModuleInterface {
         stack_info: stack::get_stack_info(stack_top),
+        smp_info,
         rsdp_address: acpi::get_rsdp_address(info),
         framebuffer_info: framebuffer::get_framebuffer_info(info),
         memory_map_info: memory::get_memory_map_info(info),
@@ -48,8 +52,24 @@ pub fn kernel_main(info: &'static mut BootInfo) -> ! {
     };
 
     run_modules(&interface);
+    smp::start_application_processors(&interface);
+
+    if option_env!("MICRODRAGON_EXIT").is_some() {
+        common::power::shutdown();
+    }
 
     loop {
         core::hint::spin_loop();
     }
 }
+
+/// Halts the machine, or exits the VM with a failure if built for automated runs.
+#[panic_handler]
+fn panic_handler(_: &core::panic::PanicInfo) -> ! {
+    if option_env!("MICRODRAGON_EXIT").is_some() {
+        common::power::exit_qemu(common::power::ExitCode::Failure);
+        common::power::reboot();
+    }
+
+    common::power::halt()
+}
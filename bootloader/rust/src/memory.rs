@@ -11,6 +11,10 @@ pub fn get_memory_map_info(info: &BootInfo) -> MemoryMapInfo {
         memory_map: info.memory_regions.as_ptr() as u64,
         memory_map_count: info.memory_regions.len(),
         memory_map_type: MemoryMapType::Rust,
+        physical_memory_offset: info
+            .physical_memory_offset
+            .into_option()
+            .expect("No physical memory mapping provided by the bootloader"),
     }
 }
 
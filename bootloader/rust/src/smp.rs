@@ -0,0 +1,440 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Starts the application processors with the INIT-SIPI-SIPI sequence, since the bootloader doesn't.
+//!
+//! The application processors start in real mode at a trampoline copied below 1 MiB.
+//! It switches to long mode using a copy of the bootloader's top-level page table,
+//! whose first entry is replaced by an identity mapping of the first 2 MiB, and jumps to the kernel.
+//! There they wait until the bootstrap processor ran all constructors, before running the per-CPU constructors.
+
+use crate::stack::{self, APPLICATION_PRIMARY_STACKS};
+use bootloader_api::info::MemoryRegionKind;
+use bootloader_api::BootInfo;
+use core::arch::x86_64::__cpuid;
+use core::arch::{asm, global_asm};
+use core::mem::offset_of;
+use core::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+use microdragon_interface::smp::{CpuInfo, SmpInfo, MAX_CPUS};
+use microdragon_interface::stack::{StackInfo, PRIMARY_STACK_SIZE};
+use microdragon_interface::ModuleInterface;
+
+/// Number of pages used by the trampoline: the code followed by up to four page tables.
+const TRAMPOLINE_PAGES: u64 = 5;
+
+const PAGE_SIZE: u64 = 4096;
+
+/// The trampoline has to be below 1 MiB, so its page number fits into the startup IPI vector.
+const TRAMPOLINE_LIMIT: u64 = 0x10_0000;
+
+const IA32_APIC_BASE: u32 = 0x1B;
+const IA32_EFER: u32 = 0xC000_0080;
+const X2APIC_ICR: u32 = 0x830;
+
+/// Offset of the low half of the interrupt command register of the xAPIC.
+const XAPIC_ICR_LOW: u64 = 0x300;
+
+/// Offset of the high half of the interrupt command register of the xAPIC.
+const XAPIC_ICR_HIGH: u64 = 0x310;
+
+/// Sends the IPI to all processors except the sender, with the level asserted.
+const ICR_ALL_EXCLUDING_SELF: u32 = (0b11 << 18) | (1 << 14);
+const ICR_INIT: u32 = 0b101 << 8;
+const ICR_STARTUP: u32 = 0b110 << 8;
+const ICR_DELIVERY_PENDING: u32 = 1 << 12;
+
+const PAGE_PRESENT: u64 = 1 << 0;
+const PAGE_WRITABLE: u64 = 1 << 1;
+const PAGE_HUGE: u64 = 1 << 7;
+const PAGE_ADDRESS_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+const CR4_PAE: u64 = 1 << 5;
+const CR4_LA57: u64 = 1 << 12;
+const EFER_LONG_MODE: u64 = 1 << 8;
+const EFER_NO_EXECUTE: u64 = 1 << 11;
+
+/// Values written into the trampoline before starting the application processors.
+#[repr(C)]
+struct TrampolineData {
+    /// Physical address of the top-level page table, which has to be below 4 GiB.
+    cr3: u64,
+
+    /// Virtual address of [`application_processor_entry`].
+    entry: u64,
+
+    cr4: u32,
+    efer: u32,
+    cr0: u32,
+
+    /// The index handed to the next application processor.
+    next_index: u32,
+}
+
+global_asm!(
+    ".section .text.ap_trampoline, \"ax\"",
+    ".global ap_trampoline_start",
+    ".global ap_trampoline_data",
+    ".global ap_trampoline_end",
+    ".set AP_GDT, ap_trampoline_gdt - ap_trampoline_start",
+    ".set AP_GDTR, ap_trampoline_gdtr - ap_trampoline_start",
+    ".set AP_PROTECTED, ap_trampoline_protected - ap_trampoline_start",
+    ".set AP_LONG, ap_trampoline_long - ap_trampoline_start",
+    ".set AP_DATA, ap_trampoline_data - ap_trampoline_start",
+    "",
+    // Real mode, CS is the page of the trampoline and IP is zero.
+    ".code16",
+    "ap_trampoline_start:",
+    "CLI",
+    "CLD",
+    "MOV AX, CS",
+    "MOV DS, AX",
+    "MOV SS, AX",
+    // The stack grows down from the end of the code page, all processors push the same values.
+    "MOV SP, {page_size}",
+    "XOR EBX, EBX",
+    "MOV BX, AX",
+    "SHL EBX, 4",
+    "LEA EAX, [EBX + AP_GDT]",
+    "MOV DWORD PTR [AP_GDTR + 2], EAX",
+    "LGDT [AP_GDTR]",
+    "MOV EAX, CR0",
+    "OR EAX, 1",
+    "MOV CR0, EAX",
+    "LEA EAX, [EBX + AP_PROTECTED]",
+    "MOV ECX, 0x08",
+    "PUSH ECX",
+    "PUSH EAX",
+    // 32-bit far return.
+    ".byte 0x66, 0xCB",
+    "",
+    // Protected mode, EBX is the physical address of the trampoline.
+    ".code32",
+    "ap_trampoline_protected:",
+    "MOV AX, 0x10",
+    "MOV DS, AX",
+    "MOV ES, AX",
+    "MOV SS, AX",
+    "LEA ESP, [EBX + {page_size}]",
+    "MOV EAX, [EBX + AP_DATA + {cr4}]",
+    "MOV CR4, EAX",
+    "MOV EAX, [EBX + AP_DATA + {cr3}]",
+    "MOV CR3, EAX",
+    "MOV ECX, {efer_msr}",
+    "MOV EAX, [EBX + AP_DATA + {efer}]",
+    "XOR EDX, EDX",
+    "WRMSR",
+    "MOV EAX, [EBX + AP_DATA + {cr0}]",
+    "MOV CR0, EAX",
+    "LEA EAX, [EBX + AP_LONG]",
+    "PUSH 0x18",
+    "PUSH EAX",
+    "RETF",
+    "",
+    // Long mode, still running at the identity mapped trampoline.
+    ".code64",
+    "ap_trampoline_long:",
+    "MOV EBX, EBX",
+    "MOV EDI, 1",
+    "LOCK",
+    "XADD DWORD PTR [RBX + AP_DATA + {next_index}], EDI",
+    "MOV RAX, [RBX + AP_DATA + {entry}]",
+    "JMP RAX",
+    "",
+    ".p2align 3",
+    "ap_trampoline_gdt:",
+    ".quad 0",
+    // 32-bit code segment.
+    ".quad 0x00CF9A000000FFFF",
+    // Data segment.
+    ".quad 0x00CF92000000FFFF",
+    // 64-bit code segment.
+    ".quad 0x00AF9A000000FFFF",
+    "ap_trampoline_gdtr:",
+    ".word 31",
+    ".long 0",
+    "",
+    ".p2align 3",
+    "ap_trampoline_data:",
+    ".fill {data_size}, 1, 0",
+    "ap_trampoline_end:",
+    "",
+    // The kernel entry of the application processors, RDI is the processor index.
+    ".section .text, \"ax\"",
+    ".global application_processor_entry",
+    "application_processor_entry:",
+    "CMP RDI, {max_cpus}",
+    "JAE 2f",
+    "LEA RSP, [RIP + {stacks}]",
+    "MOV RAX, RDI",
+    "IMUL RAX, RAX, {stack_size}",
+    "ADD RSP, RAX",
+    "XOR EBP, EBP",
+    "CALL {main}",
+    "2:",
+    "CLI",
+    "HLT",
+    "JMP 2b",
+    cr3 = const offset_of!(TrampolineData, cr3),
+    entry = const offset_of!(TrampolineData, entry),
+    cr4 = const offset_of!(TrampolineData, cr4),
+    efer = const offset_of!(TrampolineData, efer),
+    cr0 = const offset_of!(TrampolineData, cr0),
+    next_index = const offset_of!(TrampolineData, next_index),
+    data_size = const size_of::<TrampolineData>(),
+    efer_msr = const IA32_EFER,
+    page_size = const PAGE_SIZE,
+    max_cpus = const MAX_CPUS,
+    stacks = sym APPLICATION_PRIMARY_STACKS,
+    stack_size = const PRIMARY_STACK_SIZE,
+    main = sym application_processor_main,
+);
+
+extern "C" {
+    static ap_trampoline_start: u8;
+    static ap_trampoline_data: u8;
+    static ap_trampoline_end: u8;
+
+    fn application_processor_entry() -> !;
+}
+
+const EMPTY_CPU: CpuInfo = CpuInfo {
+    index: 0,
+    hardware_id: 0,
+    stack_info: StackInfo {
+        primary_stack: 0,
+        secondary_stack: 0,
+    },
+};
+
+/// The processors started by the kernel, indexed by processor index.
+static mut CPUS: [CpuInfo; MAX_CPUS] = [EMPTY_CPU; MAX_CPUS];
+
+/// The number of processors started, including the bootstrap processor.
+static CPU_COUNT: AtomicU64 = AtomicU64::new(1);
+
+/// The indices of the application processors waiting in the kernel, one bit per index.
+static ARRIVED: AtomicU64 = AtomicU64::new(0);
+
+/// The module interface, shared with the application processors once the bootstrap processor ran all constructors.
+static INTERFACE: AtomicPtr<ModuleInterface> = AtomicPtr::new(core::ptr::null_mut());
+
+/// The number of application processors which ran their constructors.
+static ONLINE: AtomicU64 = AtomicU64::new(0);
+
+/// Starts the application processors, which wait until [`start_application_processors`] is called,
+/// and creates the [`SmpInfo`] struct for the module interface.
+/// The memory used by the trampoline is removed from the memory map, since it stays in use until then.
+pub fn get_smp_info(info: &mut BootInfo, stack_info: StackInfo) -> SmpInfo {
+    unsafe {
+        let bootstrap_apic_id = current_apic_id();
+        CPUS = core::array::from_fn(|index| CpuInfo {
+            index: index as u64,
+            hardware_id: if index == 0 { bootstrap_apic_id } else { 0 },
+            stack_info: if index == 0 {
+                stack_info
+            } else {
+                stack::get_application_processor_stack_info(index)
+            },
+        });
+
+        if let Some(offset) = info.physical_memory_offset.into_option() {
+            boot_application_processors(info, offset);
+        }
+
+        SmpInfo {
+            cpu_count: CPU_COUNT.load(Ordering::Acquire),
+            cpus: &raw const CPUS as u64,
+        }
+    }
+}
+
+/// Lets the application processors run the per-CPU constructors and waits until they're done.
+/// The `interface` has to live as long as the kernel, so this may only be called from [`crate::kernel_main`].
+pub fn start_application_processors(interface: &ModuleInterface) {
+    INTERFACE.store(interface as *const _ as *mut _, Ordering::Release);
+
+    let count = CPU_COUNT.load(Ordering::Acquire) - 1;
+    while ONLINE.load(Ordering::Acquire) < count {
+        core::hint::spin_loop();
+    }
+}
+
+/// Sends the INIT-SIPI-SIPI sequence to all application processors and waits until they arrived in the kernel.
+///
+/// ## Safety
+///
+/// All physical memory has to be mapped at `offset`.
+unsafe fn boot_application_processors(info: &mut BootInfo, offset: u64) {
+    let cr4 = read_cr4();
+    let levels = if cr4 & CR4_LA57 != 0 { 5 } else { 4 };
+
+    // The identity mapping replaces the first entry of the top-level page table,
+    // so the kernel must not be mapped there.
+    let kernel = application_processor_main as *const () as u64;
+    if (kernel >> (12 + 9 * (levels - 1))) & 0x1FF == 0 {
+        return;
+    }
+
+    let Some(trampoline) = reserve_trampoline(info) else {
+        return;
+    };
+
+    // Copy the trampoline code.
+    let start = &raw const ap_trampoline_start;
+    let size = (&raw const ap_trampoline_end).offset_from(start) as usize;
+    let code = (offset + trampoline) as *mut u8;
+    core::ptr::copy_nonoverlapping(start, code, size);
+
+    // Build the page tables, the top-level one is a copy of the active one.
+    let table = |index: u64| (offset + trampoline + index * PAGE_SIZE) as *mut u64;
+    core::ptr::copy_nonoverlapping(
+        (offset + (read_cr3() & PAGE_ADDRESS_MASK)) as *const u64,
+        table(1),
+        512,
+    );
+    for level in 1..levels - 1 {
+        core::ptr::write_bytes(table(level + 1), 0, 512);
+        *table(level) = (trampoline + (level + 1) * PAGE_SIZE) | PAGE_PRESENT | PAGE_WRITABLE;
+    }
+    *table(levels - 1) = PAGE_PRESENT | PAGE_WRITABLE | PAGE_HUGE;
+
+    let data =
+        code.offset((&raw const ap_trampoline_data).offset_from(start)) as *mut TrampolineData;
+    data.write_volatile(TrampolineData {
+        cr3: trampoline + PAGE_SIZE,
+        entry: application_processor_entry as *const () as u64,
+        cr4: (CR4_PAE | (cr4 & CR4_LA57)) as u32,
+        efer: (EFER_LONG_MODE | (read_msr(IA32_EFER) & EFER_NO_EXECUTE)) as u32,
+        cr0: read_cr0() as u32,
+        next_index: 1,
+    });
+
+    let startup = ICR_STARTUP | (trampoline / PAGE_SIZE) as u32;
+    send_ipi(offset, ICR_ALL_EXCLUDING_SELF | ICR_INIT);
+    delay(10_000);
+    send_ipi(offset, ICR_ALL_EXCLUDING_SELF | startup);
+    delay(200);
+    send_ipi(offset, ICR_ALL_EXCLUDING_SELF | startup);
+
+    // Wait until all processors which got an index arrived, assuming all processors reach the trampoline in 100 ms.
+    delay(100_000);
+    let next_index = core::ptr::addr_of!((*data).next_index).read_volatile() as u64;
+    let count = next_index.min(MAX_CPUS as u64);
+    for _ in 0..100_000 {
+        if arrived() >= count - 1 {
+            break;
+        }
+        delay(1);
+    }
+
+    // Only the processors up to the first missing index are used, so the indices stay contiguous.
+    CPU_COUNT.store(arrived().min(count - 1) + 1, Ordering::Release);
+}
+
+/// Returns the number of application processors which arrived in the kernel, up to the first missing index.
+fn arrived() -> u64 {
+    // Index 0 is the bootstrap processor.
+    (!(ARRIVED.load(Ordering::Acquire) >> 1)).trailing_zeros() as u64
+}
+
+/// Removes pages for the trampoline below 1 MiB from the end of a usable region and returns their physical address.
+fn reserve_trampoline(info: &mut BootInfo) -> Option<u64> {
+    let size = TRAMPOLINE_PAGES * PAGE_SIZE;
+    let region = info.memory_regions.iter_mut().find(|x| {
+        x.kind == MemoryRegionKind::Usable
+            && x.end <= TRAMPOLINE_LIMIT
+            && (x.end & !(PAGE_SIZE - 1)) >= x.start.max(PAGE_SIZE) + size
+    })?;
+
+    region.end = (region.end & !(PAGE_SIZE - 1)) - size;
+    Some(region.end)
+}
+
+/// Rust entrypoint of the application processors, on their own stack.
+extern "C" fn application_processor_main(index: usize) -> ! {
+    unsafe { CPUS[index].hardware_id = current_apic_id() };
+    ARRIVED.fetch_or(1 << index, Ordering::Release);
+
+    let interface = loop {
+        let interface = INTERFACE.load(Ordering::Acquire);
+        if !interface.is_null() {
+            break unsafe { &*interface };
+        }
+        core::hint::spin_loop();
+    };
+
+    // Processors arriving after the processor count was determined are not part of the system.
+    if index < interface.smp_info.cpu_count as usize {
+        crate::run_cpu_modules(interface, &interface.smp_info.cpus()[index]);
+        ONLINE.fetch_add(1, Ordering::Release);
+    }
+
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Returns the local APIC id of the current processor.
+fn current_apic_id() -> u64 {
+    if __cpuid(0).eax >= 0x0B {
+        __cpuid(0x0B).edx as u64
+    } else {
+        (__cpuid(1).ebx >> 24) as u64
+    }
+}
+
+/// Sends an IPI through the local APIC, in x2APIC mode if enabled by the firmware.
+///
+/// ## Safety
+///
+/// All physical memory has to be mapped at `offset`.
+unsafe fn send_ipi(offset: u64, command: u32) {
+    let base = read_msr(IA32_APIC_BASE);
+    if base & (1 << 10) != 0 {
+        write_msr(X2APIC_ICR, command as u64);
+        return;
+    }
+
+    let registers = offset + (base & PAGE_ADDRESS_MASK);
+    ((registers + XAPIC_ICR_HIGH) as *mut u32).write_volatile(0);
+    ((registers + XAPIC_ICR_LOW) as *mut u32).write_volatile(command);
+    while ((registers + XAPIC_ICR_LOW) as *const u32).read_volatile() & ICR_DELIVERY_PENDING != 0 {
+        core::hint::spin_loop();
+    }
+}
+
+/// Waits for roughly the given number of microseconds, by writing to the unused POST code port.
+fn delay(microseconds: u64) {
+    for _ in 0..microseconds {
+        unsafe { asm!("OUT 0x80, AL", in("al") 0u8, options(nomem, nostack, preserves_flags)) };
+    }
+}
+
+unsafe fn read_cr0() -> u64 {
+    let value;
+    asm!("MOV {}, CR0", out(reg) value, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn read_cr3() -> u64 {
+    let value;
+    asm!("MOV {}, CR3", out(reg) value, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn read_cr4() -> u64 {
+    let value;
+    asm!("MOV {}, CR4", out(reg) value, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn read_msr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    asm!("RDMSR", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+    ((high as u64) << 32) | low as u64
+}
+
+unsafe fn write_msr(msr: u32, value: u64) {
+    asm!("WRMSR", in("ecx") msr, in("eax") value as u32, in("edx") (value >> 32) as u32, options(nostack, preserves_flags));
+}
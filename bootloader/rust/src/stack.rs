@@ -2,14 +2,38 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+use microdragon_interface::smp::MAX_CPUS;
 use microdragon_interface::stack::{StackInfo, PRIMARY_STACK_SIZE, SECONDARY_STACK_SIZE};
 
+/// A stack of `N` bytes, aligned so its top can be used as the stack pointer.
+#[repr(C, align(16))]
+pub struct Stack<const N: usize>([u8; N]);
+
 /// The kernel's secondary stack for the bootstrap processor.
 static mut BOOTSTRAP_SECONDARY_STACK: &mut [u8] = &mut [0; SECONDARY_STACK_SIZE];
 
+/// The kernel's primary stacks for the application processors, starting at processor index 1.
+/// The bootstrap processor keeps running on the stack set up by the bootloader.
+pub static mut APPLICATION_PRIMARY_STACKS: [Stack<PRIMARY_STACK_SIZE>; MAX_CPUS - 1] =
+    [const { Stack([0; PRIMARY_STACK_SIZE]) }; MAX_CPUS - 1];
+
+/// The kernel's secondary stacks for the application processors, starting at processor index 1.
+static mut APPLICATION_SECONDARY_STACKS: [Stack<SECONDARY_STACK_SIZE>; MAX_CPUS - 1] =
+    [const { Stack([0; SECONDARY_STACK_SIZE]) }; MAX_CPUS - 1];
+
 pub fn get_stack_info(stack: u64) -> StackInfo {
     StackInfo {
         primary_stack: stack - PRIMARY_STACK_SIZE as u64,
         secondary_stack: unsafe { BOOTSTRAP_SECONDARY_STACK.as_ptr() as u64 },
     }
 }
+
+/// Creates the [`StackInfo`] struct of the application processor with the index.
+pub fn get_application_processor_stack_info(index: usize) -> StackInfo {
+    unsafe {
+        StackInfo {
+            primary_stack: &raw const APPLICATION_PRIMARY_STACKS[index - 1] as u64,
+            secondary_stack: &raw const APPLICATION_SECONDARY_STACKS[index - 1] as u64,
+        }
+    }
+}
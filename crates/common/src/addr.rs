@@ -11,7 +11,7 @@
 //! See their respective docs to find out about the implied restrictions.
 //!
 use crate::memory::get_memory_info;
-use core::fmt::{self, Binary, Formatter, LowerHex, Octal, Pointer, UpperHex};
+use core::fmt::{self, Binary, Debug, Formatter, LowerHex, Octal, Pointer, UpperHex};
 use core::ops::{Add, AddAssign, Sub, SubAssign};
 
 /// Error returned when an invalid address is passed to [`VirtAddr`].
@@ -102,6 +102,45 @@ impl VirtAddr {
     pub fn is_aligned(self, align: u64) -> bool {
         self.align_down(align) == self
     }
+
+    /// Returns the 12-bit offset into the 4 KiB page.
+    pub const fn page_offset(self) -> u64 {
+        self.0 & 0xFFF
+    }
+
+    /// Returns the 9-bit index into the page table of the given level, from `1` to `5`.
+    pub const fn page_table_index(self, level: u8) -> usize {
+        assert!(
+            level >= 1 && level <= 5,
+            "Page table levels range from 1 to 5"
+        );
+        ((self.0 >> (12 + 9 * (level as u64 - 1))) & 0x1FF) as usize
+    }
+
+    /// Returns the 9-bit index into the level 1 page table.
+    pub const fn p1_index(self) -> usize {
+        self.page_table_index(1)
+    }
+
+    /// Returns the 9-bit index into the level 2 page table.
+    pub const fn p2_index(self) -> usize {
+        self.page_table_index(2)
+    }
+
+    /// Returns the 9-bit index into the level 3 page table.
+    pub const fn p3_index(self) -> usize {
+        self.page_table_index(3)
+    }
+
+    /// Returns the 9-bit index into the level 4 page table.
+    pub const fn p4_index(self) -> usize {
+        self.page_table_index(4)
+    }
+
+    /// Returns the 9-bit index into the level 5 page table.
+    pub const fn p5_index(self) -> usize {
+        self.page_table_index(5)
+    }
 }
 
 impl Default for VirtAddr {
@@ -196,6 +235,14 @@ impl Binary for VirtAddr {
     }
 }
 
+impl Debug for VirtAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("VirtAddr")
+            .field(&format_args!("{:#x}", self.0))
+            .finish()
+    }
+}
+
 impl LowerHex for VirtAddr {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         LowerHex::fmt(&self.0, f)
@@ -406,6 +453,14 @@ impl Binary for PhysAddr {
     }
 }
 
+impl Debug for PhysAddr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PhysAddr")
+            .field(&format_args!("{:#x}", self.0))
+            .finish()
+    }
+}
+
 impl LowerHex for PhysAddr {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         LowerHex::fmt(&self.0, f)
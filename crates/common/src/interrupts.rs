@@ -0,0 +1,120 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Interrupts
+//!
+//! Enabling and disabling interrupts, as well as the allocation of interrupt vectors.
+//!
+//! Modules driving a device request a free vector with [`allocate_vector`], attach a handler with [`register_handler`]
+//! and route the device's interrupt to the vector. Level-triggered vectors can be shared by multiple handlers,
+//! every handler is called and returns whether it acknowledged the interrupt.
+//!
+//! The vectors are implemented by the IDT module.
+//!
+pub use interrupts::*;
+
+/// An interrupt vector, the index into the interrupt descriptor table.
+pub type Vector = u8;
+
+/// Handles an interrupt on the given vector.
+/// Returns whether the interrupt was acknowledged, meaning it was raised by the handler's device.
+pub type InterruptHandler = fn(Vector) -> bool;
+
+/// How the interrupt line of a vector signals interrupts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerMode {
+    /// An interrupt is signaled by an edge of the line. Edge-triggered vectors have a single handler.
+    Edge,
+
+    /// The line is asserted until the interrupt is acknowledged. Level-triggered vectors can be shared by multiple handlers.
+    Level,
+}
+
+/// Error returned when allocating vectors or registering handlers fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorError {
+    /// All vectors are allocated.
+    Exhausted,
+
+    /// The vector isn't allocated.
+    NotAllocated,
+
+    /// The vector is edge-triggered and already has a handler.
+    AlreadyRegistered,
+
+    /// The vector is level-triggered and has the maximal number of shared handlers.
+    TooManyHandlers,
+
+    /// The handler isn't registered for the vector.
+    HandlerNotFound,
+
+    /// The vector still has handlers registered.
+    InUse,
+}
+
+/// Counters of an allocated vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VectorStatistics {
+    /// How the interrupt line of the vector signals interrupts.
+    pub trigger_mode: TriggerMode,
+
+    /// Number of registered handlers.
+    pub handlers: usize,
+
+    /// Number of interrupts raised on the vector.
+    pub count: u64,
+
+    /// Number of interrupts no handler acknowledged.
+    pub unhandled: u64,
+}
+
+/// Enables interrupts.
+#[cfg(target_arch = "x86_64")]
+pub fn enable() {
+    unsafe { core::arch::asm!("sti", options(preserves_flags)) }
+}
+
+// #[cfg(target_arch = "aarch64")]
+// pub fn enable() {
+//     unsafe { core::arch::asm!("msr DAIFSet, 0b000", options(preserves_flags, nostack)) }
+// }
+
+/// Returns whenever interrupts are enabled on the current processor.
+#[cfg(target_arch = "x86_64")]
+pub fn are_enabled() -> bool {
+    let flags: u64;
+    unsafe { core::arch::asm!("pushfq", "pop {}", out(reg) flags, options(nomem, preserves_flags)) }
+    flags & (1 << 9) != 0
+}
+
+/// Enables interrupts and halts the processor until the next interrupt arrives.
+/// Both happen at once, so an interrupt arriving in between isn't missed.
+#[cfg(target_arch = "x86_64")]
+pub fn enable_and_wait() {
+    unsafe { core::arch::asm!("sti", "hlt", options(nomem, nostack)) }
+}
+
+/// Allocates a free interrupt vector with the given trigger mode.
+pub fn allocate_vector(trigger_mode: TriggerMode) -> Result<Vector, VectorError> {
+    unsafe { crate::magic::allocate_vector(trigger_mode) }
+}
+
+/// Frees an allocated vector. All handlers have to be unregistered before.
+pub fn free_vector(vector: Vector) -> Result<(), VectorError> {
+    unsafe { crate::magic::free_vector(vector) }
+}
+
+/// Registers a handler for an allocated vector.
+pub fn register_handler(vector: Vector, handler: InterruptHandler) -> Result<(), VectorError> {
+    unsafe { crate::magic::register_handler(vector, handler) }
+}
+
+/// Unregisters a handler previously registered for the vector.
+pub fn unregister_handler(vector: Vector, handler: InterruptHandler) -> Result<(), VectorError> {
+    unsafe { crate::magic::unregister_handler(vector, handler) }
+}
+
+/// Returns the counters of the vector or `None` if it isn't allocated.
+pub fn vector_statistics(vector: Vector) -> Option<VectorStatistics> {
+    unsafe { crate::magic::vector_statistics(vector) }
+}
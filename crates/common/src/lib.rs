@@ -6,26 +6,20 @@
 //! The common kernel library contains constructs and primitives used by all parts of the kernel in an architecture-independent way.
 //!
 //! - [`addr`] Contains the [`addr::VirtAddr`] and [`addr::PhysAddr`] structs.
+//! - [`interrupts`] controls interrupts and hands out interrupt vectors to modules.
 //! - [`memory`] defines the memory layout of the kernel and the OS as a whole.
+//! - [`paging`] contains page tables and their architecture specific encoding.
+//! - [`power`] restarts and turns off the machine.
 //! - [`sync`] supplies different primitives of synchronization to be used by the kernel.
+//! - [`time`] contains the clock sources and clock events forming the time base of the kernel.
 //!
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 pub mod addr;
+pub mod interrupts;
 mod magic;
 pub mod memory;
+pub mod paging;
+pub mod power;
 pub mod sync;
-
-pub mod interrupts {
-    pub use interrupts::*;
-
-    #[cfg(target_arch = "x86_64")]
-    pub fn enable() {
-        unsafe { core::arch::asm!("sti", options(preserves_flags)) }
-    }
-
-    // #[cfg(target_arch = "aarch64")]
-    // pub fn enable() {
-    //     unsafe { core::arch::asm!("msr DAIFSet, 0b000", options(preserves_flags, nostack)) }
-    // }
-}
+pub mod time;
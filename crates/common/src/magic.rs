@@ -8,6 +8,7 @@
 //! They are resolved during linkage of the kernel.
 //!
 use crate::addr::{PhysAddr, VirtAddr};
+use crate::interrupts::{InterruptHandler, TriggerMode, Vector, VectorError, VectorStatistics};
 
 extern "Rust" {
     /// Converts a virtual address inside the userspace area to a physical address, by walking the page tables.
@@ -21,4 +22,35 @@ extern "Rust" {
     /// Implemented By: KMM
     #[link_name = "__internal_virtual_to_physical_kernel"]
     pub fn virtual_to_physical_kernel(virt: VirtAddr) -> Option<PhysAddr>;
+
+    /// Allocates a free interrupt vector with the given trigger mode.
+    ///
+    /// Implemented By: IDT
+    #[link_name = "__internal_allocate_vector"]
+    pub fn allocate_vector(trigger_mode: TriggerMode) -> Result<Vector, VectorError>;
+
+    /// Frees an allocated vector without handlers.
+    ///
+    /// Implemented By: IDT
+    #[link_name = "__internal_free_vector"]
+    pub fn free_vector(vector: Vector) -> Result<(), VectorError>;
+
+    /// Registers a handler for an allocated vector.
+    ///
+    /// Implemented By: IDT
+    #[link_name = "__internal_register_handler"]
+    pub fn register_handler(vector: Vector, handler: InterruptHandler) -> Result<(), VectorError>;
+
+    /// Unregisters a handler of a vector.
+    ///
+    /// Implemented By: IDT
+    #[link_name = "__internal_unregister_handler"]
+    pub fn unregister_handler(vector: Vector, handler: InterruptHandler)
+        -> Result<(), VectorError>;
+
+    /// Returns the counters of an allocated vector.
+    ///
+    /// Implemented By: IDT
+    #[link_name = "__internal_vector_statistics"]
+    pub fn vector_statistics(vector: Vector) -> Option<VectorStatistics>;
 }
@@ -0,0 +1,181 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! AArch64 VMSAv8-64 descriptors for a 4 KiB granule.
+//!
+//! Memory attributes are selected by an index into the MAIR:
+//! index 0 has to be normal write-back memory and index 1 device memory.
+use super::{Architecture, PageTableFlags};
+use crate::addr::PhysAddr;
+
+const VALID: u64 = 1 << 0;
+/// Set for table descriptors and level 1 page descriptors, cleared for block descriptors.
+const TABLE_OR_PAGE: u64 = 1 << 1;
+const ATTRIBUTE_DEVICE: u64 = 1 << 2;
+const ATTRIBUTE_MASK: u64 = 0b111 << 2;
+const USER: u64 = 1 << 6;
+const READ_ONLY: u64 = 1 << 7;
+const INNER_SHAREABLE: u64 = 0b11 << 8;
+const ACCESSED: u64 = 1 << 10;
+const NOT_GLOBAL: u64 = 1 << 11;
+const PRIVILEGED_EXECUTE_NEVER: u64 = 1 << 53;
+const USER_EXECUTE_NEVER: u64 = 1 << 54;
+
+/// Bits of the output address in a descriptor, without 52-bit physical address support.
+const ADDRESS_MASK: u64 = 0x0000_FFFF_FFFF_F000;
+
+/// The AArch64 [`Architecture`] backend.
+pub struct AArch64;
+
+impl Architecture for AArch64 {
+    fn table_entry(address: PhysAddr, _: PageTableFlags) -> u64 {
+        address.as_u64() | VALID | TABLE_OR_PAGE
+    }
+
+    fn page_entry(address: PhysAddr, flags: PageTableFlags, level: u8) -> u64 {
+        // The access flag is always set, since we don't handle access faults.
+        let mut entry = address.as_u64() | VALID | ACCESSED | INNER_SHAREABLE;
+        if level == 1 {
+            entry |= TABLE_OR_PAGE;
+        }
+        if !flags.contains(PageTableFlags::WRITABLE) {
+            entry |= READ_ONLY;
+        }
+        if flags.contains(PageTableFlags::USER) {
+            // Userspace code is never executable by the kernel.
+            entry |= USER | PRIVILEGED_EXECUTE_NEVER;
+            if !flags.contains(PageTableFlags::EXECUTABLE) {
+                entry |= USER_EXECUTE_NEVER;
+            }
+        } else {
+            entry |= USER_EXECUTE_NEVER;
+            if !flags.contains(PageTableFlags::EXECUTABLE) {
+                entry |= PRIVILEGED_EXECUTE_NEVER;
+            }
+        }
+        if !flags.contains(PageTableFlags::GLOBAL) {
+            entry |= NOT_GLOBAL;
+        }
+        if flags.contains(PageTableFlags::NO_CACHE) {
+            entry |= ATTRIBUTE_DEVICE;
+        }
+        entry
+    }
+
+    fn is_present(entry: u64) -> bool {
+        entry & VALID != 0
+    }
+
+    fn is_page(entry: u64, level: u8) -> bool {
+        level == 1 || entry & TABLE_OR_PAGE == 0
+    }
+
+    fn address(entry: u64) -> PhysAddr {
+        PhysAddr::new_truncate(entry & ADDRESS_MASK)
+    }
+
+    fn flags(entry: u64) -> PageTableFlags {
+        let mut flags = PageTableFlags::empty();
+        if entry & READ_ONLY == 0 {
+            flags |= PageTableFlags::WRITABLE;
+        }
+        let execute_never = if entry & USER != 0 {
+            flags |= PageTableFlags::USER;
+            USER_EXECUTE_NEVER
+        } else {
+            PRIVILEGED_EXECUTE_NEVER
+        };
+        if entry & execute_never == 0 {
+            flags |= PageTableFlags::EXECUTABLE;
+        }
+        if entry & NOT_GLOBAL == 0 {
+            flags |= PageTableFlags::GLOBAL;
+        }
+        if entry & ATTRIBUTE_MASK == ATTRIBUTE_DEVICE {
+            flags |= PageTableFlags::NO_CACHE;
+        }
+        if entry & ACCESSED != 0 {
+            flags |= PageTableFlags::ACCESSED;
+        }
+        flags
+    }
+}
+
+/// Returns the physical address of the active top-level page table of the lower half.
+#[cfg(target_arch = "aarch64")]
+pub fn active_page_table() -> PhysAddr {
+    let ttbr0: u64;
+    unsafe {
+        core::arch::asm!("MRS {}, TTBR0_EL1", out(reg) ttbr0, options(nomem, nostack, preserves_flags))
+    };
+    PhysAddr::new_truncate(ttbr0 & ADDRESS_MASK)
+}
+
+/// Switches the active top-level page table of the lower half.
+///
+/// ## Safety
+///
+/// The page table has to map the currently executing code and stack.
+#[cfg(target_arch = "aarch64")]
+pub unsafe fn set_active_page_table(table: PhysAddr) {
+    core::arch::asm!(
+        "MSR TTBR0_EL1, {}",
+        "TLBI VMALLE1IS",
+        "DSB ISH",
+        "ISB",
+        in(reg) table.as_u64(),
+        options(nostack, preserves_flags)
+    );
+}
+
+/// Flushes the TLB entry for the given address.
+#[cfg(target_arch = "aarch64")]
+pub fn flush(virt: crate::addr::VirtAddr) {
+    unsafe {
+        core::arch::asm!(
+            "DSB ISHST",
+            "TLBI VAAE1IS, {}",
+            "DSB ISH",
+            "ISB",
+            in(reg) (virt.as_u64() >> 12) & 0xFFF_FFFF_FFFF,
+            options(nostack, preserves_flags)
+        )
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::AArch64;
+    use crate::addr::PhysAddr;
+    use crate::paging::test::setup;
+    use crate::paging::{Architecture, PageTableFlags};
+
+    #[test]
+    fn test_page_entry() {
+        setup();
+
+        let address = PhysAddr::new(0x1234_5000);
+        let flags = PageTableFlags::WRITABLE | PageTableFlags::USER | PageTableFlags::ACCESSED;
+        let entry = AArch64::page_entry(address, flags, 1);
+        assert_eq!(entry, 0x0060_0000_1234_5F43);
+        assert!(AArch64::is_page(entry, 1));
+        assert_eq!(AArch64::address(entry), address);
+        assert_eq!(AArch64::flags(entry), flags);
+
+        let flags = PageTableFlags::EXECUTABLE | PageTableFlags::GLOBAL | PageTableFlags::ACCESSED;
+        let block = AArch64::page_entry(PhysAddr::new(0x20_0000), flags, 2);
+        assert_eq!(block, 0x0040_0000_0020_0781);
+        assert!(AArch64::is_page(block, 2));
+        assert_eq!(AArch64::flags(block), flags);
+    }
+
+    #[test]
+    fn test_table_entry() {
+        setup();
+
+        let entry = AArch64::table_entry(PhysAddr::new(0x2000), PageTableFlags::empty());
+        assert_eq!(entry, 0x2003);
+        assert!(!AArch64::is_page(entry, 2));
+        assert_eq!(AArch64::address(entry), PhysAddr::new(0x2000));
+    }
+}
@@ -0,0 +1,419 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use super::{
+    entry_size, Architecture, Native, Page, PageSize, PageTable, PageTableEntry, PageTableFlags,
+    PhysFrame,
+};
+use crate::addr::{PhysAddr, VirtAddr};
+use core::marker::PhantomData;
+
+/// Hands out the frames used for new page tables.
+pub trait FrameAllocator {
+    /// Allocates a 4 KiB frame.
+    /// Returns `None` if no memory is available.
+    fn allocate_frame(&mut self) -> Option<PhysFrame>;
+}
+
+/// Error returned when changing a mapping fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapError {
+    /// The page is already mapped.
+    AlreadyMapped,
+
+    /// The page isn't mapped.
+    NotMapped,
+
+    /// The address is mapped by a page of a different size.
+    HugePage,
+
+    /// No physical memory was available for a page table.
+    FrameAllocationFailed,
+}
+
+/// Walks and modifies the page tables below a table, accessing them through a mapping of all physical memory.
+///
+/// The root doesn't have to be a top-level page table, a mapper for a level 3 table only handles
+/// the part of the address space mapped by that table.
+pub struct Mapper<A: Architecture = Native> {
+    root: PhysAddr,
+    levels: u8,
+    offset: u64,
+    _architecture: PhantomData<A>,
+}
+
+impl<A: Architecture> Mapper<A> {
+    /// Creates a mapper for the page table of the given level at `root`.
+    ///
+    /// ## Safety
+    ///
+    /// All physical memory has to be mapped at `offset` and the tables below `root` must not be modified by anything else
+    /// while the mapper exists.
+    pub unsafe fn new(root: PhysAddr, levels: u8, offset: u64) -> Self {
+        Mapper {
+            root,
+            levels,
+            offset,
+            _architecture: PhantomData,
+        }
+    }
+
+    /// Returns the physical address of the root table.
+    pub fn root(&self) -> PhysAddr {
+        self.root
+    }
+
+    /// Returns the level of the root table.
+    pub fn levels(&self) -> u8 {
+        self.levels
+    }
+
+    /// Returns the table at the physical address.
+    ///
+    /// ## Safety
+    ///
+    /// `address` has to be the root or a table below it.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn table(&self, address: PhysAddr) -> &mut PageTable<A> {
+        PageTable::from_physical(address, self.offset)
+    }
+
+    /// Maps `page` to `frame`.
+    /// Missing intermediate tables are created with frames of the allocator.
+    pub fn map<S: PageSize>(
+        &mut self,
+        page: Page<S>,
+        frame: PhysFrame<S>,
+        flags: PageTableFlags,
+        allocator: &mut impl FrameAllocator,
+    ) -> Result<(), MapError> {
+        self.assert_level(S::LEVEL);
+
+        let level = S::LEVEL;
+        let page = page.start_address();
+        let mut table = self.root;
+        for current in (level + 1..=self.levels).rev() {
+            // Safety: All tables are below the root.
+            let entry = unsafe { &mut self.table(table)[page.page_table_index(current)] };
+            if entry.is_unused() {
+                let next = allocator
+                    .allocate_frame()
+                    .ok_or(MapError::FrameAllocationFailed)?
+                    .start_address();
+                // Safety: The frame was just allocated, so nothing else references it.
+                unsafe { self.table(next) }.zero();
+                entry.set_table(next, flags);
+            } else if entry.is_page(current) {
+                return Err(MapError::HugePage);
+            } else {
+                // Entries referencing tables only ever gain permissions.
+                let raw = entry.raw() | A::table_entry(entry.address(), flags);
+                // Safety: Both values are valid entries referencing the same table.
+                unsafe { entry.set_raw(raw) };
+            }
+            table = entry.address();
+        }
+
+        // Safety: All tables are below the root.
+        let entry = unsafe { &mut self.table(table)[page.page_table_index(level)] };
+        if !entry.is_unused() {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        entry.set_page(frame.start_address(), flags, level);
+        Ok(())
+    }
+
+    /// Unmaps `page`.
+    /// Returns the frame the page was mapped to. Tables left empty aren't freed.
+    pub fn unmap<S: PageSize>(&mut self, page: Page<S>) -> Result<PhysFrame<S>, MapError> {
+        let entry = self.entry(page)?;
+        let frame = PhysFrame::containing_address(entry.address());
+        entry.clear();
+        Ok(frame)
+    }
+
+    /// Changes the flags of `page`.
+    pub fn update_flags<S: PageSize>(
+        &mut self,
+        page: Page<S>,
+        flags: PageTableFlags,
+    ) -> Result<(), MapError> {
+        let entry = self.entry(page)?;
+        entry.set_page(entry.address(), flags, S::LEVEL);
+        Ok(())
+    }
+
+    /// Returns the flags of `page`.
+    pub fn flags<S: PageSize>(&mut self, page: Page<S>) -> Result<PageTableFlags, MapError> {
+        Ok(self.entry(page)?.flags())
+    }
+
+    /// Returns the physical address `virt` is mapped to, or `None` if it isn't mapped.
+    pub fn translate(&self, virt: VirtAddr) -> Option<PhysAddr> {
+        let mut table = self.root;
+        for level in (1..=self.levels).rev() {
+            // Safety: All tables are below the root.
+            let entry = unsafe { &self.table(table)[virt.page_table_index(level)] };
+            if entry.is_unused() {
+                return None;
+            }
+
+            if entry.is_page(level) {
+                let size = entry_size(level);
+                return Some(entry.address().align_down(size) + (virt.as_u64() & (size - 1)));
+            }
+            table = entry.address();
+        }
+
+        None
+    }
+
+    /// Returns the entry mapping `page`.
+    fn entry<S: PageSize>(&mut self, page: Page<S>) -> Result<&mut PageTableEntry<A>, MapError> {
+        self.assert_level(S::LEVEL);
+
+        let level = S::LEVEL;
+        let page = page.start_address();
+        let mut table = self.root;
+        for current in (level + 1..=self.levels).rev() {
+            // Safety: All tables are below the root.
+            let entry = unsafe { &self.table(table)[page.page_table_index(current)] };
+            if entry.is_unused() {
+                return Err(MapError::NotMapped);
+            } else if entry.is_page(current) {
+                return Err(MapError::HugePage);
+            }
+            table = entry.address();
+        }
+
+        // Safety: All tables are below the root.
+        let entry = unsafe { &mut self.table(table)[page.page_table_index(level)] };
+        if entry.is_unused() {
+            Err(MapError::NotMapped)
+        } else if !entry.is_page(level) {
+            Err(MapError::HugePage)
+        } else {
+            Ok(entry)
+        }
+    }
+
+    /// Panics if pages can't be mapped in a page table of the given level below the root.
+    fn assert_level(&self, level: u8) {
+        assert!(
+            level <= self.levels.min(3),
+            "Pages can't be mapped in a level {} page table",
+            level
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FrameAllocator, MapError, Mapper};
+    use crate::addr::{PhysAddr, VirtAddr};
+    use crate::paging::test::setup;
+    use crate::paging::{
+        AArch64, Architecture, Page, PageSize, PageTable, PageTableFlags, PhysFrame, RiscV64,
+        Size1GiB, Size2MiB, Size4KiB, X86_64,
+    };
+
+    const MIB: u64 = 1024 * 1024;
+    const GIB: u64 = 1024 * MIB;
+
+    /// Allocates page tables in host memory, using the host address as physical address.
+    struct TestAllocator {
+        allocated: usize,
+        limit: usize,
+    }
+
+    impl FrameAllocator for TestAllocator {
+        fn allocate_frame(&mut self) -> Option<PhysFrame> {
+            if self.allocated == self.limit {
+                return None;
+            }
+            self.allocated += 1;
+
+            let table = Box::leak(Box::new(PageTable::<X86_64>::new()));
+            PhysFrame::from_start_address(PhysAddr::new(table as *mut _ as u64)).ok()
+        }
+    }
+
+    fn mapper<A: Architecture>(levels: u8, limit: usize) -> (Mapper<A>, TestAllocator) {
+        setup();
+
+        let mut allocator = TestAllocator {
+            allocated: 0,
+            limit,
+        };
+        let root = allocator.allocate_frame().unwrap().start_address();
+        (unsafe { Mapper::new(root, levels, 0) }, allocator)
+    }
+
+    fn page<S: PageSize>(address: u64) -> Page<S> {
+        Page::from_start_address(VirtAddr::new_truncate(address)).unwrap()
+    }
+
+    fn frame<S: PageSize>(address: u64) -> PhysFrame<S> {
+        PhysFrame::from_start_address(PhysAddr::new(address)).unwrap()
+    }
+
+    fn map<A: Architecture>() {
+        let (mut mapper, mut allocator) = mapper::<A>(4, usize::MAX);
+        let page = page::<Size4KiB>(0x0000_1234_5678_9000);
+        let frame = frame(0x1_2345_6000);
+        let address = page.start_address();
+        let flags = PageTableFlags::WRITABLE | PageTableFlags::USER;
+
+        assert_eq!(mapper.translate(address), None);
+        mapper.map(page, frame, flags, &mut allocator).unwrap();
+        assert_eq!(allocator.allocated, 4);
+        assert_eq!(
+            mapper.translate(address + 0x123u64),
+            Some(frame.start_address() + 0x123u64)
+        );
+        assert_eq!(mapper.translate(address + 0x1000u64), None);
+        assert!(mapper.flags(page).unwrap().contains(flags));
+
+        // The intermediate tables are reused.
+        mapper.map(page + 1, frame, flags, &mut allocator).unwrap();
+        assert_eq!(allocator.allocated, 4);
+
+        assert_eq!(
+            mapper.map(page, frame, flags, &mut allocator),
+            Err(MapError::AlreadyMapped)
+        );
+        assert_eq!(mapper.unmap(page), Ok(frame));
+        assert_eq!(mapper.unmap(page), Err(MapError::NotMapped));
+        assert_eq!(mapper.translate(address), None);
+    }
+
+    fn huge_pages<A: Architecture>() {
+        let (mut mapper, mut allocator) = mapper::<A>(4, usize::MAX);
+        let flags = PageTableFlags::WRITABLE;
+
+        let page_1gib = page::<Size1GiB>(0x4000_0000);
+        mapper
+            .map(page_1gib, frame(GIB), flags, &mut allocator)
+            .unwrap();
+        assert_eq!(
+            mapper.translate(page_1gib.start_address() + 0x1234_5678u64),
+            Some(PhysAddr::new(GIB + 0x1234_5678))
+        );
+
+        let page_2mib = page::<Size2MiB>(0x8020_0000);
+        mapper
+            .map(page_2mib, frame(2 * MIB), flags, &mut allocator)
+            .unwrap();
+        assert_eq!(
+            mapper.translate(page_2mib.start_address() + 0x1234u64),
+            Some(PhysAddr::new(2 * MIB + 0x1234))
+        );
+
+        let page_4kib = page::<Size4KiB>(0x8020_1000);
+        assert_eq!(
+            mapper.map(page_4kib, frame(0), flags, &mut allocator),
+            Err(MapError::HugePage)
+        );
+        assert_eq!(mapper.unmap(page_4kib), Err(MapError::HugePage));
+        assert_eq!(mapper.unmap(page_2mib), Ok(frame(2 * MIB)));
+    }
+
+    fn update_flags<A: Architecture>() {
+        let (mut mapper, mut allocator) = mapper::<A>(4, usize::MAX);
+        let page = page::<Size4KiB>(0x1000);
+        let frame = frame(0x5000);
+
+        mapper
+            .map(page, frame, PageTableFlags::WRITABLE, &mut allocator)
+            .unwrap();
+        mapper
+            .update_flags(page, PageTableFlags::EXECUTABLE)
+            .unwrap();
+
+        let flags = mapper.flags(page).unwrap();
+        assert!(flags.contains(PageTableFlags::EXECUTABLE));
+        assert!(!flags.contains(PageTableFlags::WRITABLE));
+        assert_eq!(
+            mapper.translate(page.start_address()),
+            Some(frame.start_address())
+        );
+        assert_eq!(
+            mapper.update_flags(page + 1, PageTableFlags::empty()),
+            Err(MapError::NotMapped)
+        );
+    }
+
+    fn five_levels<A: Architecture>() {
+        let (mut mapper, mut allocator) = mapper::<A>(5, usize::MAX);
+        let page = page::<Size4KiB>(0xFF12_3456_7890_0000);
+        let frame = frame(0x7000);
+
+        mapper
+            .map(page, frame, PageTableFlags::empty(), &mut allocator)
+            .unwrap();
+        assert_eq!(allocator.allocated, 5);
+        assert_eq!(
+            mapper.translate(page.start_address()),
+            Some(frame.start_address())
+        );
+    }
+
+    fn out_of_memory<A: Architecture>() {
+        let (mut mapper, mut allocator) = mapper::<A>(4, 3);
+        let page = page::<Size4KiB>(0x1000);
+
+        assert_eq!(
+            mapper.map(page, frame(0), PageTableFlags::empty(), &mut allocator),
+            Err(MapError::FrameAllocationFailed)
+        );
+        assert_eq!(mapper.translate(page.start_address()), None);
+    }
+
+    #[test]
+    fn test_map() {
+        map::<X86_64>();
+        map::<AArch64>();
+        map::<RiscV64>();
+    }
+
+    #[test]
+    fn test_huge_pages() {
+        huge_pages::<X86_64>();
+        huge_pages::<AArch64>();
+        huge_pages::<RiscV64>();
+    }
+
+    #[test]
+    fn test_update_flags() {
+        update_flags::<X86_64>();
+        update_flags::<AArch64>();
+        update_flags::<RiscV64>();
+    }
+
+    #[test]
+    fn test_five_levels() {
+        five_levels::<X86_64>();
+        five_levels::<AArch64>();
+        five_levels::<RiscV64>();
+    }
+
+    #[test]
+    fn test_out_of_memory() {
+        out_of_memory::<X86_64>();
+        out_of_memory::<AArch64>();
+        out_of_memory::<RiscV64>();
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be mapped in a level 3 page table")]
+    fn test_page_above_root() {
+        let (mut mapper, mut allocator) = mapper::<X86_64>(2, usize::MAX);
+        let _ = mapper.map(
+            page::<Size1GiB>(0),
+            frame(0),
+            PageTableFlags::empty(),
+            &mut allocator,
+        );
+    }
+}
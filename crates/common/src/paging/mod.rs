@@ -0,0 +1,363 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Paging Module
+//!
+//! All supported architectures use the same page table structure:
+//! 512 entries of 64-bit per table, 4 or 5 levels and 4 KiB pages, with 2 MiB and 1 GiB pages mapped by level 2 and 3 entries.
+//! Only the encoding of the entries differs, which is handled by an [`Architecture`] backend:
+//!
+//! - [`X86_64`] for x86_64 4 and 5 level paging.
+//! - [`AArch64`] for AArch64 VMSAv8-64 with a 4 KiB granule.
+//! - [`RiscV64`] for RISC-V Sv48 and Sv57.
+//!
+//! The backend of the current architecture is [`Native`], which is the default for all generic types.
+//! Pages and frames of the three sizes are represented by [`Page`] and [`PhysFrame`], generic over the [`PageSize`].
+//! The [`Mapper`] walks page tables and creates intermediate tables as needed, using a [`FrameAllocator`].
+//!
+use crate::addr::PhysAddr;
+use core::fmt::{self, Debug, Formatter};
+use core::marker::PhantomData;
+use core::ops::{BitAnd, BitOr, BitOrAssign, Index, IndexMut, Not};
+
+mod aarch64;
+mod mapper;
+mod page;
+mod riscv64;
+mod x86_64;
+
+pub use aarch64::AArch64;
+pub use mapper::*;
+pub use page::*;
+pub use riscv64::RiscV64;
+pub use x86_64::X86_64;
+
+#[cfg(target_arch = "aarch64")]
+pub use aarch64::{active_page_table, flush, set_active_page_table};
+#[cfg(target_arch = "riscv64")]
+pub use riscv64::{active_page_table, flush, set_active_page_table};
+#[cfg(target_arch = "x86_64")]
+pub use x86_64::{active_page_table, flush, set_active_page_table};
+
+/// The [`Architecture`] backend of the architecture the kernel is compiled for.
+#[cfg(target_arch = "aarch64")]
+pub type Native = AArch64;
+
+/// The [`Architecture`] backend of the architecture the kernel is compiled for.
+#[cfg(target_arch = "riscv64")]
+pub type Native = RiscV64;
+
+/// The [`Architecture`] backend of the architecture the kernel is compiled for.
+#[cfg(target_arch = "x86_64")]
+pub type Native = X86_64;
+
+/// Number of entries in a page table, in any level.
+pub const ENTRY_COUNT: usize = 512;
+
+/// Returns the size of the memory mapped by a single entry of a page table of the given level.
+pub const fn entry_size(level: u8) -> u64 {
+    1 << (12 + 9 * (level as u64 - 1))
+}
+
+/// Encoding of page table entries for a specific architecture.
+pub trait Architecture {
+    /// Encodes an entry referencing the lower level table at `address`.
+    /// `flags` are the flags of the page mapped through the table, some architectures need to allow them in every level.
+    fn table_entry(address: PhysAddr, flags: PageTableFlags) -> u64;
+
+    /// Encodes an entry mapping the page at `address` in a page table of the given level.
+    fn page_entry(address: PhysAddr, flags: PageTableFlags, level: u8) -> u64;
+
+    /// Returns whenever the entry maps a page or references a table.
+    fn is_present(entry: u64) -> bool;
+
+    /// Returns whenever the present entry in a page table of the given level maps a page instead of referencing a table.
+    fn is_page(entry: u64, level: u8) -> bool;
+
+    /// Returns the physical address of the page or table referenced by the entry.
+    fn address(entry: u64) -> PhysAddr;
+
+    /// Returns the flags of an entry mapping a page.
+    fn flags(entry: u64) -> PageTableFlags;
+}
+
+/// Architecture independent flags of a mapped page.
+/// Every present page is readable, but only accessible by the kernel, if [`PageTableFlags::USER`] isn't set.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PageTableFlags(u32);
+
+impl PageTableFlags {
+    /// The page can be written to.
+    pub const WRITABLE: Self = PageTableFlags(1 << 0);
+
+    /// Code on the page can be executed.
+    pub const EXECUTABLE: Self = PageTableFlags(1 << 1);
+
+    /// The page can be accessed by userspace.
+    pub const USER: Self = PageTableFlags(1 << 2);
+
+    /// The page is mapped the same in every address space and not flushed from the TLB on a page table switch.
+    pub const GLOBAL: Self = PageTableFlags(1 << 3);
+
+    /// Accesses to the page bypass the cache, used for memory mapped devices.
+    pub const NO_CACHE: Self = PageTableFlags(1 << 4);
+
+    /// The page was accessed since the flag was cleared, set by the hardware.
+    pub const ACCESSED: Self = PageTableFlags(1 << 5);
+
+    /// The page was written to since the flag was cleared, set by the hardware.
+    pub const DIRTY: Self = PageTableFlags(1 << 6);
+
+    /// Returns flags with none set.
+    pub const fn empty() -> Self {
+        PageTableFlags(0)
+    }
+
+    /// Returns whenever all flags in `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns whenever no flag is set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Sets all flags in `other`.
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Clears all flags in `other`.
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl BitOr for PageTableFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        PageTableFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for PageTableFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.insert(rhs);
+    }
+}
+
+impl BitAnd for PageTableFlags {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        PageTableFlags(self.0 & rhs.0)
+    }
+}
+
+impl Not for PageTableFlags {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        PageTableFlags(!self.0 & 0x7F)
+    }
+}
+
+impl Debug for PageTableFlags {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        const NAMES: [(PageTableFlags, &str); 7] = [
+            (PageTableFlags::WRITABLE, "WRITABLE"),
+            (PageTableFlags::EXECUTABLE, "EXECUTABLE"),
+            (PageTableFlags::USER, "USER"),
+            (PageTableFlags::GLOBAL, "GLOBAL"),
+            (PageTableFlags::NO_CACHE, "NO_CACHE"),
+            (PageTableFlags::ACCESSED, "ACCESSED"),
+            (PageTableFlags::DIRTY, "DIRTY"),
+        ];
+
+        let mut set = f.debug_set();
+        for (flag, name) in NAMES {
+            if self.contains(flag) {
+                set.entry(&format_args!("{}", name));
+            }
+        }
+        set.finish()
+    }
+}
+
+/// A single entry of a [`PageTable`], encoded for the architecture `A`.
+#[repr(transparent)]
+pub struct PageTableEntry<A: Architecture = Native> {
+    entry: u64,
+    _architecture: PhantomData<A>,
+}
+
+impl<A: Architecture> PageTableEntry<A> {
+    /// Creates an empty entry.
+    pub const fn new() -> Self {
+        PageTableEntry {
+            entry: 0,
+            _architecture: PhantomData,
+        }
+    }
+
+    /// Returns the raw value of the entry.
+    pub const fn raw(&self) -> u64 {
+        self.entry
+    }
+
+    /// Sets the raw value of the entry.
+    ///
+    /// ## Safety
+    ///
+    /// The value has to be a valid entry for the architecture.
+    pub unsafe fn set_raw(&mut self, entry: u64) {
+        self.entry = entry;
+    }
+
+    /// Returns whenever the entry is unused.
+    pub fn is_unused(&self) -> bool {
+        !A::is_present(self.entry)
+    }
+
+    /// Returns whenever the entry in a page table of the given level maps a page.
+    pub fn is_page(&self, level: u8) -> bool {
+        A::is_present(self.entry) && A::is_page(self.entry, level)
+    }
+
+    /// Returns whenever the entry in a page table of the given level references a lower level table.
+    pub fn is_table(&self, level: u8) -> bool {
+        A::is_present(self.entry) && !A::is_page(self.entry, level)
+    }
+
+    /// Returns the physical address of the page or table referenced by the entry.
+    pub fn address(&self) -> PhysAddr {
+        A::address(self.entry)
+    }
+
+    /// Returns the flags of the mapped page.
+    pub fn flags(&self) -> PageTableFlags {
+        A::flags(self.entry)
+    }
+
+    /// Maps the page at `address` with the given flags in a page table of the given level.
+    pub fn set_page(&mut self, address: PhysAddr, flags: PageTableFlags, level: u8) {
+        self.entry = A::page_entry(address, flags, level);
+    }
+
+    /// References the lower level table at `address`, allowing pages with the given flags.
+    pub fn set_table(&mut self, address: PhysAddr, flags: PageTableFlags) {
+        self.entry = A::table_entry(address, flags);
+    }
+
+    /// Clears the entry.
+    pub fn clear(&mut self) {
+        self.entry = 0;
+    }
+}
+
+impl<A: Architecture> Clone for PageTableEntry<A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<A: Architecture> Copy for PageTableEntry<A> {}
+
+impl<A: Architecture> Default for PageTableEntry<A> {
+    fn default() -> Self {
+        PageTableEntry::new()
+    }
+}
+
+impl<A: Architecture> Debug for PageTableEntry<A> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PageTableEntry")
+            .field(&format_args!("{:#x}", self.entry))
+            .finish()
+    }
+}
+
+/// A page table in any level, aligned to a 4 KiB page.
+#[repr(C, align(4096))]
+pub struct PageTable<A: Architecture = Native> {
+    entries: [PageTableEntry<A>; ENTRY_COUNT],
+}
+
+impl<A: Architecture> PageTable<A> {
+    /// Creates a page table with all entries unused.
+    pub const fn new() -> Self {
+        PageTable {
+            entries: [PageTableEntry::new(); ENTRY_COUNT],
+        }
+    }
+
+    /// Returns the page table at the physical address, accessed through a mapping of all physical memory at `offset`.
+    ///
+    /// ## Safety
+    ///
+    /// `address` has to point to a page table, that isn't referenced anywhere else at the same time,
+    /// and all physical memory has to be mapped at `offset`.
+    pub unsafe fn from_physical<'a>(address: PhysAddr, offset: u64) -> &'a mut Self {
+        &mut *((offset + address.as_u64()) as *mut Self)
+    }
+
+    /// Marks all entries as unused.
+    pub fn zero(&mut self) {
+        self.entries.iter_mut().for_each(PageTableEntry::clear);
+    }
+
+    /// Returns an iterator over all entries.
+    pub fn iter(&self) -> impl Iterator<Item = &PageTableEntry<A>> {
+        self.entries.iter()
+    }
+
+    /// Returns an iterator over all entries, allowing to modify them.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut PageTableEntry<A>> {
+        self.entries.iter_mut()
+    }
+}
+
+impl<A: Architecture> Clone for PageTable<A> {
+    fn clone(&self) -> Self {
+        PageTable {
+            entries: self.entries,
+        }
+    }
+}
+
+impl<A: Architecture> Default for PageTable<A> {
+    fn default() -> Self {
+        PageTable::new()
+    }
+}
+
+impl<A: Architecture> Index<usize> for PageTable<A> {
+    type Output = PageTableEntry<A>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.entries[index]
+    }
+}
+
+impl<A: Architecture> IndexMut<usize> for PageTable<A> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.entries[index]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::memory::{MemoryInfo, MEMORY_INFO};
+
+    /// Sets up the memory info needed by the address types, for 5 level paging.
+    pub fn setup() {
+        MEMORY_INFO.get_or_init(|| MemoryInfo {
+            virtual_address_bits: 57,
+            physical_address_bits: 52,
+            page_table_entry_address_mask: 0x000ffffffffff000,
+            highest_page_table_level: 5,
+        });
+    }
+}
@@ -0,0 +1,442 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use super::entry_size;
+use crate::addr::{PhysAddr, VirtAddr};
+use core::fmt::{self, Debug, Formatter};
+use core::marker::PhantomData;
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+
+/// Error returned when an address passed to [`Page`] or [`PhysFrame`] isn't aligned to the page size.
+#[derive(Debug)]
+pub struct AddressNotAligned(pub u64);
+
+/// The size of a page or frame, named after the memory it covers.
+pub trait PageSize: Copy + Eq + Ord + Debug {
+    /// The size in bytes.
+    const SIZE: u64 = entry_size(Self::LEVEL);
+
+    /// The level of the page table mapping pages of this size.
+    const LEVEL: u8;
+
+    /// A human readable name of the size.
+    const NAME: &'static str;
+}
+
+/// A 4 KiB page, mapped by a level 1 page table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Size4KiB {}
+
+/// A 2 MiB page, mapped by a level 2 page table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Size2MiB {}
+
+/// A 1 GiB page, mapped by a level 3 page table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Size1GiB {}
+
+impl PageSize for Size4KiB {
+    const LEVEL: u8 = 1;
+    const NAME: &'static str = "4KiB";
+}
+
+impl PageSize for Size2MiB {
+    const LEVEL: u8 = 2;
+    const NAME: &'static str = "2MiB";
+}
+
+impl PageSize for Size1GiB {
+    const LEVEL: u8 = 3;
+    const NAME: &'static str = "1GiB";
+}
+
+/// A page of virtual memory of the size `S`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Page<S: PageSize = Size4KiB> {
+    start: VirtAddr,
+    _size: PhantomData<S>,
+}
+
+impl<S: PageSize> Page<S> {
+    /// The size of the page in bytes.
+    pub const SIZE: u64 = S::SIZE;
+
+    /// Returns the page starting at `address`, fails if the address isn't aligned to the page size.
+    pub fn from_start_address(address: VirtAddr) -> Result<Self, AddressNotAligned> {
+        if !address.is_aligned(S::SIZE) {
+            return Err(AddressNotAligned(address.as_u64()));
+        }
+
+        Ok(Page {
+            start: address,
+            _size: PhantomData,
+        })
+    }
+
+    /// Returns the page starting at `address`, without checking the alignment.
+    ///
+    /// ## Safety
+    ///
+    /// The address has to be aligned to the page size.
+    pub const unsafe fn from_start_address_unchecked(address: VirtAddr) -> Self {
+        Page {
+            start: address,
+            _size: PhantomData,
+        }
+    }
+
+    /// Returns the page containing `address`.
+    pub fn containing_address(address: VirtAddr) -> Self {
+        Page {
+            start: address.align_down(S::SIZE),
+            _size: PhantomData,
+        }
+    }
+
+    /// Returns the range of pages from `start` up to, but excluding, `end`.
+    pub fn range(start: Self, end: Self) -> PageRange<S> {
+        PageRange { start, end }
+    }
+
+    /// Returns the first address of the page.
+    pub const fn start_address(self) -> VirtAddr {
+        self.start
+    }
+
+    /// Returns the size of the page in bytes.
+    pub const fn size(self) -> u64 {
+        S::SIZE
+    }
+}
+
+impl<S: PageSize> Debug for Page<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Page[{}]({:#x})", S::NAME, self.start.as_u64())
+    }
+}
+
+impl<S: PageSize> Add<u64> for Page<S> {
+    type Output = Self;
+
+    fn add(self, rhs: u64) -> Self::Output {
+        Page::containing_address(self.start + rhs * S::SIZE)
+    }
+}
+
+impl<S: PageSize> AddAssign<u64> for Page<S> {
+    fn add_assign(&mut self, rhs: u64) {
+        *self = *self + rhs;
+    }
+}
+
+impl<S: PageSize> Sub<u64> for Page<S> {
+    type Output = Self;
+
+    fn sub(self, rhs: u64) -> Self::Output {
+        Page::containing_address(self.start - rhs * S::SIZE)
+    }
+}
+
+impl<S: PageSize> SubAssign<u64> for Page<S> {
+    fn sub_assign(&mut self, rhs: u64) {
+        *self = *self - rhs;
+    }
+}
+
+impl<S: PageSize> Sub<Page<S>> for Page<S> {
+    type Output = u64;
+
+    /// Returns the number of pages between both pages.
+    fn sub(self, rhs: Page<S>) -> Self::Output {
+        (self.start.as_u64() - rhs.start.as_u64()) / S::SIZE
+    }
+}
+
+/// A frame of physical memory of the size `S`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PhysFrame<S: PageSize = Size4KiB> {
+    start: PhysAddr,
+    _size: PhantomData<S>,
+}
+
+impl<S: PageSize> PhysFrame<S> {
+    /// The size of the frame in bytes.
+    pub const SIZE: u64 = S::SIZE;
+
+    /// Returns the frame starting at `address`, fails if the address isn't aligned to the frame size.
+    pub fn from_start_address(address: PhysAddr) -> Result<Self, AddressNotAligned> {
+        if !address.is_aligned(S::SIZE) {
+            return Err(AddressNotAligned(address.as_u64()));
+        }
+
+        Ok(PhysFrame {
+            start: address,
+            _size: PhantomData,
+        })
+    }
+
+    /// Returns the frame starting at `address`, without checking the alignment.
+    ///
+    /// ## Safety
+    ///
+    /// The address has to be aligned to the frame size.
+    pub const unsafe fn from_start_address_unchecked(address: PhysAddr) -> Self {
+        PhysFrame {
+            start: address,
+            _size: PhantomData,
+        }
+    }
+
+    /// Returns the frame containing `address`.
+    pub fn containing_address(address: PhysAddr) -> Self {
+        PhysFrame {
+            start: address.align_down(S::SIZE),
+            _size: PhantomData,
+        }
+    }
+
+    /// Returns the range of frames from `start` up to, but excluding, `end`.
+    pub fn range(start: Self, end: Self) -> FrameRange<S> {
+        FrameRange { start, end }
+    }
+
+    /// Returns the first address of the frame.
+    pub const fn start_address(self) -> PhysAddr {
+        self.start
+    }
+
+    /// Returns the size of the frame in bytes.
+    pub const fn size(self) -> u64 {
+        S::SIZE
+    }
+}
+
+impl<S: PageSize> Debug for PhysFrame<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "PhysFrame[{}]({:#x})", S::NAME, self.start.as_u64())
+    }
+}
+
+impl<S: PageSize> Add<u64> for PhysFrame<S> {
+    type Output = Self;
+
+    fn add(self, rhs: u64) -> Self::Output {
+        PhysFrame::containing_address(self.start + rhs * S::SIZE)
+    }
+}
+
+impl<S: PageSize> AddAssign<u64> for PhysFrame<S> {
+    fn add_assign(&mut self, rhs: u64) {
+        *self = *self + rhs;
+    }
+}
+
+impl<S: PageSize> Sub<u64> for PhysFrame<S> {
+    type Output = Self;
+
+    fn sub(self, rhs: u64) -> Self::Output {
+        PhysFrame::containing_address(self.start - rhs * S::SIZE)
+    }
+}
+
+impl<S: PageSize> SubAssign<u64> for PhysFrame<S> {
+    fn sub_assign(&mut self, rhs: u64) {
+        *self = *self - rhs;
+    }
+}
+
+impl<S: PageSize> Sub<PhysFrame<S>> for PhysFrame<S> {
+    type Output = u64;
+
+    /// Returns the number of frames between both frames.
+    fn sub(self, rhs: PhysFrame<S>) -> Self::Output {
+        (self.start.as_u64() - rhs.start.as_u64()) / S::SIZE
+    }
+}
+
+/// An iterator over the pages from `start` up to, but excluding, `end`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PageRange<S: PageSize = Size4KiB> {
+    /// The first page of the range.
+    pub start: Page<S>,
+
+    /// The page after the last page of the range.
+    pub end: Page<S>,
+}
+
+impl<S: PageSize> PageRange<S> {
+    /// Returns whenever the range contains no pages.
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    /// Returns the number of pages in the range.
+    pub fn len(&self) -> u64 {
+        if self.is_empty() {
+            0
+        } else {
+            self.end - self.start
+        }
+    }
+}
+
+impl<S: PageSize> Iterator for PageRange<S> {
+    type Item = Page<S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let page = self.start;
+        self.start += 1;
+        Some(page)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len() as usize;
+        (len, Some(len))
+    }
+}
+
+impl<S: PageSize> Debug for PageRange<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PageRange")
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .finish()
+    }
+}
+
+/// An iterator over the frames from `start` up to, but excluding, `end`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameRange<S: PageSize = Size4KiB> {
+    /// The first frame of the range.
+    pub start: PhysFrame<S>,
+
+    /// The frame after the last frame of the range.
+    pub end: PhysFrame<S>,
+}
+
+impl<S: PageSize> FrameRange<S> {
+    /// Returns whenever the range contains no frames.
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    /// Returns the number of frames in the range.
+    pub fn len(&self) -> u64 {
+        if self.is_empty() {
+            0
+        } else {
+            self.end - self.start
+        }
+    }
+}
+
+impl<S: PageSize> Iterator for FrameRange<S> {
+    type Item = PhysFrame<S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let frame = self.start;
+        self.start += 1;
+        Some(frame)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len() as usize;
+        (len, Some(len))
+    }
+}
+
+impl<S: PageSize> Debug for FrameRange<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameRange")
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Page, PhysFrame, Size1GiB, Size2MiB, Size4KiB};
+    use crate::addr::{PhysAddr, VirtAddr};
+    use crate::paging::test::setup;
+
+    #[test]
+    fn test_page() {
+        setup();
+
+        let page = Page::<Size4KiB>::containing_address(VirtAddr::new(0x1234_5678));
+        assert_eq!(page.start_address(), VirtAddr::new(0x1234_5000));
+        assert_eq!(
+            page + 2,
+            Page::containing_address(VirtAddr::new(0x1234_7000))
+        );
+        assert_eq!((page + 2) - page, 2);
+
+        assert!(Page::<Size2MiB>::from_start_address(VirtAddr::new(0x20_1000)).is_err());
+        let page = Page::<Size2MiB>::from_start_address(VirtAddr::new(0x40_0000)).unwrap();
+        assert_eq!(page.size(), 0x20_0000);
+        assert_eq!(page - 1, Page::containing_address(VirtAddr::new(0x20_0000)));
+        assert_eq!(Page::<Size1GiB>::SIZE, 0x4000_0000);
+    }
+
+    #[test]
+    fn test_frame() {
+        setup();
+
+        let frame = PhysFrame::<Size4KiB>::containing_address(PhysAddr::new(0x1FFF));
+        assert_eq!(frame.start_address(), PhysAddr::new(0x1000));
+        assert!(PhysFrame::<Size1GiB>::from_start_address(PhysAddr::new(0x20_0000)).is_err());
+        assert!(PhysFrame::<Size2MiB>::from_start_address(PhysAddr::new(0x20_0000)).is_ok());
+    }
+
+    #[test]
+    fn test_page_range() {
+        setup();
+
+        let start = Page::<Size4KiB>::containing_address(VirtAddr::new(0x1000));
+        let range = Page::range(start, start + 3);
+        assert_eq!(range.len(), 3);
+        assert_eq!(
+            range.map(Page::start_address).collect::<Vec<_>>(),
+            [
+                VirtAddr::new(0x1000),
+                VirtAddr::new(0x2000),
+                VirtAddr::new(0x3000)
+            ]
+        );
+        assert_eq!(Page::range(start + 3, start).count(), 0);
+    }
+
+    #[test]
+    fn test_frame_range() {
+        setup();
+
+        let start = PhysFrame::<Size2MiB>::containing_address(PhysAddr::new(0));
+        let range = PhysFrame::range(start, start + 2);
+        assert_eq!(range.len(), 2);
+        assert_eq!(range.last(), Some(start + 1));
+        assert!(PhysFrame::range(start, start).is_empty());
+    }
+
+    #[test]
+    fn test_indices() {
+        setup();
+
+        // Sign extension | 0x1F0 | 0x11F | 0x1C7 | 0x14D | 0x130 | 0x123
+        let address = VirtAddr::new(0xFFF0_8FF1_E9B3_0123);
+        assert_eq!(address.page_offset(), 0x123);
+        assert_eq!(address.p1_index(), 0x130);
+        assert_eq!(address.p2_index(), 0x14D);
+        assert_eq!(address.p3_index(), 0x1C7);
+        assert_eq!(address.p4_index(), 0x11F);
+        assert_eq!(address.p5_index(), 0x1F0);
+    }
+}
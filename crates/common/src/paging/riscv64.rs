@@ -0,0 +1,161 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! RISC-V Sv48 and Sv57 page table entries.
+//!
+//! Uncached mappings use the I/O memory type of the Svpbmt extension.
+use super::{Architecture, PageTableFlags};
+use crate::addr::PhysAddr;
+
+const VALID: u64 = 1 << 0;
+const READABLE: u64 = 1 << 1;
+const WRITABLE: u64 = 1 << 2;
+const EXECUTABLE: u64 = 1 << 3;
+const USER: u64 = 1 << 4;
+const GLOBAL: u64 = 1 << 5;
+const ACCESSED: u64 = 1 << 6;
+const DIRTY: u64 = 1 << 7;
+const MEMORY_TYPE_IO: u64 = 2 << 61;
+
+/// Position of the physical page number in an entry.
+const PAGE_NUMBER_SHIFT: u64 = 10;
+
+/// Mask of the 44-bit physical page number, after shifting it down.
+const PAGE_NUMBER_MASK: u64 = (1 << 44) - 1;
+
+/// The RISC-V [`Architecture`] backend.
+pub struct RiscV64;
+
+impl Architecture for RiscV64 {
+    fn table_entry(address: PhysAddr, _: PageTableFlags) -> u64 {
+        ((address.as_u64() >> 12) << PAGE_NUMBER_SHIFT) | VALID
+    }
+
+    fn page_entry(address: PhysAddr, flags: PageTableFlags, _: u8) -> u64 {
+        // Accessed and dirty are always set, since not every implementation manages them in hardware.
+        let mut entry =
+            ((address.as_u64() >> 12) << PAGE_NUMBER_SHIFT) | VALID | READABLE | ACCESSED | DIRTY;
+        if flags.contains(PageTableFlags::WRITABLE) {
+            entry |= WRITABLE;
+        }
+        if flags.contains(PageTableFlags::EXECUTABLE) {
+            entry |= EXECUTABLE;
+        }
+        if flags.contains(PageTableFlags::USER) {
+            entry |= USER;
+        }
+        if flags.contains(PageTableFlags::GLOBAL) {
+            entry |= GLOBAL;
+        }
+        if flags.contains(PageTableFlags::NO_CACHE) {
+            entry |= MEMORY_TYPE_IO;
+        }
+        entry
+    }
+
+    fn is_present(entry: u64) -> bool {
+        entry & VALID != 0
+    }
+
+    fn is_page(entry: u64, _: u8) -> bool {
+        entry & (READABLE | WRITABLE | EXECUTABLE) != 0
+    }
+
+    fn address(entry: u64) -> PhysAddr {
+        PhysAddr::new_truncate(((entry >> PAGE_NUMBER_SHIFT) & PAGE_NUMBER_MASK) << 12)
+    }
+
+    fn flags(entry: u64) -> PageTableFlags {
+        let mut flags = PageTableFlags::empty();
+        if entry & WRITABLE != 0 {
+            flags |= PageTableFlags::WRITABLE;
+        }
+        if entry & EXECUTABLE != 0 {
+            flags |= PageTableFlags::EXECUTABLE;
+        }
+        if entry & USER != 0 {
+            flags |= PageTableFlags::USER;
+        }
+        if entry & GLOBAL != 0 {
+            flags |= PageTableFlags::GLOBAL;
+        }
+        if entry & MEMORY_TYPE_IO == MEMORY_TYPE_IO {
+            flags |= PageTableFlags::NO_CACHE;
+        }
+        if entry & ACCESSED != 0 {
+            flags |= PageTableFlags::ACCESSED;
+        }
+        if entry & DIRTY != 0 {
+            flags |= PageTableFlags::DIRTY;
+        }
+        flags
+    }
+}
+
+/// Returns the physical address of the active top-level page table.
+#[cfg(target_arch = "riscv64")]
+pub fn active_page_table() -> PhysAddr {
+    let satp: u64;
+    unsafe {
+        core::arch::asm!("CSRR {}, satp", out(reg) satp, options(nomem, nostack, preserves_flags))
+    };
+    PhysAddr::new_truncate((satp & PAGE_NUMBER_MASK) << 12)
+}
+
+/// Switches the active top-level page table, using Sv57 with 5 levels and Sv48 otherwise.
+///
+/// ## Safety
+///
+/// The page table has to map the currently executing code and stack.
+#[cfg(target_arch = "riscv64")]
+pub unsafe fn set_active_page_table(table: PhysAddr) {
+    let mode: u64 = if crate::memory::get_memory_info().highest_page_table_level == 5 {
+        10
+    } else {
+        9
+    };
+    let satp = (mode << 60) | (table.as_u64() >> 12);
+    core::arch::asm!("CSRW satp, {}", "SFENCE.VMA", in(reg) satp, options(nostack, preserves_flags));
+}
+
+/// Flushes the TLB entry for the given address.
+#[cfg(target_arch = "riscv64")]
+pub fn flush(virt: crate::addr::VirtAddr) {
+    unsafe {
+        core::arch::asm!("SFENCE.VMA {}, zero", in(reg) virt.as_u64(), options(nostack, preserves_flags))
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::RiscV64;
+    use crate::addr::PhysAddr;
+    use crate::paging::test::setup;
+    use crate::paging::{Architecture, PageTableFlags};
+
+    #[test]
+    fn test_page_entry() {
+        setup();
+
+        let address = PhysAddr::new(0x1234_5000);
+        let flags = PageTableFlags::WRITABLE
+            | PageTableFlags::USER
+            | PageTableFlags::ACCESSED
+            | PageTableFlags::DIRTY;
+        let entry = RiscV64::page_entry(address, flags, 1);
+        assert_eq!(entry, 0x048D_14D7);
+        assert!(RiscV64::is_page(entry, 1));
+        assert_eq!(RiscV64::address(entry), address);
+        assert_eq!(RiscV64::flags(entry), flags);
+    }
+
+    #[test]
+    fn test_table_entry() {
+        setup();
+
+        let entry = RiscV64::table_entry(PhysAddr::new(0x2000), PageTableFlags::USER);
+        assert_eq!(entry, 0x801);
+        assert!(!RiscV64::is_page(entry, 2));
+        assert_eq!(RiscV64::address(entry), PhysAddr::new(0x2000));
+    }
+}
@@ -0,0 +1,181 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! x86_64 page table entries, for 4 and 5 level paging.
+use super::{Architecture, PageTableFlags};
+use crate::addr::PhysAddr;
+use crate::memory::get_memory_info;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+const PRESENT: u64 = 1 << 0;
+const WRITABLE: u64 = 1 << 1;
+const USER: u64 = 1 << 2;
+const WRITE_THROUGH: u64 = 1 << 3;
+const NO_CACHE: u64 = 1 << 4;
+const ACCESSED: u64 = 1 << 5;
+const DIRTY: u64 = 1 << 6;
+const HUGE_PAGE: u64 = 1 << 7;
+const GLOBAL: u64 = 1 << 8;
+const NO_EXECUTE: u64 = 1 << 63;
+
+/// Whenever the no-execute bit is enabled in the EFER.
+/// Setting the bit while it's disabled is a reserved bit violation, so it's only used once enabled.
+static NO_EXECUTE_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The x86_64 [`Architecture`] backend.
+pub struct X86_64;
+
+impl X86_64 {
+    /// Marks the no-execute bit as enabled, so pages without [`PageTableFlags::EXECUTABLE`] use it.
+    ///
+    /// ## Safety
+    ///
+    /// The no-execute bit has to be enabled in the EFER.
+    pub unsafe fn set_no_execute_enabled() {
+        NO_EXECUTE_ENABLED.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Architecture for X86_64 {
+    fn table_entry(address: PhysAddr, flags: PageTableFlags) -> u64 {
+        let user = if flags.contains(PageTableFlags::USER) {
+            USER
+        } else {
+            0
+        };
+
+        address.as_u64() | PRESENT | WRITABLE | user
+    }
+
+    fn page_entry(address: PhysAddr, flags: PageTableFlags, level: u8) -> u64 {
+        let mut entry = address.as_u64() | PRESENT;
+        if level > 1 {
+            entry |= HUGE_PAGE;
+        }
+        if flags.contains(PageTableFlags::WRITABLE) {
+            entry |= WRITABLE;
+        }
+        if !flags.contains(PageTableFlags::EXECUTABLE) && NO_EXECUTE_ENABLED.load(Ordering::Relaxed)
+        {
+            entry |= NO_EXECUTE;
+        }
+        if flags.contains(PageTableFlags::USER) {
+            entry |= USER;
+        }
+        if flags.contains(PageTableFlags::GLOBAL) {
+            entry |= GLOBAL;
+        }
+        if flags.contains(PageTableFlags::NO_CACHE) {
+            entry |= NO_CACHE | WRITE_THROUGH;
+        }
+        if flags.contains(PageTableFlags::ACCESSED) {
+            entry |= ACCESSED;
+        }
+        if flags.contains(PageTableFlags::DIRTY) {
+            entry |= DIRTY;
+        }
+        entry
+    }
+
+    fn is_present(entry: u64) -> bool {
+        entry & PRESENT != 0
+    }
+
+    fn is_page(entry: u64, level: u8) -> bool {
+        level == 1 || entry & HUGE_PAGE != 0
+    }
+
+    fn address(entry: u64) -> PhysAddr {
+        PhysAddr::new_truncate(entry & get_memory_info().page_table_entry_address_mask)
+    }
+
+    fn flags(entry: u64) -> PageTableFlags {
+        let mut flags = PageTableFlags::empty();
+        if entry & WRITABLE != 0 {
+            flags |= PageTableFlags::WRITABLE;
+        }
+        if entry & NO_EXECUTE == 0 {
+            flags |= PageTableFlags::EXECUTABLE;
+        }
+        if entry & USER != 0 {
+            flags |= PageTableFlags::USER;
+        }
+        if entry & GLOBAL != 0 {
+            flags |= PageTableFlags::GLOBAL;
+        }
+        if entry & NO_CACHE != 0 {
+            flags |= PageTableFlags::NO_CACHE;
+        }
+        if entry & ACCESSED != 0 {
+            flags |= PageTableFlags::ACCESSED;
+        }
+        if entry & DIRTY != 0 {
+            flags |= PageTableFlags::DIRTY;
+        }
+        flags
+    }
+}
+
+/// Returns the physical address of the active top-level page table.
+#[cfg(target_arch = "x86_64")]
+pub fn active_page_table() -> PhysAddr {
+    let cr3: u64;
+    unsafe {
+        core::arch::asm!("MOV {}, CR3", out(reg) cr3, options(nomem, nostack, preserves_flags))
+    };
+    PhysAddr::new_truncate(cr3 & get_memory_info().page_table_entry_address_mask)
+}
+
+/// Switches the active top-level page table.
+///
+/// ## Safety
+///
+/// The page table has to map the currently executing code and stack.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn set_active_page_table(table: PhysAddr) {
+    core::arch::asm!("MOV CR3, {}", in(reg) table.as_u64(), options(nostack, preserves_flags));
+}
+
+/// Flushes the TLB entry for the given address.
+#[cfg(target_arch = "x86_64")]
+pub fn flush(virt: crate::addr::VirtAddr) {
+    unsafe {
+        core::arch::asm!("INVLPG [{}]", in(reg) virt.as_u64(), options(nostack, preserves_flags))
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NO_EXECUTE, X86_64};
+    use crate::addr::PhysAddr;
+    use crate::paging::test::setup;
+    use crate::paging::{Architecture, PageTableFlags};
+
+    #[test]
+    fn test_page_entry() {
+        setup();
+
+        let address = PhysAddr::new(0x1234_5000);
+        let flags = PageTableFlags::WRITABLE | PageTableFlags::USER | PageTableFlags::EXECUTABLE;
+        let entry = X86_64::page_entry(address, flags, 1);
+        assert_eq!(entry, 0x1234_5007);
+        assert!(X86_64::is_present(entry));
+        assert!(X86_64::is_page(entry, 1));
+        assert_eq!(X86_64::address(entry), address);
+        assert_eq!(X86_64::flags(entry), flags);
+
+        let huge = X86_64::page_entry(PhysAddr::new(0x4000_0000), PageTableFlags::GLOBAL, 3);
+        assert!(X86_64::is_page(huge, 3));
+        assert_eq!(huge & !NO_EXECUTE, 0x4000_0181);
+    }
+
+    #[test]
+    fn test_table_entry() {
+        setup();
+
+        let entry = X86_64::table_entry(PhysAddr::new(0x2000), PageTableFlags::USER);
+        assert_eq!(entry, 0x2007);
+        assert!(!X86_64::is_page(entry, 2));
+        assert_eq!(X86_64::address(entry), PhysAddr::new(0x2000));
+    }
+}
@@ -0,0 +1,107 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Power
+//!
+//! Restarting and turning off the machine.
+//!
+//! The module managing the power of the system, usually the ACPI module, installs its handlers with
+//! [`set_reboot_handler`] and [`set_shutdown_handler`]. Without handlers [`reboot`] and [`shutdown`] just [`halt`].
+//!
+//! Automated runs shut the machine down once the kernel booted and report panics with [`exit_qemu`].
+//!
+use crate::sync::SyncOnceCell;
+
+/// Restarts the machine.
+pub type RebootHandler = fn() -> !;
+
+/// Turns the machine off, returns only if that failed.
+pub type ShutdownHandler = fn();
+
+/// The result of an automated run, written to the `isa-debug-exit` device of QEMU.
+/// QEMU exits with the status `(code << 1) | 1`, so neither code can be confused with a normal power-off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ExitCode {
+    Success = 0x10,
+    Failure = 0x11,
+}
+
+/// The I/O port of the `isa-debug-exit` device added by `cargo xtask run --exit`.
+#[cfg(target_arch = "x86_64")]
+const DEBUG_EXIT_PORT: u16 = 0xF4;
+
+static REBOOT_HANDLER: SyncOnceCell<RebootHandler> = SyncOnceCell::new();
+
+static SHUTDOWN_HANDLER: SyncOnceCell<ShutdownHandler> = SyncOnceCell::new();
+
+/// Installs the handler called by [`reboot`].
+/// The handler can only be installed once, it is returned as error if one is already installed.
+pub fn set_reboot_handler(handler: RebootHandler) -> Result<(), RebootHandler> {
+    REBOOT_HANDLER.set(handler)
+}
+
+/// Installs the handler called by [`shutdown`].
+/// The handler can only be installed once, it is returned as error if one is already installed.
+pub fn set_shutdown_handler(handler: ShutdownHandler) -> Result<(), ShutdownHandler> {
+    SHUTDOWN_HANDLER.set(handler)
+}
+
+/// Restarts the machine, or halts it if no reboot handler is installed.
+pub fn reboot() -> ! {
+    if let Some(handler) = REBOOT_HANDLER.get() {
+        handler();
+    }
+
+    halt()
+}
+
+/// Turns the machine off, or halts it if no shutdown handler is installed or turning it off failed.
+pub fn shutdown() -> ! {
+    if let Some(handler) = SHUTDOWN_HANDLER.get() {
+        handler();
+    }
+
+    halt()
+}
+
+/// Exits QEMU with the given code.
+/// Returns if the `isa-debug-exit` device isn't present, so this should only be used by automated runs.
+#[cfg(target_arch = "x86_64")]
+pub fn exit_qemu(code: ExitCode) {
+    // Safety: Writing the port only has an effect if the device is present.
+    unsafe {
+        core::arch::asm!(
+            "out dx, al",
+            in("dx") DEBUG_EXIT_PORT,
+            in("al") code as u8,
+            options(nomem, nostack, preserves_flags)
+        )
+    }
+}
+
+/// There is no `isa-debug-exit` device on other architectures, so this always returns.
+#[cfg(not(target_arch = "x86_64"))]
+pub fn exit_qemu(_code: ExitCode) {}
+
+/// Stops the current processor for good, with interrupts disabled.
+#[cfg(target_arch = "x86_64")]
+pub fn halt() -> ! {
+    loop {
+        unsafe { core::arch::asm!("cli", "hlt", options(nomem, nostack)) }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn halt() -> ! {
+    loop {
+        unsafe { core::arch::asm!("msr DAIFSet, 0b1111", "wfi", options(nomem, nostack)) }
+    }
+}
+
+#[cfg(target_arch = "riscv64")]
+pub fn halt() -> ! {
+    loop {
+        unsafe { core::arch::asm!("csrci sstatus, 2", "wfi", options(nomem, nostack)) }
+    }
+}
@@ -0,0 +1,337 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Time
+//!
+//! The time base of the kernel, shared by timeouts, scheduling and timestamps.
+//!
+//! - A [`ClockSource`] is a free running counter with a known frequency.
+//!   Timer modules register their clock sources during initialization and the one with the highest rating
+//!   drives the monotonic clock of [`monotonic_nanoseconds`]. Switching to a better source keeps the clock continuous.
+//! - A [`ClockEvent`] is a device raising an interrupt after a delay, once or periodically.
+//!   The clock event with the highest rating is returned by [`clock_event`],
+//!   its interrupts call the handler installed with [`set_clock_event_handler`].
+//!
+//! Until the first clock source is registered [`monotonic_nanoseconds`] returns `None`,
+//! so early users have to cope without a time source.
+//!
+use crate::sync::{Spinlock, SyncOnceCell};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The maximal number of clock sources and clock events, which can be selected over the lifetime of the kernel.
+const MAX_SELECTIONS: usize = 8;
+
+/// Index of [`CURRENT_SOURCE`] and [`CURRENT_EVENT`] while nothing is selected.
+const NONE_SELECTED: usize = usize::MAX;
+
+/// Nanoseconds per second.
+const NANOSECONDS_PER_SECOND: u128 = 1_000_000_000;
+
+/// A free running counter with a known frequency.
+pub trait ClockSource: Sync {
+    /// The name of the clock source, used in logs.
+    fn name(&self) -> &'static str;
+
+    /// The quality of the clock source, the source with the highest rating is used.
+    fn rating(&self) -> u32;
+
+    /// The frequency of the counter in Hz.
+    fn frequency(&self) -> u64;
+
+    /// Reads the counter. Successive reads never return a smaller value.
+    fn read(&self) -> u64;
+}
+
+/// Error returned when programming a clock event fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockEventError {
+    /// The clock event doesn't support periodic events.
+    PeriodicNotSupported,
+
+    /// The delay or period can't be represented by the device.
+    OutOfRange,
+}
+
+/// A device raising an interrupt after a delay, once or periodically.
+/// Every event calls the handler installed with [`set_clock_event_handler`].
+pub trait ClockEvent: Sync {
+    /// The name of the clock event, used in logs.
+    fn name(&self) -> &'static str;
+
+    /// The quality of the clock event, the event with the highest rating is used.
+    fn rating(&self) -> u32;
+
+    /// Whether every processor has its own device, raising events on the processor which programmed it.
+    fn is_per_cpu(&self) -> bool;
+
+    /// Whether the device supports periodic events.
+    fn supports_periodic(&self) -> bool;
+
+    /// Raises a single event after `delay` nanoseconds, replacing any event programmed before.
+    fn set_oneshot(&self, delay: u64) -> Result<(), ClockEventError>;
+
+    /// Raises an event every `period` nanoseconds, replacing any event programmed before.
+    fn set_periodic(&self, period: u64) -> Result<(), ClockEventError>;
+
+    /// Stops raising events.
+    fn stop(&self);
+}
+
+/// Called on every event of the selected [`ClockEvent`].
+pub type ClockEventHandler = fn();
+
+/// A clock source selected for the monotonic clock and the time it was selected at.
+struct Epoch {
+    source: &'static dyn ClockSource,
+
+    /// The monotonic clock when the source was selected.
+    base_nanoseconds: u64,
+
+    /// The counter of the source when it was selected.
+    base_count: u64,
+}
+
+impl Epoch {
+    fn nanoseconds(&self) -> u64 {
+        let ticks = self.source.read().saturating_sub(self.base_count);
+        let nanoseconds = ticks as u128 * NANOSECONDS_PER_SECOND / self.source.frequency() as u128;
+        self.base_nanoseconds + nanoseconds as u64
+    }
+}
+
+/// The clock sources selected so far, each slot is only set once, so readers don't need a lock.
+static EPOCHS: [SyncOnceCell<Epoch>; MAX_SELECTIONS] =
+    [const { SyncOnceCell::new() }; MAX_SELECTIONS];
+
+/// Index into [`EPOCHS`] of the selected clock source.
+static CURRENT_SOURCE: AtomicUsize = AtomicUsize::new(NONE_SELECTED);
+
+/// The clock events selected so far, each slot is only set once, so readers don't need a lock.
+static EVENTS: [SyncOnceCell<&'static dyn ClockEvent>; MAX_SELECTIONS] =
+    [const { SyncOnceCell::new() }; MAX_SELECTIONS];
+
+/// Index into [`EVENTS`] of the selected clock event.
+static CURRENT_EVENT: AtomicUsize = AtomicUsize::new(NONE_SELECTED);
+
+static CLOCK_EVENT_HANDLER: SyncOnceCell<ClockEventHandler> = SyncOnceCell::new();
+
+/// Lock serializing registrations. Readers never take it, so it's never taken in interrupt handlers.
+static REGISTRATION_LOCK: Spinlock<()> = Spinlock::new(());
+
+/// Registers a clock source, which drives the monotonic clock if it has the highest rating so far.
+/// Returns whether the source was selected.
+pub fn register_clock_source(source: &'static dyn ClockSource) -> bool {
+    if source.frequency() == 0 {
+        return false;
+    }
+
+    let _lock = REGISTRATION_LOCK.lock();
+    let index = CURRENT_SOURCE.load(Ordering::Acquire);
+    let current = EPOCHS.get(index).and_then(|x| x.get());
+    if current.is_some_and(|x| x.source.rating() >= source.rating()) {
+        return false;
+    }
+
+    let next = current.map_or(0, |_| index + 1);
+    let Some(slot) = EPOCHS.get(next) else {
+        return false;
+    };
+
+    let epoch = Epoch {
+        source,
+        base_nanoseconds: current.map_or(0, |x| x.nanoseconds()),
+        base_count: source.read(),
+    };
+    if slot.set(epoch).is_err() {
+        return false;
+    }
+
+    CURRENT_SOURCE.store(next, Ordering::Release);
+    true
+}
+
+/// Returns the clock source driving the monotonic clock.
+pub fn clock_source() -> Option<&'static dyn ClockSource> {
+    Some(current_epoch()?.source)
+}
+
+/// Returns the nanoseconds passed since the first clock source was registered, or `None` if there is none yet.
+pub fn monotonic_nanoseconds() -> Option<u64> {
+    Some(current_epoch()?.nanoseconds())
+}
+
+fn current_epoch() -> Option<&'static Epoch> {
+    EPOCHS.get(CURRENT_SOURCE.load(Ordering::Acquire))?.get()
+}
+
+/// Registers a clock event, which is used if it has the highest rating so far.
+/// Returns whether the event was selected, the previously selected event is stopped then.
+pub fn register_clock_event(event: &'static dyn ClockEvent) -> bool {
+    let _lock = REGISTRATION_LOCK.lock();
+    let index = CURRENT_EVENT.load(Ordering::Acquire);
+    let current = EVENTS.get(index).and_then(|x| x.get());
+    if current.is_some_and(|x| x.rating() >= event.rating()) {
+        return false;
+    }
+
+    let next = current.map_or(0, |_| index + 1);
+    if EVENTS.get(next).is_none_or(|x| x.set(event).is_err()) {
+        return false;
+    }
+
+    CURRENT_EVENT.store(next, Ordering::Release);
+    if let Some(current) = current {
+        current.stop();
+    }
+    true
+}
+
+/// Returns the clock event with the highest rating.
+pub fn clock_event() -> Option<&'static dyn ClockEvent> {
+    EVENTS
+        .get(CURRENT_EVENT.load(Ordering::Acquire))?
+        .get()
+        .copied()
+}
+
+/// Installs the handler called on every clock event.
+/// The handler can only be installed once, it is returned as error if one is already installed.
+pub fn set_clock_event_handler(handler: ClockEventHandler) -> Result<(), ClockEventHandler> {
+    CLOCK_EVENT_HANDLER.set(handler)
+}
+
+/// Called by the clock event devices on every event, from their interrupt handler.
+pub fn handle_clock_event() {
+    if let Some(handler) = CLOCK_EVENT_HANDLER.get() {
+        handler();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        clock_event, clock_source, monotonic_nanoseconds, register_clock_event,
+        register_clock_source, ClockEvent, ClockEventError, ClockSource,
+    };
+    use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    struct TestSource {
+        rating: u32,
+        frequency: u64,
+        count: AtomicU64,
+    }
+
+    impl ClockSource for TestSource {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        fn rating(&self) -> u32 {
+            self.rating
+        }
+
+        fn frequency(&self) -> u64 {
+            self.frequency
+        }
+
+        fn read(&self) -> u64 {
+            self.count.load(Ordering::Relaxed)
+        }
+    }
+
+    struct TestEvent {
+        rating: u32,
+        stopped: AtomicBool,
+    }
+
+    impl ClockEvent for TestEvent {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        fn rating(&self) -> u32 {
+            self.rating
+        }
+
+        fn is_per_cpu(&self) -> bool {
+            false
+        }
+
+        fn supports_periodic(&self) -> bool {
+            false
+        }
+
+        fn set_oneshot(&self, _: u64) -> Result<(), ClockEventError> {
+            Ok(())
+        }
+
+        fn set_periodic(&self, _: u64) -> Result<(), ClockEventError> {
+            Err(ClockEventError::PeriodicNotSupported)
+        }
+
+        fn stop(&self) {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    static SLOW: TestSource = TestSource {
+        rating: 100,
+        frequency: 1_000_000,
+        count: AtomicU64::new(500),
+    };
+
+    static FAST: TestSource = TestSource {
+        rating: 300,
+        frequency: 1_000_000_000,
+        count: AtomicU64::new(7_000),
+    };
+
+    static WORSE: TestSource = TestSource {
+        rating: 200,
+        frequency: 1_000,
+        count: AtomicU64::new(0),
+    };
+
+    static HPET: TestEvent = TestEvent {
+        rating: 50,
+        stopped: AtomicBool::new(false),
+    };
+
+    static LAPIC: TestEvent = TestEvent {
+        rating: 100,
+        stopped: AtomicBool::new(false),
+    };
+
+    #[test]
+    fn test_select_clock_source_by_rating() {
+        assert_eq!(monotonic_nanoseconds(), None);
+
+        assert!(register_clock_source(&SLOW));
+        assert_eq!(monotonic_nanoseconds(), Some(0));
+        SLOW.count.fetch_add(2, Ordering::Relaxed);
+        assert_eq!(monotonic_nanoseconds(), Some(2_000));
+
+        // Switching to a better source continues at the current time.
+        assert!(register_clock_source(&FAST));
+        assert_eq!(clock_source().map(|x| x.rating()), Some(300));
+        assert_eq!(monotonic_nanoseconds(), Some(2_000));
+        FAST.count.fetch_add(10, Ordering::Relaxed);
+        assert_eq!(monotonic_nanoseconds(), Some(2_010));
+
+        assert!(!register_clock_source(&WORSE));
+        assert_eq!(clock_source().map(|x| x.rating()), Some(300));
+    }
+
+    #[test]
+    fn test_select_clock_event_by_rating() {
+        assert!(clock_event().is_none());
+
+        assert!(register_clock_event(&HPET));
+        assert!(register_clock_event(&LAPIC));
+        assert!(!register_clock_event(&HPET));
+
+        assert_eq!(clock_event().map(|x| x.rating()), Some(100));
+        assert!(HPET.stopped.load(Ordering::Relaxed));
+        assert!(!LAPIC.stopped.load(Ordering::Relaxed));
+    }
+}
@@ -15,14 +15,18 @@ pub extern crate microdragon_macros as macros;
 pub mod framebuffer;
 pub mod link;
 pub mod memory;
+pub mod smp;
 pub mod stack;
 
 /// Interface to be used bt the different kernel modules.
 #[repr(C)]
 pub struct ModuleInterface {
-    /// Provides info about the kernel's stacks.
+    /// Provides info about the kernel's stacks on the bootstrap processor.
     pub stack_info: stack::StackInfo,
 
+    /// Provides info about all processors and their stacks.
+    pub smp_info: smp::SmpInfo,
+
     /// Pointer to the Root System Description Pointer (RSDP) or `0` if this system doesn't have ACPI.
     pub rsdp_address: u64,
 
@@ -13,6 +13,10 @@ pub struct MemoryMapInfo {
 
     /// Type of the memory map.
     pub memory_map_type: MemoryMapType,
+
+    /// Virtual address at which the bootloader mapped all physical memory.
+    /// Only valid until the kernel memory manager (KMM) switched to it's own page tables.
+    pub physical_memory_offset: u64,
 }
 
 /// The type of the memory map.
@@ -0,0 +1,39 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use crate::stack::StackInfo;
+
+/// The maximal number of processors started by the bootloader, including the bootstrap processor.
+pub const MAX_CPUS: usize = 32;
+
+/// Provides info about the processors of this system.
+#[repr(C)]
+pub struct SmpInfo {
+    /// The number of processors started, including the bootstrap processor.
+    pub cpu_count: u64,
+
+    /// Pointer to an array of `cpu_count` [`CpuInfo`] structs, the bootstrap processor is the first entry.
+    pub cpus: u64,
+}
+
+impl SmpInfo {
+    /// Returns the info of all processors, indexed by [`CpuInfo::index`].
+    pub fn cpus(&self) -> &[CpuInfo] {
+        // Safety: The bootloader provides `cpu_count` entries, which live as long as the kernel.
+        unsafe { core::slice::from_raw_parts(self.cpus as *const CpuInfo, self.cpu_count as usize) }
+    }
+}
+
+/// Provides info about a single processor.
+#[repr(C)]
+pub struct CpuInfo {
+    /// Index of the processor, `0` is the bootstrap processor.
+    pub index: u64,
+
+    /// The id of the processor assigned by the hardware, its local APIC id on x86_64.
+    pub hardware_id: u64,
+
+    /// Provides info about the processor's stacks.
+    pub stack_info: StackInfo,
+}
@@ -10,6 +10,7 @@ pub const SECONDARY_STACK_SIZE: usize = 16 * 1024;
 
 /// Provides info about the kernel's stack.
 #[repr(C)]
+#[derive(Debug, Clone, Copy)]
 pub struct StackInfo {
     /// Pointer to the kernel's primary stack.
     pub primary_stack: u64,
@@ -21,6 +21,7 @@ pub fn include_runner(item: TokenStream) -> syn::Result<TokenStream> {
     } else {
         Ok(quote! {
             fn run_modules(interface: &ModuleInterface) {}
+            fn run_cpu_modules(interface: &ModuleInterface, cpu: &::microdragon_interface::smp::CpuInfo) {}
         })
     }
 }
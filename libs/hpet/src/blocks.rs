@@ -0,0 +1,138 @@
+use crate::HighPrecisionEventTimer;
+
+/// The maximal number of event timer blocks in a system.
+pub const MAX_BLOCKS: usize = 8;
+
+/// Error returned when adding an event timer block fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError {
+    /// The HPET number is not below [`MAX_BLOCKS`].
+    InvalidNumber(u8),
+
+    /// A block with the HPET number was already added.
+    AlreadyPresent(u8),
+}
+
+/// The event timer blocks of a system, each one a separate HPET.
+///
+/// Blocks are identified by their HPET number, the sequence number given to them by the firmware.
+/// On ACPI systems this is the `hpet_number` of the block's HPET table.
+pub struct HpetBlocks {
+    blocks: [Option<HighPrecisionEventTimer>; MAX_BLOCKS],
+}
+
+impl HpetBlocks {
+    /// Creates an empty set of event timer blocks.
+    pub const fn new() -> Self {
+        HpetBlocks {
+            blocks: [const { None }; MAX_BLOCKS],
+        }
+    }
+
+    /// Adds the event timer block with the given HPET number.
+    /// On error the block is dropped.
+    pub fn insert(&mut self, number: u8, hpet: HighPrecisionEventTimer) -> Result<(), BlockError> {
+        let block = self
+            .blocks
+            .get_mut(number as usize)
+            .ok_or(BlockError::InvalidNumber(number))?;
+        if block.is_some() {
+            return Err(BlockError::AlreadyPresent(number));
+        }
+
+        *block = Some(hpet);
+        Ok(())
+    }
+
+    /// Returns the event timer block with the given HPET number.
+    pub fn get(&self, number: u8) -> Option<&HighPrecisionEventTimer> {
+        self.blocks.get(number as usize)?.as_ref()
+    }
+
+    /// Returns the event timer block with the given HPET number.
+    pub fn get_mut(&mut self, number: u8) -> Option<&mut HighPrecisionEventTimer> {
+        self.blocks.get_mut(number as usize)?.as_mut()
+    }
+
+    /// Returns the number of event timer blocks added.
+    pub fn len(&self) -> usize {
+        self.blocks.iter().flatten().count()
+    }
+
+    /// Returns whenever no event timer block was added.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over all event timer blocks with their HPET number, ordered by the number.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &HighPrecisionEventTimer)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(number, block)| Some((number as u8, block.as_ref()?)))
+    }
+
+    /// Iterates over all event timer blocks with their HPET number, ordered by the number.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (u8, &mut HighPrecisionEventTimer)> {
+        self.blocks
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(number, block)| Some((number as u8, block.as_mut()?)))
+    }
+}
+
+impl Default for HpetBlocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BlockError, HpetBlocks, MAX_BLOCKS};
+    use crate::mock::MockHpet;
+    use crate::HighPrecisionEventTimer;
+
+    fn hpet(mock: &MockHpet) -> HighPrecisionEventTimer {
+        unsafe { HighPrecisionEventTimer::new(mock.address(), false) }
+    }
+
+    #[test]
+    fn test_insert_blocks() {
+        let first = MockHpet::new(3, 69841279);
+        let second = MockHpet::new(8, 10000000);
+        let mut blocks = HpetBlocks::new();
+        assert!(blocks.is_empty());
+
+        assert_eq!(blocks.insert(1, hpet(&second)), Ok(()));
+        assert_eq!(blocks.insert(0, hpet(&first)), Ok(()));
+        assert_eq!(blocks.len(), 2);
+
+        assert_eq!(blocks.get(0).map(|x| x.comparator_count()), Some(3));
+        assert_eq!(blocks.get(1).map(|x| x.comparator_count()), Some(8));
+        assert!(blocks.get(2).is_none());
+
+        let numbers = blocks
+            .iter()
+            .map(|(number, _)| number)
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(numbers, [0, 1]);
+    }
+
+    #[test]
+    fn test_insert_invalid_blocks() {
+        let mock = MockHpet::new(3, 69841279);
+        let mut blocks = HpetBlocks::new();
+
+        assert_eq!(blocks.insert(0, hpet(&mock)), Ok(()));
+        assert_eq!(
+            blocks.insert(0, hpet(&mock)),
+            Err(BlockError::AlreadyPresent(0))
+        );
+        assert_eq!(
+            blocks.insert(MAX_BLOCKS as u8, hpet(&mock)),
+            Err(BlockError::InvalidNumber(MAX_BLOCKS as u8))
+        );
+        assert_eq!(blocks.len(), 1);
+    }
+}
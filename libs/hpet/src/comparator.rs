@@ -1,4 +1,53 @@
 use crate::timer::HpetTimer;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// Capabilities of a comparator, used to request a comparator with [`crate::HighPrecisionEventTimer::lend_comparator_with`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ComparatorCapabilities {
+    /// The comparator supports periodic mode.
+    pub periodic: bool,
+
+    /// The comparator supports 64-bit values.
+    pub is_64_bit: bool,
+
+    /// The comparator supports FSB interrupt delivery.
+    pub fsb: bool,
+}
+
+impl ComparatorCapabilities {
+    /// Returns whenever all capabilities of `required` are also present in `self`.
+    pub fn contains(&self, required: ComparatorCapabilities) -> bool {
+        (self.periodic || !required.periodic)
+            && (self.is_64_bit || !required.is_64_bit)
+            && (self.fsb || !required.fsb)
+    }
+}
+
+/// The destination of the interrupts generated by a comparator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptRoute {
+    /// The interrupt is signaled on the given input of the I/O APIC.
+    IoApic(u8),
+
+    /// The interrupt is delivered as FSB message, by writing `value` to `address` like a PCI MSI.
+    Fsb {
+        /// The address the message is written to.
+        address: u32,
+
+        /// The value written as message.
+        value: u32,
+    },
+}
+
+/// Error returned when a comparator can't deliver its interrupts to a route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteError {
+    /// The comparator can't signal the input of the I/O APIC.
+    UnsupportedIoApicInput(u8),
+
+    /// The comparator doesn't support FSB interrupt delivery.
+    FsbNotSupported,
+}
 
 /// A comparator is value that is compared against the counter register and if it matches, an interrupt is generated.
 ///
@@ -33,14 +82,20 @@ use crate::timer::HpetTimer;
 /// The interrupts generated from a comparator can either be routed through an IOAPIC or using FSB Messaging.
 /// FSB Messaging takes priority over I/O routing, but is also not required to be implemented, so check using [`supports_fsb_interrupt`].
 /// For I/O routing, only the interrupts specified by [`supported_io_interrupt_routes`] can be used.
+/// [`set_interrupt_route`] validates a route against these capabilities before programming it.
+///
+/// ## Lending
+/// A comparator is lent from its [`crate::HighPrecisionEventTimer`] and returned when dropped.
+/// On drop it's disabled and its value is reset, so the next borrower finds it in a known state.
 pub struct HpetComparator<'a> {
     timer: &'a mut HpetTimer,
     index: u8,
+    lent: &'a AtomicU32,
 }
 
 impl<'a> HpetComparator<'a> {
-    pub(crate) fn new(timer: &'a mut HpetTimer, index: u8) -> Self {
-        HpetComparator { timer, index }
+    pub(crate) fn new(timer: &'a mut HpetTimer, index: u8, lent: &'a AtomicU32) -> Self {
+        HpetComparator { timer, index, lent }
     }
 
     /// Gets the index of the comparator.
@@ -48,6 +103,15 @@ impl<'a> HpetComparator<'a> {
         self.index
     }
 
+    /// Returns the capabilities of this comparator.
+    pub fn capabilities(&self) -> ComparatorCapabilities {
+        ComparatorCapabilities {
+            periodic: self.supports_periodic_mode(),
+            is_64_bit: self.is_64_bit(),
+            fsb: self.supports_fsb_interrupt(),
+        }
+    }
+
     /// Returns whenever the interrupts are level-triggered or edge-triggered.
     /// TODO: Example with the difference
     pub fn is_level_triggered(&self) -> bool {
@@ -96,6 +160,22 @@ impl<'a> HpetComparator<'a> {
         self.timer.set_configuration_and_capability_register(value)
     }
 
+    /// Starts periodic mode, raising the first interrupt when the counter reaches `first` and then every `period` ticks.
+    /// Check [`supports_periodic_mode`] before using. It has no effect otherwise.
+    pub fn start_periodic(&mut self, first: u64, period: u64) {
+        if !self.supports_periodic_mode() {
+            return;
+        }
+
+        self.disable();
+        // Setting the value set bit allows writing the accumulator, the next write sets the period.
+        let value = self.timer.configuration_and_capability_register() | 0b1001000;
+        self.timer.set_configuration_and_capability_register(value);
+        self.set_value(first);
+        self.set_value(period);
+        self.enable();
+    }
+
     /// Sets the comparator into one-shot mode.
     pub fn set_one_shot_mode(&mut self) {
         let value = self.timer.configuration_and_capability_register() & (!0b1000);
@@ -146,8 +226,8 @@ impl<'a> HpetComparator<'a> {
     /// Can only be one of [`supported_io_interrupt_routes`].
     /// Will be ignored if [`is_fsb_interrupt_enabled`].
     pub fn set_io_interrupt_route(&mut self, value: u8) {
-        let value =
-            self.timer.configuration_and_capability_register() | ((value as u64 & 0b11111) << 9);
+        let value = (self.timer.configuration_and_capability_register() & !(0b11111 << 9))
+            | ((value as u64 & 0b11111) << 9);
         self.timer.set_configuration_and_capability_register(value)
     }
 
@@ -174,7 +254,7 @@ impl<'a> HpetComparator<'a> {
     /// Check [`supports_fsb_interrupt`] before using. It has no effect otherwise.
     pub fn disable_fsb_interrupt(&mut self) {
         if self.supports_fsb_interrupt() {
-            let value = self.timer.configuration_and_capability_register() & (!0b100000000);
+            let value = self.timer.configuration_and_capability_register() & (!0b100000000000000);
             self.timer.set_configuration_and_capability_register(value)
         }
     }
@@ -225,11 +305,167 @@ impl<'a> HpetComparator<'a> {
             (self.timer.fsb_interrupt_route_register() & (!(u32::MAX as u64))) | value as u64;
         self.timer.set_fsb_interrupt_route_register(value)
     }
+
+    /// Returns where the interrupts of this comparator are currently delivered.
+    pub fn interrupt_route(&self) -> InterruptRoute {
+        if self.is_fsb_interrupt_enabled() {
+            InterruptRoute::Fsb {
+                address: self.fsb_interrupt_address(),
+                value: self.fsb_interrupt_value(),
+            }
+        } else {
+            InterruptRoute::IoApic(self.io_interrupt_route())
+        }
+    }
+
+    /// Delivers the interrupts of this comparator to the given route.
+    /// Nothing is changed if the comparator doesn't support the route.
+    pub fn set_interrupt_route(&mut self, route: InterruptRoute) -> Result<(), RouteError> {
+        match route {
+            InterruptRoute::IoApic(input) => {
+                if input >= 32 || self.supported_io_interrupt_routes() & (1 << input) == 0 {
+                    return Err(RouteError::UnsupportedIoApicInput(input));
+                }
+
+                let configuration = self.timer.configuration_and_capability_register();
+                self.disable_fsb_interrupt();
+                self.set_io_interrupt_route(input);
+
+                // The route bits of unsupported inputs don't stick, restore the previous route and FSB delivery.
+                if self.io_interrupt_route() != input {
+                    self.timer
+                        .set_configuration_and_capability_register(configuration);
+                    return Err(RouteError::UnsupportedIoApicInput(input));
+                }
+            }
+            InterruptRoute::Fsb { address, value } => {
+                if !self.supports_fsb_interrupt() {
+                    return Err(RouteError::FsbNotSupported);
+                }
+
+                self.set_fsb_interrupt_address(address);
+                self.set_fsb_interrupt_value(value);
+                self.enable_fsb_interrupt();
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl<'a> Drop for HpetComparator<'a> {
     fn drop(&mut self) {
         self.disable();
-        self.set_value(u64::MAX)
+        self.set_value(u64::MAX);
+        self.lent.fetch_and(!(1 << self.index), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InterruptRoute, RouteError};
+    use crate::mock::MockHpet;
+    use crate::HighPrecisionEventTimer;
+
+    fn hpet(mock: &MockHpet) -> HighPrecisionEventTimer {
+        unsafe { HighPrecisionEventTimer::new(mock.address(), false) }
+    }
+
+    #[test]
+    fn test_route_to_io_apic() {
+        let mock = MockHpet::new(3, 69841279);
+        mock.set_timer_capabilities(0, true, true, false, (1 << 20) | (1 << 2));
+        let hpet = hpet(&mock);
+        let mut comparator = hpet.lend_comparator().unwrap();
+
+        assert_eq!(
+            comparator.set_interrupt_route(InterruptRoute::IoApic(20)),
+            Ok(())
+        );
+        assert_eq!(comparator.interrupt_route(), InterruptRoute::IoApic(20));
+        assert_eq!(
+            comparator.set_interrupt_route(InterruptRoute::IoApic(2)),
+            Ok(())
+        );
+        assert_eq!(comparator.interrupt_route(), InterruptRoute::IoApic(2));
+        assert_eq!((mock.timer_configuration(0) >> 9) & 0b11111, 2);
+    }
+
+    #[test]
+    fn test_unsupported_io_apic_route() {
+        let mock = MockHpet::new(3, 69841279);
+        mock.set_timer_capabilities(0, true, true, false, 1 << 20);
+        let hpet = hpet(&mock);
+        let mut comparator = hpet.lend_comparator().unwrap();
+        let configuration = mock.timer_configuration(0);
+
+        assert_eq!(
+            comparator.set_interrupt_route(InterruptRoute::IoApic(21)),
+            Err(RouteError::UnsupportedIoApicInput(21))
+        );
+        assert_eq!(
+            comparator.set_interrupt_route(InterruptRoute::IoApic(32)),
+            Err(RouteError::UnsupportedIoApicInput(32))
+        );
+        assert_eq!(mock.timer_configuration(0), configuration);
+    }
+
+    #[test]
+    fn test_route_to_fsb() {
+        let mock = MockHpet::new(3, 69841279);
+        mock.set_timer_capabilities(0, true, true, true, 1 << 20);
+        let hpet = hpet(&mock);
+        let mut comparator = hpet.lend_comparator().unwrap();
+
+        let route = InterruptRoute::Fsb {
+            address: 0xFEE0_0000,
+            value: 0x41,
+        };
+        assert_eq!(comparator.set_interrupt_route(route), Ok(()));
+        assert_eq!(comparator.interrupt_route(), route);
+        assert_eq!(mock.timer_fsb_route(0), 0xFEE0_0000_0000_0041);
+
+        assert_eq!(
+            comparator.set_interrupt_route(InterruptRoute::IoApic(20)),
+            Ok(())
+        );
+        assert!(!comparator.is_fsb_interrupt_enabled());
+        assert_eq!(comparator.interrupt_route(), InterruptRoute::IoApic(20));
+    }
+
+    #[test]
+    fn test_unsupported_fsb_route() {
+        let mock = MockHpet::new(3, 69841279);
+        mock.set_timer_capabilities(0, true, true, false, 1 << 20);
+        let hpet = hpet(&mock);
+        let mut comparator = hpet.lend_comparator().unwrap();
+
+        let route = InterruptRoute::Fsb {
+            address: 0xFEE0_0000,
+            value: 0x41,
+        };
+        assert_eq!(
+            comparator.set_interrupt_route(route),
+            Err(RouteError::FsbNotSupported)
+        );
+        assert_eq!(mock.timer_fsb_route(0), 0);
+    }
+
+    #[test]
+    fn test_start_periodic() {
+        let mock = MockHpet::new(3, 69841279);
+        mock.set_timer_capabilities(0, true, true, false, 1 << 20);
+        mock.set_timer_capabilities(1, false, true, false, 1 << 20);
+        let hpet = hpet(&mock);
+
+        let mut periodic = hpet.lend_comparator().unwrap();
+        periodic.start_periodic(1000, 100);
+        assert!(periodic.is_periodic_mode());
+        assert!(periodic.is_enabled());
+
+        let mut one_shot = hpet.lend_comparator().unwrap();
+        one_shot.start_periodic(1000, 100);
+        assert!(!one_shot.is_periodic_mode());
+        assert!(!one_shot.is_enabled());
     }
 }
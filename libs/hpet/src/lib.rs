@@ -7,27 +7,47 @@
 //! This mode uses up the first two comparators of the HPET for PIT and RTC Timer respectively.
 //! Even though these comparators can be adjusted, this library restrains from doing so.
 //! Legacy replacement mode can be disabled though.
-#![no_std]
+//!
+//! ## Multiple Event Timer Blocks
+//! A system can have more than one HPET, called event timer blocks.
+//! Each block is managed by its own [`HighPrecisionEventTimer`], [`HpetBlocks`] keeps them by their HPET number.
+#![cfg_attr(not(test), no_std)]
 #![warn(missing_docs)]
 
+mod blocks;
 mod comparator;
+#[cfg(test)]
+mod mock;
 mod registers;
 mod timer;
 
 use core::mem;
-use core::slice;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU32, Ordering};
 
-pub use comparator::HpetComparator;
+pub use blocks::{BlockError, HpetBlocks, MAX_BLOCKS};
+pub use comparator::{ComparatorCapabilities, HpetComparator, InterruptRoute, RouteError};
 
 /// This is the main data type of this library,
 /// keeping a reference to the registers of the HPET and it's comparators.
-/// As well as having a mechanism to lend and return comparators.
+/// As well as having a mechanism to lend comparators, which are returned when dropped.
+///
+/// Comparators are lent through a shared reference, so the HPET can be shared across CPUs,
+/// while each comparator is only lent out once at a time.
 pub struct HighPrecisionEventTimer {
     registers: &'static mut registers::HpetRegisters,
-    timers: &'static mut [timer::HpetTimer],
-    tracking: u8,
+    timers: NonNull<timer::HpetTimer>,
+    timer_count: u8,
+
+    /// Bitmask of the comparators currently lent out or reserved by legacy replacement mode.
+    lent: AtomicU32,
 }
 
+// Safety: The general registers are only changed through mutable references,
+// and each comparator is only accessed by the single `HpetComparator` it is lent to, tracked by `lent`.
+unsafe impl Send for HighPrecisionEventTimer {}
+unsafe impl Sync for HighPrecisionEventTimer {}
+
 impl HighPrecisionEventTimer {
     /// Creates a new HPET management struct with the registers at the given address.
     /// You can chose whenever to enable Legacy Replacement Mode, if available.
@@ -39,12 +59,12 @@ impl HighPrecisionEventTimer {
         let timers =
             (address + mem::size_of::<registers::HpetRegisters>() as u64) as *mut timer::HpetTimer;
         let gci = registers.general_capabilitis_and_id_register();
-        let timers = unsafe { slice::from_raw_parts_mut(timers, ((gci >> 8) & 0b11111) as usize) };
+        let timer_count = ((gci >> 8) & 0b11111) as u8 + 1;
 
-        let tracking = if (gci & 0x8000) == 0x8000 {
+        let lent = if (gci & 0x8000) == 0x8000 {
             if legacy_replacement {
                 registers.set_general_configuration_register(
-                    registers.general_configuration_register() & 2,
+                    registers.general_configuration_register() | 2,
                 );
                 0b11
             } else {
@@ -59,8 +79,9 @@ impl HighPrecisionEventTimer {
 
         HighPrecisionEventTimer {
             registers,
-            timers,
-            tracking,
+            timers: unsafe { NonNull::new_unchecked(timers) },
+            timer_count,
+            lent: AtomicU32::new(lent),
         }
     }
 
@@ -72,7 +93,7 @@ impl HighPrecisionEventTimer {
     /// Get the number of comparators offered by the HPET
     /// Two comparators cannot be used, if legacy replacement mode is used.
     pub fn comparator_count(&self) -> u8 {
-        ((self.registers.general_capabilitis_and_id_register() >> 8) & 0b11111) as u8
+        ((self.registers.general_capabilitis_and_id_register() >> 8) & 0b11111) as u8 + 1
     }
 
     /// Returns whenever the counter of the HPET is 64 bits wide.
@@ -128,29 +149,182 @@ impl HighPrecisionEventTimer {
     }
 
     /// Searches for an available comparator, marks it as in use and returns it.
-    /// The comparator won't be marked as available again on drop,
-    /// so be sure to call [`return_comparator`] with it when you are done.
+    /// The comparator is marked as available again, when it is dropped.
     /// If no comparator is available, `None` will be returned.
-    pub fn lend_comparator(&mut self) -> Option<HpetComparator> {
-        let idx = self.tracking.trailing_ones() as usize;
-        let timer = self.timers.get_mut(idx)?;
+    pub fn lend_comparator(&self) -> Option<HpetComparator<'_>> {
+        self.lend_comparator_with(ComparatorCapabilities::default())
+    }
 
-        self.tracking |= 1 << idx;
-        Some(HpetComparator::new(timer, idx as u8))
+    /// Searches for an available comparator with at least the `required` capabilities, marks it as in use and returns it.
+    /// The comparator is marked as available again, when it is dropped.
+    /// If no such comparator is available, `None` will be returned.
+    pub fn lend_comparator_with(
+        &self,
+        required: ComparatorCapabilities,
+    ) -> Option<HpetComparator<'_>> {
+        (0..self.timer_count).find_map(|index| {
+            let comparator = self.try_lend(index)?;
+            if comparator.capabilities().contains(required) {
+                Some(comparator)
+            } else {
+                // The comparator wasn't touched, so it doesn't need to be reset like on drop.
+                mem::forget(comparator);
+                self.release(index);
+                None
+            }
+        })
     }
 
-    /// Returns a lended comparator, marking it as available again.
-    pub fn return_comparator(&mut self, comparator: HpetComparator) {
-        unsafe { self.return_comparator_unchecked(comparator.index()) }
+    /// Returns whenever the comparator with the index is currently lent out or reserved by legacy replacement mode.
+    pub fn is_lent(&self, index: u8) -> bool {
+        index < self.timer_count && self.lent.load(Ordering::Acquire) & (1 << index) != 0
     }
 
-    /// Returns a lended comparator by index, marking it as available again, without checking if the comparator has been lended before.
-    ///
-    /// ## Safety
-    /// Since it cannot be checked if the comparator might still be in use,
-    /// a comaprator could be lended out twice, causing a race condition.
-    pub unsafe fn return_comparator_unchecked(&mut self, index: u8) {
-        let mask = !(1u8 << index);
-        self.tracking &= mask;
+    /// Marks the comparator with the index as in use and returns it, if it's available.
+    fn try_lend(&self, index: u8) -> Option<HpetComparator<'_>> {
+        let mask = 1 << index;
+        if index >= self.timer_count || self.lent.fetch_or(mask, Ordering::AcqRel) & mask != 0 {
+            return None;
+        }
+
+        // Safety: The comparator exists and was just marked as in use, so there is no other reference to it.
+        let timer = unsafe { &mut *self.timers.as_ptr().add(index as usize) };
+        Some(HpetComparator::new(timer, index, &self.lent))
+    }
+
+    /// Marks the comparator with the index as available again.
+    fn release(&self, index: u8) {
+        self.lent.fetch_and(!(1 << index), Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ComparatorCapabilities, HighPrecisionEventTimer};
+    use crate::mock::MockHpet;
+
+    #[test]
+    fn test_capabilities() {
+        let mock = MockHpet::new(3, 69841279);
+        let hpet = unsafe { HighPrecisionEventTimer::new(mock.address(), false) };
+
+        assert_eq!(hpet.comparator_count(), 3);
+        assert_eq!(hpet.main_counter_tick_period(), 69841279);
+        assert_eq!(hpet.vendor_id(), 0x8086);
+        assert!(hpet.counter_is_64_bit());
+        assert!(hpet.supports_legacy_replacement_mode());
+    }
+
+    #[test]
+    fn test_legacy_replacement_mode() {
+        let mock = MockHpet::new(3, 69841279);
+        let hpet = unsafe { HighPrecisionEventTimer::new(mock.address(), true) };
+        assert_eq!(mock.general_configuration() & 2, 2);
+
+        // The first two comparators are used by legacy replacement mode.
+        assert_eq!(hpet.lend_comparator().map(|x| x.index()), Some(2));
+
+        let mock = MockHpet::new(3, 69841279);
+        let hpet = unsafe { HighPrecisionEventTimer::new(mock.address(), false) };
+        assert_eq!(mock.general_configuration() & 2, 0);
+        assert_eq!(hpet.lend_comparator().map(|x| x.index()), Some(0));
+    }
+
+    #[test]
+    fn test_lend_all_comparators() {
+        let mock = MockHpet::new(32, 69841279);
+        let hpet = unsafe { HighPrecisionEventTimer::new(mock.address(), false) };
+
+        let comparators = (0..32)
+            .map(|_| hpet.lend_comparator().unwrap())
+            .collect::<std::vec::Vec<_>>();
+        assert!(comparators.iter().map(|x| x.index()).eq(0..32));
+        assert!(hpet.lend_comparator().is_none());
+        assert!(hpet.is_lent(31));
+    }
+
+    #[test]
+    fn test_return_comparator_on_drop() {
+        let mock = MockHpet::new(3, 69841279);
+        let hpet = unsafe { HighPrecisionEventTimer::new(mock.address(), false) };
+
+        let first = hpet.lend_comparator().unwrap();
+        let mut second = hpet.lend_comparator().unwrap();
+        assert_eq!(second.index(), 1);
+        second.set_value(1000);
+        second.enable();
+        drop(second);
+
+        assert!(!hpet.is_lent(1));
+        assert_eq!(mock.timer_configuration(1) & 0b100, 0);
+
+        let second = hpet.lend_comparator().unwrap();
+        assert_eq!(second.index(), 1);
+        assert_eq!(second.value(), u64::MAX);
+        drop(first);
+    }
+
+    #[test]
+    fn test_lend_comparator_by_capabilities() {
+        let mock = MockHpet::new(4, 69841279);
+        mock.set_timer_capabilities(0, true, true, false, 0);
+        mock.set_timer_capabilities(1, false, false, false, 0);
+        mock.set_timer_capabilities(2, false, true, true, 0);
+        mock.set_timer_capabilities(3, true, true, true, 0);
+        let hpet = unsafe { HighPrecisionEventTimer::new(mock.address(), false) };
+
+        let fsb = ComparatorCapabilities {
+            fsb: true,
+            ..Default::default()
+        };
+        let periodic_fsb = ComparatorCapabilities {
+            periodic: true,
+            fsb: true,
+            ..Default::default()
+        };
+
+        let first = hpet.lend_comparator_with(fsb).unwrap();
+        assert_eq!(first.index(), 2);
+        let second = hpet.lend_comparator_with(periodic_fsb).unwrap();
+        assert_eq!(second.index(), 3);
+        assert!(hpet.lend_comparator_with(fsb).is_none());
+
+        // Comparators which didn't match aren't marked as lent.
+        assert!(!hpet.is_lent(0));
+        assert!(!hpet.is_lent(1));
+    }
+
+    #[test]
+    fn test_lend_comparators_concurrently() {
+        let mock = MockHpet::new(32, 69841279);
+        let hpet = unsafe { HighPrecisionEventTimer::new(mock.address(), false) };
+
+        let lent = std::thread::scope(|scope| {
+            let threads = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let comparators = (0..8)
+                            .map(|_| hpet.lend_comparator().unwrap())
+                            .collect::<std::vec::Vec<_>>();
+                        let indices = comparators
+                            .iter()
+                            .map(|x| x.index())
+                            .collect::<std::vec::Vec<_>>();
+                        core::mem::forget(comparators);
+                        indices
+                    })
+                })
+                .collect::<std::vec::Vec<_>>();
+
+            let mut lent = threads
+                .into_iter()
+                .flat_map(|x| x.join().unwrap())
+                .collect::<std::vec::Vec<_>>();
+            lent.sort();
+            lent
+        });
+
+        assert!(lent.into_iter().eq(0..32));
+        assert!(hpet.lend_comparator().is_none());
     }
 }
@@ -0,0 +1,85 @@
+//! A register-level mock of an HPET for host tests.
+//! The registers are backed by plain memory, so they keep any value written to them.
+use std::boxed::Box;
+use std::ptr;
+
+/// Number of general registers, the comparators start right behind them.
+const GENERAL_REGISTERS: usize = 32;
+
+/// Number of registers of a single comparator.
+const TIMER_REGISTERS: usize = 4;
+
+/// The maximal number of comparators of an HPET.
+const MAX_TIMERS: usize = 32;
+
+/// A mocked HPET with leaked registers, so they can back a [`crate::HighPrecisionEventTimer`].
+pub struct MockHpet(*mut u64);
+
+impl MockHpet {
+    /// Creates an HPET with `comparators` comparators and a 64-bit counter,
+    /// ticking every `tick_period` femtoseconds and supporting legacy replacement mode.
+    pub fn new(comparators: u8, tick_period: u32) -> MockHpet {
+        let registers = Box::leak(Box::new(
+            [0u64; GENERAL_REGISTERS + TIMER_REGISTERS * MAX_TIMERS],
+        ));
+        let mock = MockHpet(registers.as_mut_ptr());
+
+        let capabilities = (tick_period as u64) << 32
+            | 0x8086 << 16
+            | 0x8000
+            | 0x2000
+            | ((comparators as u64 - 1) << 8)
+            | 1;
+        mock.write(0, capabilities);
+        mock
+    }
+
+    /// Sets the capabilities of the comparator with the index.
+    pub fn set_timer_capabilities(
+        &self,
+        index: usize,
+        periodic: bool,
+        is_64_bit: bool,
+        fsb: bool,
+        io_routes: u32,
+    ) {
+        let capabilities = ((io_routes as u64) << 32)
+            | if fsb { 0x8000 } else { 0 }
+            | if is_64_bit { 0b100000 } else { 0 }
+            | if periodic { 0b10000 } else { 0 };
+        self.write(Self::timer(index), capabilities);
+    }
+
+    /// Returns the address of the registers.
+    pub fn address(&self) -> u64 {
+        self.0 as u64
+    }
+
+    /// Returns the general configuration register.
+    pub fn general_configuration(&self) -> u64 {
+        self.read(2)
+    }
+
+    /// Returns the configuration and capability register of the comparator with the index.
+    pub fn timer_configuration(&self, index: usize) -> u64 {
+        self.read(Self::timer(index))
+    }
+
+    /// Returns the FSB interrupt route register of the comparator with the index.
+    pub fn timer_fsb_route(&self, index: usize) -> u64 {
+        self.read(Self::timer(index) + 2)
+    }
+
+    fn timer(index: usize) -> usize {
+        assert!(index < MAX_TIMERS);
+        GENERAL_REGISTERS + index * TIMER_REGISTERS
+    }
+
+    fn read(&self, register: usize) -> u64 {
+        unsafe { ptr::read_volatile(self.0.add(register)) }
+    }
+
+    fn write(&self, register: usize, value: u64) {
+        unsafe { ptr::write_volatile(self.0.add(register), value) }
+    }
+}
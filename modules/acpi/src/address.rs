@@ -0,0 +1,465 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+#[cfg(target_arch = "x86_64")]
+use crate::port;
+use common::addr::PhysAddr;
+use common::memory::physical_to_virtual;
+#[cfg(target_arch = "x86_64")]
+use common::sync::Spinlock;
+
+/// Configuration address port of the PCI configuration access mechanism #1.
+#[cfg(target_arch = "x86_64")]
+const PCI_CONFIG_ADDRESS: u16 = 0xCF8;
+
+/// Configuration data port of the PCI configuration access mechanism #1.
+#[cfg(target_arch = "x86_64")]
+const PCI_CONFIG_DATA: u16 = 0xCFC;
+
+/// Bit of [`PCI_CONFIG_ADDRESS`] enabling the access.
+#[cfg(target_arch = "x86_64")]
+const PCI_CONFIG_ENABLE: u32 = 1 << 31;
+
+/// Serializes the accesses to the PCI configuration space, which take two port accesses.
+#[cfg(target_arch = "x86_64")]
+static PCI_CONFIG_LOCK: Spinlock<()> = Spinlock::new(());
+
+/// The address space of a [`GenericAddress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressSpace {
+    SystemMemory,
+    SystemIo,
+    /// The configuration space of a function on PCI segment `0` and bus `0`.
+    PciConfiguration,
+    EmbeddedController,
+    SmBus,
+    FunctionalFixedHardware,
+    /// Any other address space, by its id.
+    Other(u8),
+}
+
+impl From<u8> for AddressSpace {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::SystemMemory,
+            1 => Self::SystemIo,
+            2 => Self::PciConfiguration,
+            3 => Self::EmbeddedController,
+            4 => Self::SmBus,
+            0x7F => Self::FunctionalFixedHardware,
+            x => Self::Other(x),
+        }
+    }
+}
+
+impl From<AddressSpace> for u8 {
+    fn from(value: AddressSpace) -> Self {
+        match value {
+            AddressSpace::SystemMemory => 0,
+            AddressSpace::SystemIo => 1,
+            AddressSpace::PciConfiguration => 2,
+            AddressSpace::EmbeddedController => 3,
+            AddressSpace::SmBus => 4,
+            AddressSpace::FunctionalFixedHardware => 0x7F,
+            AddressSpace::Other(x) => x,
+        }
+    }
+}
+
+/// Error returned when accessing a [`GenericAddress`] fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressError {
+    /// The address space can't be accessed by the kernel.
+    UnsupportedAddressSpace(AddressSpace),
+
+    /// The register is wider than 64 bits or not accessible with the access size.
+    InvalidWidth,
+
+    /// The access size isn't defined by the specification.
+    InvalidAccessSize(u8),
+}
+
+/// The ACPI Generic Address Structure, describing the location of a register.
+///
+/// The register is [`GenericAddress::register_bit_width`] bits wide and starts at [`GenericAddress::register_bit_offset`],
+/// it's accessed in units of [`GenericAddress::access_size`], which may take multiple accesses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C, packed)]
+pub struct GenericAddress {
+    /// See [`GenericAddress::address_space`].
+    pub address_space_id: u8,
+    pub register_bit_width: u8,
+    pub register_bit_offset: u8,
+    /// `0` for legacy reasons, derived from the register width then, else `1` for byte, `2` for word, `3` for dword or `4` for qword accesses.
+    pub access_size: u8,
+    pub address: u64,
+}
+
+impl GenericAddress {
+    /// Describes an I/O port register, accessed as a whole.
+    pub const fn system_io(port: u16, bit_width: u8) -> Self {
+        Self {
+            address_space_id: 1,
+            register_bit_width: bit_width,
+            register_bit_offset: 0,
+            access_size: 0,
+            address: port as u64,
+        }
+    }
+
+    /// Describes a memory-mapped register at a physical address, accessed as a whole.
+    pub const fn system_memory(address: u64, bit_width: u8) -> Self {
+        Self {
+            address_space_id: 0,
+            register_bit_width: bit_width,
+            register_bit_offset: 0,
+            access_size: 0,
+            address,
+        }
+    }
+
+    /// Returns the address space of the register.
+    pub fn address_space(&self) -> AddressSpace {
+        self.address_space_id.into()
+    }
+
+    /// Reads the register, its bits are shifted down to start at bit `0`.
+    ///
+    /// ## Safety
+    ///
+    /// The register has to exist and reading it mustn't have side effects breaking the system,
+    /// as for the registers described by the ACPI tables.
+    pub unsafe fn read(&self) -> Result<u64, AddressError> {
+        let space = self.address_space();
+        self.read_with(|address, size| read_unit(space, address, size))
+    }
+
+    /// Writes the register, the bits around it in the accessed units are preserved.
+    ///
+    /// ## Safety
+    ///
+    /// The register has to exist and writing it mustn't have side effects breaking the system,
+    /// as for the registers described by the ACPI tables.
+    pub unsafe fn write(&self, value: u64) -> Result<(), AddressError> {
+        let space = self.address_space();
+        self.write_with(
+            value,
+            |address, size| read_unit(space, address, size),
+            |address, size, value| write_unit(space, address, size, value),
+        )
+    }
+
+    /// Returns the size of a single access in bytes, the number of accesses, the bit offset and the bit width.
+    fn layout(&self) -> Result<(usize, usize, u32, u32), AddressError> {
+        let offset = self.register_bit_offset as u32;
+        let unit = match self.access_size {
+            0 => match offset + self.register_bit_width as u32 {
+                0 => return Err(AddressError::InvalidWidth),
+                1..=8 => 1,
+                9..=16 => 2,
+                17..=32 => 4,
+                33..=64 => 8,
+                _ => return Err(AddressError::InvalidWidth),
+            },
+            x @ 1..=4 => 1 << (x - 1),
+            x => return Err(AddressError::InvalidAccessSize(x)),
+        };
+
+        // Some firmware leaves the width empty, the register fills the access then.
+        let width = match self.register_bit_width {
+            0 => unit as u32 * 8 - offset.min(unit as u32 * 8),
+            x => x as u32,
+        };
+        if width == 0 || offset + width > u64::BITS {
+            return Err(AddressError::InvalidWidth);
+        }
+
+        let count = (offset + width).div_ceil(unit as u32 * 8) as usize;
+        Ok((unit, count, offset, width))
+    }
+
+    /// Reads the register with `read_unit(address, size)` reading a single unit.
+    fn read_with(
+        &self,
+        mut read_unit: impl FnMut(u64, usize) -> Result<u64, AddressError>,
+    ) -> Result<u64, AddressError> {
+        let (unit, count, offset, width) = self.layout()?;
+        let address = self.address;
+
+        let mut raw = 0;
+        for i in 0..count {
+            raw |= read_unit(address + (i * unit) as u64, unit)? << (i * unit * 8);
+        }
+
+        Ok((raw >> offset) & mask(width))
+    }
+
+    /// Writes the register with `read_unit(address, size)` and `write_unit(address, size, value)` accessing a single unit.
+    fn write_with(
+        &self,
+        value: u64,
+        read_unit: impl FnMut(u64, usize) -> Result<u64, AddressError>,
+        mut write_unit: impl FnMut(u64, usize, u64) -> Result<(), AddressError>,
+    ) -> Result<(), AddressError> {
+        let (unit, count, offset, width) = self.layout()?;
+        let address = self.address;
+
+        // Only read the units if the register doesn't cover them entirely.
+        let register = mask(width) << offset;
+        let units = mask((count * unit * 8) as u32);
+        let raw = if register == units {
+            0
+        } else {
+            Self {
+                register_bit_width: (count * unit * 8) as u8,
+                register_bit_offset: 0,
+                ..*self
+            }
+            .read_with(read_unit)?
+        };
+
+        let raw = (raw & !register) | ((value << offset) & register);
+        for i in 0..count {
+            write_unit(address + (i * unit) as u64, unit, raw >> (i * unit * 8))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns a mask of the lower `width` bits.
+fn mask(width: u32) -> u64 {
+    u64::MAX >> (u64::BITS - width)
+}
+
+unsafe fn read_unit(space: AddressSpace, address: u64, size: usize) -> Result<u64, AddressError> {
+    match space {
+        AddressSpace::SystemMemory => {
+            let pointer = physical_to_virtual(PhysAddr::new_truncate(address)).as_ptr::<u8>();
+            Ok(match size {
+                1 => pointer.read_volatile() as u64,
+                2 => pointer.cast::<u16>().read_volatile() as u64,
+                4 => pointer.cast::<u32>().read_volatile() as u64,
+                _ => pointer.cast::<u64>().read_volatile(),
+            })
+        }
+        // Only x86 has I/O ports, the PCI configuration space is then accessed through MMIO as given by the MCFG.
+        #[cfg(target_arch = "x86_64")]
+        AddressSpace::SystemIo => {
+            let port = u16::try_from(address).map_err(|_| AddressError::InvalidWidth)?;
+            match size {
+                1 => Ok(port::read_u8(port) as u64),
+                2 => Ok(port::read_u16(port) as u64),
+                4 => Ok(port::read_u32(port) as u64),
+                _ => Err(AddressError::InvalidWidth),
+            }
+        }
+        #[cfg(target_arch = "x86_64")]
+        AddressSpace::PciConfiguration => {
+            let port = select_pci_config(address, size)?;
+            let _guard = common::interrupts::disable();
+            let _lock = PCI_CONFIG_LOCK.lock();
+            port::write_u32(PCI_CONFIG_ADDRESS, port.0);
+            match size {
+                1 => Ok(port::read_u8(port.1) as u64),
+                2 => Ok(port::read_u16(port.1) as u64),
+                _ => Ok(port::read_u32(port.1) as u64),
+            }
+        }
+        space => Err(AddressError::UnsupportedAddressSpace(space)),
+    }
+}
+
+unsafe fn write_unit(
+    space: AddressSpace,
+    address: u64,
+    size: usize,
+    value: u64,
+) -> Result<(), AddressError> {
+    match space {
+        AddressSpace::SystemMemory => {
+            let pointer = physical_to_virtual(PhysAddr::new_truncate(address)).as_mut_ptr::<u8>();
+            match size {
+                1 => pointer.write_volatile(value as u8),
+                2 => pointer.cast::<u16>().write_volatile(value as u16),
+                4 => pointer.cast::<u32>().write_volatile(value as u32),
+                _ => pointer.cast::<u64>().write_volatile(value),
+            }
+            Ok(())
+        }
+        #[cfg(target_arch = "x86_64")]
+        AddressSpace::SystemIo => {
+            let port = u16::try_from(address).map_err(|_| AddressError::InvalidWidth)?;
+            match size {
+                1 => port::write_u8(port, value as u8),
+                2 => port::write_u16(port, value as u16),
+                4 => port::write_u32(port, value as u32),
+                _ => return Err(AddressError::InvalidWidth),
+            }
+            Ok(())
+        }
+        #[cfg(target_arch = "x86_64")]
+        AddressSpace::PciConfiguration => {
+            let port = select_pci_config(address, size)?;
+            let _guard = common::interrupts::disable();
+            let _lock = PCI_CONFIG_LOCK.lock();
+            port::write_u32(PCI_CONFIG_ADDRESS, port.0);
+            match size {
+                1 => port::write_u8(port.1, value as u8),
+                2 => port::write_u16(port.1, value as u16),
+                _ => port::write_u32(port.1, value as u32),
+            }
+            Ok(())
+        }
+        space => Err(AddressError::UnsupportedAddressSpace(space)),
+    }
+}
+
+/// Returns the configuration address and the data port of a PCI configuration space access.
+/// The address holds the device in bits 32 to 47, the function in bits 16 to 31 and the offset in bits 0 to 15.
+#[cfg(target_arch = "x86_64")]
+fn select_pci_config(address: u64, size: usize) -> Result<(u32, u16), AddressError> {
+    let device = (address >> 32) as u16;
+    let function = (address >> 16) as u16;
+    let offset = address as u16;
+    if device >= 32 || function >= 8 || offset >= 256 || size > 4 || offset as usize % 4 + size > 4
+    {
+        return Err(AddressError::InvalidWidth);
+    }
+
+    let config_address =
+        PCI_CONFIG_ENABLE | (device as u32) << 11 | (function as u32) << 8 | (offset as u32 & 0xFC);
+    Ok((config_address, PCI_CONFIG_DATA + offset % 4))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{select_pci_config, AddressError, AddressSpace, GenericAddress};
+    use core::cell::Cell;
+
+    /// Memory accessed in units, starting at address `0`.
+    struct Memory(Vec<Cell<u8>>);
+
+    impl Memory {
+        fn new(bytes: &[u8]) -> Self {
+            Self(bytes.iter().copied().map(Cell::new).collect())
+        }
+
+        fn read(&self, address: u64, size: usize) -> Result<u64, AddressError> {
+            let bytes = &self.0[address as usize..address as usize + size];
+            Ok(bytes.iter().rev().fold(0, |x, y| x << 8 | y.get() as u64))
+        }
+
+        fn write(&self, address: u64, size: usize, value: u64) -> Result<(), AddressError> {
+            let bytes = &self.0[address as usize..address as usize + size];
+            for (byte, value) in bytes.iter().zip(value.to_le_bytes()) {
+                byte.set(value);
+            }
+            Ok(())
+        }
+
+        fn bytes(&self) -> Vec<u8> {
+            self.0.iter().map(Cell::get).collect()
+        }
+    }
+
+    fn register(width: u8, offset: u8, access_size: u8) -> GenericAddress {
+        GenericAddress {
+            register_bit_width: width,
+            register_bit_offset: offset,
+            access_size,
+            ..GenericAddress::system_memory(0, 0)
+        }
+    }
+
+    #[test]
+    fn test_address_space() {
+        let register = GenericAddress::system_io(0xCF9, 8);
+        assert_eq!(register.address_space(), AddressSpace::SystemIo);
+        assert_eq!(
+            AddressSpace::from(0x7F),
+            AddressSpace::FunctionalFixedHardware
+        );
+        assert_eq!(AddressSpace::from(0x0A), AddressSpace::Other(0x0A));
+        assert_eq!(u8::from(AddressSpace::PciConfiguration), 2);
+    }
+
+    #[test]
+    fn test_bit_field() {
+        // The sleep type of a PM1 control register, accessed as a word.
+        let memory = Memory::new(&[0x01, 0x20, 0xAA, 0xAA]);
+        let sleep_type = register(3, 10, 2);
+        assert_eq!(sleep_type.read_with(|a, s| memory.read(a, s)), Ok(0));
+
+        sleep_type
+            .write_with(
+                0b101,
+                |a, s| memory.read(a, s),
+                |a, s, v| memory.write(a, s, v),
+            )
+            .unwrap();
+        assert_eq!(memory.bytes(), [0x01, 0x34, 0xAA, 0xAA]);
+    }
+
+    #[test]
+    fn test_multiple_accesses() {
+        // A 32-bit register accessed by bytes, at an offset into the first byte.
+        let memory = Memory::new(&[0x0F, 0x22, 0x33, 0x44, 0xF5]);
+        let value = register(32, 4, 1).read_with(|a, s| memory.read(a, s));
+        assert_eq!(value, Ok(0x5443_3220));
+
+        register(32, 4, 1)
+            .write_with(
+                0x1234_5678,
+                |a, s| memory.read(a, s),
+                |a, s, v| memory.write(a, s, v),
+            )
+            .unwrap();
+        assert_eq!(memory.bytes(), [0x8F, 0x67, 0x45, 0x23, 0xF1]);
+    }
+
+    #[test]
+    fn test_legacy_access_size() {
+        let memory = Memory::new(&[0x11, 0x22, 0x33, 0x44]);
+        assert_eq!(
+            register(16, 0, 0).read_with(|a, s| memory.read(a, s)),
+            Ok(0x2211)
+        );
+
+        // An empty width fills the access.
+        assert_eq!(
+            register(0, 0, 3).read_with(|a, s| memory.read(a, s)),
+            Ok(0x4433_2211)
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let memory = Memory::new(&[0; 16]);
+        assert_eq!(
+            register(128, 0, 1).read_with(|a, s| memory.read(a, s)),
+            Err(AddressError::InvalidWidth)
+        );
+        assert_eq!(
+            register(8, 0, 5).read_with(|a, s| memory.read(a, s)),
+            Err(AddressError::InvalidAccessSize(5))
+        );
+    }
+
+    #[test]
+    fn test_pci_config() {
+        // Device 0x1F, function 3, offset 0x42.
+        assert_eq!(
+            select_pci_config(0x1F_0003_0042, 2),
+            Ok((0x8000_FB40, 0xCFE))
+        );
+        assert_eq!(
+            select_pci_config(0x1F_0003_0043, 2),
+            Err(AddressError::InvalidWidth)
+        );
+        assert_eq!(
+            select_pci_config(0x20_0000_0000, 1),
+            Err(AddressError::InvalidWidth)
+        );
+    }
+}
@@ -0,0 +1,119 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! Minimal scanning of AML code for the few objects the kernel needs before the userspace ACPI service runs.
+//! There is no interpreter, only objects defined by a plain `Name` with constant data can be found.
+
+const NAME_OP: u8 = 0x08;
+const ROOT_PREFIX: u8 = b'\\';
+const PACKAGE_OP: u8 = 0x12;
+const ZERO_OP: u8 = 0x00;
+const ONE_OP: u8 = 0x01;
+const ONES_OP: u8 = 0xFF;
+const BYTE_PREFIX: u8 = 0x0A;
+const WORD_PREFIX: u8 = 0x0B;
+const DWORD_PREFIX: u8 = 0x0C;
+const QWORD_PREFIX: u8 = 0x0D;
+
+/// Finds the sleep state package `name` like `_S5_` and returns its `SLP_TYPa` and `SLP_TYPb` values.
+pub(crate) fn find_sleep_type(aml: &[u8], name: &[u8; 4]) -> Option<(u8, u8)> {
+    let mut start = 0;
+    while let Some(offset) = find(&aml[start..], name) {
+        let position = start + offset;
+        start = position + 1;
+
+        if !matches!(aml[..position], [.., NAME_OP] | [.., NAME_OP, ROOT_PREFIX]) {
+            continue;
+        }
+
+        let mut bytes = &aml[position + name.len()..];
+        let [PACKAGE_OP, ..] = bytes else {
+            continue;
+        };
+        bytes = &bytes[1..];
+        skip_package_length(&mut bytes)?;
+
+        // The number of elements is followed by the elements.
+        bytes = bytes.get(1..)?;
+        let a = integer(&mut bytes)?;
+        let b = integer(&mut bytes).unwrap_or(0);
+        return Some((a as u8, b as u8));
+    }
+
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|x| x == needle)
+}
+
+/// Skips an encoded package length, the bits 6 and 7 of the first byte give the number of following bytes.
+fn skip_package_length(bytes: &mut &[u8]) -> Option<()> {
+    let lead = *bytes.first()?;
+    let length = 1 + (lead >> 6) as usize;
+    *bytes = bytes.get(length..)?;
+    Some(())
+}
+
+/// Reads a constant integer.
+fn integer(bytes: &mut &[u8]) -> Option<u64> {
+    let (&op, rest) = bytes.split_first()?;
+    let (value, length) = match op {
+        ZERO_OP => (0, 0),
+        ONE_OP => (1, 0),
+        ONES_OP => (u64::MAX, 0),
+        BYTE_PREFIX => (little_endian(rest, 1)?, 1),
+        WORD_PREFIX => (little_endian(rest, 2)?, 2),
+        DWORD_PREFIX => (little_endian(rest, 4)?, 4),
+        QWORD_PREFIX => (little_endian(rest, 8)?, 8),
+        _ => return None,
+    };
+
+    *bytes = &rest[length..];
+    Some(value)
+}
+
+fn little_endian(bytes: &[u8], length: usize) -> Option<u64> {
+    let bytes = bytes.get(..length)?;
+    Some(
+        bytes
+            .iter()
+            .rev()
+            .fold(0, |value, byte| value << 8 | *byte as u64),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::find_sleep_type;
+
+    #[test]
+    fn test_qemu() {
+        // Name (_S5, Package (0x04) { Zero, Zero, Zero, Zero }) preceded by _S4.
+        let aml = [
+            0x08, b'_', b'S', b'4', b'_', 0x12, 0x06, 0x04, 0x0A, 0x01, 0x00, 0x00, 0x00, 0x08,
+            b'_', b'S', b'5', b'_', 0x12, 0x06, 0x04, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(find_sleep_type(&aml, b"_S4_"), Some((1, 0)));
+        assert_eq!(find_sleep_type(&aml, b"_S5_"), Some((0, 0)));
+        assert_eq!(find_sleep_type(&aml, b"_S3_"), None);
+    }
+
+    #[test]
+    fn test_root_prefix() {
+        // Name (\_S5, Package (0x04) { 0x07, 0x07, Zero, Zero }) with a method referencing it before.
+        let aml = [
+            0x14, 0x08, b'_', b'P', b'T', b'S', 0x01, 0x70, b'_', b'S', b'5', b'_', 0x60, 0x08,
+            b'\\', b'_', b'S', b'5', b'_', 0x12, 0x08, 0x04, 0x0A, 0x07, 0x0A, 0x07, 0x00, 0x00,
+        ];
+        assert_eq!(find_sleep_type(&aml, b"_S5_"), Some((7, 7)));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let aml = [0x08, b'_', b'S', b'5', b'_', 0x12, 0x48];
+        assert_eq!(find_sleep_type(&aml, b"_S5_"), None);
+        let aml = [0x08, b'_', b'S', b'5', b'_', 0x12, 0x06, 0x04, 0x0A];
+        assert_eq!(find_sleep_type(&aml, b"_S5_"), None);
+    }
+}
@@ -0,0 +1,61 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use crate::{AcpiTable, AcpiTableHeader};
+
+/// The Boot Graphics Resource Table, describing the logo the firmware drew on the screen during boot.
+#[repr(C, packed)]
+pub struct BgrtTable {
+    /// ACPI Table Header
+    pub header: AcpiTableHeader,
+    /// Always `1`.
+    pub version: u16,
+    /// See [`BgrtTable::DISPLAYED`].
+    pub status: u8,
+    /// See [`BgrtTable::BITMAP`].
+    pub image_type: u8,
+    /// Physical address of the image in memory.
+    pub image_address: u64,
+    /// The position of the upper left corner of the image on the screen.
+    pub image_offset_x: u32,
+    pub image_offset_y: u32,
+}
+
+impl BgrtTable {
+    /// Flag set in [`BgrtTable::status`] if the image is still shown on the screen.
+    pub const DISPLAYED: u8 = 1 << 0;
+
+    /// The image is a bitmap (BMP file).
+    pub const BITMAP: u8 = 0;
+
+    /// Returns whenever the image is still shown on the screen.
+    pub fn is_displayed(&self) -> bool {
+        self.status & Self::DISPLAYED != 0
+    }
+}
+
+impl AcpiTable for BgrtTable {
+    const SIGNATURE: &'static [u8; 4] = b"BGRT";
+
+    fn header(&self) -> &AcpiTableHeader {
+        &self.header
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BgrtTable;
+    use crate::entry::blob;
+    use crate::AcpiTable;
+
+    #[test]
+    fn test_ovmf() {
+        let bgrt = blob::<BgrtTable>(include_bytes!("../testdata/ovmf-bgrt.dat"));
+        assert!(bgrt.validate());
+        assert!(bgrt.is_displayed());
+        assert_eq!(bgrt.image_type, BgrtTable::BITMAP);
+
+        let (address, x, y) = (bgrt.image_address, bgrt.image_offset_x, bgrt.image_offset_y);
+        assert_eq!((address, x, y), (0x7E6B_4018, 0x1D9, 0x128));
+    }
+}
@@ -0,0 +1,296 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use crate::entry::{cast, split_entry, trailing_bytes};
+use crate::{AcpiTable, AcpiTableHeader};
+use core::mem::size_of;
+use core::slice;
+
+/// The DMA Remapping Table, describing the Intel VT-d IOMMUs of the system.
+/// The fixed part is followed by a list of variable-length remapping structures, see [`DmarTable::entries`].
+#[repr(C, packed)]
+pub struct DmarTable {
+    /// ACPI Table Header
+    pub header: AcpiTableHeader,
+    /// The maximal DMA physical address width minus one.
+    pub host_address_width: u8,
+    /// See [`DmarTable::INTERRUPT_REMAPPING`] and [`DmarTable::X2APIC_OPT_OUT`].
+    pub flags: u8,
+    pub reserved: [u8; 10],
+}
+
+impl DmarTable {
+    /// The IOMMUs support interrupt remapping.
+    pub const INTERRUPT_REMAPPING: u8 = 1 << 0;
+
+    /// The firmware requests to not enable x2APIC mode, if possible.
+    pub const X2APIC_OPT_OUT: u8 = 1 << 1;
+
+    /// Returns an iterator over the remapping structures of the table.
+    pub fn entries(&self) -> DmarEntries<'_> {
+        // Safety: The entries fill the rest of the table, as given by the table's length.
+        let bytes = unsafe { trailing_bytes(self, self.header.length as usize) };
+        DmarEntries { bytes }
+    }
+}
+
+impl AcpiTable for DmarTable {
+    const SIGNATURE: &'static [u8; 4] = b"DMAR";
+
+    fn header(&self) -> &AcpiTableHeader {
+        &self.header
+    }
+}
+
+/// The header of every remapping structure of the [`DmarTable`].
+#[repr(C, packed)]
+pub struct DmarEntryHeader {
+    /// The type of the remapping structure.
+    pub kind: u16,
+
+    /// Length of the whole remapping structure in bytes.
+    pub length: u16,
+}
+
+/// DMA Remapping Hardware Unit Definition, an IOMMU and the devices it handles.
+#[repr(C, packed)]
+pub struct HardwareUnitEntry {
+    pub header: DmarEntryHeader,
+    /// See [`HardwareUnitEntry::INCLUDE_PCI_ALL`].
+    pub flags: u8,
+    /// The size of the register set as a power of two of 4 KiB pages.
+    pub size: u8,
+    pub segment: u16,
+    /// Physical address of the registers of the IOMMU.
+    pub register_base_address: u64,
+}
+
+impl HardwareUnitEntry {
+    /// The IOMMU handles all devices of its segment not handled by another IOMMU, the device scopes are ignored.
+    pub const INCLUDE_PCI_ALL: u8 = 1 << 0;
+
+    /// Returns an iterator over the devices handled by the IOMMU.
+    pub fn device_scopes(&self) -> DeviceScopes<'_> {
+        // Safety: The device scopes fill the rest of the structure, as given by its length.
+        let bytes = unsafe { trailing_bytes(self, self.header.length as usize) };
+        DeviceScopes { bytes }
+    }
+}
+
+/// Reserved Memory Region Reporting, memory used for DMA by devices, which has to stay identity mapped.
+#[repr(C, packed)]
+pub struct ReservedMemoryEntry {
+    pub header: DmarEntryHeader,
+    pub reserved: u16,
+    pub segment: u16,
+    pub base_address: u64,
+    /// The last byte of the memory region, inclusive.
+    pub limit_address: u64,
+}
+
+impl ReservedMemoryEntry {
+    /// Returns an iterator over the devices using the memory region.
+    pub fn device_scopes(&self) -> DeviceScopes<'_> {
+        // Safety: The device scopes fill the rest of the structure, as given by its length.
+        let bytes = unsafe { trailing_bytes(self, self.header.length as usize) };
+        DeviceScopes { bytes }
+    }
+}
+
+/// Root Port ATS Capability Reporting, the root ports supporting address translation services.
+#[repr(C, packed)]
+pub struct RootPortAtsEntry {
+    pub header: DmarEntryHeader,
+    /// See [`RootPortAtsEntry::ALL_PORTS`].
+    pub flags: u8,
+    pub reserved: u8,
+    pub segment: u16,
+}
+
+impl RootPortAtsEntry {
+    /// All root ports of the segment support ATS, the device scopes are ignored.
+    pub const ALL_PORTS: u8 = 1 << 0;
+
+    /// Returns an iterator over the root ports supporting ATS.
+    pub fn device_scopes(&self) -> DeviceScopes<'_> {
+        // Safety: The device scopes fill the rest of the structure, as given by its length.
+        let bytes = unsafe { trailing_bytes(self, self.header.length as usize) };
+        DeviceScopes { bytes }
+    }
+}
+
+/// Remapping Hardware Static Affinity, the NUMA proximity domain of an IOMMU.
+#[repr(C, packed)]
+pub struct HardwareAffinityEntry {
+    pub header: DmarEntryHeader,
+    pub reserved: u32,
+    /// Same as [`HardwareUnitEntry::register_base_address`].
+    pub register_base_address: u64,
+    pub proximity_domain: u32,
+}
+
+/// A remapping structure of the [`DmarTable`].
+pub enum DmarEntry<'a> {
+    HardwareUnit(&'a HardwareUnitEntry),
+    ReservedMemory(&'a ReservedMemoryEntry),
+    RootPortAts(&'a RootPortAtsEntry),
+    HardwareAffinity(&'a HardwareAffinityEntry),
+    /// A remapping structure of a type not supported, or too short for its type.
+    Unknown(&'a DmarEntryHeader),
+}
+
+/// Iterator over the remapping structures of a [`DmarTable`].
+pub struct DmarEntries<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for DmarEntries<'a> {
+    type Item = DmarEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let [kind0, kind1, length0, length1, ..] = *self.bytes else {
+            return None;
+        };
+        let length = u16::from_le_bytes([length0, length1]);
+        let entry = split_entry(
+            &mut self.bytes,
+            length as usize,
+            size_of::<DmarEntryHeader>(),
+        )?;
+
+        Some(match u16::from_le_bytes([kind0, kind1]) {
+            0 => cast(entry).map_or_else(|| unknown(entry), DmarEntry::HardwareUnit),
+            1 => cast(entry).map_or_else(|| unknown(entry), DmarEntry::ReservedMemory),
+            2 => cast(entry).map_or_else(|| unknown(entry), DmarEntry::RootPortAts),
+            3 => cast(entry).map_or_else(|| unknown(entry), DmarEntry::HardwareAffinity),
+            _ => unknown(entry),
+        })
+    }
+}
+
+fn unknown(entry: &[u8]) -> DmarEntry<'_> {
+    // Safety: Every entry is at least as long as its header.
+    DmarEntry::Unknown(unsafe { &*(entry.as_ptr() as *const DmarEntryHeader) })
+}
+
+/// A device or hierarchy of devices, identified by its path from a bus.
+#[repr(C, packed)]
+pub struct DeviceScope {
+    /// The type of the device, see [`DeviceScope::PCI_ENDPOINT`] and following.
+    pub kind: u8,
+    /// Length of the device scope including the path in bytes.
+    pub length: u8,
+    pub flags: u8,
+    pub reserved: u8,
+    /// The I/O APIC id or HPET number for these types, else reserved.
+    pub enumeration_id: u8,
+    /// The bus the path starts at.
+    pub start_bus: u8,
+}
+
+impl DeviceScope {
+    /// A PCI endpoint device.
+    pub const PCI_ENDPOINT: u8 = 1;
+
+    /// A PCI-PCI bridge and all devices behind it.
+    pub const PCI_SUB_HIERARCHY: u8 = 2;
+
+    /// An I/O APIC.
+    pub const IO_APIC: u8 = 3;
+
+    /// An HPET event timer block.
+    pub const HPET: u8 = 4;
+
+    /// An ACPI namespace device.
+    pub const ACPI_NAMESPACE_DEVICE: u8 = 5;
+
+    /// Returns the path of devices and functions from [`DeviceScope::start_bus`] to the device,
+    /// each entry but the last is a PCI-PCI bridge.
+    pub fn path(&self) -> &[DevicePath] {
+        // Safety: The path fills the rest of the device scope, as given by its length.
+        let bytes = unsafe { trailing_bytes(self, self.length as usize) };
+        // Safety: The path struct is packed, so it has no alignment requirements.
+        unsafe {
+            slice::from_raw_parts(
+                bytes.as_ptr() as *const DevicePath,
+                bytes.len() / size_of::<DevicePath>(),
+            )
+        }
+    }
+}
+
+/// An entry of the path of a [`DeviceScope`].
+#[repr(C, packed)]
+pub struct DevicePath {
+    pub device: u8,
+    pub function: u8,
+}
+
+/// Iterator over the device scopes of a remapping structure of the [`DmarTable`].
+pub struct DeviceScopes<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for DeviceScopes<'a> {
+    type Item = &'a DeviceScope;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let [_, length, ..] = *self.bytes else {
+            return None;
+        };
+        let entry = split_entry(&mut self.bytes, length as usize, size_of::<DeviceScope>())?;
+        cast(entry)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DeviceScope, DmarEntry, DmarTable, HardwareUnitEntry};
+    use crate::entry::blob;
+    use crate::AcpiTable;
+
+    #[test]
+    fn test_qemu_q35() {
+        let dmar = blob::<DmarTable>(include_bytes!("../testdata/qemu-q35-dmar.dat"));
+        assert!(dmar.validate());
+        assert_eq!(dmar.host_address_width, 38);
+        assert_ne!(dmar.flags & DmarTable::INTERRUPT_REMAPPING, 0);
+
+        let mut entries = dmar.entries();
+        let Some(DmarEntry::HardwareUnit(unit)) = entries.next() else {
+            panic!("Expected a hardware unit");
+        };
+        let address = unit.register_base_address;
+        assert_eq!(address, 0xFED9_0000);
+        assert_ne!(unit.flags & HardwareUnitEntry::INCLUDE_PCI_ALL, 0);
+        assert!(unit.device_scopes().next().is_none());
+
+        let Some(DmarEntry::ReservedMemory(memory)) = entries.next() else {
+            panic!("Expected a reserved memory region");
+        };
+        let (base, limit) = (memory.base_address, memory.limit_address);
+        assert_eq!((base, limit), (0x7FF0_0000, 0x7FFF_FFFF));
+        let mut scopes = memory.device_scopes();
+        let scope = scopes.next().expect("Expected a device scope");
+        assert_eq!(scope.kind, DeviceScope::PCI_ENDPOINT);
+        let [path] = scope.path() else {
+            panic!("Expected a single path entry");
+        };
+        assert_eq!((path.device, path.function), (0x1D, 0));
+        assert!(scopes.next().is_none());
+
+        assert!(matches!(entries.next(), Some(DmarEntry::RootPortAts(_))));
+        let Some(DmarEntry::HardwareAffinity(affinity)) = entries.next() else {
+            panic!("Expected a hardware affinity");
+        };
+        let proximity_domain = affinity.proximity_domain;
+        assert_eq!(proximity_domain, 1);
+
+        let Some(DmarEntry::Unknown(header)) = entries.next() else {
+            panic!("Expected an unknown entry");
+        };
+        let kind = header.kind;
+        assert_eq!(kind, 0x7F);
+        assert!(entries.next().is_none());
+    }
+}
@@ -0,0 +1,54 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! Helpers for the variable-length structures following the fixed part of many tables.
+use core::mem::size_of;
+use core::slice;
+
+/// Returns the bytes following `value` up to `length` bytes from its start.
+///
+/// # Safety
+/// `length` bytes starting at `value` have to be readable, usually given by a length field of `value`.
+pub(crate) unsafe fn trailing_bytes<T>(value: &T, length: usize) -> &[u8] {
+    let length = length.saturating_sub(size_of::<T>());
+    slice::from_raw_parts((value as *const T).add(1) as *const u8, length)
+}
+
+/// Splits the next entry of `length` bytes off `bytes`.
+/// Returns `None` and empties `bytes` if the length is shorter than `minimum` or longer than the remaining bytes,
+/// since the following entries can't be found anymore.
+pub(crate) fn split_entry<'a>(
+    bytes: &mut &'a [u8],
+    length: usize,
+    minimum: usize,
+) -> Option<&'a [u8]> {
+    if length < minimum.max(1) || length > bytes.len() {
+        *bytes = &[];
+        return None;
+    }
+
+    let (entry, rest) = bytes.split_at(length);
+    *bytes = rest;
+    Some(entry)
+}
+
+/// Reinterprets the entry as `T`, if it's long enough.
+pub(crate) fn cast<T>(entry: &[u8]) -> Option<&T> {
+    // Safety: All entry structs are packed, so they have no alignment requirements.
+    (entry.len() >= size_of::<T>()).then(|| unsafe { &*(entry.as_ptr() as *const T) })
+}
+
+/// Reinterprets a captured table blob as `T`.
+/// Tables of older revisions are shorter than `T`, so the blob is copied into a zeroed buffer of at least its size.
+#[cfg(test)]
+pub(crate) fn blob<T: crate::AcpiTable>(bytes: &[u8]) -> &'static T {
+    assert!(bytes.len() >= size_of::<crate::AcpiTableHeader>());
+    let padded = vec![0; bytes.len().max(size_of::<T>())].leak();
+    padded[..bytes.len()].copy_from_slice(bytes);
+
+    // Safety: All table structs are packed, so they have no alignment requirements, and the buffer holds a `T`.
+    let table = unsafe { &*(padded.as_ptr() as *const T) };
+    assert_eq!(&table.header().signature, T::SIGNATURE);
+    assert_eq!(table.header().length as usize, bytes.len());
+    table
+}
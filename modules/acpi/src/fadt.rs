@@ -0,0 +1,228 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use crate::{AcpiTable, AcpiTableHeader, GenericAddress};
+use core::mem::{offset_of, size_of};
+
+/// The Fixed ACPI Description Table, describing the fixed hardware of the ACPI power management.
+///
+/// Older revisions of the table are shorter, fields after [`FadtTable::reset_register`] are missing then.
+/// Only read them through the methods, which check the length of the table.
+#[repr(C, packed)]
+pub struct FadtTable {
+    /// ACPI Table Header
+    pub header: AcpiTableHeader,
+    /// 32-bit physical address of the Firmware ACPI Control Structure.
+    pub firmware_ctrl: u32,
+    /// 32-bit physical address of the Differentiated System Description Table, see [`FadtTable::dsdt_address`].
+    pub dsdt: u32,
+    pub reserved: u8,
+    pub preferred_pm_profile: u8,
+    /// The ISA interrupt of the System Control Interrupt.
+    pub sci_interrupt: u16,
+    /// I/O port to write [`FadtTable::acpi_enable`] and [`FadtTable::acpi_disable`] to.
+    pub smi_command_port: u32,
+    pub acpi_enable: u8,
+    pub acpi_disable: u8,
+    pub s4bios_request: u8,
+    pub pstate_control: u8,
+    pub pm1a_event_block: u32,
+    pub pm1b_event_block: u32,
+    pub pm1a_control_block: u32,
+    pub pm1b_control_block: u32,
+    pub pm2_control_block: u32,
+    pub pm_timer_block: u32,
+    pub gpe0_block: u32,
+    pub gpe1_block: u32,
+    pub pm1_event_length: u8,
+    pub pm1_control_length: u8,
+    pub pm2_control_length: u8,
+    pub pm_timer_length: u8,
+    pub gpe0_length: u8,
+    pub gpe1_length: u8,
+    pub gpe1_base: u8,
+    pub c_state_control: u8,
+    pub worst_c2_latency: u16,
+    pub worst_c3_latency: u16,
+    pub flush_size: u16,
+    pub flush_stride: u16,
+    pub duty_offset: u8,
+    pub duty_width: u8,
+    pub day_alarm: u8,
+    pub month_alarm: u8,
+    pub century: u8,
+    /// See [`FadtTable::LEGACY_DEVICES`] and [`FadtTable::PS2_CONTROLLER`].
+    pub boot_architecture_flags: u16,
+    pub reserved2: u8,
+    /// See [`FadtTable::RESET_REGISTER_SUPPORTED`] and [`FadtTable::HARDWARE_REDUCED`].
+    pub flags: u32,
+    /// Register to write [`FadtTable::reset_value`] to for resetting the system.
+    pub reset_register: GenericAddress,
+    pub reset_value: u8,
+    pub arm_boot_architecture_flags: u16,
+    pub minor_version: u8,
+    pub x_firmware_ctrl: u64,
+    pub x_dsdt: u64,
+    pub x_pm1a_event_block: GenericAddress,
+    pub x_pm1b_event_block: GenericAddress,
+    pub x_pm1a_control_block: GenericAddress,
+    pub x_pm1b_control_block: GenericAddress,
+    pub x_pm2_control_block: GenericAddress,
+    pub x_pm_timer_block: GenericAddress,
+    pub x_gpe0_block: GenericAddress,
+    pub x_gpe1_block: GenericAddress,
+    pub sleep_control_register: GenericAddress,
+    pub sleep_status_register: GenericAddress,
+    pub hypervisor_vendor_id: u64,
+}
+
+impl FadtTable {
+    /// Flag set in [`FadtTable::boot_architecture_flags`] if the system has legacy devices on the LPC or ISA bus.
+    pub const LEGACY_DEVICES: u16 = 1 << 0;
+
+    /// Flag set in [`FadtTable::boot_architecture_flags`] if the system has a PS/2 controller, usually an 8042.
+    pub const PS2_CONTROLLER: u16 = 1 << 1;
+
+    /// Flag set in [`FadtTable::flags`] if the system can be reset through [`FadtTable::reset_register`].
+    pub const RESET_REGISTER_SUPPORTED: u32 = 1 << 10;
+
+    /// Flag set in [`FadtTable::flags`] if the system has no fixed hardware, like the PM1 blocks.
+    pub const HARDWARE_REDUCED: u32 = 1 << 20;
+
+    /// Returns the physical address of the Differentiated System Description Table, containing the AML code.
+    /// The 64-bit address is preferred if the table has one.
+    pub fn dsdt_address(&self) -> Option<u64> {
+        let address = if self.contains(offset_of!(FadtTable, x_dsdt) + size_of::<u64>()) {
+            self.x_dsdt
+        } else {
+            0
+        };
+
+        match address {
+            0 => (self.dsdt != 0).then_some(self.dsdt as u64),
+            address => Some(address),
+        }
+    }
+
+    /// Returns the register and the value to write to it for resetting the system, if supported.
+    pub fn reset(&self) -> Option<(&GenericAddress, u8)> {
+        if !self.contains(offset_of!(FadtTable, reset_value) + size_of::<u8>())
+            || self.flags & Self::RESET_REGISTER_SUPPORTED == 0
+        {
+            return None;
+        }
+
+        Some((&self.reset_register, self.reset_value))
+    }
+
+    /// Returns the PM1a and PM1b control blocks, preferring the 64-bit addresses if the table has them.
+    pub fn pm1_control_blocks(&self) -> (Option<GenericAddress>, Option<GenericAddress>) {
+        let (pm1a, pm1b) = if self
+            .contains(offset_of!(FadtTable, x_pm1b_control_block) + size_of::<GenericAddress>())
+        {
+            (
+                Some(self.x_pm1a_control_block),
+                Some(self.x_pm1b_control_block),
+            )
+        } else {
+            (None, None)
+        };
+
+        let width = self.pm1_control_length.saturating_mul(8);
+        let block = |extended: Option<GenericAddress>, port: u32| match extended {
+            Some(block) if block.address != 0 => Some(block),
+            _ => (port != 0).then_some(GenericAddress::system_io(port as u16, width)),
+        };
+
+        (
+            block(pm1a, self.pm1a_control_block),
+            block(pm1b, self.pm1b_control_block),
+        )
+    }
+
+    /// Returns the sleep control and status registers, which replace the PM1 blocks on hardware-reduced systems.
+    pub fn sleep_registers(&self) -> Option<(&GenericAddress, &GenericAddress)> {
+        if !self
+            .contains(offset_of!(FadtTable, sleep_status_register) + size_of::<GenericAddress>())
+        {
+            return None;
+        }
+
+        Some((&self.sleep_control_register, &self.sleep_status_register))
+    }
+
+    /// Returns whenever the system has no fixed hardware, like the PM1 blocks.
+    pub fn is_hardware_reduced(&self) -> bool {
+        self.flags & Self::HARDWARE_REDUCED != 0
+    }
+
+    /// Returns whenever the table is at least `length` bytes long.
+    fn contains(&self, length: usize) -> bool {
+        self.header.length as usize >= length
+    }
+}
+
+impl AcpiTable for FadtTable {
+    const SIGNATURE: &'static [u8; 4] = b"FACP";
+    const MIN_LENGTH: usize = offset_of!(FadtTable, reset_register);
+
+    fn header(&self) -> &AcpiTableHeader {
+        &self.header
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FadtTable;
+    use crate::entry::blob;
+    use crate::{AcpiTable, AddressSpace, GenericAddress};
+
+    #[test]
+    fn test_qemu_q35() {
+        let fadt = blob::<FadtTable>(include_bytes!("../testdata/qemu-q35-facp.dat"));
+        assert!(fadt.validate());
+        assert_eq!(fadt.dsdt_address(), Some(0x7FFE_0040));
+        assert!(!fadt.is_hardware_reduced());
+
+        let Some((register, value)) = fadt.reset() else {
+            panic!("Expected a reset register");
+        };
+        let address = register.address;
+        assert_eq!(
+            (register.address_space(), address, value),
+            (AddressSpace::SystemIo, 0xCF9, 0x0F)
+        );
+
+        let (pm1a, sci_interrupt) = (fadt.pm1a_control_block, fadt.sci_interrupt);
+        assert_eq!((pm1a, sci_interrupt), (0x604, 9));
+        let (Some(pm1a), None) = fadt.pm1_control_blocks() else {
+            panic!("Expected only a PM1a control block");
+        };
+        let address = pm1a.address;
+        assert_eq!(
+            (pm1a.address_space(), address),
+            (AddressSpace::SystemIo, 0x604)
+        );
+
+        // Revision 3 of the table ends before the sleep registers.
+        assert!(fadt.sleep_registers().is_none());
+    }
+
+    #[test]
+    fn test_short_table() {
+        // A revision 1 table ends before the reset register.
+        assert_eq!(FadtTable::MIN_LENGTH, 116);
+        let mut bytes = include_bytes!("../testdata/qemu-q35-facp.dat").to_vec();
+        bytes.truncate(116);
+        bytes[4..8].copy_from_slice(&116u32.to_le_bytes());
+        // The blob is padded to the size of the struct, keeping the length of 116 bytes.
+        let fadt = blob::<FadtTable>(&bytes);
+
+        assert!(fadt.reset().is_none());
+        assert_eq!(
+            fadt.pm1_control_blocks(),
+            (Some(GenericAddress::system_io(0x604, 16)), None)
+        );
+        assert_eq!(fadt.dsdt_address(), Some(0x7FFE_0040));
+    }
+}
@@ -0,0 +1,300 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use crate::entry::{cast, split_entry, trailing_bytes};
+use crate::{AcpiTable, AcpiTableHeader};
+use core::mem::size_of;
+
+/// The I/O Virtualization Reporting Structure, describing the AMD-Vi IOMMUs of the system.
+/// The fixed part is followed by a list of variable-length definition blocks, see [`IvrsTable::entries`].
+#[repr(C, packed)]
+pub struct IvrsTable {
+    /// ACPI Table Header
+    pub header: AcpiTableHeader,
+    /// The virtualization address sizes and capabilities common to all IOMMUs.
+    pub iv_info: u32,
+    pub reserved: u64,
+}
+
+impl IvrsTable {
+    /// Returns an iterator over the definition blocks of the table.
+    pub fn entries(&self) -> IvrsEntries<'_> {
+        // Safety: The entries fill the rest of the table, as given by the table's length.
+        let bytes = unsafe { trailing_bytes(self, self.header.length as usize) };
+        IvrsEntries { bytes }
+    }
+}
+
+impl AcpiTable for IvrsTable {
+    const SIGNATURE: &'static [u8; 4] = b"IVRS";
+
+    fn header(&self) -> &AcpiTableHeader {
+        &self.header
+    }
+}
+
+/// The header of every definition block of the [`IvrsTable`].
+#[repr(C, packed)]
+pub struct IvrsEntryHeader {
+    /// The type of the definition block.
+    pub kind: u8,
+    pub flags: u8,
+    /// Length of the whole definition block in bytes.
+    pub length: u16,
+    /// The PCI device id of the IOMMU or the first device of a memory block.
+    pub device_id: u16,
+}
+
+/// I/O Virtualization Hardware Definition of type 10h, an IOMMU and the devices it handles.
+#[repr(C, packed)]
+pub struct HardwareDefinitionEntry {
+    pub header: IvrsEntryHeader,
+    /// Offset of the IOMMU capability block in the PCI configuration space.
+    pub capability_offset: u16,
+    /// Physical address of the registers of the IOMMU.
+    pub base_address: u64,
+    pub segment: u16,
+    pub iommu_info: u16,
+    pub feature_reporting: u32,
+}
+
+impl HardwareDefinitionEntry {
+    /// Returns an iterator over the device entries of the IOMMU.
+    pub fn devices(&self) -> IvhdDevices<'_> {
+        // Safety: The device entries fill the rest of the block, as given by its length.
+        let bytes = unsafe { trailing_bytes(self, self.header.length as usize) };
+        IvhdDevices { bytes }
+    }
+}
+
+/// I/O Virtualization Hardware Definition of type 11h or 40h, with the extended feature register of the IOMMU.
+#[repr(C, packed)]
+pub struct ExtendedHardwareDefinitionEntry {
+    pub header: IvrsEntryHeader,
+    /// Offset of the IOMMU capability block in the PCI configuration space.
+    pub capability_offset: u16,
+    /// Physical address of the registers of the IOMMU.
+    pub base_address: u64,
+    pub segment: u16,
+    pub iommu_info: u16,
+    pub attributes: u32,
+    /// Copy of the extended feature register of the IOMMU.
+    pub extended_features: u64,
+    /// Copy of the second extended feature register of the IOMMU, reserved for type 11h.
+    pub extended_features2: u64,
+}
+
+impl ExtendedHardwareDefinitionEntry {
+    /// Returns an iterator over the device entries of the IOMMU.
+    pub fn devices(&self) -> IvhdDevices<'_> {
+        // Safety: The device entries fill the rest of the block, as given by its length.
+        let bytes = unsafe { trailing_bytes(self, self.header.length as usize) };
+        IvhdDevices { bytes }
+    }
+}
+
+/// I/O Virtualization Memory Definition, memory used for DMA by devices, which has to stay identity mapped.
+/// The type selects whether it's used by all devices, one device or a range of devices.
+#[repr(C, packed)]
+pub struct MemoryDefinitionEntry {
+    pub header: IvrsEntryHeader,
+    /// The last device of the range for type 22h, else reserved.
+    pub auxiliary_data: u16,
+    pub reserved: u64,
+    pub start_address: u64,
+    /// Length of the memory block in bytes.
+    pub block_length: u64,
+}
+
+/// A definition block of the [`IvrsTable`].
+pub enum IvrsEntry<'a> {
+    HardwareDefinition(&'a HardwareDefinitionEntry),
+    ExtendedHardwareDefinition(&'a ExtendedHardwareDefinitionEntry),
+    MemoryDefinition(&'a MemoryDefinitionEntry),
+    /// A definition block of a type not supported, or too short for its type.
+    Unknown(&'a IvrsEntryHeader),
+}
+
+/// Iterator over the definition blocks of a [`IvrsTable`].
+pub struct IvrsEntries<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for IvrsEntries<'a> {
+    type Item = IvrsEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let [kind, _, length0, length1, ..] = *self.bytes else {
+            return None;
+        };
+        let length = u16::from_le_bytes([length0, length1]);
+        let entry = split_entry(
+            &mut self.bytes,
+            length as usize,
+            size_of::<IvrsEntryHeader>(),
+        )?;
+
+        Some(match kind {
+            0x10 => cast(entry).map_or_else(|| unknown(entry), IvrsEntry::HardwareDefinition),
+            0x11 | 0x40 => {
+                cast(entry).map_or_else(|| unknown(entry), IvrsEntry::ExtendedHardwareDefinition)
+            }
+            0x20..=0x22 => cast(entry).map_or_else(|| unknown(entry), IvrsEntry::MemoryDefinition),
+            _ => unknown(entry),
+        })
+    }
+}
+
+fn unknown(entry: &[u8]) -> IvrsEntry<'_> {
+    // Safety: Every entry is at least as long as its header.
+    IvrsEntry::Unknown(unsafe { &*(entry.as_ptr() as *const IvrsEntryHeader) })
+}
+
+/// A device entry of an I/O Virtualization Hardware Definition, selecting devices and their settings.
+/// Most types have a fixed length of 4, 8 or 16 bytes, the layout depends on the type.
+#[derive(Clone, Copy)]
+pub struct IvhdDevice<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> IvhdDevice<'a> {
+    /// Selects all devices.
+    pub const ALL: u8 = 0x01;
+
+    /// Selects a single device.
+    pub const SELECT: u8 = 0x02;
+
+    /// Starts a range of devices, ended by a [`IvhdDevice::RANGE_END`] entry.
+    pub const RANGE_START: u8 = 0x03;
+
+    /// Ends a range of devices.
+    pub const RANGE_END: u8 = 0x04;
+
+    /// Selects a device, which uses the device id of another device.
+    pub const ALIAS_SELECT: u8 = 0x42;
+
+    /// Selects a special device, like an I/O APIC or HPET.
+    pub const SPECIAL: u8 = 0x48;
+
+    /// Selects an ACPI namespace device by its hardware id.
+    pub const ACPI_HID: u8 = 0xF0;
+
+    /// Returns the type of the entry.
+    pub fn kind(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    /// Returns the device id selected, or `0` if the type selects no device.
+    pub fn device_id(&self) -> u16 {
+        u16::from_le_bytes([self.bytes[1], self.bytes[2]])
+    }
+
+    /// Returns the device table entry settings of the selected devices.
+    pub fn settings(&self) -> u8 {
+        self.bytes[3]
+    }
+
+    /// Returns the whole entry, for types with additional data.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+/// Iterator over the device entries of an I/O Virtualization Hardware Definition.
+pub struct IvhdDevices<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for IvhdDevices<'a> {
+    type Item = IvhdDevice<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let kind = *self.bytes.first()?;
+        let length = match kind {
+            0x00..=0x3F => 4,
+            0x40..=0x7F => 8,
+            0x80..=0xBF => 16,
+            // The length of the unique id follows the hardware and compatible ids.
+            IvhdDevice::ACPI_HID => 22 + *self.bytes.get(21)? as usize,
+            // Variable-length types have their length in different places, the following entries can't be found.
+            _ => 0,
+        };
+
+        let bytes = split_entry(&mut self.bytes, length, 4)?;
+        Some(IvhdDevice { bytes })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IvhdDevice, IvrsEntry, IvrsTable};
+    use crate::entry::blob;
+    use crate::AcpiTable;
+
+    #[test]
+    fn test_qemu_amd_iommu() {
+        let ivrs = blob::<IvrsTable>(include_bytes!("../testdata/qemu-amd-ivrs.dat"));
+        assert!(ivrs.validate());
+
+        let mut entries = ivrs.entries();
+        let Some(IvrsEntry::HardwareDefinition(hardware)) = entries.next() else {
+            panic!("Expected a hardware definition");
+        };
+        let (device_id, base_address) = (hardware.header.device_id, hardware.base_address);
+        assert_eq!((device_id, base_address), (0x0002, 0xFEB8_0000));
+        let kinds = hardware.devices().map(|x| x.kind());
+        assert!(kinds.eq([
+            IvhdDevice::RANGE_START,
+            IvhdDevice::RANGE_END,
+            IvhdDevice::ALIAS_SELECT
+        ]));
+
+        let Some(IvrsEntry::ExtendedHardwareDefinition(hardware)) = entries.next() else {
+            panic!("Expected an extended hardware definition");
+        };
+        let extended_features = hardware.extended_features;
+        assert_eq!(extended_features, 0x2465_77EF_A225_4AFA);
+        let mut devices = hardware.devices().skip(3);
+        let device = devices.next().expect("Expected an ACPI device");
+        assert_eq!(
+            (device.kind(), device.device_id()),
+            (IvhdDevice::ACPI_HID, 0xA5)
+        );
+        assert_eq!(&device.bytes()[4..12], b"AMDI0020");
+        assert_eq!(device.bytes().len(), 24);
+        assert!(devices.next().is_none());
+
+        let Some(IvrsEntry::MemoryDefinition(memory)) = entries.next() else {
+            panic!("Expected a memory definition");
+        };
+        let (start, length) = (memory.start_address, memory.block_length);
+        assert_eq!((start, length), (0xFEE0_0000, 0x10_0000));
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn test_wide_device() {
+        // The QEMU table with a 16 byte entry and a select entry added to the first hardware definition.
+        let ivrs = blob::<IvrsTable>(include_bytes!("../testdata/amd-ivrs-wide-device.dat"));
+        assert!(ivrs.validate());
+
+        let mut entries = ivrs.entries();
+        let Some(IvrsEntry::HardwareDefinition(hardware)) = entries.next() else {
+            panic!("Expected a hardware definition");
+        };
+        let mut devices = hardware.devices().skip(3);
+        let device = devices.next().expect("Expected a 16 byte entry");
+        assert_eq!((device.kind(), device.bytes().len()), (0x80, 16));
+        let device = devices.next().expect("Expected a select entry");
+        assert_eq!(
+            (device.kind(), device.device_id()),
+            (IvhdDevice::SELECT, 0x08)
+        );
+        assert!(devices.next().is_none());
+
+        assert!(matches!(
+            entries.next(),
+            Some(IvrsEntry::ExtendedHardwareDefinition(_))
+        ));
+    }
+}
@@ -8,59 +8,113 @@
 //! In addition it's a very big standard even including a custom programming language called AML.
 //! This Module just allows finding so-called ACPI Tables based on their unique signature,
 //! but only until the userspace ACPI service takes over.
+//! The tables listed by the (Extended) System Descriptor Table are parsed and validated once during initialization,
+//! [`tables`] returns all of them for handing them over to userspace.
 //!
-#![no_std]
-
+//! [`reboot`] and [`shutdown`] use the fixed hardware described by the [`FadtTable`],
+//! they are installed as handlers of [`common::power`].
+//!
+//! The tables needed by the kernel itself are available as typed [`AcpiTable`]s for [`find_table`],
+//! like the [`FadtTable`], [`MadtTable`], [`McfgTable`], [`SratTable`] and [`SlitTable`], [`DmarTable`] and [`IvrsTable`].
+//!
+#![cfg_attr(not(test), no_std)]
+
+mod address;
+mod aml;
+mod bgrt;
+mod dmar;
+mod entry;
+mod fadt;
 mod header;
 mod hpet;
+mod ivrs;
+mod list;
+mod madt;
+mod mcfg;
+#[cfg(target_arch = "x86_64")]
+mod port;
+mod power;
 mod rsdp;
+mod slit;
+mod srat;
 mod table;
-mod utils;
 
+pub use address::*;
+pub use bgrt::*;
+pub use dmar::*;
+pub use fadt::*;
 pub use header::*;
 pub use hpet::*;
+pub use ivrs::*;
+pub use list::{TableEntry, MAX_TABLES};
+pub use madt::*;
+pub use mcfg::*;
 use microdragon_interface::macros::init;
+pub use power::{reboot, shutdown, ShutdownError};
+pub use slit::*;
+pub use srat::*;
 pub use table::*;
-pub use utils::*;
 
 use common::addr::PhysAddr;
 use common::addr::VirtAddr;
 use common::memory::physical_to_virtual;
 use common::sync::SyncOnceCell;
 use core::mem;
-use core::slice;
+use list::TableList;
 use log::{debug, info, log_enabled, warn, Level};
 use microdragon_interface::ModuleInterface;
 
+/// Signature of the System Descriptor Table.
+const SIGNATURE: &[u8; 4] = b"RSDT";
+
 /// Signature of the Extended System Descriptor Table used in ACPI 2.0+.
 /// It uses 64-bit pointers, so we have to detect that and choose accordingly.
-const EXTENDED_SIGNATURE: &[u8] = b"XSDT";
+const EXTENDED_SIGNATURE: &[u8; 4] = b"XSDT";
 
-/// Static reference to the (Extended) System Descriptor Table.
-/// This is used by [`find_table`] to iterate though all available ACPI tables.
-static SYSTEM_DESCRIPTOR_TABLE: SyncOnceCell<&'static AcpiTableHeader> = SyncOnceCell::new();
+/// The valid tables listed by the (Extended) System Descriptor Table.
+/// This is used by [`find_table`] and [`tables`] to iterate though all available ACPI tables.
+static TABLES: SyncOnceCell<TableList> = SyncOnceCell::new();
 
 /// Entrypoint to the ACPI module.
 #[init]
 pub fn init(interface: &ModuleInterface) {
-    if SYSTEM_DESCRIPTOR_TABLE.is_initialized() {
+    if TABLES.is_initialized() {
         warn!("ACPI Kernel Module already initialized");
         return;
     }
 
-    if interface.rsdp_address == 0 {
-        info!("ACPI not available");
-        return;
+    // Safety: We assume the address given by the interface points to the RSDP.
+    let given = (interface.rsdp_address != 0)
+        .then(|| unsafe { rsdp::read(PhysAddr::new_truncate(interface.rsdp_address)) });
+    if let Some(Err(error)) = given {
+        warn!(
+            "ACPI Root System Description Pointer given by the bootloader is invalid: {:?}",
+            error
+        );
     }
-    let address = PhysAddr::new_truncate(interface.rsdp_address);
 
-    // Safety: We assume the address given by the interface is valid.
-    let Some(address) = rsdp::read(physical_to_virtual(address)) else {
-        return;
+    let address = match given {
+        Some(Ok(address)) => address,
+        _ => {
+            let Some((rsdp, address)) = rsdp::search() else {
+                info!("ACPI not available");
+                return;
+            };
+            debug!("Found ACPI Root System Description Pointer at {:#x}", rsdp);
+            address
+        }
     };
 
     // Safety: We assume the address given by the RSDP is valid.
     let sdp = unsafe { &*physical_to_virtual(address).as_ptr::<AcpiTableHeader>() };
+    if sdp.signature != *SIGNATURE && sdp.signature != *EXTENDED_SIGNATURE {
+        warn!("ACPI System Descriptor Table corrupted? Signature didn't match");
+        return;
+    }
+    if (sdp.length as usize) < mem::size_of::<AcpiTableHeader>() {
+        warn!("ACPI System Descriptor Table corrupted? Length is too small");
+        return;
+    }
     if !sdp.validate() {
         warn!("ACPI System Descriptor Table corrupted? Checksum didn't match");
         return;
@@ -81,59 +135,101 @@ pub fn init(interface: &ModuleInterface) {
         );
     }
 
-    SYSTEM_DESCRIPTOR_TABLE.get_or_init(|| sdp);
+    let mut tables = TableList::new();
+    for address in table_addresses(sdp) {
+        // Safety: We assume the addresses given by the System Descriptor Table are valid.
+        let header = unsafe { &*physical_to_virtual(address).as_ptr::<AcpiTableHeader>() };
+        let signature = core::str::from_utf8(&header.signature).unwrap_or_default();
+        if (header.length as usize) < mem::size_of::<AcpiTableHeader>() {
+            warn!("ACPI Table '{}' corrupted? Length is too small", signature);
+            continue;
+        }
+        if !header.validate() {
+            warn!(
+                "ACPI Table '{}' corrupted? Checksum didn't match",
+                signature
+            );
+            continue;
+        }
+
+        debug!("Found ACPI Table '{}' at {:#x}", signature, address);
+        if !tables.push(TableEntry { address, header }) {
+            warn!("Too many ACPI Tables, ignoring ACPI Table '{}'", signature);
+        }
+    }
+
+    TABLES.get_or_init(|| tables);
+
+    if find_table::<FadtTable>().is_some() {
+        let _ = common::power::set_reboot_handler(reboot);
+        let _ = common::power::set_shutdown_handler(power::shutdown_handler);
+    }
+}
+
+/// Returns all valid ACPI tables, in the order listed by the (Extended) System Descriptor Table.
+/// This function only works if [`init`] was called before, else the iterator will always be empty.
+pub fn tables() -> impl Iterator<Item = TableEntry> {
+    TABLES.get().into_iter().flat_map(|x| x.iter())
+}
+
+/// Tries to find the ACPI table with the signature.
+/// This function only works if [`init`] was called before, else it will always return `None`.
+pub fn find_table_by_signature(signature: &[u8; 4]) -> Option<TableEntry> {
+    find_tables_by_signature(signature).next()
+}
+
+/// Finds all ACPI tables with the signature, in the order listed by the (Extended) System Descriptor Table.
+/// This function only works if [`init`] was called before, else the iterator will always be empty.
+pub fn find_tables_by_signature(signature: &[u8; 4]) -> impl Iterator<Item = TableEntry> {
+    let signature = *signature;
+    TABLES
+        .get()
+        .into_iter()
+        .flat_map(move |x| x.with_signature(signature))
 }
 
 /// Tries to find the ACPI Table `T`.
 /// This function only works if [`init`] was called before, else it will always return `None`.
-pub fn find_table<T: AcpiTable>() -> Option<&'static T> {
-    let sdt = *SYSTEM_DESCRIPTOR_TABLE.get()?;
+pub fn find_table<T: AcpiTable + 'static>() -> Option<&'static T> {
+    find_tables::<T>().next()
+}
+
+/// Finds all instances of the ACPI Table `T`, in the order listed by the (Extended) System Descriptor Table.
+/// This function only works if [`init`] was called before, else the iterator will always be empty.
+pub fn find_tables<T: AcpiTable + 'static>() -> impl Iterator<Item = &'static T> {
+    find_tables_by_signature(T::SIGNATURE).filter_map(|x| {
+        if (x.header.length as usize) < T::MIN_LENGTH {
+            let signature = core::str::from_utf8(T::SIGNATURE).unwrap_or_default();
+            warn!("ACPI Table '{}' corrupted? Length is too small", signature);
+            return None;
+        }
+
+        // Safety: The signature identifies the table as `T` and the table is long enough.
+        Some(unsafe { &*(x.header as *const AcpiTableHeader as *const T) })
+    })
+}
+
+/// Returns the physical addresses of all tables listed by the (Extended) System Descriptor Table.
+fn table_addresses(sdt: &AcpiTableHeader) -> impl Iterator<Item = PhysAddr> {
     let start = VirtAddr::from(sdt as *const _) + mem::size_of::<AcpiTableHeader>();
     let length = sdt.length as usize - mem::size_of::<AcpiTableHeader>();
     debug!("SDP Entry List Start: {:#x} Size: {}", start, length);
 
-    let table = if sdt.signature == EXTENDED_SIGNATURE {
-        let count = length / mem::size_of::<u64>();
-        let tables = unsafe { slice::from_raw_parts(start.as_ptr::<u64>(), count) };
-
-        tables.iter().find_map(|x| {
-            let table = PhysAddr::new_truncate(*x);
-            let table = unsafe { &*physical_to_virtual(table).as_ptr::<AcpiTableHeader>() };
-
-            if &table.signature == T::SIGNATURE {
-                Some(table)
-            } else {
-                None
-            }
-        })?
+    let entry_size = if sdt.signature == *EXTENDED_SIGNATURE {
+        mem::size_of::<u64>()
     } else {
-        let count = length / mem::size_of::<u32>();
-        let tables = unsafe { slice::from_raw_parts(start.as_ptr::<u32>(), count) };
-
-        tables.iter().find_map(|x| {
-            let table = PhysAddr::new_truncate(*x as u64);
-            let table = unsafe { &*physical_to_virtual(table).as_ptr::<AcpiTableHeader>() };
-
-            if &table.signature == T::SIGNATURE {
-                Some(table)
-            } else {
-                None
-            }
-        })?
+        mem::size_of::<u32>()
     };
 
-    debug!(
-        "Found ACPI Table '{}'",
-        core::str::from_utf8(&table.signature).unwrap_or_default()
-    );
-
-    if !table.validate() {
-        warn!(
-            "ACPI Table '{}' corrupted? Checksum didn't match",
-            core::str::from_utf8(&table.signature).unwrap_or_default()
-        );
-        return None;
-    }
-
-    Some(unsafe { &*(table as *const _ as *const T) })
+    (0..length / entry_size).map(move |i| {
+        // The 64-bit entries of the Extended System Descriptor Table are only 4-byte aligned.
+        let entry = start + i * entry_size;
+        let address = if entry_size == mem::size_of::<u64>() {
+            unsafe { entry.as_ptr::<u64>().read_unaligned() }
+        } else {
+            unsafe { entry.as_ptr::<u32>().read_unaligned() as u64 }
+        };
+
+        PhysAddr::new_truncate(address)
+    })
 }
@@ -0,0 +1,120 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use crate::AcpiTableHeader;
+use common::addr::PhysAddr;
+
+/// The maximal number of ACPI tables, further tables are ignored.
+pub const MAX_TABLES: usize = 64;
+
+/// An ACPI table listed by the (Extended) System Descriptor Table.
+#[derive(Clone, Copy)]
+pub struct TableEntry {
+    /// Physical address of the table.
+    pub address: PhysAddr,
+
+    /// The header of the table, followed by the rest of the table.
+    pub header: &'static AcpiTableHeader,
+}
+
+impl TableEntry {
+    /// Returns the 4 byte signature of the table.
+    pub fn signature(&self) -> [u8; 4] {
+        self.header.signature
+    }
+}
+
+/// The tables listed by the (Extended) System Descriptor Table, parsed once during initialization.
+pub(crate) struct TableList {
+    entries: [Option<TableEntry>; MAX_TABLES],
+    len: usize,
+}
+
+impl TableList {
+    pub const fn new() -> Self {
+        TableList {
+            entries: [None; MAX_TABLES],
+            len: 0,
+        }
+    }
+
+    /// Adds the table to the end of the list, returns `false` if the list is full.
+    pub fn push(&mut self, entry: TableEntry) -> bool {
+        let Some(slot) = self.entries.get_mut(self.len) else {
+            return false;
+        };
+
+        *slot = Some(entry);
+        self.len += 1;
+        true
+    }
+
+    /// Iterates over all tables in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = TableEntry> + '_ {
+        self.entries[..self.len].iter().flatten().copied()
+    }
+
+    /// Iterates over all tables with the signature in the order they were added.
+    pub fn with_signature(&self, signature: [u8; 4]) -> impl Iterator<Item = TableEntry> + '_ {
+        self.iter().filter(move |x| x.signature() == signature)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TableEntry, TableList, MAX_TABLES};
+    use crate::AcpiTableHeader;
+    use common::addr::PhysAddr;
+    use std::boxed::Box;
+
+    fn entry(address: u64, signature: &[u8; 4]) -> TableEntry {
+        let header = Box::leak(Box::new(AcpiTableHeader {
+            signature: *signature,
+            length: core::mem::size_of::<AcpiTableHeader>() as u32,
+            revision: 1,
+            checksum: 0,
+            oem_id: *b"OEMID ",
+            oem_table_id: *b"OEMTABLE",
+            oem_revision: 0,
+            creator_id: *b"TEST",
+            creator_revision: 0,
+        }));
+        TableEntry {
+            address: unsafe { PhysAddr::new_unsafe(address) },
+            header,
+        }
+    }
+
+    #[test]
+    fn test_lookup_by_signature() {
+        let mut list = TableList::new();
+        assert!(list.push(entry(0x1000, b"FACP")));
+        assert!(list.push(entry(0x2000, b"SSDT")));
+        assert!(list.push(entry(0x3000, b"APIC")));
+        assert!(list.push(entry(0x4000, b"SSDT")));
+
+        let all = list
+            .iter()
+            .map(|x| x.address.as_u64())
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(all, [0x1000, 0x2000, 0x3000, 0x4000]);
+
+        let ssdts = list
+            .with_signature(*b"SSDT")
+            .map(|x| x.address.as_u64())
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(ssdts, [0x2000, 0x4000]);
+        assert!(list.with_signature(*b"HPET").next().is_none());
+    }
+
+    #[test]
+    fn test_list_full() {
+        let mut list = TableList::new();
+        for i in 0..MAX_TABLES {
+            assert!(list.push(entry(i as u64 * 0x1000, b"SSDT")));
+        }
+
+        assert!(!list.push(entry(0, b"FACP")));
+        assert_eq!(list.iter().count(), MAX_TABLES);
+    }
+}
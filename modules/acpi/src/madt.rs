@@ -0,0 +1,342 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use crate::entry::{cast, split_entry, trailing_bytes};
+use crate::{AcpiTable, AcpiTableHeader};
+use core::mem::size_of;
+
+/// The Multiple APIC Description Table, describing the interrupt controllers of the system.
+/// The fixed part is followed by a list of variable-length entries, see [`MadtTable::entries`].
+#[repr(C, packed)]
+pub struct MadtTable {
+    /// ACPI Table Header
+    pub header: AcpiTableHeader,
+
+    /// Physical address of the local APIC of each processor.
+    pub local_apic_address: u32,
+
+    /// See [`MadtTable::PCAT_COMPAT`].
+    pub flags: u32,
+}
+
+impl MadtTable {
+    /// Flag set if the system also has a legacy dual 8259 PIC, which has to be masked when using the APIC.
+    pub const PCAT_COMPAT: u32 = 1 << 0;
+
+    /// Returns an iterator over the entries of the table.
+    pub fn entries(&self) -> MadtEntries<'_> {
+        // Safety: The entries fill the rest of the table, as given by the table's length.
+        let bytes = unsafe { trailing_bytes(self, self.header.length as usize) };
+        MadtEntries { bytes }
+    }
+
+    /// Returns whenever the system has a legacy dual 8259 PIC.
+    pub fn has_legacy_pic(&self) -> bool {
+        self.flags & Self::PCAT_COMPAT != 0
+    }
+}
+
+impl AcpiTable for MadtTable {
+    const SIGNATURE: &'static [u8; 4] = b"APIC";
+
+    fn header(&self) -> &AcpiTableHeader {
+        &self.header
+    }
+}
+
+/// The header of every entry of the [`MadtTable`].
+#[repr(C, packed)]
+pub struct MadtEntryHeader {
+    /// The type of the entry.
+    pub kind: u8,
+
+    /// Length of the whole entry in bytes.
+    pub length: u8,
+}
+
+/// A processor and its local APIC.
+#[repr(C, packed)]
+pub struct LocalApicEntry {
+    pub header: MadtEntryHeader,
+    pub processor_uid: u8,
+    pub apic_id: u8,
+    /// See [`LocalApicEntry::ENABLED`] and [`LocalApicEntry::ONLINE_CAPABLE`].
+    pub flags: u32,
+}
+
+impl LocalApicEntry {
+    /// The processor is ready to use.
+    pub const ENABLED: u32 = 1 << 0;
+
+    /// The processor is disabled, but can be enabled by the OS.
+    pub const ONLINE_CAPABLE: u32 = 1 << 1;
+}
+
+/// An I/O APIC and the range of global system interrupts (GSI) it handles.
+#[repr(C, packed)]
+pub struct IoApicEntry {
+    pub header: MadtEntryHeader,
+    pub io_apic_id: u8,
+    pub reserved: u8,
+    /// Physical address of the I/O APIC registers.
+    pub address: u32,
+    /// The first global system interrupt handled by the I/O APIC.
+    pub gsi_base: u32,
+}
+
+/// Maps an ISA interrupt to a different global system interrupt or with a non-default polarity or trigger mode.
+#[repr(C, packed)]
+pub struct InterruptSourceOverrideEntry {
+    pub header: MadtEntryHeader,
+    /// Always `0` for ISA.
+    pub bus: u8,
+    /// The ISA interrupt.
+    pub source: u8,
+    pub gsi: u32,
+    pub flags: InterruptFlags,
+}
+
+/// A global system interrupt connected to the NMI of the processors.
+#[repr(C, packed)]
+pub struct NmiSourceEntry {
+    pub header: MadtEntryHeader,
+    pub flags: InterruptFlags,
+    pub gsi: u32,
+}
+
+/// The local APIC interrupt pin (LINT) a processor's NMI is connected to.
+#[repr(C, packed)]
+pub struct LocalApicNmiEntry {
+    pub header: MadtEntryHeader,
+    /// The processor or `0xFF` for all processors.
+    pub processor_uid: u8,
+    pub flags: InterruptFlags,
+    /// Either `0` for LINT0 or `1` for LINT1.
+    pub lint: u8,
+}
+
+/// Overrides the 32-bit address of the local APICs in the [`MadtTable`].
+#[repr(C, packed)]
+pub struct LocalApicAddressOverrideEntry {
+    pub header: MadtEntryHeader,
+    pub reserved: u16,
+    pub address: u64,
+}
+
+/// A processor and its local x2APIC, used for APIC ids above 254.
+#[repr(C, packed)]
+pub struct LocalX2ApicEntry {
+    pub header: MadtEntryHeader,
+    pub reserved: u16,
+    pub x2apic_id: u32,
+    /// Same as [`LocalApicEntry::flags`].
+    pub flags: u32,
+    pub processor_uid: u32,
+}
+
+/// The local x2APIC interrupt pin (LINT) a processor's NMI is connected to.
+#[repr(C, packed)]
+pub struct LocalX2ApicNmiEntry {
+    pub header: MadtEntryHeader,
+    pub flags: InterruptFlags,
+    /// The processor or `0xFFFFFFFF` for all processors.
+    pub processor_uid: u32,
+    /// Either `0` for LINT0 or `1` for LINT1.
+    pub lint: u8,
+    pub reserved: [u8; 3],
+}
+
+/// An entry of the [`MadtTable`].
+pub enum MadtEntry<'a> {
+    LocalApic(&'a LocalApicEntry),
+    IoApic(&'a IoApicEntry),
+    InterruptSourceOverride(&'a InterruptSourceOverrideEntry),
+    NmiSource(&'a NmiSourceEntry),
+    LocalApicNmi(&'a LocalApicNmiEntry),
+    LocalApicAddressOverride(&'a LocalApicAddressOverrideEntry),
+    LocalX2Apic(&'a LocalX2ApicEntry),
+    LocalX2ApicNmi(&'a LocalX2ApicNmiEntry),
+    /// An entry of a type not supported, or too short for its type.
+    Unknown(&'a MadtEntryHeader),
+}
+
+/// Iterator over the entries of a [`MadtTable`].
+pub struct MadtEntries<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for MadtEntries<'a> {
+    type Item = MadtEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let [kind, length, ..] = *self.bytes else {
+            return None;
+        };
+        let entry = split_entry(
+            &mut self.bytes,
+            length as usize,
+            size_of::<MadtEntryHeader>(),
+        )?;
+
+        Some(match kind {
+            0 => cast(entry).map_or_else(|| unknown(entry), MadtEntry::LocalApic),
+            1 => cast(entry).map_or_else(|| unknown(entry), MadtEntry::IoApic),
+            2 => cast(entry).map_or_else(|| unknown(entry), MadtEntry::InterruptSourceOverride),
+            3 => cast(entry).map_or_else(|| unknown(entry), MadtEntry::NmiSource),
+            4 => cast(entry).map_or_else(|| unknown(entry), MadtEntry::LocalApicNmi),
+            5 => cast(entry).map_or_else(|| unknown(entry), MadtEntry::LocalApicAddressOverride),
+            9 => cast(entry).map_or_else(|| unknown(entry), MadtEntry::LocalX2Apic),
+            10 => cast(entry).map_or_else(|| unknown(entry), MadtEntry::LocalX2ApicNmi),
+            _ => unknown(entry),
+        })
+    }
+}
+
+fn unknown(entry: &[u8]) -> MadtEntry<'_> {
+    // Safety: Every entry is at least as long as its header.
+    MadtEntry::Unknown(unsafe { &*(entry.as_ptr() as *const MadtEntryHeader) })
+}
+
+/// The polarity of an interrupt line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptPolarity {
+    /// The default polarity of the bus, active high for ISA.
+    BusDefault,
+    ActiveHigh,
+    ActiveLow,
+}
+
+/// The trigger mode of an interrupt line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptTrigger {
+    /// The default trigger mode of the bus, edge-triggered for ISA.
+    BusDefault,
+    Edge,
+    Level,
+}
+
+/// The MPS INTI flags, describing the polarity and trigger mode of an interrupt line.
+#[derive(Clone, Copy)]
+#[repr(transparent)]
+pub struct InterruptFlags(pub u16);
+
+impl InterruptFlags {
+    /// Returns the polarity of the interrupt line.
+    pub fn polarity(self) -> InterruptPolarity {
+        match self.0 & 0b11 {
+            0b01 => InterruptPolarity::ActiveHigh,
+            0b11 => InterruptPolarity::ActiveLow,
+            _ => InterruptPolarity::BusDefault,
+        }
+    }
+
+    /// Returns the trigger mode of the interrupt line.
+    pub fn trigger(self) -> InterruptTrigger {
+        match (self.0 >> 2) & 0b11 {
+            0b01 => InterruptTrigger::Edge,
+            0b11 => InterruptTrigger::Level,
+            _ => InterruptTrigger::BusDefault,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InterruptPolarity, InterruptTrigger, MadtEntry, MadtTable};
+    use crate::AcpiTableHeader;
+    use core::mem::size_of;
+
+    /// Builds a MADT with the given entries, aligned like the tables in memory.
+    fn table(entries: &[&[u8]]) -> Vec<u64> {
+        let mut bytes = vec![0u8; size_of::<MadtTable>()];
+        bytes[..4].copy_from_slice(b"APIC");
+        bytes[36..40].copy_from_slice(&0xFEE0_0000u32.to_le_bytes());
+        bytes[40] = 1;
+        for entry in entries {
+            bytes.extend_from_slice(entry);
+        }
+        let length = bytes.len() as u32;
+        bytes[4..8].copy_from_slice(&length.to_le_bytes());
+
+        let mut words = vec![0u64; bytes.len().div_ceil(8)];
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                words.as_mut_ptr() as *mut u8,
+                bytes.len(),
+            )
+        };
+        words
+    }
+
+    fn madt(words: &[u64]) -> &MadtTable {
+        assert!(words.len() * 8 >= size_of::<AcpiTableHeader>());
+        unsafe { &*(words.as_ptr() as *const MadtTable) }
+    }
+
+    #[test]
+    fn test_entries() {
+        let words = table(&[
+            &[0, 8, 1, 2, 1, 0, 0, 0],
+            &[1, 12, 3, 0, 0x00, 0x00, 0xC0, 0xFE, 0, 0, 0, 0],
+            &[2, 10, 0, 0, 2, 0, 0, 0, 0x0F, 0],
+            &[4, 6, 0xFF, 0x05, 0, 1],
+            &[9, 16, 0, 0, 0x00, 0x01, 0, 0, 1, 0, 0, 0, 7, 0, 0, 0],
+            &[0x7F, 4, 0, 0],
+        ]);
+        let madt = madt(&words);
+        assert!(madt.has_legacy_pic());
+        let local_apic_address = madt.local_apic_address;
+        assert_eq!(local_apic_address, 0xFEE0_0000);
+
+        let mut entries = madt.entries();
+        let Some(MadtEntry::LocalApic(entry)) = entries.next() else {
+            panic!("Expected a local APIC entry");
+        };
+        assert_eq!((entry.processor_uid, entry.apic_id), (1, 2));
+
+        let Some(MadtEntry::IoApic(entry)) = entries.next() else {
+            panic!("Expected an I/O APIC entry");
+        };
+        let address = entry.address;
+        assert_eq!(address, 0xFEC0_0000);
+
+        let Some(MadtEntry::InterruptSourceOverride(entry)) = entries.next() else {
+            panic!("Expected an interrupt source override entry");
+        };
+        let (gsi, flags) = (entry.gsi, entry.flags);
+        assert_eq!((entry.source, gsi), (0, 2));
+        assert_eq!(flags.polarity(), InterruptPolarity::ActiveLow);
+        assert_eq!(flags.trigger(), InterruptTrigger::Level);
+
+        let Some(MadtEntry::LocalApicNmi(entry)) = entries.next() else {
+            panic!("Expected a local APIC NMI entry");
+        };
+        let flags = entry.flags;
+        assert_eq!((entry.processor_uid, entry.lint), (0xFF, 1));
+        assert_eq!(flags.polarity(), InterruptPolarity::ActiveHigh);
+        assert_eq!(flags.trigger(), InterruptTrigger::Edge);
+
+        let Some(MadtEntry::LocalX2Apic(entry)) = entries.next() else {
+            panic!("Expected a local x2APIC entry");
+        };
+        let (x2apic_id, processor_uid) = (entry.x2apic_id, entry.processor_uid);
+        assert_eq!((x2apic_id, processor_uid), (0x100, 7));
+
+        assert!(matches!(entries.next(), Some(MadtEntry::Unknown(x)) if x.kind == 0x7F));
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn test_corrupted_entries() {
+        // An entry too short for its type, followed by one overflowing the table.
+        let words = table(&[&[1, 4, 0, 0], &[0, 32, 0, 0]]);
+        let mut entries = madt(&words).entries();
+        assert!(matches!(entries.next(), Some(MadtEntry::Unknown(x)) if x.kind == 1));
+        assert!(entries.next().is_none());
+
+        // A zero length would loop forever.
+        let words = table(&[&[0, 0, 0, 0]]);
+        assert!(madt(&words).entries().next().is_none());
+    }
+}
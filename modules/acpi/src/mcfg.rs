@@ -0,0 +1,85 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use crate::entry::trailing_bytes;
+use crate::{AcpiTable, AcpiTableHeader};
+use core::mem::size_of;
+use core::slice;
+
+/// The PCI Express Memory-mapped Configuration Table, listing the enhanced configuration access mechanism (ECAM) regions.
+#[repr(C, packed)]
+pub struct McfgTable {
+    /// ACPI Table Header
+    pub header: AcpiTableHeader,
+    pub reserved: u64,
+}
+
+impl McfgTable {
+    /// Returns the ECAM regions, one for each PCI segment group and bus range.
+    pub fn segments(&self) -> &[McfgSegment] {
+        // Safety: The segments fill the rest of the table, as given by the table's length.
+        let bytes = unsafe { trailing_bytes(self, self.header.length as usize) };
+        // Safety: The segment struct is packed, so it has no alignment requirements.
+        unsafe {
+            slice::from_raw_parts(
+                bytes.as_ptr() as *const McfgSegment,
+                bytes.len() / size_of::<McfgSegment>(),
+            )
+        }
+    }
+}
+
+impl AcpiTable for McfgTable {
+    const SIGNATURE: &'static [u8; 4] = b"MCFG";
+
+    fn header(&self) -> &AcpiTableHeader {
+        &self.header
+    }
+}
+
+/// The ECAM region of a PCI segment group and bus range.
+#[repr(C, packed)]
+pub struct McfgSegment {
+    /// Physical address of the region, as if it started at bus `0`.
+    pub base_address: u64,
+    pub segment_group: u16,
+    pub start_bus: u8,
+    pub end_bus: u8,
+    pub reserved: u32,
+}
+
+impl McfgSegment {
+    /// Returns the physical address of the configuration space of a function, if it's in this region.
+    pub fn function_address(&self, bus: u8, device: u8, function: u8) -> Option<u64> {
+        if bus < self.start_bus || bus > self.end_bus || device >= 32 || function >= 8 {
+            return None;
+        }
+
+        let offset = (bus as u64) << 20 | (device as u64) << 15 | (function as u64) << 12;
+        Some(self.base_address + offset)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::McfgTable;
+    use crate::entry::blob;
+    use crate::AcpiTable;
+
+    #[test]
+    fn test_qemu_q35() {
+        let mcfg = blob::<McfgTable>(include_bytes!("../testdata/qemu-q35-mcfg.dat"));
+        assert!(mcfg.validate());
+
+        let [segment] = mcfg.segments() else {
+            panic!("Expected a single segment");
+        };
+        let (base_address, segment_group) = (segment.base_address, segment.segment_group);
+        assert_eq!((base_address, segment_group), (0xB000_0000, 0));
+        assert_eq!((segment.start_bus, segment.end_bus), (0, 0xFF));
+
+        assert_eq!(segment.function_address(0, 0, 0), Some(0xB000_0000));
+        assert_eq!(segment.function_address(1, 2, 3), Some(0xB011_3000));
+        assert_eq!(segment.function_address(0, 32, 0), None);
+    }
+}
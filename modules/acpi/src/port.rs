@@ -0,0 +1,35 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! Access to the x86 I/O ports.
+use core::arch::asm;
+
+pub(crate) unsafe fn read_u8(port: u16) -> u8 {
+    let value;
+    asm!("IN AL, DX", in("dx") port, out("al") value, options(nomem, nostack, preserves_flags));
+    value
+}
+
+pub(crate) unsafe fn read_u16(port: u16) -> u16 {
+    let value;
+    asm!("IN AX, DX", in("dx") port, out("ax") value, options(nomem, nostack, preserves_flags));
+    value
+}
+
+pub(crate) unsafe fn read_u32(port: u16) -> u32 {
+    let value;
+    asm!("IN EAX, DX", in("dx") port, out("eax") value, options(nomem, nostack, preserves_flags));
+    value
+}
+
+pub(crate) unsafe fn write_u8(port: u16, value: u8) {
+    asm!("OUT DX, AL", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+}
+
+pub(crate) unsafe fn write_u16(port: u16, value: u16) {
+    asm!("OUT DX, AX", in("dx") port, in("ax") value, options(nomem, nostack, preserves_flags));
+}
+
+pub(crate) unsafe fn write_u32(port: u16, value: u32) {
+    asm!("OUT DX, EAX", in("dx") port, in("eax") value, options(nomem, nostack, preserves_flags));
+}
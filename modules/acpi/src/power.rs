@@ -0,0 +1,220 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! Restarting and turning off the machine through the fixed hardware described by the [`FadtTable`].
+use crate::entry::trailing_bytes;
+use crate::{aml, find_table, find_tables_by_signature};
+use crate::{AcpiTableHeader, AddressError, FadtTable, GenericAddress};
+use common::addr::PhysAddr;
+use common::memory::physical_to_virtual;
+use core::convert::Infallible;
+use log::{info, warn};
+
+/// Bits of the sleep type in the PM1 control registers.
+const PM1_SLP_TYP: u16 = 0b111 << 10;
+
+/// Bit of the PM1 control registers entering the sleep state given by the sleep type.
+const PM1_SLP_EN: u16 = 1 << 13;
+
+/// Bit of the PM1 control registers set while ACPI is enabled.
+const PM1_SCI_EN: u16 = 1 << 0;
+
+/// Shift of the sleep type in the sleep control register of hardware-reduced systems.
+const SLEEP_CONTROL_SLP_TYP_SHIFT: u8 = 2;
+
+/// Bit of the sleep control register of hardware-reduced systems entering the sleep state.
+const SLEEP_CONTROL_SLP_EN: u8 = 1 << 5;
+
+/// Status and command port of the PS/2 keyboard controller.
+const KEYBOARD_CONTROLLER: u16 = 0x64;
+
+/// Bit of the keyboard controller's status set while its input buffer is full.
+const KEYBOARD_INPUT_FULL: u8 = 1 << 1;
+
+/// Keyboard controller command pulsing the CPU reset line.
+const KEYBOARD_RESET: u8 = 0xFE;
+
+/// Iterations of waiting for the hardware to react, before trying something else.
+const WAIT_ITERATIONS: usize = 10_000_000;
+
+/// Error returned when turning the machine off fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownError {
+    /// The system has no valid [`FadtTable`].
+    NoFadt,
+
+    /// Neither the DSDT nor an SSDT defines the `\_S5` sleep state.
+    NoSleepState,
+
+    /// The FADT doesn't describe the PM1a control block or the sleep control register.
+    NoControlRegister,
+
+    /// Accessing a register failed.
+    Register(AddressError),
+
+    /// ACPI couldn't be enabled, the firmware still owns the fixed hardware.
+    AcpiNotEnabled,
+
+    /// The machine was still running after entering the sleep state.
+    StillRunning,
+}
+
+/// Restarts the machine.
+///
+/// The reset register of the [`FadtTable`] is tried first,
+/// then the PS/2 keyboard controller and at last a triple fault.
+pub fn reboot() -> ! {
+    let _guard = common::interrupts::disable();
+
+    if let Some((register, value)) = find_table::<FadtTable>().and_then(|x| x.reset()) {
+        info!("Rebooting through the ACPI reset register");
+        // Safety: The FADT gives the register.
+        if unsafe { register.write(value as u64) }.is_ok() {
+            wait();
+        }
+    }
+
+    // Safety: Port 0x64 is the PS/2 keyboard controller on PC compatible systems, resetting is harmless if absent.
+    // Other architectures don't have I/O ports, the accesses fail then.
+    let keyboard = GenericAddress::system_io(KEYBOARD_CONTROLLER, 8);
+    unsafe {
+        for _ in 0..WAIT_ITERATIONS {
+            match keyboard.read() {
+                Ok(status) if status as u8 & KEYBOARD_INPUT_FULL != 0 => core::hint::spin_loop(),
+                _ => break,
+            }
+        }
+        if keyboard.write(KEYBOARD_RESET as u64).is_ok() {
+            wait();
+        }
+    }
+
+    triple_fault()
+}
+
+/// Turns the machine off by entering the `\_S5` soft-off sleep state.
+/// Returns only if the machine couldn't be turned off.
+///
+/// The `\_PTS` method isn't run, since there is no AML interpreter in the kernel.
+/// Most firmware doesn't need it for turning the machine off.
+pub fn shutdown() -> Result<Infallible, ShutdownError> {
+    let fadt = find_table::<FadtTable>().ok_or(ShutdownError::NoFadt)?;
+    let (slp_typa, slp_typb) = sleep_type(fadt, b"_S5_").ok_or(ShutdownError::NoSleepState)?;
+
+    let _guard = common::interrupts::disable();
+    info!("Shutting down");
+
+    // Safety: The FADT gives the registers.
+    unsafe {
+        if fadt.is_hardware_reduced() {
+            let (control, _) = fadt
+                .sleep_registers()
+                .ok_or(ShutdownError::NoControlRegister)?;
+            let value = (slp_typa & 0b111) << SLEEP_CONTROL_SLP_TYP_SHIFT | SLEEP_CONTROL_SLP_EN;
+            control.write(value as u64)?;
+        } else {
+            let (pm1a, pm1b) = fadt.pm1_control_blocks();
+            let pm1a = pm1a.ok_or(ShutdownError::NoControlRegister)?;
+            enable_acpi(fadt, &pm1a)?;
+
+            for (block, slp_typ) in [(Some(pm1a), slp_typa), (pm1b, slp_typb)] {
+                let Some(block) = block else {
+                    continue;
+                };
+                let value = block.read()? as u16 & !PM1_SLP_TYP;
+                block.write((value | (slp_typ as u16 & 0b111) << 10 | PM1_SLP_EN) as u64)?;
+            }
+        }
+    }
+
+    wait();
+    Err(ShutdownError::StillRunning)
+}
+
+/// Installed as [`common::power::ShutdownHandler`].
+pub(crate) fn shutdown_handler() {
+    let Err(error) = shutdown();
+    warn!("Failed to shut down: {:?}", error);
+}
+
+/// Finds the sleep state package `name` in the DSDT or any SSDT and returns its sleep type values.
+fn sleep_type(fadt: &FadtTable, name: &[u8; 4]) -> Option<(u8, u8)> {
+    // Safety: We assume the address given by the FADT is valid.
+    let dsdt = fadt
+        .dsdt_address()
+        .map(|x| unsafe {
+            &*physical_to_virtual(PhysAddr::new_truncate(x)).as_ptr::<AcpiTableHeader>()
+        })
+        .filter(|x| x.validate());
+
+    dsdt.into_iter()
+        .chain(find_tables_by_signature(b"SSDT").map(|x| x.header))
+        .find_map(|x| {
+            // Safety: The AML code fills the rest of the table, as given by the table's length.
+            let code = unsafe { trailing_bytes(x, x.length as usize) };
+            aml::find_sleep_type(code, name)
+        })
+}
+
+/// Hands the fixed hardware from the firmware to the OS, if it doesn't own it already.
+///
+/// ## Safety
+///
+/// The PM1a control block has to be the one given by the FADT.
+unsafe fn enable_acpi(fadt: &FadtTable, pm1a: &GenericAddress) -> Result<(), ShutdownError> {
+    if pm1a.read()? as u16 & PM1_SCI_EN != 0 {
+        return Ok(());
+    }
+
+    let (smi_command_port, command) = (fadt.smi_command_port, fadt.acpi_enable);
+    if smi_command_port == 0 || command == 0 {
+        // The system has no legacy mode, ACPI is always enabled.
+        return Ok(());
+    }
+
+    GenericAddress::system_io(smi_command_port as u16, 8).write(command as u64)?;
+    for _ in 0..WAIT_ITERATIONS {
+        if pm1a.read()? as u16 & PM1_SCI_EN != 0 {
+            return Ok(());
+        }
+        core::hint::spin_loop();
+    }
+
+    Err(ShutdownError::AcpiNotEnabled)
+}
+
+impl From<AddressError> for ShutdownError {
+    fn from(value: AddressError) -> Self {
+        Self::Register(value)
+    }
+}
+
+/// Gives the hardware time to react.
+fn wait() {
+    for _ in 0..WAIT_ITERATIONS {
+        core::hint::spin_loop();
+    }
+}
+
+/// Resets the processor by raising an exception without a valid interrupt descriptor table.
+#[cfg(target_arch = "x86_64")]
+fn triple_fault() -> ! {
+    #[repr(C, packed)]
+    struct Idtr {
+        limit: u16,
+        base: u64,
+    }
+
+    let idtr = Idtr { limit: 0, base: 0 };
+    // Safety: Resetting is what we want.
+    unsafe {
+        core::arch::asm!("LIDT [{}]", "INT3", in(reg) &idtr, options(readonly, nostack));
+    }
+    common::power::halt()
+}
+
+/// There is no way of resetting without the firmware on other architectures.
+#[cfg(not(target_arch = "x86_64"))]
+fn triple_fault() -> ! {
+    common::power::halt()
+}
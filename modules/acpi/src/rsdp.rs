@@ -1,77 +1,233 @@
 // This Source Code Form is subject to the terms of the Mozilla Public
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! Finding and validating the Root System Description Pointer (RSDP).
+//!
+//! The bootloader usually passes the RSDP, else it's searched for in the first KiB of the Extended BIOS Data Area
+//! and the BIOS area from `0xE0000` to `0xFFFFF`, like on legacy BIOS systems.
+//! Every candidate is bounds checked before its fields are read, since the memory may contain anything.
 use common::addr::PhysAddr;
-use common::addr::VirtAddr;
-use core::mem;
+use common::memory::physical_to_virtual;
 use core::slice;
-use log::warn;
-
-/// Structure containing the pointer to the Root System Descriptor Table.
-#[repr(C, packed)]
-struct RootSystemDescriptionPointer {
-    _signature: [u8; 8],
-    _checksum: u8,
-    _oedm_id: [u8; 6],
-    revision: u8,
-    rsdt_address: u32,
+
+/// Signature at the start of the RSDP.
+const SIGNATURE: &[u8; 8] = b"RSD PTR ";
+
+/// Length of the structure of ACPI 1.0.
+const LENGTH: usize = 20;
+
+/// Length of the extended structure of ACPI 2.0+.
+const EXTENDED_LENGTH: usize = 36;
+
+/// The maximal length of the extended structure accepted, later revisions may append fields.
+const MAX_EXTENDED_LENGTH: usize = 1024;
+
+/// The first revision having the extended structure with the Extended System Descriptor Table.
+const EXTENDED_REVISION: u8 = 2;
+
+/// The RSDP is aligned to 16 bytes when searched for.
+const ALIGNMENT: usize = 16;
+
+/// Physical address of the real mode segment of the Extended BIOS Data Area.
+const EBDA_SEGMENT: u64 = 0x40E;
+
+/// Number of bytes at the start of the Extended BIOS Data Area searched for the RSDP.
+const EBDA_SEARCH_LENGTH: usize = 1024;
+
+/// The end of the conventional memory, the Extended BIOS Data Area is right below it.
+const CONVENTIONAL_MEMORY_END: u64 = 0xA0000;
+
+/// The BIOS area searched for the RSDP.
+const BIOS_AREA_START: u64 = 0xE0000;
+const BIOS_AREA_END: u64 = 0x100000;
+
+/// Error returned when an RSDP is invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RsdpError {
+    /// The signature didn't match.
+    InvalidSignature,
+
+    /// The memory ends before the structure.
+    Truncated,
+
+    /// The checksum didn't match.
+    InvalidChecksum,
+
+    /// The length of the extended structure is out of bounds.
+    InvalidLength(u32),
+}
+
+/// Reads the System Descriptor Table from a pointer to the Root System Description Pointer.
+/// The Extended System Descriptor Table is returned instead for ACPI 2.0+.
+///
+/// ## Safety
+///
+/// At least the 20 bytes of the ACPI 1.0 structure at `address` have to be readable,
+/// the structure is only read further if it announces a later revision.
+pub unsafe fn read(address: PhysAddr) -> Result<PhysAddr, RsdpError> {
+    let start = physical_to_virtual(address).as_ptr::<u8>();
+    let bytes = slice::from_raw_parts(start, LENGTH);
+    if &bytes[..SIGNATURE.len()] != SIGNATURE {
+        return Err(RsdpError::InvalidSignature);
+    }
+
+    let length = if revision(bytes) >= EXTENDED_REVISION {
+        let length = slice::from_raw_parts(start, LENGTH + 4);
+        extended_length(length).clamp(LENGTH, MAX_EXTENDED_LENGTH)
+    } else {
+        LENGTH
+    };
+
+    parse(slice::from_raw_parts(start, length)).map(PhysAddr::new_truncate)
 }
 
-impl RootSystemDescriptionPointer {
-    /// Validates the structure, returns true if valid.
-    fn validate(&self) -> bool {
-        let bytes = unsafe {
+/// Searches the Extended BIOS Data Area and the BIOS area for the Root System Description Pointer,
+/// returns the address of the RSDP found and the address of the table it points to.
+pub fn search() -> Option<(PhysAddr, PhysAddr)> {
+    // Safety: The segment is part of the BIOS Data Area, which is always mapped by the direct mapping.
+    let segment = unsafe {
+        physical_to_virtual(PhysAddr::new(EBDA_SEGMENT))
+            .as_ptr::<u16>()
+            .read_unaligned()
+    };
+    let ebda = (segment as u64) << 4;
+
+    // On systems without BIOS the segment may be anything, so it's only used if it's in the conventional memory.
+    let ebda = (ebda >= 0x500 && ebda + EBDA_SEARCH_LENGTH as u64 <= CONVENTIONAL_MEMORY_END)
+        .then_some((ebda, EBDA_SEARCH_LENGTH));
+    let bios = Some((BIOS_AREA_START, (BIOS_AREA_END - BIOS_AREA_START) as usize));
+
+    ebda.into_iter().chain(bios).find_map(|(start, length)| {
+        // Safety: The areas are below 1 MiB, which is always mapped by the direct mapping.
+        let area = unsafe {
             slice::from_raw_parts(
-                self as *const _ as *const u8,
-                mem::size_of::<RootSystemDescriptionPointer>(),
+                physical_to_virtual(PhysAddr::new(start)).as_ptr::<u8>(),
+                length,
             )
         };
-        let checksum = bytes.iter().fold(0u8, |l, r| l.wrapping_add(*r));
+        let (offset, table) = scan(area)?;
+        Some((
+            PhysAddr::new(start + offset as u64),
+            PhysAddr::new_truncate(table),
+        ))
+    })
+}
+
+/// Returns the offset of the first valid RSDP in `area` and the address of the table it points to.
+fn scan(area: &[u8]) -> Option<(usize, u64)> {
+    (0..area.len())
+        .step_by(ALIGNMENT)
+        .filter(|x| area[*x..].starts_with(SIGNATURE))
+        .find_map(|x| Some((x, parse(&area[x..]).ok()?)))
+}
 
-        checksum == 0
+/// Validates the RSDP at the start of `bytes` and returns the address of the (Extended) System Descriptor Table.
+/// `bytes` may extend beyond the structure.
+fn parse(bytes: &[u8]) -> Result<u64, RsdpError> {
+    if !bytes.starts_with(SIGNATURE) {
+        return Err(RsdpError::InvalidSignature);
+    }
+    let structure = bytes.get(..LENGTH).ok_or(RsdpError::Truncated)?;
+    if checksum(structure) != 0 {
+        return Err(RsdpError::InvalidChecksum);
     }
+    let rsdt_address = u32::from_le_bytes(structure[16..20].try_into().unwrap());
+
+    if revision(structure) >= EXTENDED_REVISION {
+        let length = extended_length(bytes.get(..LENGTH + 4).ok_or(RsdpError::Truncated)?);
+        if !(EXTENDED_LENGTH..=MAX_EXTENDED_LENGTH).contains(&length) {
+            return Err(RsdpError::InvalidLength(length as u32));
+        }
+
+        let structure = bytes.get(..length).ok_or(RsdpError::Truncated)?;
+        if checksum(structure) != 0 {
+            return Err(RsdpError::InvalidChecksum);
+        }
+
+        let xsdt_address = u64::from_le_bytes(structure[24..32].try_into().unwrap());
+        if xsdt_address != 0 {
+            return Ok(xsdt_address);
+        }
+    }
+
+    Ok(rsdt_address as u64)
 }
 
-/// Structure containing the pointer to the Extended System Descriptor Table.
-/// Used starting ACPI 2.0+.
-#[repr(C, packed)]
-struct RootSystemDescriptionPointer2 {
-    _base: RootSystemDescriptionPointer,
-    length: u32,
-    xsdt_address: u64,
-    _extended_checksum: u8,
-    _reserved: [u8; 3],
+fn revision(bytes: &[u8]) -> u8 {
+    bytes[15]
 }
 
-impl RootSystemDescriptionPointer2 {
-    /// Validates the structure, returns true if valid.
-    fn validate(&self) -> bool {
-        let bytes =
-            unsafe { slice::from_raw_parts(self as *const _ as *const u8, self.length as usize) };
-        let checksum = bytes.iter().fold(0u8, |l, r| l.wrapping_add(*r));
+fn extended_length(bytes: &[u8]) -> usize {
+    u32::from_le_bytes(bytes[20..24].try_into().unwrap()) as usize
+}
 
-        checksum == 0
-    }
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |l, r| l.wrapping_add(*r))
 }
 
-/// Reads the System Descriptor Table from a pointer to the Root System Description Pointer.
-/// It checks if it's the ACPI 2 version and returns the Extended System Descriptor Table instead.
-pub fn read(address: VirtAddr) -> Option<PhysAddr> {
-    let rsdp = unsafe { &*address.as_ptr::<RootSystemDescriptionPointer>() };
-    if !rsdp.validate() {
-        warn!("ACPI Root System Description Pointer corrupted? Checksum didn't match");
-        return None;
-    }
+#[cfg(test)]
+mod test {
+    use super::{parse, scan, RsdpError};
 
-    if rsdp.revision == 2 {
-        let rsdp = unsafe { &*address.as_ptr::<RootSystemDescriptionPointer2>() };
-        if !rsdp.validate() {
-            warn!("ACPI Root System Description Pointer 2.0 corrupted? Checksum didn't match");
-            return None;
+    /// Builds an RSDP of the revision, with valid checksums.
+    fn rsdp(revision: u8, rsdt: u32, xsdt: u64) -> Vec<u8> {
+        let mut bytes = b"RSD PTR \0BOCHS ".to_vec();
+        bytes.push(revision);
+        bytes.extend_from_slice(&rsdt.to_le_bytes());
+        if revision >= 2 {
+            bytes.extend_from_slice(&36u32.to_le_bytes());
+            bytes.extend_from_slice(&xsdt.to_le_bytes());
+            bytes.extend_from_slice(&[0; 4]);
         }
 
-        Some(PhysAddr::new_truncate(rsdp.xsdt_address))
-    } else {
-        Some(PhysAddr::new_truncate(rsdp.rsdt_address as u64))
+        bytes[8] = 0u8.wrapping_sub(super::checksum(&bytes[..20]));
+        if revision >= 2 {
+            bytes[32] = 0u8.wrapping_sub(super::checksum(&bytes));
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_revisions() {
+        assert_eq!(parse(&rsdp(0, 0x7FFE_1000, 0)), Ok(0x7FFE_1000));
+        assert_eq!(parse(&rsdp(2, 0x7FFE_1000, 0x7FFE_2000)), Ok(0x7FFE_2000));
+        // Later revisions use the extended structure as well.
+        assert_eq!(parse(&rsdp(6, 0x7FFE_1000, 0x7FFE_2000)), Ok(0x7FFE_2000));
+        // Without an XSDT the RSDT is used.
+        assert_eq!(parse(&rsdp(2, 0x7FFE_1000, 0)), Ok(0x7FFE_1000));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut bytes = rsdp(0, 0x7FFE_1000, 0);
+        bytes[0] = b'X';
+        assert_eq!(parse(&bytes), Err(RsdpError::InvalidSignature));
+
+        let mut bytes = rsdp(2, 0x7FFE_1000, 0x7FFE_2000);
+        bytes[24] ^= 1;
+        assert_eq!(parse(&bytes), Err(RsdpError::InvalidChecksum));
+        assert_eq!(parse(&bytes[..30]), Err(RsdpError::Truncated));
+
+        // The length is part of the extended structure, covered only by the extended checksum,
+        // and an implausible length is rejected before that checksum is verified.
+        let mut bytes = rsdp(2, 0x7FFE_1000, 0x7FFE_2000);
+        bytes[20..24].copy_from_slice(&0x1000_0000u32.to_le_bytes());
+        assert_eq!(parse(&bytes), Err(RsdpError::InvalidLength(0x1000_0000)));
+    }
+
+    #[test]
+    fn test_scan() {
+        let mut area = vec![0u8; 256];
+        // Neither a misaligned signature nor a corrupted RSDP is used.
+        area[8..16].copy_from_slice(b"RSD PTR ");
+        area[32..68].copy_from_slice(&rsdp(2, 0, 0x1000));
+        area[40] ^= 1;
+        area[96..132].copy_from_slice(&rsdp(2, 0, 0x2000));
+        assert_eq!(scan(&area), Some((96, 0x2000)));
+
+        // A signature at the end of the area.
+        let mut area = vec![0u8; 256];
+        area[240..248].copy_from_slice(b"RSD PTR ");
+        assert_eq!(scan(&area), None);
     }
 }
@@ -0,0 +1,70 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use crate::entry::trailing_bytes;
+use crate::{AcpiTable, AcpiTableHeader};
+
+/// The System Locality Information Table, giving the relative distances between NUMA proximity domains.
+/// The fixed part is followed by a matrix of [`SlitTable::locality_count`] squared distances.
+#[repr(C, packed)]
+pub struct SlitTable {
+    /// ACPI Table Header
+    pub header: AcpiTableHeader,
+    pub locality_count: u64,
+}
+
+impl SlitTable {
+    /// The distance of a locality to itself, other distances are relative to it.
+    pub const LOCAL_DISTANCE: u8 = 10;
+
+    /// The distance between localities which can't reach each other.
+    pub const UNREACHABLE: u8 = 0xFF;
+
+    /// Returns the relative distance from locality `from` to locality `to`.
+    pub fn distance(&self, from: u64, to: u64) -> Option<u8> {
+        self.row(from)?.get(usize::try_from(to).ok()?).copied()
+    }
+
+    /// Returns the relative distances from locality `from` to every locality.
+    pub fn row(&self, from: u64) -> Option<&[u8]> {
+        let count = usize::try_from(self.locality_count).ok()?;
+        let start = usize::try_from(from).ok()?.checked_mul(count)?;
+        self.matrix().get(start..start.checked_add(count)?)
+    }
+
+    fn matrix(&self) -> &[u8] {
+        // Safety: The matrix fills the rest of the table, as given by the table's length.
+        unsafe { trailing_bytes(self, self.header.length as usize) }
+    }
+}
+
+impl AcpiTable for SlitTable {
+    const SIGNATURE: &'static [u8; 4] = b"SLIT";
+
+    fn header(&self) -> &AcpiTableHeader {
+        &self.header
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SlitTable;
+    use crate::entry::blob;
+    use crate::AcpiTable;
+
+    #[test]
+    fn test_qemu_numa() {
+        let slit = blob::<SlitTable>(include_bytes!("../testdata/qemu-numa-slit.dat"));
+        assert!(slit.validate());
+
+        let locality_count = slit.locality_count;
+        assert_eq!(locality_count, 2);
+        assert_eq!(slit.distance(0, 0), Some(SlitTable::LOCAL_DISTANCE));
+        assert_eq!(slit.distance(0, 1), Some(20));
+        assert_eq!(slit.row(1), Some(&[20, 10][..]));
+
+        assert_eq!(slit.distance(0, 2), None);
+        assert_eq!(slit.row(2), None);
+        assert_eq!(slit.row(u64::MAX), None);
+    }
+}
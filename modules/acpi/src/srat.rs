@@ -0,0 +1,196 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use crate::entry::{cast, split_entry, trailing_bytes};
+use crate::{AcpiTable, AcpiTableHeader};
+use core::mem::size_of;
+
+/// The System Resource Affinity Table, assigning processors and memory to NUMA proximity domains.
+/// The fixed part is followed by a list of variable-length entries, see [`SratTable::entries`].
+#[repr(C, packed)]
+pub struct SratTable {
+    /// ACPI Table Header
+    pub header: AcpiTableHeader,
+    pub reserved: u32,
+    pub reserved2: u64,
+}
+
+impl SratTable {
+    /// Returns an iterator over the entries of the table.
+    pub fn entries(&self) -> SratEntries<'_> {
+        // Safety: The entries fill the rest of the table, as given by the table's length.
+        let bytes = unsafe { trailing_bytes(self, self.header.length as usize) };
+        SratEntries { bytes }
+    }
+}
+
+impl AcpiTable for SratTable {
+    const SIGNATURE: &'static [u8; 4] = b"SRAT";
+
+    fn header(&self) -> &AcpiTableHeader {
+        &self.header
+    }
+}
+
+/// The header of every entry of the [`SratTable`].
+#[repr(C, packed)]
+pub struct SratEntryHeader {
+    /// The type of the entry.
+    pub kind: u8,
+
+    /// Length of the whole entry in bytes.
+    pub length: u8,
+}
+
+/// The proximity domain of a processor and its local APIC.
+#[repr(C, packed)]
+pub struct ProcessorAffinityEntry {
+    pub header: SratEntryHeader,
+    /// Bits 0 to 7 of the proximity domain, see [`ProcessorAffinityEntry::proximity_domain`].
+    pub proximity_domain_low: u8,
+    pub apic_id: u8,
+    /// See [`ProcessorAffinityEntry::ENABLED`].
+    pub flags: u32,
+    pub local_sapic_eid: u8,
+    /// Bits 8 to 31 of the proximity domain.
+    pub proximity_domain_high: [u8; 3],
+    pub clock_domain: u32,
+}
+
+impl ProcessorAffinityEntry {
+    /// The entry is used, else it has to be ignored.
+    pub const ENABLED: u32 = 1 << 0;
+
+    /// Returns the proximity domain of the processor.
+    pub fn proximity_domain(&self) -> u32 {
+        let [a, b, c] = self.proximity_domain_high;
+        u32::from_le_bytes([self.proximity_domain_low, a, b, c])
+    }
+}
+
+/// The proximity domain of a range of physical memory.
+#[repr(C, packed)]
+pub struct MemoryAffinityEntry {
+    pub header: SratEntryHeader,
+    pub proximity_domain: u32,
+    pub reserved: u16,
+    pub base_address: u64,
+    /// Length of the memory range in bytes.
+    pub region_length: u64,
+    pub reserved2: u32,
+    /// See [`MemoryAffinityEntry::ENABLED`], [`MemoryAffinityEntry::HOT_PLUGGABLE`] and [`MemoryAffinityEntry::NON_VOLATILE`].
+    pub flags: u32,
+    pub reserved3: u64,
+}
+
+impl MemoryAffinityEntry {
+    /// The entry is used, else it has to be ignored.
+    pub const ENABLED: u32 = 1 << 0;
+
+    /// The memory range can be hot-plugged.
+    pub const HOT_PLUGGABLE: u32 = 1 << 1;
+
+    /// The memory range is non-volatile.
+    pub const NON_VOLATILE: u32 = 1 << 2;
+}
+
+/// The proximity domain of a processor and its local x2APIC.
+#[repr(C, packed)]
+pub struct X2ApicAffinityEntry {
+    pub header: SratEntryHeader,
+    pub reserved: u16,
+    pub proximity_domain: u32,
+    pub x2apic_id: u32,
+    /// Same as [`ProcessorAffinityEntry::flags`].
+    pub flags: u32,
+    pub clock_domain: u32,
+    pub reserved2: u32,
+}
+
+/// An entry of the [`SratTable`].
+pub enum SratEntry<'a> {
+    ProcessorAffinity(&'a ProcessorAffinityEntry),
+    MemoryAffinity(&'a MemoryAffinityEntry),
+    X2ApicAffinity(&'a X2ApicAffinityEntry),
+    /// An entry of a type not supported, or too short for its type.
+    Unknown(&'a SratEntryHeader),
+}
+
+/// Iterator over the entries of a [`SratTable`].
+pub struct SratEntries<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for SratEntries<'a> {
+    type Item = SratEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let [kind, length, ..] = *self.bytes else {
+            return None;
+        };
+        let entry = split_entry(
+            &mut self.bytes,
+            length as usize,
+            size_of::<SratEntryHeader>(),
+        )?;
+
+        Some(match kind {
+            0 => cast(entry).map_or_else(|| unknown(entry), SratEntry::ProcessorAffinity),
+            1 => cast(entry).map_or_else(|| unknown(entry), SratEntry::MemoryAffinity),
+            2 => cast(entry).map_or_else(|| unknown(entry), SratEntry::X2ApicAffinity),
+            _ => unknown(entry),
+        })
+    }
+}
+
+fn unknown(entry: &[u8]) -> SratEntry<'_> {
+    // Safety: Every entry is at least as long as its header.
+    SratEntry::Unknown(unsafe { &*(entry.as_ptr() as *const SratEntryHeader) })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MemoryAffinityEntry, SratEntry, SratTable};
+    use crate::entry::blob;
+    use crate::AcpiTable;
+
+    #[test]
+    fn test_qemu_numa() {
+        let srat = blob::<SratTable>(include_bytes!("../testdata/qemu-numa-srat.dat"));
+        assert!(srat.validate());
+
+        let mut processors = [(0, 0); 4];
+        let mut memory = [(0, 0, 0); 4];
+        let (mut processor_count, mut memory_count) = (0, 0);
+        let mut x2apic = None;
+        for entry in srat.entries() {
+            match entry {
+                SratEntry::ProcessorAffinity(x) => {
+                    processors[processor_count] = (x.apic_id, x.proximity_domain());
+                    processor_count += 1;
+                }
+                SratEntry::MemoryAffinity(x) => {
+                    memory[memory_count] = (x.proximity_domain, x.base_address, x.region_length);
+                    memory_count += 1;
+                    if x.base_address == 0x1_0000_0000 {
+                        assert_ne!(x.flags & MemoryAffinityEntry::HOT_PLUGGABLE, 0);
+                    }
+                }
+                SratEntry::X2ApicAffinity(x) => x2apic = Some((x.x2apic_id, x.proximity_domain)),
+                SratEntry::Unknown(_) => panic!("Unexpected unknown entry"),
+            }
+        }
+
+        assert_eq!(processors, [(0, 0), (1, 0), (2, 1), (3, 1)]);
+        assert_eq!(x2apic, Some((0x100, 1)));
+        assert_eq!(
+            memory,
+            [
+                (0, 0, 0xA_0000),
+                (0, 0x10_0000, 0x3FF0_0000),
+                (1, 0x4000_0000, 0x4000_0000),
+                (1, 0x1_0000_0000, 0x4000_0000),
+            ]
+        );
+    }
+}
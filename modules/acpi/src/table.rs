@@ -2,12 +2,17 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 use crate::AcpiTableHeader;
+use core::mem::size_of;
 
 /// Common trait implemented for every ACPI table struct in this library.
-pub trait AcpiTable {
+pub trait AcpiTable: Sized {
     /// The 4 byte signature in the [`crate::AcpiTableHeader`] that identifies this table.
     const SIGNATURE: &'static [u8; 4];
 
+    /// The length of the shortest valid table, [`crate::find_tables`] skips shorter ones.
+    /// Only tables with fields depending on the revision are allowed to be shorter than the struct.
+    const MIN_LENGTH: usize = size_of::<Self>();
+
     /// Returns the [`crate::AcpiTableHeader`] of this table.
     fn header(&self) -> &AcpiTableHeader;
 
@@ -1,12 +0,0 @@
-// This Source Code Form is subject to the terms of the Mozilla Public
-// License, v. 2.0. If a copy of the MPL was not distributed with this
-// file, You can obtain one at http://mozilla.org/MPL/2.0/.
-/// An ACPI address structure struct used in some tables.
-#[repr(C, packed)]
-pub struct AddressStructure {
-    pub address_space_id: u8,
-    pub register_bit_width: u8,
-    pub register_bit_offset: u8,
-    pub reserved: u8,
-    pub address: u64,
-}
@@ -0,0 +1,141 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! The I/O APICs, routing global system interrupts (GSI) to the local APICs.
+use crate::Polarity;
+use common::addr::VirtAddr;
+use common::interrupts::{TriggerMode, Vector};
+use core::ptr;
+
+/// Offset of the register selecting the register accessed through [`WINDOW`].
+const SELECT: u64 = 0x00;
+
+/// Offset of the register accessing the selected register.
+const WINDOW: u64 = 0x10;
+
+/// The version register, containing the number of redirection entries.
+const VERSION: u32 = 0x01;
+
+/// The first redirection entry register, each entry takes two registers.
+const REDIRECTION_TABLE: u32 = 0x10;
+
+const REDIRECTION_DELIVERY_NMI: u64 = 0b100 << 8;
+const REDIRECTION_ACTIVE_LOW: u64 = 1 << 13;
+const REDIRECTION_LEVEL: u64 = 1 << 15;
+const REDIRECTION_MASKED: u64 = 1 << 16;
+
+/// An I/O APIC handling a range of global system interrupts.
+#[derive(Debug)]
+pub struct IoApic {
+    registers: VirtAddr,
+    gsi_base: u32,
+    count: u32,
+}
+
+impl IoApic {
+    /// Creates the I/O APIC with the registers at `registers`, handling the interrupts starting at `gsi_base`.
+    ///
+    /// ## Safety
+    ///
+    /// `registers` has to map the I/O APIC registers.
+    pub unsafe fn new(registers: VirtAddr, gsi_base: u32) -> Self {
+        let mut io_apic = IoApic {
+            registers,
+            gsi_base,
+            count: 0,
+        };
+        io_apic.count = ((io_apic.read(VERSION) >> 16) & 0xFF) + 1;
+        io_apic
+    }
+
+    /// Returns the first global system interrupt handled by this I/O APIC.
+    pub fn gsi_base(&self) -> u32 {
+        self.gsi_base
+    }
+
+    /// Returns the number of interrupts handled by this I/O APIC.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Returns whenever this I/O APIC handles the global system interrupt.
+    pub fn handles(&self, gsi: u32) -> bool {
+        (self.gsi_base..self.gsi_base + self.count).contains(&gsi)
+    }
+
+    /// Routes the global system interrupt to `vector` of the local APIC `destination`.
+    ///
+    /// ## Safety
+    ///
+    /// The registers must not be accessed concurrently.
+    pub unsafe fn route(
+        &self,
+        gsi: u32,
+        vector: Vector,
+        trigger_mode: TriggerMode,
+        polarity: Polarity,
+        destination: u8,
+    ) {
+        let mut entry = vector as u64 | (destination as u64) << 56;
+        if trigger_mode == TriggerMode::Level {
+            entry |= REDIRECTION_LEVEL;
+        }
+        if polarity == Polarity::ActiveLow {
+            entry |= REDIRECTION_ACTIVE_LOW;
+        }
+        self.write_entry(gsi, entry);
+    }
+
+    /// Routes the global system interrupt to the NMI of the local APIC `destination`.
+    ///
+    /// ## Safety
+    ///
+    /// The registers must not be accessed concurrently.
+    pub unsafe fn route_nmi(&self, gsi: u32, polarity: Polarity, destination: u8) {
+        let mut entry = REDIRECTION_DELIVERY_NMI | (destination as u64) << 56;
+        if polarity == Polarity::ActiveLow {
+            entry |= REDIRECTION_ACTIVE_LOW;
+        }
+        self.write_entry(gsi, entry);
+    }
+
+    /// Masks the global system interrupt.
+    ///
+    /// ## Safety
+    ///
+    /// The registers must not be accessed concurrently.
+    pub unsafe fn mask(&self, gsi: u32) {
+        self.write_entry(gsi, REDIRECTION_MASKED);
+    }
+
+    /// Masks all interrupts of this I/O APIC.
+    ///
+    /// ## Safety
+    ///
+    /// The registers must not be accessed concurrently.
+    pub unsafe fn mask_all(&self) {
+        for gsi in self.gsi_base..self.gsi_base + self.count {
+            self.mask(gsi);
+        }
+    }
+
+    /// Writes the redirection entry of the global system interrupt, with the mask bit set first.
+    unsafe fn write_entry(&self, gsi: u32, entry: u64) {
+        assert!(self.handles(gsi), "GSI {} not handled by I/O APIC", gsi);
+
+        let register = REDIRECTION_TABLE + (gsi - self.gsi_base) * 2;
+        self.write(register, REDIRECTION_MASKED as u32);
+        self.write(register + 1, (entry >> 32) as u32);
+        self.write(register, entry as u32);
+    }
+
+    unsafe fn read(&self, register: u32) -> u32 {
+        ptr::write_volatile((self.registers + SELECT).as_mut_ptr(), register);
+        ptr::read_volatile((self.registers + WINDOW).as_ptr())
+    }
+
+    unsafe fn write(&self, register: u32, value: u32) {
+        ptr::write_volatile((self.registers + SELECT).as_mut_ptr(), register);
+        ptr::write_volatile((self.registers + WINDOW).as_mut_ptr(), value);
+    }
+}
@@ -0,0 +1,369 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Advanced Programmable Interrupt Controller (APIC) Module
+//!
+//! The APIC module replaces the legacy 8259 PIC with the APICs described by the ACPI [`acpi::MadtTable`]:
+//!
+//! - The local APIC of each processor is enabled, in x2APIC mode if supported.
+//!   It signals the end of every interrupt dispatched by the IDT module.
+//! - The legacy PIC is remapped behind the CPU exceptions and masked.
+//! - All I/O APIC interrupts are masked, until a module routes one to its vector with [`route_gsi`] or [`route_isa_irq`].
+//! - The local APIC timer is calibrated against the clock source of [`common::time`] and registered as clock event,
+//!   once the timer modules are initialized.
+//!
+#![no_std]
+
+mod io;
+mod local;
+mod pic;
+mod timer;
+
+pub use io::IoApic;
+pub use local::LocalApic;
+
+use acpi::{InterruptPolarity, InterruptTrigger, MadtEntry, MadtTable};
+use common::addr::{PhysAddr, VirtAddr};
+use common::interrupts::{TriggerMode, Vector};
+use common::memory::physical_to_virtual;
+use common::sync::{Spinlock, SyncOnceCell};
+use log::{debug, info, warn};
+use microdragon_interface::macros::init;
+use microdragon_interface::smp::CpuInfo;
+use microdragon_interface::ModuleInterface;
+
+/// The first vector of the legacy PIC, right behind the CPU exceptions.
+const PIC_VECTOR_BASE: u8 = 32;
+
+/// Maximal number of I/O APICs supported.
+const MAX_IO_APICS: usize = 8;
+
+/// Number of ISA interrupts.
+const ISA_IRQ_COUNT: usize = 16;
+
+/// Processor UID of local APIC NMI entries applying to all processors.
+const ALL_PROCESSORS: u32 = u32::MAX;
+
+/// The polarity of an interrupt line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Polarity {
+    ActiveHigh,
+    ActiveLow,
+}
+
+/// Error returned when routing an interrupt fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteError {
+    /// The APIC module isn't initialized.
+    NotInitialized,
+
+    /// No I/O APIC handles the global system interrupt.
+    InvalidGsi,
+
+    /// The APIC id of the bootstrap processor doesn't fit the 8 bit destination of the I/O APICs.
+    UnroutableDestination(u32),
+}
+
+/// The global system interrupt an ISA interrupt is connected to.
+#[derive(Debug, Clone, Copy)]
+struct IsaRoute {
+    gsi: u32,
+    trigger_mode: TriggerMode,
+    polarity: Polarity,
+}
+
+/// The interrupt controllers found in the MADT.
+struct Apic {
+    /// The address of the memory mapped local APIC registers, which are the same for every processor.
+    registers: VirtAddr,
+    local: LocalApic,
+    /// The APIC id of the bootstrap processor, which gets the routed interrupts.
+    bsp_id: u32,
+    io_apics: [Option<IoApic>; MAX_IO_APICS],
+    isa_routes: [IsaRoute; ISA_IRQ_COUNT],
+}
+
+static APIC: SyncOnceCell<Apic> = SyncOnceCell::new();
+
+/// Lock taken while accessing the I/O APIC registers.
+static IO_APIC_LOCK: Spinlock<()> = Spinlock::new(());
+
+/// Entrypoint to the APIC module.
+/// Interrupts have to be disabled while this is run.
+#[init]
+pub fn init(_: &ModuleInterface) {
+    if APIC.is_initialized() {
+        warn!("APIC Kernel Module already initialized");
+        return;
+    }
+
+    let Some(madt) = acpi::find_table::<MadtTable>() else {
+        warn!("APIC Kernel Module needs the ACPI MADT");
+        return;
+    };
+
+    if madt.has_legacy_pic() {
+        // Safety: The PIC exists and is replaced by the APICs.
+        unsafe { pic::disable(PIC_VECTOR_BASE) };
+        debug!("Legacy PIC masked");
+    }
+
+    let mut local_apic_address = madt.local_apic_address as u64;
+    let mut io_apics = [const { None }; MAX_IO_APICS];
+    let mut isa_routes = core::array::from_fn(|irq| IsaRoute {
+        gsi: irq as u32,
+        trigger_mode: TriggerMode::Edge,
+        polarity: Polarity::ActiveHigh,
+    });
+    for entry in madt.entries() {
+        match entry {
+            MadtEntry::LocalApicAddressOverride(entry) => local_apic_address = entry.address,
+            MadtEntry::IoApic(entry) => {
+                let (address, gsi_base) = (entry.address, entry.gsi_base);
+                let Some(slot) = io_apics.iter_mut().find(|x| x.is_none()) else {
+                    warn!("Too many I/O APICs, ignoring I/O APIC at {:#x}", address);
+                    continue;
+                };
+                // Safety: The MADT gives the address of the registers.
+                let io_apic = unsafe {
+                    IoApic::new(physical_to_virtual(PhysAddr::new(address as u64)), gsi_base)
+                };
+                debug!(
+                    "I/O APIC {} at {:#x} handling GSI {} to {}",
+                    entry.io_apic_id,
+                    address,
+                    gsi_base,
+                    gsi_base + io_apic.count() - 1
+                );
+                *slot = Some(io_apic);
+            }
+            MadtEntry::InterruptSourceOverride(entry) => {
+                let (gsi, flags) = (entry.gsi, entry.flags);
+                let Some(route) = isa_routes.get_mut(entry.source as usize) else {
+                    continue;
+                };
+                *route = IsaRoute {
+                    gsi,
+                    trigger_mode: match flags.trigger() {
+                        InterruptTrigger::Level => TriggerMode::Level,
+                        _ => TriggerMode::Edge,
+                    },
+                    polarity: polarity(flags.polarity()),
+                };
+                debug!("ISA IRQ {} routed to {:?}", entry.source, route);
+            }
+            _ => {}
+        }
+    }
+
+    let registers = physical_to_virtual(PhysAddr::new(local_apic_address));
+    let local = enable_local_apic(madt, registers);
+    // The module is initialized on the bootstrap processor.
+    let bsp_id = local.id();
+
+    let _lock = IO_APIC_LOCK.lock();
+    for io_apic in io_apics.iter().flatten() {
+        // Safety: The lock is held.
+        unsafe { io_apic.mask_all() };
+    }
+    configure_nmi_sources(madt, bsp_id, &io_apics);
+
+    let _ = APIC.set(Apic {
+        registers,
+        local,
+        bsp_id,
+        io_apics,
+        isa_routes,
+    });
+    idt::set_end_of_interrupt(end_of_interrupt);
+
+    info!("APIC ready");
+}
+
+/// Per-CPU entrypoint to the APIC module.
+/// Interrupts have to be disabled while this is run.
+#[init]
+pub fn init_cpu(_: &ModuleInterface, _: &CpuInfo) {
+    let (Some(apic), Some(madt)) = (APIC.get(), acpi::find_table::<MadtTable>()) else {
+        warn!("APIC Kernel Module not initialized on the bootstrap processor");
+        return;
+    };
+
+    enable_local_apic(madt, apic.registers);
+}
+
+/// Registers the local APIC timer as clock event, calibrated against the clock source registered before.
+/// Interrupts have to be disabled while this is run.
+#[init]
+pub fn init_timer(_: &ModuleInterface) {
+    let Some(apic) = APIC.get() else {
+        warn!("APIC Kernel Module not initialized");
+        return;
+    };
+
+    timer::register(apic.local);
+}
+
+/// Returns the local APIC of the current processor, or `None` if the APIC module isn't initialized.
+pub fn local_apic() -> Option<LocalApic> {
+    Some(APIC.get()?.local)
+}
+
+/// Signals the end of the interrupt currently handled to the local APIC.
+pub fn end_of_interrupt() {
+    if let Some(apic) = APIC.get() {
+        apic.local.end_of_interrupt();
+    }
+}
+
+/// Routes the global system interrupt to `vector` of the bootstrap processor.
+pub fn route_gsi(
+    gsi: u32,
+    vector: Vector,
+    trigger_mode: TriggerMode,
+    polarity: Polarity,
+) -> Result<(), RouteError> {
+    let apic = APIC.get().ok_or(RouteError::NotInitialized)?;
+    let io_apic = io_apic(&apic.io_apics, gsi).ok_or(RouteError::InvalidGsi)?;
+    let destination =
+        destination(apic.bsp_id).ok_or(RouteError::UnroutableDestination(apic.bsp_id))?;
+
+    let _guard = common::interrupts::disable();
+    let _lock = IO_APIC_LOCK.lock();
+    // Safety: The lock is held.
+    unsafe { io_apic.route(gsi, vector, trigger_mode, polarity, destination) };
+    Ok(())
+}
+
+/// Routes the ISA interrupt to `vector` of the bootstrap processor, applying the interrupt source overrides of the MADT.
+pub fn route_isa_irq(irq: u8, vector: Vector) -> Result<(), RouteError> {
+    let route = isa_route(irq)?;
+    route_gsi(route.gsi, vector, route.trigger_mode, route.polarity)
+}
+
+/// Masks the global system interrupt.
+pub fn mask_gsi(gsi: u32) -> Result<(), RouteError> {
+    let apic = APIC.get().ok_or(RouteError::NotInitialized)?;
+    let io_apic = io_apic(&apic.io_apics, gsi).ok_or(RouteError::InvalidGsi)?;
+
+    let _guard = common::interrupts::disable();
+    let _lock = IO_APIC_LOCK.lock();
+    // Safety: The lock is held.
+    unsafe { io_apic.mask(gsi) };
+    Ok(())
+}
+
+/// Masks the ISA interrupt.
+pub fn mask_isa_irq(irq: u8) -> Result<(), RouteError> {
+    mask_gsi(isa_route(irq)?.gsi)
+}
+
+/// Returns the global system interrupt the ISA interrupt is connected to.
+pub fn isa_irq_to_gsi(irq: u8) -> Result<u32, RouteError> {
+    Ok(isa_route(irq)?.gsi)
+}
+
+fn isa_route(irq: u8) -> Result<IsaRoute, RouteError> {
+    let apic = APIC.get().ok_or(RouteError::NotInitialized)?;
+    apic.isa_routes
+        .get(irq as usize)
+        .copied()
+        .ok_or(RouteError::InvalidGsi)
+}
+
+fn io_apic(io_apics: &[Option<IoApic>], gsi: u32) -> Option<&IoApic> {
+    io_apics.iter().flatten().find(|x| x.handles(gsi))
+}
+
+/// Returns the destination of I/O APIC redirection entries targeting the local APIC with the given id.
+/// I/O APICs can only target the first 255 APIC ids without interrupt remapping, `0xFF` is the broadcast id.
+fn destination(apic_id: u32) -> Option<u8> {
+    u8::try_from(apic_id).ok().filter(|x| *x != 0xFF)
+}
+
+/// Converts the polarity of the MADT, ISA lines are active high by default.
+fn polarity(polarity: InterruptPolarity) -> Polarity {
+    match polarity {
+        InterruptPolarity::ActiveLow => Polarity::ActiveLow,
+        _ => Polarity::ActiveHigh,
+    }
+}
+
+/// Enables the local APIC of the current processor and connects its local NMI sources.
+fn enable_local_apic(madt: &MadtTable, registers: VirtAddr) -> LocalApic {
+    // Safety: The MADT gives the address of the registers.
+    let local = unsafe { LocalApic::enable(registers, idt::SPURIOUS_VECTOR) };
+    let id = local.id();
+    let processor_uid = madt.entries().find_map(|x| match x {
+        MadtEntry::LocalApic(entry) if entry.apic_id as u32 == id => {
+            Some(entry.processor_uid as u32)
+        }
+        MadtEntry::LocalX2Apic(entry) if entry.x2apic_id == id => Some(entry.processor_uid),
+        _ => None,
+    });
+
+    for entry in madt.entries() {
+        let (uid, lint, flags) = match entry {
+            MadtEntry::LocalApicNmi(entry) => {
+                let uid = if entry.processor_uid == 0xFF {
+                    ALL_PROCESSORS
+                } else {
+                    entry.processor_uid as u32
+                };
+                (uid, entry.lint, entry.flags)
+            }
+            MadtEntry::LocalX2ApicNmi(entry) => (entry.processor_uid, entry.lint, entry.flags),
+            _ => continue,
+        };
+
+        if uid != ALL_PROCESSORS && Some(uid) != processor_uid {
+            continue;
+        }
+
+        let mut value = local::LVT_DELIVERY_NMI;
+        if flags.polarity() == InterruptPolarity::ActiveLow {
+            value |= local::LVT_ACTIVE_LOW;
+        }
+        let register = if lint == 0 {
+            local::LVT_LINT0
+        } else {
+            local::LVT_LINT1
+        };
+        // Safety: NMIs are handled by the IDT module.
+        unsafe { local.write(register, value) };
+    }
+
+    info!(
+        "Local APIC {} in {} mode",
+        id,
+        match local {
+            LocalApic::XApic(_) => "xAPIC",
+            LocalApic::X2Apic => "x2APIC",
+        }
+    );
+    local
+}
+
+/// Routes the NMI sources of the I/O APICs to the bootstrap processor.
+/// The I/O APIC lock has to be held.
+fn configure_nmi_sources(madt: &MadtTable, bsp_id: u32, io_apics: &[Option<IoApic>]) {
+    let Some(destination) = destination(bsp_id) else {
+        warn!(
+            "NMI sources not routed, APIC id {} isn't an I/O APIC destination",
+            bsp_id
+        );
+        return;
+    };
+
+    for entry in madt.entries() {
+        let MadtEntry::NmiSource(entry) = entry else {
+            continue;
+        };
+
+        let (gsi, flags) = (entry.gsi, entry.flags);
+        if let Some(io_apic) = io_apic(io_apics, gsi) {
+            // Safety: The caller holds the lock.
+            unsafe { io_apic.route_nmi(gsi, polarity(flags.polarity()), destination) };
+        }
+    }
+}
@@ -0,0 +1,151 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! The local APIC of each processor, in xAPIC or x2APIC mode.
+use common::addr::VirtAddr;
+use core::arch::asm;
+use core::arch::x86_64::__cpuid;
+use core::ptr;
+
+/// The MSR containing the physical address and the mode of the local APIC.
+const IA32_APIC_BASE: u32 = 0x1B;
+
+/// Bit in [`IA32_APIC_BASE`] enabling the local APIC.
+const APIC_BASE_ENABLE: u64 = 1 << 11;
+
+/// Bit in [`IA32_APIC_BASE`] switching the local APIC to x2APIC mode.
+const APIC_BASE_X2APIC: u64 = 1 << 10;
+
+/// The first MSR of the x2APIC registers, the register at offset `x` is MSR `X2APIC_MSR_BASE + x / 16`.
+const X2APIC_MSR_BASE: u32 = 0x800;
+
+pub const ID: u32 = 0x20;
+pub const TASK_PRIORITY: u32 = 0x80;
+pub const END_OF_INTERRUPT: u32 = 0xB0;
+pub const SPURIOUS_INTERRUPT_VECTOR: u32 = 0xF0;
+pub const ERROR_STATUS: u32 = 0x280;
+pub const LVT_TIMER: u32 = 0x320;
+pub const LVT_LINT0: u32 = 0x350;
+pub const LVT_LINT1: u32 = 0x360;
+pub const LVT_ERROR: u32 = 0x370;
+pub const TIMER_INITIAL_COUNT: u32 = 0x380;
+pub const TIMER_CURRENT_COUNT: u32 = 0x390;
+pub const TIMER_DIVIDE_CONFIGURATION: u32 = 0x3E0;
+
+/// Bit in [`SPURIOUS_INTERRUPT_VECTOR`] enabling the local APIC.
+const SOFTWARE_ENABLE: u32 = 1 << 8;
+
+/// Bit in the local vector table (LVT) registers masking the interrupt.
+pub const LVT_MASKED: u32 = 1 << 16;
+
+/// Delivery mode of a local vector table (LVT) register for NMIs.
+pub const LVT_DELIVERY_NMI: u32 = 0b100 << 8;
+
+/// Bit in the local vector table (LVT) registers selecting an active low polarity.
+pub const LVT_ACTIVE_LOW: u32 = 1 << 13;
+
+/// Bit in the [`LVT_TIMER`] register selecting periodic mode.
+pub const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+
+/// Access to the local APIC of the current processor.
+#[derive(Debug, Clone, Copy)]
+pub enum LocalApic {
+    /// The registers are memory mapped at the address.
+    XApic(VirtAddr),
+
+    /// The registers are accessed through MSRs.
+    X2Apic,
+}
+
+impl LocalApic {
+    /// Enables the local APIC of the current processor, in x2APIC mode if supported.
+    /// `registers` is the address of the memory mapped registers, used in xAPIC mode.
+    /// All local interrupts are masked and spurious interrupts are delivered to `spurious_vector`.
+    ///
+    /// ## Safety
+    ///
+    /// `registers` has to map the local APIC registers.
+    pub unsafe fn enable(registers: VirtAddr, spurious_vector: u8) -> Self {
+        let mut base = read_msr(IA32_APIC_BASE) | APIC_BASE_ENABLE;
+        let apic = if supports_x2apic() {
+            base |= APIC_BASE_X2APIC;
+            LocalApic::X2Apic
+        } else {
+            LocalApic::XApic(registers)
+        };
+        write_msr(IA32_APIC_BASE, base);
+
+        apic.write(TASK_PRIORITY, 0);
+        for register in [LVT_TIMER, LVT_LINT0, LVT_LINT1, LVT_ERROR] {
+            apic.write(register, LVT_MASKED);
+        }
+
+        // The error status register has to be written before it's read.
+        apic.write(ERROR_STATUS, 0);
+        apic.write(ERROR_STATUS, 0);
+
+        apic.write(
+            SPURIOUS_INTERRUPT_VECTOR,
+            SOFTWARE_ENABLE | spurious_vector as u32,
+        );
+        apic.end_of_interrupt();
+        apic
+    }
+
+    /// Returns the APIC id of the current processor.
+    pub fn id(self) -> u32 {
+        // Safety: Reading the id has no side effects.
+        let id = unsafe { self.read(ID) };
+        match self {
+            LocalApic::XApic(_) => id >> 24,
+            LocalApic::X2Apic => id,
+        }
+    }
+
+    /// Signals the end of the interrupt currently handled.
+    pub fn end_of_interrupt(self) {
+        // Safety: Writing zero to the EOI register only completes the current interrupt.
+        unsafe { self.write(END_OF_INTERRUPT, 0) };
+    }
+
+    /// Reads the register at the xAPIC register offset.
+    ///
+    /// ## Safety
+    ///
+    /// The register has to exist and reading it must not have side effects the caller doesn't expect.
+    pub unsafe fn read(self, register: u32) -> u32 {
+        match self {
+            LocalApic::XApic(base) => ptr::read_volatile((base + register as u64).as_ptr()),
+            LocalApic::X2Apic => read_msr(X2APIC_MSR_BASE + register / 16) as u32,
+        }
+    }
+
+    /// Writes the register at the xAPIC register offset.
+    ///
+    /// ## Safety
+    ///
+    /// The register has to exist and the value must not break the kernel's use of the local APIC.
+    pub unsafe fn write(self, register: u32, value: u32) {
+        match self {
+            LocalApic::XApic(base) => {
+                ptr::write_volatile((base + register as u64).as_mut_ptr(), value)
+            }
+            LocalApic::X2Apic => write_msr(X2APIC_MSR_BASE + register / 16, value as u64),
+        }
+    }
+}
+
+/// Returns whenever the processor supports x2APIC mode.
+fn supports_x2apic() -> bool {
+    (__cpuid(1).ecx & (1 << 21)) != 0
+}
+
+unsafe fn read_msr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    asm!("RDMSR", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+    ((high as u64) << 32) | low as u64
+}
+
+unsafe fn write_msr(msr: u32, value: u64) {
+    asm!("WRMSR", in("ecx") msr, in("eax") value as u32, in("edx") (value >> 32) as u32, options(nostack, preserves_flags));
+}
@@ -0,0 +1,43 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! The legacy dual 8259 Programmable Interrupt Controller (PIC).
+use core::arch::asm;
+
+const PRIMARY_COMMAND: u16 = 0x20;
+const PRIMARY_DATA: u16 = 0x21;
+const SECONDARY_COMMAND: u16 = 0xA0;
+const SECONDARY_DATA: u16 = 0xA1;
+
+/// Initialization command word 1: Start initialization, ICW4 follows.
+const ICW1_INIT: u8 = 0x11;
+
+/// Initialization command word 4: 8086 mode.
+const ICW4_8086: u8 = 0x01;
+
+/// Remaps the PIC to `vector_base` and the following 15 vectors and masks all interrupts.
+/// Spurious interrupts may still be raised on the vectors of IRQ 7 and 15.
+///
+/// ## Safety
+///
+/// The system has to have a legacy PIC and nothing else may use it.
+pub unsafe fn disable(vector_base: u8) {
+    write(PRIMARY_COMMAND, ICW1_INIT);
+    write(SECONDARY_COMMAND, ICW1_INIT);
+    write(PRIMARY_DATA, vector_base);
+    write(SECONDARY_DATA, vector_base + 8);
+    // The secondary PIC is connected to IRQ 2 of the primary one.
+    write(PRIMARY_DATA, 1 << 2);
+    write(SECONDARY_DATA, 2);
+    write(PRIMARY_DATA, ICW4_8086);
+    write(SECONDARY_DATA, ICW4_8086);
+
+    write(PRIMARY_DATA, 0xFF);
+    write(SECONDARY_DATA, 0xFF);
+}
+
+/// Writes to the port and waits for the PIC to process it, by writing to the unused POST code port.
+unsafe fn write(port: u16, value: u8) {
+    asm!("OUT DX, AL", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+    asm!("OUT 0x80, AL", in("al") 0u8, options(nomem, nostack, preserves_flags));
+}
@@ -0,0 +1,156 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! The timer of the local APIC, registered as clock event of each processor.
+use crate::local::{
+    LocalApic, LVT_MASKED, LVT_TIMER, LVT_TIMER_PERIODIC, TIMER_CURRENT_COUNT,
+    TIMER_DIVIDE_CONFIGURATION, TIMER_INITIAL_COUNT,
+};
+use common::interrupts::{TriggerMode, Vector};
+use common::sync::SyncOnceCell;
+use common::time::{ClockEvent, ClockEventError};
+use log::{info, warn};
+
+/// Rating of the local APIC timer, every processor has its own.
+const RATING: u32 = 100;
+
+/// Divide configuration dividing the bus clock by 16.
+const DIVIDE_BY_16: u32 = 0b0011;
+
+/// Duration of the calibration in nanoseconds.
+const CALIBRATION_NANOSECONDS: u64 = 10_000_000;
+
+/// Nanoseconds per second.
+const NANOSECONDS_PER_SECOND: u128 = 1_000_000_000;
+
+static TIMER: SyncOnceCell<LocalApicTimer> = SyncOnceCell::new();
+
+struct LocalApicTimer {
+    local: LocalApic,
+    vector: Vector,
+
+    /// Frequency of the timer in Hz, after dividing the bus clock.
+    frequency: u64,
+}
+
+impl LocalApicTimer {
+    /// Converts the nanoseconds into the initial count of the timer.
+    fn count(&self, nanoseconds: u64) -> Result<u32, ClockEventError> {
+        let count = nanoseconds as u128 * self.frequency as u128 / NANOSECONDS_PER_SECOND;
+        u32::try_from(count.max(1)).map_err(|_| ClockEventError::OutOfRange)
+    }
+
+    /// Starts the timer of the current processor.
+    fn start(&self, mode: u32, count: u32) {
+        // Safety: The timer is only used as clock event.
+        unsafe {
+            self.local.write(TIMER_DIVIDE_CONFIGURATION, DIVIDE_BY_16);
+            self.local.write(LVT_TIMER, mode | self.vector as u32);
+            self.local.write(TIMER_INITIAL_COUNT, count);
+        }
+    }
+}
+
+impl ClockEvent for LocalApicTimer {
+    fn name(&self) -> &'static str {
+        "lapic"
+    }
+
+    fn rating(&self) -> u32 {
+        RATING
+    }
+
+    fn is_per_cpu(&self) -> bool {
+        true
+    }
+
+    fn supports_periodic(&self) -> bool {
+        true
+    }
+
+    fn set_oneshot(&self, delay: u64) -> Result<(), ClockEventError> {
+        self.start(0, self.count(delay)?);
+        Ok(())
+    }
+
+    fn set_periodic(&self, period: u64) -> Result<(), ClockEventError> {
+        self.start(LVT_TIMER_PERIODIC, self.count(period)?);
+        Ok(())
+    }
+
+    fn stop(&self) {
+        // Safety: The timer is only used as clock event.
+        unsafe {
+            self.local.write(LVT_TIMER, LVT_MASKED);
+            self.local.write(TIMER_INITIAL_COUNT, 0);
+        }
+    }
+}
+
+/// Calibrates the local APIC timer against the clock source and registers it as clock event.
+pub fn register(local: LocalApic) {
+    if TIMER.is_initialized() {
+        return;
+    }
+
+    let Some(frequency) = calibrate(local) else {
+        warn!("Local APIC timer needs a clock source for calibration");
+        return;
+    };
+    info!("Local APIC timer running at {} Hz", frequency);
+
+    let vector = match common::interrupts::allocate_vector(TriggerMode::Edge) {
+        Ok(vector) => vector,
+        Err(error) => {
+            warn!("Local APIC timer has no interrupt vector: {:?}", error);
+            return;
+        }
+    };
+    if let Err(error) = common::interrupts::register_handler(vector, handle_interrupt) {
+        warn!("Failed to register local APIC timer handler: {:?}", error);
+        let _ = common::interrupts::free_vector(vector);
+        return;
+    }
+
+    let timer = TIMER.get_or_init(|| LocalApicTimer {
+        local,
+        vector,
+        frequency,
+    });
+    if common::time::register_clock_event(timer) {
+        info!("Local APIC timer used as clock event");
+    }
+}
+
+/// Measures the frequency of the timer against the clock source, in Hz.
+fn calibrate(local: LocalApic) -> Option<u64> {
+    let start = common::time::monotonic_nanoseconds()?;
+
+    // Safety: The timer isn't used yet and stays masked.
+    unsafe {
+        local.write(TIMER_DIVIDE_CONFIGURATION, DIVIDE_BY_16);
+        local.write(LVT_TIMER, LVT_MASKED);
+        local.write(TIMER_INITIAL_COUNT, u32::MAX);
+    }
+
+    let mut now = start;
+    while now - start < CALIBRATION_NANOSECONDS {
+        core::hint::spin_loop();
+        now = common::time::monotonic_nanoseconds().unwrap_or(now);
+    }
+
+    // Safety: Reading the count has no side effects, writing zero stops the timer.
+    let ticks = unsafe {
+        let ticks = u32::MAX - local.read(TIMER_CURRENT_COUNT);
+        local.write(TIMER_INITIAL_COUNT, 0);
+        ticks
+    };
+
+    let frequency = ticks as u128 * NANOSECONDS_PER_SECOND / (now - start) as u128;
+    (frequency != 0).then_some(frequency as u64)
+}
+
+fn handle_interrupt(_: Vector) -> bool {
+    common::time::handle_clock_event();
+    true
+}
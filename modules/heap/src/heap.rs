@@ -0,0 +1,711 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use core::alloc::Layout;
+use core::mem::size_of;
+use core::ptr::NonNull;
+
+/// Size of a page, the granularity in which the heap grows.
+pub const PAGE_SIZE: usize = 4096;
+
+/// Object sizes handed out by slabs, bigger allocations get their own pages.
+const SIZE_CLASSES: [usize; 8] = [8, 16, 32, 64, 128, 256, 512, 1024];
+
+/// Number of words needed for the bitmap of the size class with the most objects per slab.
+const BITMAP_WORDS: usize = PAGE_SIZE / SIZE_CLASSES[0] / u64::BITS as usize;
+
+/// Pattern written to newly allocated memory in debug builds.
+#[cfg(debug_assertions)]
+pub const ALLOCATED_POISON: u8 = 0xA5;
+
+/// Pattern written to freed memory in debug builds.
+#[cfg(debug_assertions)]
+pub const FREED_POISON: u8 = 0xDE;
+
+/// Provides the memory backing the pages of the heap.
+pub trait Backend {
+    /// Backs the page at `page` with memory.
+    /// Returns `false` if no memory is available.
+    fn map(&mut self, page: usize) -> bool;
+
+    /// Releases the memory backing the page at `page`.
+    /// Returns `false` if the page wasn't backed by memory.
+    fn unmap(&mut self, page: usize) -> bool;
+}
+
+/// Header at the start of every slab page.
+/// Set bits in `used` are allocated objects, the objects overlapping the header are always marked as used.
+#[repr(C)]
+struct Slab {
+    next: Option<NonNull<Slab>>,
+    prev: Option<NonNull<Slab>>,
+    used: [u64; BITMAP_WORDS],
+    free: usize,
+    class: usize,
+}
+
+/// A range of unused pages, kept in a list sorted by address.
+struct Range {
+    start: usize,
+    pages: usize,
+    next: Option<NonNull<Range>>,
+}
+
+impl Range {
+    fn end(&self) -> usize {
+        self.start + self.pages * PAGE_SIZE
+    }
+}
+
+/// A heap growing page by page inside a range of virtual memory.
+///
+/// Allocations up to 1 KiB are served from slabs, pages divided into objects of a single size class.
+/// Bigger allocations are served by whole pages, taken from the list of unused ranges or from the top of the heap.
+/// The nodes of that list are allocated from the slabs themselves.
+///
+/// Freeing memory that isn't allocated panics. In debug builds, memory is poisoned on allocation and free,
+/// and slab objects are checked for modifications after they were freed.
+pub struct Heap<B: Backend> {
+    backend: B,
+    top: usize,
+    end: usize,
+    ranges: Option<NonNull<Range>>,
+    slabs: [Option<NonNull<Slab>>; SIZE_CLASSES.len()],
+}
+
+// Safety: The heap exclusively owns all memory it points to.
+unsafe impl<B: Backend + Send> Send for Heap<B> {}
+
+impl<B: Backend> Heap<B> {
+    /// Creates an empty heap inside `start..end`, using `backend` to back the pages with memory.
+    ///
+    /// ## Safety
+    ///
+    /// `start` and `end` have to be page aligned. The range must not be used by anything else
+    /// and has to be accessible once the backend mapped a page.
+    pub unsafe fn new(backend: B, start: usize, end: usize) -> Self {
+        debug_assert!(start.is_multiple_of(PAGE_SIZE) && end.is_multiple_of(PAGE_SIZE));
+
+        Heap {
+            backend,
+            top: start,
+            end,
+            ranges: None,
+            slabs: [None; SIZE_CLASSES.len()],
+        }
+    }
+
+    /// Allocates memory for the given layout.
+    /// Returns `None` if there is no memory left.
+    pub fn allocate(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        let ptr = match size_class(layout) {
+            Some(class) => self.allocate_object(class)?,
+            None => self.allocate_pages(pages(layout), layout.align())?,
+        };
+
+        // Safety: The memory was just allocated with the size of the layout.
+        #[cfg(debug_assertions)]
+        unsafe {
+            ptr.as_ptr().write_bytes(ALLOCATED_POISON, layout.size())
+        };
+
+        Some(ptr)
+    }
+
+    /// Frees memory previously returned by [`Heap::allocate`].
+    /// Panics if the memory isn't allocated.
+    ///
+    /// ## Safety
+    ///
+    /// `ptr` has to be allocated by this heap with the same layout and must not be used afterwards.
+    pub unsafe fn deallocate(&mut self, ptr: NonNull<u8>, layout: Layout) {
+        match size_class(layout) {
+            Some(class) => self.deallocate_object(ptr, class),
+            None => self.deallocate_pages(ptr.as_ptr() as usize, pages(layout)),
+        }
+    }
+
+    /// Allocates an object of the given size class, creating a new slab if needed.
+    fn allocate_object(&mut self, class: usize) -> Option<NonNull<u8>> {
+        if self.slabs[class].is_none() {
+            let slab = self.create_slab(class)?;
+            self.push_slab(class, slab);
+        }
+        let slab = self.slabs[class]?;
+        let size = SIZE_CLASSES[class];
+
+        // Safety: Slabs in the list are valid and owned by the heap.
+        let header = unsafe { &mut *slab.as_ptr() };
+        let index = header
+            .used
+            .iter()
+            .enumerate()
+            .find(|(_, word)| **word != u64::MAX)
+            .map(|(index, word)| index * u64::BITS as usize + word.trailing_ones() as usize)
+            .expect("Slab without free objects in the free list");
+        header.used[index / u64::BITS as usize] |= 1 << (index % u64::BITS as usize);
+        header.free -= 1;
+        if header.free == 0 {
+            self.remove_slab(class, slab);
+        }
+
+        let object = slab.as_ptr() as usize + index * size;
+
+        // Safety: The object is inside the slab page.
+        #[cfg(debug_assertions)]
+        unsafe {
+            let bytes = core::slice::from_raw_parts(object as *const u8, size);
+            assert!(
+                bytes.iter().all(|x| *x == FREED_POISON),
+                "Heap corruption, object {:#x} was modified after it was freed",
+                object
+            );
+        }
+
+        NonNull::new(object as *mut u8)
+    }
+
+    /// Frees an object of the given size class, releasing the slab if it's empty and not the only one left.
+    ///
+    /// ## Safety
+    ///
+    /// `ptr` has to be inside a slab of this heap.
+    unsafe fn deallocate_object(&mut self, ptr: NonNull<u8>, class: usize) {
+        let size = SIZE_CLASSES[class];
+        let object = ptr.as_ptr() as usize;
+        let slab = NonNull::new_unchecked((object & !(PAGE_SIZE - 1)) as *mut Slab);
+        let header = &mut *slab.as_ptr();
+        assert!(
+            header.class == class && object.is_multiple_of(size),
+            "Heap corruption, object {:#x} is not part of a slab of size {}",
+            object,
+            size
+        );
+
+        let index = (object - slab.as_ptr() as usize) / size;
+        let bit = 1 << (index % u64::BITS as usize);
+        let word = &mut header.used[index / u64::BITS as usize];
+        assert!(
+            *word & bit != 0,
+            "Object {:#x} not allocated, double free?",
+            object
+        );
+
+        #[cfg(debug_assertions)]
+        ptr.as_ptr().write_bytes(FREED_POISON, size);
+
+        *word &= !bit;
+        header.free += 1;
+        let free = header.free;
+        let linked = header.next.is_some() || header.prev.is_some();
+
+        if free == 1 {
+            // The slab was full, so it's not in the list.
+            self.push_slab(class, slab);
+        } else if free == capacity(class) && linked {
+            self.remove_slab(class, slab);
+            self.deallocate_pages(slab.as_ptr() as usize, 1);
+        }
+    }
+
+    /// Creates a new slab for the given size class, without adding it to the list.
+    fn create_slab(&mut self, class: usize) -> Option<NonNull<Slab>> {
+        let page = self.allocate_pages(1, PAGE_SIZE)?;
+        let size = SIZE_CLASSES[class];
+        let first = first_object(class);
+        let objects = PAGE_SIZE / size;
+
+        let mut used = [u64::MAX; BITMAP_WORDS];
+        for index in first..objects {
+            used[index / u64::BITS as usize] &= !(1 << (index % u64::BITS as usize));
+        }
+
+        let slab = page.cast::<Slab>();
+        // Safety: The page was just allocated and is big enough for the header and the objects.
+        unsafe {
+            slab.as_ptr().write(Slab {
+                next: None,
+                prev: None,
+                used,
+                free: objects - first,
+                class,
+            });
+
+            #[cfg(debug_assertions)]
+            page.as_ptr()
+                .add(first * size)
+                .write_bytes(FREED_POISON, PAGE_SIZE - first * size);
+        }
+
+        Some(slab)
+    }
+
+    /// Adds a slab to the front of the list of its size class.
+    fn push_slab(&mut self, class: usize, slab: NonNull<Slab>) {
+        let head = self.slabs[class];
+        // Safety: Slabs are valid and owned by the heap.
+        unsafe {
+            (*slab.as_ptr()).prev = None;
+            (*slab.as_ptr()).next = head;
+            if let Some(head) = head {
+                (*head.as_ptr()).prev = Some(slab);
+            }
+        }
+        self.slabs[class] = Some(slab);
+    }
+
+    /// Removes a slab from the list of its size class.
+    fn remove_slab(&mut self, class: usize, slab: NonNull<Slab>) {
+        // Safety: Slabs are valid and owned by the heap.
+        unsafe {
+            let Slab { next, prev, .. } = *slab.as_ptr();
+            match prev {
+                Some(prev) => (*prev.as_ptr()).next = next,
+                None => self.slabs[class] = next,
+            }
+            if let Some(next) = next {
+                (*next.as_ptr()).prev = prev;
+            }
+            (*slab.as_ptr()).next = None;
+            (*slab.as_ptr()).prev = None;
+        }
+    }
+
+    /// Allocates `pages` backed pages aligned to `align`.
+    fn allocate_pages(&mut self, pages: usize, align: usize) -> Option<NonNull<u8>> {
+        let align = align.max(PAGE_SIZE);
+        let size = pages * PAGE_SIZE;
+
+        // Splitting a range in the middle needs another node, which has to be allocated upfront.
+        let mut spare = if align > PAGE_SIZE {
+            Some(self.allocate_node()?)
+        } else {
+            None
+        };
+
+        let start = match self.take_range(size, align, &mut spare) {
+            Some((start, removed)) => {
+                if let Some(removed) = removed {
+                    self.deallocate_node(removed);
+                }
+                Some(start)
+            }
+            None => self.take_top(size, align),
+        };
+
+        if let Some(spare) = spare {
+            self.deallocate_node(spare);
+        }
+        let start = start?;
+
+        for page in 0..pages {
+            if !self.backend.map(start + page * PAGE_SIZE) {
+                for page in 0..page {
+                    self.backend.unmap(start + page * PAGE_SIZE);
+                }
+                self.insert_range(start, pages);
+                return None;
+            }
+        }
+
+        NonNull::new(start as *mut u8)
+    }
+
+    /// Frees pages previously returned by [`Heap::allocate_pages`].
+    fn deallocate_pages(&mut self, start: usize, pages: usize) {
+        for page in 0..pages {
+            let page = start + page * PAGE_SIZE;
+            assert!(
+                self.backend.unmap(page),
+                "Page {:#x} not allocated, double free?",
+                page
+            );
+        }
+
+        self.insert_range(start, pages);
+    }
+
+    /// Takes `size` bytes aligned to `align` from the first unused range big enough.
+    /// Returns the start and the node of the range, if it was used up completely.
+    fn take_range(
+        &mut self,
+        size: usize,
+        align: usize,
+        spare: &mut Option<NonNull<Range>>,
+    ) -> Option<(usize, Option<NonNull<Range>>)> {
+        let mut prev: Option<NonNull<Range>> = None;
+        let mut current = self.ranges;
+        while let Some(node) = current {
+            // Safety: Nodes in the list are valid and owned by the heap.
+            let range = unsafe { &mut *node.as_ptr() };
+            let start = range.start.next_multiple_of(align);
+            let end = range.end();
+            if start + size > end {
+                prev = current;
+                current = range.next;
+                continue;
+            }
+
+            let leading = (start - range.start) / PAGE_SIZE;
+            let trailing = (end - start - size) / PAGE_SIZE;
+            let mut removed = None;
+            match (leading, trailing) {
+                (0, 0) => {
+                    match prev {
+                        // Safety: Nodes in the list are valid and owned by the heap.
+                        Some(prev) => unsafe { (*prev.as_ptr()).next = range.next },
+                        None => self.ranges = range.next,
+                    }
+                    removed = Some(node);
+                }
+                (0, _) => {
+                    range.start += size;
+                    range.pages = trailing;
+                }
+                (_, 0) => range.pages = leading,
+                (_, _) => {
+                    let split = spare.take().expect("No spare node to split a range");
+                    // Safety: The spare node was allocated for this.
+                    unsafe {
+                        split.as_ptr().write(Range {
+                            start: start + size,
+                            pages: trailing,
+                            next: range.next,
+                        })
+                    };
+                    range.pages = leading;
+                    range.next = Some(split);
+                }
+            }
+
+            return Some((start, removed));
+        }
+
+        None
+    }
+
+    /// Takes `size` bytes aligned to `align` from the top of the heap.
+    fn take_top(&mut self, size: usize, align: usize) -> Option<usize> {
+        let top = self.top;
+        let start = top.next_multiple_of(align);
+        if start.checked_add(size)? > self.end {
+            return None;
+        }
+
+        self.top = start + size;
+        if start > top {
+            self.insert_range(top, (start - top) / PAGE_SIZE);
+        }
+
+        Some(start)
+    }
+
+    /// Adds unused pages to the list of ranges, merging them with adjacent ranges.
+    /// If no node can be allocated, the pages are lost.
+    fn insert_range(&mut self, start: usize, pages: usize) {
+        let mut node = None;
+        loop {
+            // Allocating a node may change the list, so try merging again.
+            if self.merge_range(start, pages) {
+                break;
+            }
+
+            if let Some(node) = node {
+                self.link_range(node, start, pages);
+                return;
+            }
+
+            match self.allocate_node() {
+                Some(new) => node = Some(new),
+                None => return,
+            }
+        }
+
+        if let Some(node) = node {
+            self.deallocate_node(node);
+        }
+    }
+
+    /// Merges the pages into an adjacent range, returning `false` if there is none.
+    fn merge_range(&mut self, start: usize, pages: usize) -> bool {
+        let (prev, next) = self.neighbours(start);
+        let end = start + pages * PAGE_SIZE;
+
+        // Safety: Nodes in the list are valid and owned by the heap.
+        unsafe {
+            let prev_adjacent = prev.is_some_and(|x| (*x.as_ptr()).end() == start);
+            let next_adjacent = next.is_some_and(|x| (*x.as_ptr()).start == end);
+            match (prev, next) {
+                (Some(prev), Some(next)) if prev_adjacent && next_adjacent => {
+                    let prev = &mut *prev.as_ptr();
+                    prev.pages += pages + (*next.as_ptr()).pages;
+                    prev.next = (*next.as_ptr()).next;
+                    self.deallocate_node(next);
+                }
+                (Some(prev), _) if prev_adjacent => (*prev.as_ptr()).pages += pages,
+                (_, Some(next)) if next_adjacent => {
+                    let next = &mut *next.as_ptr();
+                    next.start = start;
+                    next.pages += pages;
+                }
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    /// Inserts a new range into the sorted list.
+    fn link_range(&mut self, node: NonNull<Range>, start: usize, pages: usize) {
+        let (prev, next) = self.neighbours(start);
+        // Safety: The node was allocated for this and nodes in the list are valid and owned by the heap.
+        unsafe {
+            node.as_ptr().write(Range { start, pages, next });
+            match prev {
+                Some(prev) => (*prev.as_ptr()).next = Some(node),
+                None => self.ranges = Some(node),
+            }
+        }
+    }
+
+    /// Returns the ranges before and after `start`.
+    fn neighbours(&self, start: usize) -> (Option<NonNull<Range>>, Option<NonNull<Range>>) {
+        let mut prev = None;
+        let mut current = self.ranges;
+        while let Some(node) = current {
+            // Safety: Nodes in the list are valid and owned by the heap.
+            let range = unsafe { &*node.as_ptr() };
+            if range.start > start {
+                break;
+            }
+            prev = current;
+            current = range.next;
+        }
+
+        (prev, current)
+    }
+
+    fn allocate_node(&mut self) -> Option<NonNull<Range>> {
+        let class = size_class(Layout::new::<Range>())?;
+        self.allocate_object(class).map(NonNull::cast)
+    }
+
+    fn deallocate_node(&mut self, node: NonNull<Range>) {
+        let class = size_class(Layout::new::<Range>()).unwrap();
+        // Safety: Nodes are allocated from the slabs of their size class.
+        unsafe { self.deallocate_object(node.cast(), class) };
+    }
+}
+
+/// Returns the size class for the layout or `None` if it needs whole pages.
+fn size_class(layout: Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align());
+    SIZE_CLASSES.iter().position(|x| *x >= size)
+}
+
+/// Returns the number of pages needed for the layout.
+fn pages(layout: Layout) -> usize {
+    layout.size().div_ceil(PAGE_SIZE).max(1)
+}
+
+/// Returns the index of the first object not overlapping the slab header.
+fn first_object(class: usize) -> usize {
+    size_of::<Slab>().div_ceil(SIZE_CLASSES[class])
+}
+
+/// Returns the number of objects in a slab of the given size class.
+fn capacity(class: usize) -> usize {
+    PAGE_SIZE / SIZE_CLASSES[class] - first_object(class)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Backend, Heap, PAGE_SIZE};
+    use std::alloc::{alloc, Layout};
+    use std::collections::HashSet;
+    use std::ptr::NonNull;
+
+    /// Backend over host memory, keeping track of the mapped pages.
+    #[derive(Default)]
+    struct TestBackend {
+        mapped: HashSet<usize>,
+        limit: Option<usize>,
+    }
+
+    impl Backend for TestBackend {
+        fn map(&mut self, page: usize) -> bool {
+            if self.limit.is_some_and(|x| self.mapped.len() >= x) {
+                return false;
+            }
+            self.mapped.insert(page)
+        }
+
+        fn unmap(&mut self, page: usize) -> bool {
+            self.mapped.remove(&page)
+        }
+    }
+
+    /// Creates a heap over `pages` pages of leaked host memory.
+    fn heap(pages: usize) -> Heap<TestBackend> {
+        let size = pages * PAGE_SIZE;
+        let start =
+            unsafe { alloc(Layout::from_size_align(size, 64 * PAGE_SIZE).unwrap()) } as usize;
+        unsafe { Heap::new(TestBackend::default(), start, start + size) }
+    }
+
+    fn layout(size: usize, align: usize) -> Layout {
+        Layout::from_size_align(size, align).unwrap()
+    }
+
+    #[test]
+    fn test_small_allocations() {
+        let mut heap = heap(64);
+
+        let mut allocations = Vec::new();
+        for size in [1, 8, 13, 64, 100, 512, 1000, 1024] {
+            for _ in 0..20 {
+                let layout = layout(size, 1);
+                let ptr = heap.allocate(layout).unwrap();
+                assert_eq!(ptr.as_ptr() as usize % size.next_power_of_two().max(8), 0);
+                unsafe { ptr.as_ptr().write_bytes(size as u8, size) };
+                allocations.push((ptr, layout));
+            }
+        }
+
+        for (ptr, layout) in &allocations {
+            let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), layout.size()) };
+            assert!(bytes.iter().all(|x| *x == layout.size() as u8));
+        }
+
+        for (ptr, layout) in allocations {
+            unsafe { heap.deallocate(ptr, layout) };
+        }
+
+        // One slab per size class is kept.
+        assert_eq!(heap.backend.mapped.len(), 7);
+    }
+
+    #[test]
+    fn test_slab_reuse() {
+        let mut heap = heap(16);
+        let layout = layout(32, 8);
+
+        let first = heap.allocate(layout).unwrap();
+        unsafe { heap.deallocate(first, layout) };
+        assert_eq!(heap.allocate(layout), Some(first));
+    }
+
+    #[test]
+    fn test_large_allocations() {
+        let mut heap = heap(64);
+
+        let pages = layout(3 * PAGE_SIZE, 8);
+        let a = heap.allocate(pages).unwrap();
+        let b = heap.allocate(pages).unwrap();
+        let c = heap.allocate(pages).unwrap();
+        assert_eq!(a.as_ptr() as usize % PAGE_SIZE, 0);
+        assert_eq!(heap.backend.mapped.len(), 9);
+
+        // The freed range needs a node, so a slab is created.
+        unsafe { heap.deallocate(b, pages) };
+        assert_eq!(heap.backend.mapped.len(), 7);
+        assert_eq!(heap.allocate(pages), Some(b));
+
+        unsafe {
+            heap.deallocate(a, pages);
+            heap.deallocate(b, pages);
+            heap.deallocate(c, pages);
+        }
+
+        // Only the slab holding the node of the merged range is left.
+        assert_eq!(heap.backend.mapped.len(), 1);
+        assert_eq!(heap.allocate(layout(9 * PAGE_SIZE, 8)), Some(a));
+    }
+
+    #[test]
+    fn test_aligned_allocation() {
+        let mut heap = heap(64);
+
+        let small = heap.allocate(layout(PAGE_SIZE, 8)).unwrap();
+        let aligned = heap.allocate(layout(PAGE_SIZE, 16 * PAGE_SIZE)).unwrap();
+        assert_eq!(aligned.as_ptr() as usize % (16 * PAGE_SIZE), 0);
+
+        // The skipped pages are reused.
+        let next = heap.allocate(layout(2 * PAGE_SIZE, 8)).unwrap();
+        assert!(next.as_ptr() > small.as_ptr() && next.as_ptr() < aligned.as_ptr());
+    }
+
+    #[test]
+    fn test_out_of_memory() {
+        let mut heap = heap(8);
+        assert_eq!(heap.allocate(layout(9 * PAGE_SIZE, 8)), None);
+
+        heap.backend.limit = Some(3);
+        assert_eq!(heap.allocate(layout(4 * PAGE_SIZE, 8)), None);
+
+        // Only the slab holding the node of the returned range is left.
+        assert_eq!(heap.backend.mapped.len(), 1);
+        assert!(heap.allocate(layout(2 * PAGE_SIZE, 8)).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "double free")]
+    fn test_double_free_small() {
+        let mut heap = heap(16);
+        let layout = layout(64, 8);
+
+        let _keep = heap.allocate(layout).unwrap();
+        let ptr = heap.allocate(layout).unwrap();
+        unsafe {
+            heap.deallocate(ptr, layout);
+            heap.deallocate(ptr, layout);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "double free")]
+    fn test_double_free_large() {
+        let mut heap = heap(16);
+        let layout = layout(2 * PAGE_SIZE, 8);
+
+        let ptr = heap.allocate(layout).unwrap();
+        unsafe {
+            heap.deallocate(ptr, layout);
+            heap.deallocate(ptr, layout);
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_poison() {
+        use super::{ALLOCATED_POISON, FREED_POISON};
+
+        let mut heap = heap(16);
+        let layout = layout(128, 8);
+
+        let ptr = heap.allocate(layout).unwrap();
+        let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), 128) };
+        assert!(bytes.iter().all(|x| *x == ALLOCATED_POISON));
+
+        let _keep = heap.allocate(layout).unwrap();
+        unsafe { heap.deallocate(ptr, layout) };
+        let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), 128) };
+        assert!(bytes.iter().all(|x| *x == FREED_POISON));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "modified after it was freed")]
+    fn test_use_after_free() {
+        let mut heap = heap(16);
+        let layout = layout(128, 8);
+
+        let _keep = heap.allocate(layout).unwrap();
+        let ptr: NonNull<u8> = heap.allocate(layout).unwrap();
+        unsafe {
+            heap.deallocate(ptr, layout);
+            ptr.as_ptr().write(0);
+        }
+        heap.allocate(layout);
+    }
+}
@@ -0,0 +1,111 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Kernel Heap Module
+//!
+//! The kernel heap grows inside the kernel dynamic heap area described in [`common::memory`],
+//! with pages mapped by the kernel memory manager (KMM) to frames of the physical memory manager (PMM).
+//! It's registered as the `#[global_allocator]`, so modules can use the `alloc` crate.
+//!
+//! Allocations fail until the module is initialized.
+//!
+#![cfg_attr(not(test), no_std)]
+
+mod heap;
+
+pub use heap::*;
+
+use common::addr::VirtAddr;
+use common::interrupts;
+use common::memory::{KERNEL_DYNAMIC_END, KERNEL_DYNAMIC_START, KERNEL_LEVEL_3_PAGE_TABLE};
+use common::paging::{Page, PhysFrame};
+use common::sync::Spinlock;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{null_mut, NonNull};
+use log::{info, warn};
+use microdragon_interface::macros::init;
+use microdragon_interface::ModuleInterface;
+use pmm::FrameSize;
+
+/// The kernel heap, `None` until [`init`] ran.
+static HEAP: Spinlock<Option<Heap<KernelPages>>> = Spinlock::new(None);
+
+#[cfg(not(test))]
+#[global_allocator]
+static ALLOCATOR: KernelAllocator = KernelAllocator;
+
+/// Entrypoint to the heap module.
+#[init]
+pub fn init(_: &ModuleInterface) {
+    if !KERNEL_LEVEL_3_PAGE_TABLE.is_initialized() {
+        warn!("Heap Kernel Module needs the KMM Kernel Module to be initialized");
+        return;
+    }
+
+    let _guard = interrupts::disable();
+    let mut guard = HEAP.lock();
+    if guard.is_some() {
+        warn!("Heap Kernel Module already initialized");
+        return;
+    }
+
+    // Safety: The kernel dynamic heap area is reserved for the heap and mapped by the KMM.
+    *guard = Some(unsafe {
+        Heap::new(
+            KernelPages,
+            KERNEL_DYNAMIC_START.as_u64() as usize,
+            KERNEL_DYNAMIC_END.as_u64() as usize,
+        )
+    });
+
+    info!("Kernel heap ready");
+}
+
+/// Backs the pages of the kernel heap with frames from the PMM.
+struct KernelPages;
+
+impl Backend for KernelPages {
+    fn map(&mut self, page: usize) -> bool {
+        let Some(frame) = pmm::allocate_frame() else {
+            return false;
+        };
+
+        let page = Page::containing_address(VirtAddr::new(page as u64));
+        // Safety: 4 KiB frames are aligned to their size.
+        let frame_4kib = unsafe { PhysFrame::from_start_address_unchecked(frame) };
+        if kmm::map(page, frame_4kib, true).is_err() {
+            pmm::free(frame, FrameSize::Size4KiB);
+            return false;
+        }
+
+        true
+    }
+
+    fn unmap(&mut self, page: usize) -> bool {
+        kmm::unmap(Page::containing_address(VirtAddr::new(page as u64)))
+            .map(|frame| pmm::free(frame.start_address(), FrameSize::Size4KiB))
+            .is_some()
+    }
+}
+
+/// The global allocator, forwarding to the kernel heap.
+pub struct KernelAllocator;
+
+unsafe impl GlobalAlloc for KernelAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _guard = interrupts::disable();
+        HEAP.lock()
+            .as_mut()
+            .and_then(|heap| heap.allocate(layout))
+            .map_or(null_mut(), NonNull::as_ptr)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _guard = interrupts::disable();
+        let ptr = NonNull::new(ptr).expect("Freeing a null pointer");
+        HEAP.lock()
+            .as_mut()
+            .expect("Heap not initialized")
+            .deallocate(ptr, layout)
+    }
+}
@@ -0,0 +1,248 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! The clock source and clock event backed by the HPET.
+use crate::HPET;
+use apic::Polarity;
+use common::interrupts::{TriggerMode, Vector};
+use common::sync::{Spinlock, SyncOnceCell};
+use common::time::{ClockEvent, ClockEventError, ClockSource};
+use hpet::{ComparatorCapabilities, HpetComparator, InterruptRoute};
+use log::{debug, info, warn};
+
+/// Rating of the HPET clock source, it's slow to read but has a stable frequency.
+const SOURCE_RATING: u32 = 250;
+
+/// Rating of the HPET clock event, which is shared by all processors.
+const EVENT_RATING: u32 = 50;
+
+/// Femtoseconds per second.
+const FEMTOSECONDS_PER_SECOND: u64 = 1_000_000_000_000_000;
+
+/// Femtoseconds per nanosecond.
+const FEMTOSECONDS_PER_NANOSECOND: u128 = 1_000_000;
+
+/// Address of FSB messages targeting a local APIC, the APIC id is placed at bit 12.
+const FSB_ADDRESS: u32 = 0xFEE0_0000;
+
+/// The first I/O APIC input not connected to an ISA interrupt.
+const FIRST_NON_ISA_INPUT: u8 = 16;
+
+static SOURCE: HpetClockSource = HpetClockSource;
+
+static EVENT: SyncOnceCell<HpetClockEvent> = SyncOnceCell::new();
+
+struct HpetClockSource;
+
+impl ClockSource for HpetClockSource {
+    fn name(&self) -> &'static str {
+        "hpet"
+    }
+
+    fn rating(&self) -> u32 {
+        SOURCE_RATING
+    }
+
+    fn frequency(&self) -> u64 {
+        HPET.get()
+            .map_or(0, |x| FEMTOSECONDS_PER_SECOND / x.tick_period as u64)
+    }
+
+    fn read(&self) -> u64 {
+        crate::counter()
+    }
+}
+
+struct HpetClockEvent {
+    comparator: Spinlock<HpetComparator<'static>>,
+    periodic: bool,
+    is_64_bit: bool,
+}
+
+impl HpetClockEvent {
+    /// Converts the nanoseconds into ticks of the HPET, at least the minimal ticks of the HPET.
+    fn ticks(&self, nanoseconds: u64) -> Result<u64, ClockEventError> {
+        let hpet = HPET.get().ok_or(ClockEventError::OutOfRange)?;
+        let ticks = nanoseconds as u128 * FEMTOSECONDS_PER_NANOSECOND / hpet.tick_period as u128;
+        let ticks = ticks.max(hpet.minimum_tick.max(1) as u128);
+
+        let limit = if self.is_64_bit {
+            u64::MAX
+        } else {
+            u32::MAX as u64
+        };
+        if ticks > limit as u128 {
+            return Err(ClockEventError::OutOfRange);
+        }
+
+        Ok(ticks as u64)
+    }
+}
+
+impl ClockEvent for HpetClockEvent {
+    fn name(&self) -> &'static str {
+        "hpet"
+    }
+
+    fn rating(&self) -> u32 {
+        EVENT_RATING
+    }
+
+    fn is_per_cpu(&self) -> bool {
+        false
+    }
+
+    fn supports_periodic(&self) -> bool {
+        self.periodic
+    }
+
+    fn set_oneshot(&self, delay: u64) -> Result<(), ClockEventError> {
+        let ticks = self.ticks(delay)?;
+
+        let _guard = common::interrupts::disable();
+        let mut comparator = self.comparator.lock();
+        comparator.disable();
+        comparator.set_one_shot_mode();
+        comparator.set_value(raw_counter().wrapping_add(ticks));
+        comparator.enable();
+        Ok(())
+    }
+
+    fn set_periodic(&self, period: u64) -> Result<(), ClockEventError> {
+        if !self.periodic {
+            return Err(ClockEventError::PeriodicNotSupported);
+        }
+        let ticks = self.ticks(period)?;
+
+        let _guard = common::interrupts::disable();
+        let mut comparator = self.comparator.lock();
+        comparator.start_periodic(raw_counter().wrapping_add(ticks), ticks);
+        Ok(())
+    }
+
+    fn stop(&self) {
+        let _guard = common::interrupts::disable();
+        self.comparator.lock().disable();
+    }
+}
+
+/// Registers the HPET clock source and sets up a comparator as clock event.
+pub fn register() {
+    if common::time::register_clock_source(&SOURCE) {
+        info!("HPET used as clock source");
+    }
+
+    let Some(event) = setup_clock_event() else {
+        return;
+    };
+    let event = EVENT.get_or_init(|| event);
+    if common::time::register_clock_event(event) {
+        info!("HPET used as clock event");
+    }
+}
+
+fn setup_clock_event() -> Option<HpetClockEvent> {
+    let hpet = HPET.get()?;
+    let timer = hpet.blocks.get(hpet.clock)?;
+
+    let fsb = ComparatorCapabilities {
+        fsb: true,
+        ..Default::default()
+    };
+    let Some(mut comparator) = timer
+        .lend_comparator_with(fsb)
+        .or_else(|| timer.lend_comparator())
+    else {
+        warn!("HPET has no comparator available for a clock event");
+        return None;
+    };
+
+    let Some(local_apic) = apic::local_apic() else {
+        warn!("HPET clock event needs the APIC Kernel Module");
+        return None;
+    };
+
+    // The FSB message only has 8 bits for the destination APIC id, `0xFF` being the broadcast id.
+    let input = if comparator.supports_fsb_interrupt() && local_apic.id() < 0xFF {
+        None
+    } else {
+        let Some(input) = io_apic_input(comparator.supported_io_interrupt_routes()) else {
+            warn!("HPET comparator {} can't be routed", comparator.index());
+            return None;
+        };
+        Some(input)
+    };
+
+    let vector = match common::interrupts::allocate_vector(TriggerMode::Edge) {
+        Ok(vector) => vector,
+        Err(error) => {
+            warn!("HPET clock event has no interrupt vector: {:?}", error);
+            return None;
+        }
+    };
+    if let Err(error) = common::interrupts::register_handler(vector, handle_interrupt) {
+        warn!("Failed to register HPET interrupt handler: {:?}", error);
+        let _ = common::interrupts::free_vector(vector);
+        return None;
+    }
+
+    comparator.disable();
+    comparator.set_edge_triggered();
+    let route = match input {
+        None => InterruptRoute::Fsb {
+            address: FSB_ADDRESS | (local_apic.id() << 12),
+            value: vector as u32,
+        },
+        Some(input) => {
+            if let Err(error) = apic::route_gsi(
+                input as u32,
+                vector,
+                TriggerMode::Edge,
+                Polarity::ActiveHigh,
+            ) {
+                warn!("Failed to route HPET interrupt: {:?}", error);
+                let _ = common::interrupts::unregister_handler(vector, handle_interrupt);
+                let _ = common::interrupts::free_vector(vector);
+                return None;
+            }
+            InterruptRoute::IoApic(input)
+        }
+    };
+    if let Err(error) = comparator.set_interrupt_route(route) {
+        warn!("Failed to route HPET interrupt: {:?}", error);
+        let _ = common::interrupts::unregister_handler(vector, handle_interrupt);
+        let _ = common::interrupts::free_vector(vector);
+        return None;
+    }
+    debug!(
+        "HPET comparator {} routed to {:?} on vector {}",
+        comparator.index(),
+        route,
+        vector
+    );
+
+    Some(HpetClockEvent {
+        periodic: comparator.supports_periodic_mode(),
+        is_64_bit: comparator.is_64_bit(),
+        comparator: Spinlock::new(comparator),
+    })
+}
+
+/// Picks an I/O APIC input from the supported routes, preferring inputs not shared with ISA interrupts.
+fn io_apic_input(routes: u32) -> Option<u8> {
+    let non_isa = routes & !((1 << FIRST_NON_ISA_INPUT) - 1);
+    let routes = if non_isa != 0 { non_isa } else { routes };
+    (routes != 0).then(|| routes.trailing_zeros() as u8)
+}
+
+/// Reads the counter of the clock block, without extending it to 64 bits.
+fn raw_counter() -> u64 {
+    HPET.get()
+        .and_then(|x| x.blocks.get(x.clock))
+        .map_or(0, |x| x.counter())
+}
+
+fn handle_interrupt(_: Vector) -> bool {
+    common::time::handle_clock_event();
+    true
+}
@@ -0,0 +1,184 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # High Precision Event Timer (HPET) Module
+//!
+//! The HPET module drives the event timer blocks described by the ACPI [`acpi::HpetTable`]s.
+//! The block with the lowest HPET number is registered with [`common::time`]:
+//!
+//! - Its main counter is started from zero and registered as clock source.
+//! - One of its comparators is routed to an interrupt vector, through FSB messages if supported,
+//!   else through the I/O APIC, and registered as clock event.
+//!
+#![no_std]
+
+mod clock;
+
+use acpi::{AddressSpace, HpetTable};
+use common::addr::PhysAddr;
+use common::memory::physical_to_virtual;
+use common::sync::SyncOnceCell;
+use core::sync::atomic::{AtomicU64, Ordering};
+use hpet::{ComparatorCapabilities, HighPrecisionEventTimer, HpetBlocks, HpetComparator};
+use log::{info, warn};
+use microdragon_interface::macros::init;
+use microdragon_interface::ModuleInterface;
+
+/// The maximal tick period allowed by the specification, in femtoseconds.
+const MAX_TICK_PERIOD: u32 = 0x05F5E100;
+
+/// The event timer blocks of the system.
+static HPET: SyncOnceCell<Hpet> = SyncOnceCell::new();
+
+/// The last counter value read, extended to 64 bits if the counter is only 32 bits wide.
+static LAST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+struct Hpet {
+    blocks: HpetBlocks,
+
+    /// HPET number of the block used as monotonic clock.
+    clock: u8,
+
+    /// Period of a counter tick of the clock in femtoseconds.
+    tick_period: u32,
+
+    /// The minimal number of ticks between periodic events, without losing interrupts.
+    minimum_tick: u16,
+
+    counter_is_64_bit: bool,
+}
+
+/// Entrypoint to the HPET module.
+#[init]
+pub fn init(_: &ModuleInterface) {
+    if HPET.is_initialized() {
+        warn!("HPET Kernel Module already initialized");
+        return;
+    }
+
+    let mut blocks = HpetBlocks::new();
+    for table in acpi::find_tables::<HpetTable>() {
+        let number = table.hpet_number;
+        let Some(timer) = open_block(table) else {
+            continue;
+        };
+
+        info!(
+            "HPET {} running at {} Hz with {} comparators and a {}-bit counter",
+            number,
+            1_000_000_000_000_000 / timer.main_counter_tick_period() as u64,
+            timer.comparator_count(),
+            if timer.counter_is_64_bit() { 64 } else { 32 }
+        );
+
+        if let Err(error) = blocks.insert(number, timer) {
+            warn!("HPET {} ignored: {:?}", number, error);
+        }
+    }
+
+    // The block with the lowest number is used as clock.
+    let Some((clock, timer)) = blocks.iter_mut().next() else {
+        warn!("HPET not available");
+        return;
+    };
+
+    timer.disable();
+    timer.set_counter(0);
+    timer.enable();
+
+    let minimum_tick = acpi::find_tables::<HpetTable>()
+        .find(|x| x.hpet_number == clock)
+        .map_or(0, |x| x.minimum_tick);
+    let hpet = Hpet {
+        clock,
+        tick_period: timer.main_counter_tick_period(),
+        minimum_tick,
+        counter_is_64_bit: timer.counter_is_64_bit(),
+        blocks,
+    };
+    if HPET.set(hpet).is_err() {
+        warn!("HPET Kernel Module already initialized");
+        return;
+    }
+
+    clock::register();
+}
+
+/// Lends an available comparator with at least the `required` capabilities from any event timer block.
+/// The comparator is returned when dropped. Returns `None` if the module isn't initialized or no such comparator is available.
+pub fn lend_comparator(required: ComparatorCapabilities) -> Option<HpetComparator<'static>> {
+    HPET.get()?
+        .blocks
+        .iter()
+        .find_map(|(_, timer)| timer.lend_comparator_with(required))
+}
+
+/// Maps the event timer block described by the ACPI table.
+fn open_block(table: &HpetTable) -> Option<HighPrecisionEventTimer> {
+    let number = table.hpet_number;
+    let address_space = table.address.address_space();
+    let address = table.address.address;
+    if address_space != AddressSpace::SystemMemory {
+        warn!(
+            "HPET {} not in system memory, address space: {:?}",
+            number, address_space
+        );
+        return None;
+    }
+
+    // Safety: We assume the address given by the ACPI table points to the HPET registers.
+    let timer = unsafe {
+        HighPrecisionEventTimer::new(physical_to_virtual(PhysAddr::new(address)).as_u64(), false)
+    };
+
+    let tick_period = timer.main_counter_tick_period();
+    if tick_period == 0 || tick_period > MAX_TICK_PERIOD {
+        warn!(
+            "HPET {} reports an invalid tick period of {} fs",
+            number, tick_period
+        );
+        return None;
+    }
+
+    Some(timer)
+}
+
+/// Returns the ticks counted since the HPET module was initialized, or `0` if it isn't.
+///
+/// A 32-bit counter is extended to 64 bits, which requires it to be read at least once per half overflow period,
+/// about 2.5 minutes at the common frequency of 14.318 MHz.
+pub fn counter() -> u64 {
+    let Some(hpet) = HPET.get() else {
+        return 0;
+    };
+    let Some(timer) = hpet.blocks.get(hpet.clock) else {
+        return 0;
+    };
+
+    if hpet.counter_is_64_bit {
+        return timer.counter();
+    }
+
+    let raw = timer.counter() & u32::MAX as u64;
+    let mut last = LAST_COUNTER.load(Ordering::Acquire);
+    loop {
+        let mut extended = (last & !(u32::MAX as u64)) | raw;
+        if extended < last {
+            if last - extended < 1 << 31 {
+                // Another processor already read a newer value.
+                return last;
+            }
+            extended += 1 << 32;
+        }
+
+        match LAST_COUNTER.compare_exchange_weak(
+            last,
+            extended,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => return extended,
+            Err(x) => last = x,
+        }
+    }
+}
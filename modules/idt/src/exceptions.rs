@@ -0,0 +1,97 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! Handlers for the 32 CPU exceptions.
+use crate::stubs::InterruptFrame;
+use core::arch::asm;
+use log::error;
+
+/// Number of exception vectors reserved by the CPU.
+pub const EXCEPTION_COUNT: usize = 32;
+
+/// Vector of the non-maskable interrupt.
+pub const NMI: u8 = 2;
+
+/// Vector of the double fault exception.
+pub const DOUBLE_FAULT: u8 = 8;
+
+/// Vector of the page fault exception.
+pub const PAGE_FAULT: u8 = 14;
+
+/// Vector of the machine check exception.
+pub const MACHINE_CHECK: u8 = 18;
+
+/// Names of the exceptions, indexed by vector.
+const NAMES: [&str; EXCEPTION_COUNT] = [
+    "Divide Error",
+    "Debug",
+    "Non-Maskable Interrupt",
+    "Breakpoint",
+    "Overflow",
+    "Bound Range Exceeded",
+    "Invalid Opcode",
+    "Device Not Available",
+    "Double Fault",
+    "Coprocessor Segment Overrun",
+    "Invalid TSS",
+    "Segment Not Present",
+    "Stack-Segment Fault",
+    "General Protection Fault",
+    "Page Fault",
+    "Reserved",
+    "x87 Floating-Point Exception",
+    "Alignment Check",
+    "Machine Check",
+    "SIMD Floating-Point Exception",
+    "Virtualization Exception",
+    "Control Protection Exception",
+    "Reserved",
+    "Reserved",
+    "Reserved",
+    "Reserved",
+    "Reserved",
+    "Reserved",
+    "Hypervisor Injection Exception",
+    "VMM Communication Exception",
+    "Security Exception",
+    "Reserved",
+];
+
+/// Logs a register dump of the interrupted code and panics.
+pub fn handle_exception(frame: &InterruptFrame) -> ! {
+    let name = NAMES[frame.vector as usize];
+
+    error!(
+        "{} exception (vector {}, error code {:#x})",
+        name, frame.vector, frame.error_code
+    );
+    error!(
+        "RIP {:#018x} CS  {:#06x} RFLAGS {:#018x}",
+        frame.rip, frame.cs, frame.rflags
+    );
+    error!("RSP {:#018x} SS  {:#06x}", frame.rsp, frame.ss);
+    if frame.vector == PAGE_FAULT as u64 {
+        let cr2: u64;
+        // Safety: Reading CR2 has no side effects.
+        unsafe { asm!("MOV {}, CR2", out(reg) cr2, options(nomem, nostack, preserves_flags)) };
+        error!("CR2 {:#018x}", cr2);
+    }
+    error!(
+        "RAX {:#018x} RBX {:#018x} RCX {:#018x} RDX {:#018x}",
+        frame.rax, frame.rbx, frame.rcx, frame.rdx
+    );
+    error!(
+        "RSI {:#018x} RDI {:#018x} RBP {:#018x} R8  {:#018x}",
+        frame.rsi, frame.rdi, frame.rbp, frame.r8
+    );
+    error!(
+        "R9  {:#018x} R10 {:#018x} R11 {:#018x} R12 {:#018x}",
+        frame.r9, frame.r10, frame.r11, frame.r12
+    );
+    error!(
+        "R13 {:#018x} R14 {:#018x} R15 {:#018x}",
+        frame.r13, frame.r14, frame.r15
+    );
+
+    panic!("Unhandled {} exception at {:#x}", name, frame.rip);
+}
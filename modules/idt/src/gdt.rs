@@ -0,0 +1,137 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! Global Descriptor Table and Task State Segment.
+use core::arch::asm;
+use core::mem::size_of;
+
+/// Selector of the 64-bit kernel code segment.
+pub const KERNEL_CODE_SELECTOR: u16 = 0x08;
+
+/// Selector of the kernel data segment.
+pub const KERNEL_DATA_SELECTOR: u16 = 0x10;
+
+/// Selector of the Task State Segment.
+pub const TSS_SELECTOR: u16 = 0x18;
+
+/// Interrupt Stack Table index used for double faults.
+pub const DOUBLE_FAULT_IST: u8 = 1;
+
+/// Interrupt Stack Table index used for non-maskable interrupts.
+pub const NMI_IST: u8 = 2;
+
+/// Interrupt Stack Table index used for machine checks.
+pub const MACHINE_CHECK_IST: u8 = 3;
+
+/// Present, ring 0, executable, readable and 64-bit code segment.
+const KERNEL_CODE: u64 = 0x00AF_9A00_0000_FFFF;
+
+/// Present, ring 0, writable data segment.
+const KERNEL_DATA: u64 = 0x00CF_9200_0000_FFFF;
+
+/// Type of an available 64-bit TSS descriptor, with the present bit set.
+const TSS_AVAILABLE: u64 = 0x89;
+
+/// Operand of the `LGDT` and `LIDT` instructions.
+#[repr(C, packed)]
+pub struct DescriptorTablePointer {
+    /// Size of the table in bytes minus one.
+    pub limit: u16,
+
+    /// Virtual address of the table.
+    pub base: u64,
+}
+
+/// The 64-bit Task State Segment, only used for its stack pointers.
+#[repr(C, packed(4))]
+pub struct TaskStateSegment {
+    _reserved_1: u32,
+
+    /// Stack pointers loaded when switching to the given privilege level.
+    privilege_stacks: [u64; 3],
+
+    _reserved_2: u64,
+
+    /// Stack pointers of the Interrupt Stack Table, starting at index 1.
+    interrupt_stacks: [u64; 7],
+
+    _reserved_3: u64,
+    _reserved_4: u16,
+
+    /// Offset of the I/O permission bitmap, pointing behind the segment since there is none.
+    io_map_base: u16,
+}
+
+impl TaskStateSegment {
+    /// Creates a TSS whose Interrupt Stack Table stacks are carved out of the stack at `stack` of `size` bytes.
+    /// Double faults get half of the stack, NMIs and machine checks a quarter each.
+    pub fn new(stack: u64, size: u64) -> Self {
+        let quarter = (size / 4) & !0xF;
+        let mut interrupt_stacks = [0; 7];
+        interrupt_stacks[DOUBLE_FAULT_IST as usize - 1] = stack + 2 * quarter;
+        interrupt_stacks[NMI_IST as usize - 1] = stack + 3 * quarter;
+        interrupt_stacks[MACHINE_CHECK_IST as usize - 1] = stack + 4 * quarter;
+
+        TaskStateSegment {
+            _reserved_1: 0,
+            privilege_stacks: [0; 3],
+            _reserved_2: 0,
+            interrupt_stacks,
+            _reserved_3: 0,
+            _reserved_4: 0,
+            io_map_base: size_of::<TaskStateSegment>() as u16,
+        }
+    }
+}
+
+/// The Global Descriptor Table with the kernel segments and a TSS.
+#[repr(C, align(16))]
+pub struct Gdt([u64; 5]);
+
+impl Gdt {
+    /// Creates a GDT referencing the TSS.
+    pub fn new(tss: &'static TaskStateSegment) -> Self {
+        let base = tss as *const _ as u64;
+        let limit = size_of::<TaskStateSegment>() as u64 - 1;
+        let tss_low = (limit & 0xFFFF)
+            | (base & 0xFF_FFFF) << 16
+            | TSS_AVAILABLE << 40
+            | (limit >> 16 & 0xF) << 48
+            | (base >> 24 & 0xFF) << 56;
+        let tss_high = base >> 32;
+
+        Gdt([0, KERNEL_CODE, KERNEL_DATA, tss_low, tss_high])
+    }
+
+    /// Loads the GDT, reloads the segment registers and the task register.
+    ///
+    /// ## Safety
+    ///
+    /// The GDT has to stay alive while it is loaded.
+    pub unsafe fn load(&'static self) {
+        let pointer = DescriptorTablePointer {
+            limit: size_of::<Gdt>() as u16 - 1,
+            base: self as *const _ as u64,
+        };
+
+        asm!(
+            "LGDT [{pointer}]",
+            // Reload CS with a far return to the next instruction.
+            "PUSH {code}",
+            "LEA {tmp}, [RIP + 2f]",
+            "PUSH {tmp}",
+            "RETFQ",
+            "2:",
+            "MOV DS, {data:x}",
+            "MOV ES, {data:x}",
+            "MOV SS, {data:x}",
+            "LTR {tss:x}",
+            pointer = in(reg) &pointer,
+            code = const KERNEL_CODE_SELECTOR as u64,
+            tmp = out(reg) _,
+            data = in(reg) KERNEL_DATA_SELECTOR as u64,
+            tss = in(reg) TSS_SELECTOR as u64,
+            options(preserves_flags),
+        );
+    }
+}
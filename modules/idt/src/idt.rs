@@ -0,0 +1,78 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! Interrupt Descriptor Table entries.
+use crate::gdt::{DescriptorTablePointer, KERNEL_CODE_SELECTOR};
+use core::arch::asm;
+use core::mem::size_of;
+
+/// Number of entries in the IDT.
+pub const ENTRY_COUNT: usize = 256;
+
+/// Present, ring 0, 64-bit interrupt gate, which disables interrupts while the handler runs.
+const INTERRUPT_GATE: u8 = 0x8E;
+
+/// A gate descriptor of the IDT.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct IdtEntry {
+    offset_low: u16,
+    selector: u16,
+    ist: u8,
+    attributes: u8,
+    offset_middle: u16,
+    offset_high: u32,
+    _reserved: u32,
+}
+
+impl IdtEntry {
+    /// An entry that isn't present. Delivering its vector raises a general protection fault.
+    pub const MISSING: IdtEntry = IdtEntry {
+        offset_low: 0,
+        selector: 0,
+        ist: 0,
+        attributes: 0,
+        offset_middle: 0,
+        offset_high: 0,
+        _reserved: 0,
+    };
+
+    /// Creates an interrupt gate to the handler at `address`,
+    /// switching to the Interrupt Stack Table stack `ist` or staying on the current stack if it's `0`.
+    pub fn new(address: u64, ist: u8) -> Self {
+        IdtEntry {
+            offset_low: address as u16,
+            selector: KERNEL_CODE_SELECTOR,
+            ist,
+            attributes: INTERRUPT_GATE,
+            offset_middle: (address >> 16) as u16,
+            offset_high: (address >> 32) as u32,
+            _reserved: 0,
+        }
+    }
+}
+
+/// The Interrupt Descriptor Table.
+#[repr(C, align(16))]
+pub struct Idt(pub [IdtEntry; ENTRY_COUNT]);
+
+impl Idt {
+    /// Creates an IDT without any present entries.
+    pub const fn new() -> Self {
+        Idt([IdtEntry::MISSING; ENTRY_COUNT])
+    }
+
+    /// Loads the IDT.
+    ///
+    /// ## Safety
+    ///
+    /// The IDT has to stay alive while it is loaded and all entries have to point to valid handlers.
+    pub unsafe fn load(&'static self) {
+        let pointer = DescriptorTablePointer {
+            limit: size_of::<Idt>() as u16 - 1,
+            base: self as *const _ as u64,
+        };
+
+        asm!("LIDT [{}]", in(reg) &pointer, options(readonly, nostack, preserves_flags));
+    }
+}
@@ -0,0 +1,89 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Interrupt Descriptor Table (IDT) Module
+//!
+//! The IDT module sets up everything the CPU needs to deliver interrupts and exceptions:
+//!
+//! - A Global Descriptor Table (GDT) with the kernel segments and a Task State Segment (TSS) for each processor.
+//! - Interrupt Stack Table (IST) stacks inside the processor's secondary stack, so double faults, NMIs and machine checks
+//!   always run on a known good stack, even if the primary stack overflowed.
+//! - An IDT with handlers for all 32 CPU exceptions, which log a register dump and panic.
+//! - Handlers for all other vectors, dispatching to the handlers registered through [`common::interrupts`].
+//!   Vectors [`FIRST_VECTOR`] to [`LAST_VECTOR`] are handed out by [`common::interrupts::allocate_vector`].
+//!
+//! It runs right after the logging module, so faults in all later modules are reported instead of triple faulting.
+//! The application processors load their own GDT and the shared IDT in their per-CPU constructor.
+//!
+#![no_std]
+
+mod exceptions;
+mod gdt;
+mod idt;
+mod stubs;
+mod vectors;
+
+pub use vectors::{
+    log_statistics, set_end_of_interrupt, FIRST_VECTOR, LAST_VECTOR, MAX_SHARED_HANDLERS,
+    SPURIOUS_VECTOR,
+};
+
+use common::sync::SyncOnceCell;
+use gdt::{Gdt, TaskStateSegment};
+use idt::Idt;
+use log::{info, warn};
+use microdragon_interface::macros::init;
+use microdragon_interface::smp::{CpuInfo, MAX_CPUS};
+use microdragon_interface::stack::SECONDARY_STACK_SIZE;
+use microdragon_interface::ModuleInterface;
+
+/// The Task State Segments referenced by [`GDT`], indexed by processor index.
+static TSS: [SyncOnceCell<TaskStateSegment>; MAX_CPUS] = [const { SyncOnceCell::new() }; MAX_CPUS];
+
+/// The Global Descriptor Tables, indexed by processor index.
+static GDT: [SyncOnceCell<Gdt>; MAX_CPUS] = [const { SyncOnceCell::new() }; MAX_CPUS];
+
+/// The Interrupt Descriptor Table.
+static IDT: SyncOnceCell<Idt> = SyncOnceCell::new();
+
+/// Entrypoint to the IDT module.
+/// Interrupts have to be disabled while this is run.
+#[init]
+pub fn init(interface: &ModuleInterface) {
+    if IDT.is_initialized() {
+        warn!("IDT Kernel Module already initialized");
+        return;
+    }
+
+    load_gdt(0, interface.stack_info.secondary_stack);
+
+    let idt = IDT.get_or_init(stubs::create_idt);
+    // Safety: The IDT is static and all handlers use the segments of the loaded GDT.
+    unsafe { idt.load() };
+
+    info!("IDT ready");
+}
+
+/// Per-CPU entrypoint to the IDT module.
+/// Interrupts have to be disabled while this is run.
+#[init]
+pub fn init_cpu(_: &ModuleInterface, cpu: &CpuInfo) {
+    let Some(idt) = IDT.get() else {
+        warn!("IDT Kernel Module not initialized on the bootstrap processor");
+        return;
+    };
+
+    load_gdt(cpu.index as usize, cpu.stack_info.secondary_stack);
+
+    // Safety: The IDT is static and all handlers use the segments of the loaded GDT.
+    unsafe { idt.load() };
+}
+
+/// Creates and loads the GDT of the processor, whose TSS uses the secondary stack.
+fn load_gdt(index: usize, secondary_stack: u64) {
+    let tss = TSS[index]
+        .get_or_init(|| TaskStateSegment::new(secondary_stack, SECONDARY_STACK_SIZE as u64));
+    let gdt = GDT[index].get_or_init(|| Gdt::new(tss));
+    // Safety: The GDT is static and contains the kernel segments the code is currently running with.
+    unsafe { gdt.load() };
+}
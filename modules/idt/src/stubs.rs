@@ -0,0 +1,173 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! Entry stubs for all 256 interrupt vectors.
+//!
+//! Every vector has a small assembly stub, which pushes a dummy error code if the CPU doesn't push one
+//! and the vector, before jumping to a common stub saving all general purpose registers
+//! and calling [`interrupt_handler`] with the resulting [`InterruptFrame`].
+use crate::exceptions::{self, EXCEPTION_COUNT};
+use crate::gdt::{DOUBLE_FAULT_IST, MACHINE_CHECK_IST, NMI_IST};
+use crate::idt::{Idt, IdtEntry};
+use crate::vectors;
+use core::arch::global_asm;
+
+/// Distance between the stubs of consecutive vectors in bytes.
+const STUB_SIZE: u64 = 16;
+
+/// The state of the interrupted code, as saved by the CPU and the interrupt stubs.
+#[derive(Debug)]
+#[repr(C)]
+pub struct InterruptFrame {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rbp: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rbx: u64,
+    pub rax: u64,
+    pub vector: u64,
+    /// The error code pushed by the CPU or `0` for vectors without one.
+    pub error_code: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+global_asm!(
+    ".macro interrupt_stub vector, error_code",
+    ".p2align 4",
+    ".if \\error_code == 0",
+    "PUSH 0",
+    ".endif",
+    "PUSH \\vector",
+    "JMP interrupt_common",
+    ".endm",
+    "",
+    ".section .text.interrupt_stubs, \"ax\"",
+    ".p2align 4",
+    ".global interrupt_stubs",
+    "interrupt_stubs:",
+    "interrupt_stub 0, 0",
+    "interrupt_stub 1, 0",
+    "interrupt_stub 2, 0",
+    "interrupt_stub 3, 0",
+    "interrupt_stub 4, 0",
+    "interrupt_stub 5, 0",
+    "interrupt_stub 6, 0",
+    "interrupt_stub 7, 0",
+    "interrupt_stub 8, 1",
+    "interrupt_stub 9, 0",
+    "interrupt_stub 10, 1",
+    "interrupt_stub 11, 1",
+    "interrupt_stub 12, 1",
+    "interrupt_stub 13, 1",
+    "interrupt_stub 14, 1",
+    "interrupt_stub 15, 0",
+    "interrupt_stub 16, 0",
+    "interrupt_stub 17, 1",
+    "interrupt_stub 18, 0",
+    "interrupt_stub 19, 0",
+    "interrupt_stub 20, 0",
+    "interrupt_stub 21, 1",
+    "interrupt_stub 22, 0",
+    "interrupt_stub 23, 0",
+    "interrupt_stub 24, 0",
+    "interrupt_stub 25, 0",
+    "interrupt_stub 26, 0",
+    "interrupt_stub 27, 0",
+    "interrupt_stub 28, 0",
+    "interrupt_stub 29, 1",
+    "interrupt_stub 30, 1",
+    "interrupt_stub 31, 0",
+    // All other vectors don't have an error code.
+    ".altmacro",
+    ".set vector, 32",
+    ".rept 224",
+    "interrupt_stub %vector, 0",
+    ".set vector, vector + 1",
+    ".endr",
+    ".noaltmacro",
+    ".purgem interrupt_stub",
+    "",
+    // Saves the general purpose registers, calls the handler and restores the interrupted code.
+    "interrupt_common:",
+    "PUSH RAX",
+    "PUSH RBX",
+    "PUSH RCX",
+    "PUSH RDX",
+    "PUSH RSI",
+    "PUSH RDI",
+    "PUSH RBP",
+    "PUSH R8",
+    "PUSH R9",
+    "PUSH R10",
+    "PUSH R11",
+    "PUSH R12",
+    "PUSH R13",
+    "PUSH R14",
+    "PUSH R15",
+    "MOV RDI, RSP",
+    "CLD",
+    "CALL {handler}",
+    "POP R15",
+    "POP R14",
+    "POP R13",
+    "POP R12",
+    "POP R11",
+    "POP R10",
+    "POP R9",
+    "POP R8",
+    "POP RBP",
+    "POP RDI",
+    "POP RSI",
+    "POP RDX",
+    "POP RCX",
+    "POP RBX",
+    "POP RAX",
+    // Drop the vector and error code.
+    "ADD RSP, 16",
+    "IRETQ",
+    ".text",
+    handler = sym interrupt_handler,
+);
+
+extern "C" {
+    /// The stub of vector 0, followed by the stubs of the other vectors every [`STUB_SIZE`] bytes.
+    fn interrupt_stubs();
+}
+
+/// Creates an IDT with entries for all vectors.
+pub fn create_idt() -> Idt {
+    let mut idt = Idt::new();
+    let stubs = interrupt_stubs as *const () as u64;
+    for (vector, entry) in idt.0.iter_mut().enumerate() {
+        let ist = match vector as u8 {
+            exceptions::NMI => NMI_IST,
+            exceptions::DOUBLE_FAULT => DOUBLE_FAULT_IST,
+            exceptions::MACHINE_CHECK => MACHINE_CHECK_IST,
+            _ => 0,
+        };
+        *entry = IdtEntry::new(stubs + vector as u64 * STUB_SIZE, ist);
+    }
+    idt
+}
+
+/// Dispatches an interrupt to the exception or the registered handlers of the vector.
+extern "C" fn interrupt_handler(frame: &InterruptFrame) {
+    if (frame.vector as usize) < EXCEPTION_COUNT {
+        exceptions::handle_exception(frame);
+    } else {
+        vectors::dispatch(frame.vector as u8);
+    }
+}
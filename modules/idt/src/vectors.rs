@@ -0,0 +1,217 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! Allocation of interrupt vectors and dispatching to their handlers.
+use common::interrupts::{
+    self, InterruptHandler, TriggerMode, Vector, VectorError, VectorStatistics,
+};
+use common::sync::{Spinlock, SyncOnceCell};
+use core::ptr::fn_addr_eq;
+use log::{info, warn};
+
+/// The first vector handed out, the vectors below are used by CPU exceptions
+/// and the legacy PIC, which may still raise spurious interrupts while masked.
+pub const FIRST_VECTOR: Vector = 48;
+
+/// The last vector handed out.
+pub const LAST_VECTOR: Vector = 254;
+
+/// The vector reserved for spurious interrupts of the local APIC, which are ignored.
+pub const SPURIOUS_VECTOR: Vector = 255;
+
+/// Maximal number of handlers sharing a level-triggered vector.
+pub const MAX_SHARED_HANDLERS: usize = 4;
+
+const VECTOR_COUNT: usize = (LAST_VECTOR - FIRST_VECTOR) as usize + 1;
+
+/// State of an allocated vector.
+#[derive(Clone, Copy)]
+struct VectorEntry {
+    trigger_mode: TriggerMode,
+    handlers: [Option<InterruptHandler>; MAX_SHARED_HANDLERS],
+    count: u64,
+    unhandled: u64,
+}
+
+/// The allocatable vectors, `None` if the vector is free.
+static VECTORS: Spinlock<[Option<VectorEntry>; VECTOR_COUNT]> = Spinlock::new([None; VECTOR_COUNT]);
+
+/// Signals the end of an interrupt to the interrupt controller.
+static END_OF_INTERRUPT: SyncOnceCell<fn()> = SyncOnceCell::new();
+
+/// Sets the function signaling the end of an interrupt to the interrupt controller,
+/// called after the handlers of a vector ran. Can only be set once.
+pub fn set_end_of_interrupt(end_of_interrupt: fn()) {
+    if END_OF_INTERRUPT.set(end_of_interrupt).is_err() {
+        warn!("End of interrupt already set");
+    }
+}
+
+/// Allocates a free interrupt vector with the given trigger mode.
+#[export_name = "__internal_allocate_vector"]
+pub fn allocate_vector(trigger_mode: TriggerMode) -> Result<Vector, VectorError> {
+    let _guard = interrupts::disable();
+    let mut vectors = VECTORS.lock();
+
+    let index = vectors
+        .iter()
+        .position(Option::is_none)
+        .ok_or(VectorError::Exhausted)?;
+    vectors[index] = Some(VectorEntry {
+        trigger_mode,
+        handlers: [None; MAX_SHARED_HANDLERS],
+        count: 0,
+        unhandled: 0,
+    });
+    Ok(FIRST_VECTOR + index as Vector)
+}
+
+/// Frees an allocated vector without handlers.
+#[export_name = "__internal_free_vector"]
+pub fn free_vector(vector: Vector) -> Result<(), VectorError> {
+    let _guard = interrupts::disable();
+    let mut vectors = VECTORS.lock();
+
+    let slot = slot(&mut vectors, vector)?;
+    let entry = slot.as_ref().ok_or(VectorError::NotAllocated)?;
+    if entry.handlers.iter().any(Option::is_some) {
+        return Err(VectorError::InUse);
+    }
+
+    *slot = None;
+    Ok(())
+}
+
+/// Registers a handler for an allocated vector.
+#[export_name = "__internal_register_handler"]
+pub fn register_handler(vector: Vector, handler: InterruptHandler) -> Result<(), VectorError> {
+    let _guard = interrupts::disable();
+    let mut vectors = VECTORS.lock();
+
+    let entry = entry(&mut vectors, vector)?;
+    let registered = entry.handlers.iter().flatten().count();
+    if entry.trigger_mode == TriggerMode::Edge && registered > 0 {
+        return Err(VectorError::AlreadyRegistered);
+    }
+
+    let free = entry
+        .handlers
+        .iter_mut()
+        .find(|x| x.is_none())
+        .ok_or(VectorError::TooManyHandlers)?;
+    *free = Some(handler);
+    Ok(())
+}
+
+/// Unregisters a handler of a vector.
+#[export_name = "__internal_unregister_handler"]
+pub fn unregister_handler(vector: Vector, handler: InterruptHandler) -> Result<(), VectorError> {
+    let _guard = interrupts::disable();
+    let mut vectors = VECTORS.lock();
+
+    let entry = entry(&mut vectors, vector)?;
+    let registered = entry
+        .handlers
+        .iter_mut()
+        .find(|x| x.is_some_and(|x| fn_addr_eq(x, handler)))
+        .ok_or(VectorError::HandlerNotFound)?;
+    *registered = None;
+    Ok(())
+}
+
+/// Returns the counters of an allocated vector.
+#[export_name = "__internal_vector_statistics"]
+pub fn vector_statistics(vector: Vector) -> Option<VectorStatistics> {
+    let _guard = interrupts::disable();
+    let mut vectors = VECTORS.lock();
+
+    entry(&mut vectors, vector).ok().map(|x| VectorStatistics {
+        trigger_mode: x.trigger_mode,
+        handlers: x.handlers.iter().flatten().count(),
+        count: x.count,
+        unhandled: x.unhandled,
+    })
+}
+
+/// Logs the counters of all allocated vectors.
+pub fn log_statistics() {
+    for vector in FIRST_VECTOR..=LAST_VECTOR {
+        if let Some(statistics) = vector_statistics(vector) {
+            info!(
+                "Vector {}: {:?}-triggered, {} handlers, {} interrupts, {} unhandled",
+                vector,
+                statistics.trigger_mode,
+                statistics.handlers,
+                statistics.count,
+                statistics.unhandled
+            );
+        }
+    }
+}
+
+/// Calls all handlers of the vector and signals the end of the interrupt. Runs with interrupts disabled.
+pub fn dispatch(vector: Vector) {
+    // Spurious interrupts must not be acknowledged.
+    if vector == SPURIOUS_VECTOR {
+        return;
+    }
+
+    call_handlers(vector);
+
+    if let Some(end_of_interrupt) = END_OF_INTERRUPT.get() {
+        end_of_interrupt();
+    }
+}
+
+/// Calls all handlers of the vector and updates its counters.
+fn call_handlers(vector: Vector) {
+    // Copy the handlers, so they can (un)register handlers themselves.
+    let handlers = {
+        let mut vectors = VECTORS.lock();
+        match entry(&mut vectors, vector) {
+            Ok(entry) => {
+                entry.count += 1;
+                entry.handlers
+            }
+            Err(_) => {
+                drop(vectors);
+                warn!("Interrupt on unallocated vector {}", vector);
+                return;
+            }
+        }
+    };
+
+    // Every handler of a shared line is called, since multiple devices may assert it at the same time.
+    let mut acknowledged = false;
+    for handler in handlers.iter().flatten() {
+        acknowledged |= handler(vector);
+    }
+
+    if !acknowledged {
+        if let Ok(entry) = entry(&mut VECTORS.lock(), vector) {
+            entry.unhandled += 1;
+        }
+    }
+}
+
+/// Returns the slot of an allocatable vector.
+fn slot(
+    vectors: &mut [Option<VectorEntry>; VECTOR_COUNT],
+    vector: Vector,
+) -> Result<&mut Option<VectorEntry>, VectorError> {
+    if !(FIRST_VECTOR..=LAST_VECTOR).contains(&vector) {
+        return Err(VectorError::NotAllocated);
+    }
+
+    Ok(&mut vectors[(vector - FIRST_VECTOR) as usize])
+}
+
+/// Returns the entry of an allocated vector.
+fn entry(
+    vectors: &mut [Option<VectorEntry>; VECTOR_COUNT],
+    vector: Vector,
+) -> Result<&mut VectorEntry, VectorError> {
+    slot(vectors, vector)?
+        .as_mut()
+        .ok_or(VectorError::NotAllocated)
+}
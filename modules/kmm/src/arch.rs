@@ -0,0 +1,39 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! Architecture specific features of the MMU.
+use common::paging::X86_64;
+use core::arch::asm;
+use core::arch::x86_64::__cpuid;
+
+/// The Extended Feature Enable Register.
+const EFER: u32 = 0xC000_0080;
+
+/// Bit in [`EFER`] enabling the no-execute bit in page table entries.
+const EFER_NO_EXECUTE_ENABLE: u64 = 1 << 11;
+
+/// Returns whenever level 3 page table entries can map 1 GiB pages.
+pub fn supports_1gib_pages() -> bool {
+    __cpuid(0x8000_0000).eax >= 0x8000_0001 && (__cpuid(0x8000_0001).edx & (1 << 26)) != 0
+}
+
+/// Enables the no-execute bit in page table entries, if supported.
+/// Returns whenever the bit can be used.
+pub fn enable_no_execute() -> bool {
+    if __cpuid(0x8000_0000).eax < 0x8000_0001 || (__cpuid(0x8000_0001).edx & (1 << 20)) == 0 {
+        return false;
+    }
+
+    let (low, high): (u32, u32);
+    unsafe {
+        asm!("RDMSR", in("ecx") EFER, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+    }
+
+    let value = (((high as u64) << 32) | low as u64) | EFER_NO_EXECUTE_ENABLE;
+    unsafe {
+        asm!("WRMSR", in("ecx") EFER, in("eax") value as u32, in("edx") (value >> 32) as u32, options(nostack, preserves_flags));
+        X86_64::set_no_execute_enabled();
+    }
+
+    true
+}
@@ -0,0 +1,256 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Kernel Memory Manager (KMM) Module
+//!
+//! The KMM owns the page tables of the kernel areas described in [`common::memory`].
+//! On init it builds a new set of page tables:
+//!
+//! - The direct mapping area maps all physical memory, using 1 GiB pages if available or 2 MiB pages otherwise.
+//! - The kernel load area is taken over from the bootloader's page tables.
+//! - Everything else the bootloader mapped is kept until the bootloader's memory is reclaimed.
+//!
+//! Both kernel areas are mapped by a single level 3 page table each,
+//! which are shared with every address space, so the kernel is mapped everywhere the same.
+//! The application processors switch to the kernel's page tables in their per-CPU constructor.
+//!
+#![no_std]
+
+mod arch;
+
+pub use common::paging::MapError;
+
+use common::addr::{PhysAddr, VirtAddr};
+use common::interrupts;
+use common::memory::{
+    get_memory_info, DIRECT_MAPPING_LEVEL_3_PAGE_TABLE, DIRECT_MAPPING_SIZE, DIRECT_MAPPING_START,
+    KERNEL_DYNAMIC_END, KERNEL_DYNAMIC_START, KERNEL_LEVEL_3_PAGE_TABLE, KERNEL_LOAD_START,
+};
+use common::paging::{
+    self, Mapper, Page, PageSize, PageTable, PageTableFlags, PhysFrame, Size1GiB, Size2MiB,
+    ENTRY_COUNT,
+};
+use common::sync::{Spinlock, SyncOnceCell};
+use log::{debug, info, warn};
+use microdragon_interface::macros::init;
+use microdragon_interface::smp::CpuInfo;
+use microdragon_interface::ModuleInterface;
+use pmm::{GlobalFrameAllocator, MemoryRegions};
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+/// Physical address of the kernel's top-level page table.
+pub static KERNEL_PAGE_TABLE: SyncOnceCell<PhysAddr> = SyncOnceCell::new();
+
+/// Lock taken while modifying the page tables of the kernel areas.
+static KERNEL_PAGE_TABLE_LOCK: Spinlock<()> = Spinlock::new(());
+
+/// Entrypoint to the KMM module.
+#[init]
+pub fn init(interface: &ModuleInterface) {
+    if KERNEL_PAGE_TABLE.is_initialized() {
+        warn!("KMM Kernel Module already initialized");
+        return;
+    }
+
+    arch::enable_no_execute();
+
+    // Map all physical memory in the memory map, but at least the first 4 GiB,
+    // so memory mapped devices below 4 GiB are always reachable.
+    // Safety: We assume the memory map given by the interface is valid.
+    let regions = unsafe { MemoryRegions::new(&interface.memory_map_info) };
+    let end = regions
+        .map(|x| x.end)
+        .max()
+        .unwrap_or_default()
+        .max(4 * GIB);
+    let end = end.div_ceil(GIB).min(DIRECT_MAPPING_SIZE / GIB) * GIB;
+
+    // Until we switch page tables, all physical memory is mapped at the offset given by the bootloader.
+    let offset = interface.memory_map_info.physical_memory_offset;
+    let bootloader = paging::active_page_table();
+
+    // Safety: The bootloader page table is the active one and valid.
+    let Some((root, direct, kernel)) = (unsafe { build_page_tables(offset, bootloader, end) })
+    else {
+        warn!("Not enough physical memory for the kernel page tables");
+        return;
+    };
+
+    // Safety: The new page tables map the kernel load area and everything else the bootloader mapped.
+    unsafe { paging::set_active_page_table(root) };
+
+    let _ = KERNEL_PAGE_TABLE.set(root);
+    let _ = DIRECT_MAPPING_LEVEL_3_PAGE_TABLE.set(direct);
+    let _ = KERNEL_LEVEL_3_PAGE_TABLE.set(kernel);
+    #[cfg(debug_assertions)]
+    common::memory::set_initialized();
+
+    info!("Kernel page tables active, {} GiB direct mapped", end / GIB);
+}
+
+/// Per-CPU entrypoint to the KMM module.
+#[init]
+pub fn init_cpu(_: &ModuleInterface, _: &CpuInfo) {
+    let Some(&root) = KERNEL_PAGE_TABLE.get() else {
+        warn!("KMM Kernel Module not initialized on the bootstrap processor");
+        return;
+    };
+
+    arch::enable_no_execute();
+
+    // Safety: The kernel page tables map the kernel load area and everything else the bootloader mapped.
+    unsafe { paging::set_active_page_table(root) };
+}
+
+/// Allocates an empty page table, accessed through a mapping of all physical memory at `offset`.
+///
+/// ## Safety
+///
+/// All physical memory has to be mapped at `offset`.
+unsafe fn create_table(offset: u64) -> Option<PhysAddr> {
+    let address = pmm::allocate_frame()?;
+    PageTable::<paging::Native>::from_physical(address, offset).zero();
+    Some(address)
+}
+
+/// Builds the kernel page tables, returning the top-level, direct mapping and kernel area tables.
+///
+/// ## Safety
+///
+/// `bootloader` has to be the active top-level page table and all physical memory has to be mapped at `offset`.
+unsafe fn build_page_tables(
+    offset: u64,
+    bootloader: PhysAddr,
+    end: u64,
+) -> Option<(PhysAddr, PhysAddr, PhysAddr)> {
+    let levels = get_memory_info().highest_page_table_level;
+    let flags = PageTableFlags::WRITABLE | PageTableFlags::GLOBAL;
+
+    // Direct mapping area.
+    let direct = create_table(offset)?;
+    let mut mapper = Mapper::<paging::Native>::new(direct, 3, offset);
+    if arch::supports_1gib_pages() {
+        map_direct::<Size1GiB>(&mut mapper, end, flags)?;
+    } else {
+        map_direct::<Size2MiB>(&mut mapper, end, flags)?;
+    }
+    debug!("Direct mapping level 3 page table at {:#x}", direct);
+
+    // Kernel areas, taking over the bootloader's mapping of the kernel load area.
+    let kernel = create_table(offset)?;
+    let mut level_3 = bootloader;
+    for level in (4..=levels).rev() {
+        level_3 = PageTable::<paging::Native>::from_physical(level_3, offset)
+            [KERNEL_LOAD_START.page_table_index(level)]
+        .address();
+    }
+    let bootloader_level_3 = PageTable::<paging::Native>::from_physical(level_3, offset);
+    let kernel_level_3 = PageTable::<paging::Native>::from_physical(kernel, offset);
+    for index in KERNEL_LOAD_START.p3_index()..ENTRY_COUNT {
+        kernel_level_3[index] = bootloader_level_3[index];
+    }
+    debug!("Kernel level 3 page table at {:#x}", kernel);
+
+    // The top-level table, keeping the bootloader's other mappings.
+    let root = create_table(offset)?;
+    *PageTable::<paging::Native>::from_physical(root, offset) =
+        PageTable::from_physical(bootloader, offset).clone();
+
+    // With 5 levels, both kernel areas are inside the last level 4 table, which needs to be copied too.
+    let level_4 = if levels == 5 {
+        let level_4 = create_table(offset)?;
+        let entry = &mut PageTable::<paging::Native>::from_physical(root, offset)
+            [DIRECT_MAPPING_START.p5_index()];
+        if entry.is_table(5) {
+            *PageTable::<paging::Native>::from_physical(level_4, offset) =
+                PageTable::from_physical(entry.address(), offset).clone();
+        }
+        entry.set_table(level_4, PageTableFlags::empty());
+        level_4
+    } else {
+        root
+    };
+
+    let level_4 = PageTable::<paging::Native>::from_physical(level_4, offset);
+    let entry = &mut level_4[DIRECT_MAPPING_START.p4_index()];
+    if !entry.is_unused() {
+        warn!("Bootloader mapping inside the direct mapping area is dropped");
+    }
+    entry.set_table(direct, PageTableFlags::empty());
+    level_4[KERNEL_DYNAMIC_START.p4_index()].set_table(kernel, PageTableFlags::empty());
+
+    Some((root, direct, kernel))
+}
+
+/// Maps all physical memory below `end` into the direct mapping area, using pages of the size `S`.
+fn map_direct<S: PageSize>(mapper: &mut Mapper, end: u64, flags: PageTableFlags) -> Option<()> {
+    let frames = PhysFrame::<S>::range(
+        PhysFrame::containing_address(PhysAddr::new(0)),
+        PhysFrame::containing_address(PhysAddr::new(end + S::SIZE - 1)),
+    );
+    for frame in frames {
+        let page = Page::containing_address(DIRECT_MAPPING_START + frame.start_address().as_u64());
+        mapper
+            .map(page, frame, flags, &mut GlobalFrameAllocator)
+            .ok()?;
+    }
+    Some(())
+}
+
+/// Returns a mapper for the kernel areas, accessing the page tables through the direct mapping area.
+///
+/// ## Safety
+///
+/// The kernel page tables must not be modified by anything else while the mapper exists.
+unsafe fn kernel_mapper() -> Option<Mapper> {
+    let kernel = *KERNEL_LEVEL_3_PAGE_TABLE.get()?;
+    Some(Mapper::new(kernel, 3, DIRECT_MAPPING_START.as_u64()))
+}
+
+/// Maps a 4 KiB page inside the kernel dynamic heap area to the given frame.
+/// The mapping is never executable.
+pub fn map(page: Page, frame: PhysFrame, writable: bool) -> Result<(), MapError> {
+    assert_dynamic_page(page);
+
+    let _guard = interrupts::disable();
+    let _lock = KERNEL_PAGE_TABLE_LOCK.lock();
+
+    // Safety: The kernel page tables are only modified while holding the lock.
+    let mut mapper = unsafe { kernel_mapper() }.expect("KMM not initialized");
+    let mut flags = PageTableFlags::GLOBAL;
+    if writable {
+        flags |= PageTableFlags::WRITABLE;
+    }
+    mapper.map(page, frame, flags, &mut GlobalFrameAllocator)
+}
+
+/// Unmaps a 4 KiB page inside the kernel dynamic heap area.
+/// Returns the frame the page was mapped to or `None` if it wasn't mapped.
+pub fn unmap(page: Page) -> Option<PhysFrame> {
+    assert_dynamic_page(page);
+
+    let _guard = interrupts::disable();
+    let _lock = KERNEL_PAGE_TABLE_LOCK.lock();
+
+    // Safety: The kernel page tables are only modified while holding the lock.
+    let frame = unsafe { kernel_mapper() }?.unmap(page).ok()?;
+    paging::flush(page.start_address());
+    Some(frame)
+}
+
+/// Panics if the page isn't inside the kernel dynamic heap area.
+fn assert_dynamic_page(page: Page) {
+    assert!(
+        (KERNEL_DYNAMIC_START..KERNEL_DYNAMIC_END).contains(&page.start_address()),
+        "{:?} is not a page inside the kernel dynamic heap area",
+        page
+    );
+}
+
+/// Converts a virtual address inside the kernel dynamic heap or kernel load area to a physical address, by walking the page tables.
+#[export_name = "__internal_virtual_to_physical_kernel"]
+pub fn virtual_to_physical_kernel(virt: VirtAddr) -> Option<PhysAddr> {
+    // Safety: The mapper is only used to read the page tables.
+    unsafe { kernel_mapper() }?.translate(virt)
+}
@@ -0,0 +1,615 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! # Log Buffer
+//!
+//! Every record is kept in a fixed-size ring buffer, overwriting the oldest entries once it's full.
+//! Outputs attached after a record was logged replay it from the buffer,
+//! and readers like a userspace `dmesg` service drain it using a cursor.
+
+use core::fmt::{self, Write};
+use log::kv::{self, Key, Value, VisitSource};
+use log::Level;
+
+/// The maximal length of the message of an entry, longer messages are truncated.
+pub const MAX_MESSAGE_LENGTH: usize = 512;
+
+/// The maximal length of the source file of an entry, longer paths are truncated.
+pub const MAX_FILE_LENGTH: usize = 128;
+
+/// The maximal length of the encoded key-value pairs of an entry, pairs not fitting are dropped.
+pub const MAX_FIELDS_LENGTH: usize = 256;
+
+/// Length of the encoded fixed-size fields of an entry.
+const HEADER_LENGTH: usize = 23;
+
+/// The maximal length of an encoded entry, which is the minimal size of a [`LogBuffer`].
+pub const MAX_ENTRY_LENGTH: usize =
+    HEADER_LENGTH + MAX_FILE_LENGTH + MAX_MESSAGE_LENGTH + MAX_FIELDS_LENGTH;
+
+/// Separates the key from the value in encoded [`Fields`].
+const KEY_SEPARATOR: char = '\x1F';
+
+/// Terminates a key-value pair in encoded [`Fields`].
+const PAIR_SEPARATOR: char = '\x1E';
+
+/// Encoded timestamp of entries logged before a clock source was available.
+const NO_TIMESTAMP: u64 = u64::MAX;
+
+/// A record in the [`LogBuffer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogEntry<'a> {
+    /// The number of entries logged before this one.
+    pub sequence: u64,
+
+    /// The nanoseconds of the monotonic clock, `None` if logged before the first clock source was registered.
+    pub timestamp: Option<u64>,
+
+    /// The id of the CPU logging the entry.
+    pub cpu: u32,
+
+    pub level: Level,
+
+    /// The source file or module path logging the entry.
+    pub file: &'a str,
+
+    pub line: u32,
+
+    pub message: &'a str,
+
+    /// The key-value pairs of the record.
+    pub fields: Fields<'a>,
+}
+
+/// The key-value pairs of a [`LogEntry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Fields<'a>(&'a str);
+
+impl<'a> Fields<'a> {
+    /// Iterates over the keys and values, in the order they were logged.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a str)> {
+        // A pair cut off by truncation has no separator at its end.
+        self.0
+            .split_inclusive(PAIR_SEPARATOR)
+            .filter_map(|x| x.strip_suffix(PAIR_SEPARATOR)?.split_once(KEY_SEPARATOR))
+    }
+}
+
+/// A ring buffer of `N` bytes for [`LogEntry`]s.
+pub struct LogBuffer<const N: usize> {
+    bytes: [u8; N],
+
+    /// Offset of the oldest entry, counting all bytes ever written.
+    start: u64,
+
+    /// Offset after the newest entry, counting all bytes ever written.
+    end: u64,
+
+    /// Sequence number of the oldest entry.
+    first: u64,
+
+    /// Sequence number of the next entry.
+    next: u64,
+}
+
+impl<const N: usize> LogBuffer<N> {
+    /// Creates an empty buffer, which has to hold at least one entry of maximal length.
+    pub const fn new() -> Self {
+        assert!(N >= MAX_ENTRY_LENGTH, "Log buffer too small");
+
+        LogBuffer {
+            bytes: [0; N],
+            start: 0,
+            end: 0,
+            first: 0,
+            next: 0,
+        }
+    }
+
+    /// Appends the entry, overwriting the oldest entries if the buffer is full.
+    /// The sequence number of the entry is ignored, the entry's actual sequence number is returned.
+    pub fn push(&mut self, entry: &LogEntry) -> u64 {
+        let file = truncate(entry.file, MAX_FILE_LENGTH);
+        let message = truncate(entry.message, MAX_MESSAGE_LENGTH);
+        let fields = truncate(entry.fields.0, MAX_FIELDS_LENGTH);
+        let length = (HEADER_LENGTH + file.len() + message.len() + fields.len()) as u64;
+
+        while self.end + length - self.start > N as u64 {
+            let lengths = lengths(&self.read_header(self.start));
+            self.start += (HEADER_LENGTH + lengths.iter().sum::<usize>()) as u64;
+            self.first += 1;
+        }
+
+        let mut header = [0; HEADER_LENGTH];
+        header[0..2].copy_from_slice(&(file.len() as u16).to_le_bytes());
+        header[2..4].copy_from_slice(&(message.len() as u16).to_le_bytes());
+        header[4..6].copy_from_slice(&(fields.len() as u16).to_le_bytes());
+        header[6] = entry.level as u8;
+        header[7..11].copy_from_slice(&entry.cpu.to_le_bytes());
+        header[11..15].copy_from_slice(&entry.line.to_le_bytes());
+        header[15..23].copy_from_slice(&entry.timestamp.unwrap_or(NO_TIMESTAMP).to_le_bytes());
+
+        let mut offset = self.end;
+        for part in [
+            &header[..],
+            file.as_bytes(),
+            message.as_bytes(),
+            fields.as_bytes(),
+        ] {
+            self.copy_in(offset, part);
+            offset += part.len() as u64;
+        }
+        self.end = offset;
+
+        self.next += 1;
+        self.next - 1
+    }
+
+    /// Calls `f` with every entry starting at the sequence number `cursor`, oldest first,
+    /// and moves the cursor after the last entry.
+    /// Returns the number of entries overwritten before they could be read.
+    pub fn read(&self, cursor: &mut u64, mut f: impl FnMut(&LogEntry)) -> u64 {
+        let lost = self.first.saturating_sub(*cursor);
+        let from = *cursor;
+
+        for (sequence, offset, header) in self.headers().filter(|x| x.0 >= from) {
+            f(&self.copy_at(sequence, offset, header).entry());
+            *cursor = sequence + 1;
+        }
+
+        lost
+    }
+
+    /// Copies the entry at the cursor out of the buffer and moves the cursor after it.
+    /// If the entry was overwritten already, the oldest entry is copied instead.
+    /// Returns `None` if there is no entry at or after the cursor.
+    pub fn copy(&self, cursor: &mut CopyCursor) -> Option<EntryCopy> {
+        if cursor.sequence < self.first {
+            *cursor = CopyCursor {
+                sequence: self.first,
+                offset: self.start,
+            };
+        }
+        if cursor.sequence >= self.next {
+            return None;
+        }
+
+        let header = self.read_header(cursor.offset);
+        let copy = self.copy_at(cursor.sequence, cursor.offset, header);
+        cursor.sequence += 1;
+        cursor.offset += (HEADER_LENGTH + lengths(&header).iter().sum::<usize>()) as u64;
+        Some(copy)
+    }
+
+    /// Iterates over the sequence numbers, offsets and headers of the entries, oldest first.
+    fn headers(&self) -> impl Iterator<Item = (u64, u64, [u8; HEADER_LENGTH])> + '_ {
+        let mut offset = self.start;
+        (self.first..self.next).map(move |sequence| {
+            let header = self.read_header(offset);
+            let entry = (sequence, offset, header);
+            offset += (HEADER_LENGTH + lengths(&header).iter().sum::<usize>()) as u64;
+            entry
+        })
+    }
+
+    fn copy_at(&self, sequence: u64, offset: u64, header: [u8; HEADER_LENGTH]) -> EntryCopy {
+        let mut copy = EntryCopy {
+            sequence,
+            header,
+            data: [0; MAX_ENTRY_LENGTH - HEADER_LENGTH],
+        };
+        let length = lengths(&header).iter().sum::<usize>();
+        self.copy_out(offset + HEADER_LENGTH as u64, &mut copy.data[..length]);
+        copy
+    }
+
+    fn read_header(&self, offset: u64) -> [u8; HEADER_LENGTH] {
+        let mut header = [0; HEADER_LENGTH];
+        self.copy_out(offset, &mut header);
+        header
+    }
+
+    /// Copies `bytes` into the buffer at `offset`, wrapping around at the end.
+    fn copy_in(&mut self, offset: u64, bytes: &[u8]) {
+        let index = (offset % N as u64) as usize;
+        let (first, second) = bytes.split_at(bytes.len().min(N - index));
+        self.bytes[index..index + first.len()].copy_from_slice(first);
+        self.bytes[..second.len()].copy_from_slice(second);
+    }
+
+    /// Copies the bytes of the buffer at `offset` into `bytes`, wrapping around at the end.
+    fn copy_out(&self, offset: u64, bytes: &mut [u8]) {
+        let index = (offset % N as u64) as usize;
+        let split = bytes.len().min(N - index);
+        let (first, second) = bytes.split_at_mut(split);
+        first.copy_from_slice(&self.bytes[index..index + split]);
+        second.copy_from_slice(&self.bytes[..second.len()]);
+    }
+}
+
+/// The position of the next entry copied by [`LogBuffer::copy`].
+/// It keeps the offset of the entry, so it's found without walking the buffer as long as it isn't overwritten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CopyCursor {
+    sequence: u64,
+    offset: u64,
+}
+
+impl CopyCursor {
+    /// Creates a cursor at the first entry ever logged, or the oldest entry still in the buffer.
+    pub const fn new() -> Self {
+        CopyCursor {
+            sequence: 0,
+            offset: 0,
+        }
+    }
+}
+
+/// An entry copied out of the [`LogBuffer`], for using it after the buffer is unlocked.
+pub struct EntryCopy {
+    sequence: u64,
+    header: [u8; HEADER_LENGTH],
+    data: [u8; MAX_ENTRY_LENGTH - HEADER_LENGTH],
+}
+
+impl EntryCopy {
+    pub fn entry(&self) -> LogEntry<'_> {
+        let header = &self.header;
+        let [file_length, message_length, fields_length] = lengths(header);
+        let (file, data) = self.data.split_at(file_length);
+        let (message, data) = data.split_at(message_length);
+        let fields = &data[..fields_length];
+
+        let timestamp = u64::from_le_bytes(header[15..23].try_into().unwrap());
+        LogEntry {
+            sequence: self.sequence,
+            timestamp: (timestamp != NO_TIMESTAMP).then_some(timestamp),
+            cpu: u32::from_le_bytes(header[7..11].try_into().unwrap()),
+            level: level(header[6]),
+            file: core::str::from_utf8(file).unwrap_or_default(),
+            line: u32::from_le_bytes(header[11..15].try_into().unwrap()),
+            message: core::str::from_utf8(message).unwrap_or_default(),
+            fields: Fields(core::str::from_utf8(fields).unwrap_or_default()),
+        }
+    }
+}
+
+/// A [`fmt::Write`] into a fixed-size buffer, silently truncating what doesn't fit.
+/// Nothing is written after the first truncation.
+pub struct MessageWriter<const N: usize> {
+    bytes: [u8; N],
+    length: usize,
+    truncated: bool,
+}
+
+impl<const N: usize> MessageWriter<N> {
+    pub const fn new() -> Self {
+        MessageWriter {
+            bytes: [0; N],
+            length: 0,
+            truncated: false,
+        }
+    }
+
+    /// Returns the text written so far.
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.length]).unwrap_or_default()
+    }
+}
+
+impl<const N: usize> fmt::Write for MessageWriter<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.truncated {
+            return Ok(());
+        }
+
+        let part = truncate(s, N - self.length);
+        self.bytes[self.length..self.length + part.len()].copy_from_slice(part.as_bytes());
+        self.length += part.len();
+        self.truncated = part.len() < s.len();
+        Ok(())
+    }
+}
+
+/// Encodes the key-value pairs of a record into a fixed-size buffer, dropping the pairs not fitting.
+pub struct FieldsWriter<const N: usize> {
+    writer: MessageWriter<N>,
+}
+
+impl<const N: usize> FieldsWriter<N> {
+    pub const fn new() -> Self {
+        FieldsWriter {
+            writer: MessageWriter::new(),
+        }
+    }
+
+    /// Returns the pairs written so far.
+    pub fn fields(&self) -> Fields<'_> {
+        Fields(self.writer.as_str())
+    }
+
+    /// Writes the text, replacing the separators of the encoding.
+    fn write_escaped(&mut self, text: impl fmt::Display) -> fmt::Result {
+        struct Escape<'a, const N: usize>(&'a mut MessageWriter<N>);
+
+        impl<const N: usize> fmt::Write for Escape<'_, N> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                for (i, part) in s.split([KEY_SEPARATOR, PAIR_SEPARATOR]).enumerate() {
+                    if i > 0 {
+                        self.0.write_char('?')?;
+                    }
+                    self.0.write_str(part)?;
+                }
+                Ok(())
+            }
+        }
+
+        write!(Escape(&mut self.writer), "{}", text)
+    }
+}
+
+impl<'kvs, const N: usize> VisitSource<'kvs> for FieldsWriter<N> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.write_escaped(key)?;
+        self.writer.write_char(KEY_SEPARATOR)?;
+        self.write_escaped(value)?;
+        self.writer.write_char(PAIR_SEPARATOR)?;
+        Ok(())
+    }
+}
+
+/// Truncates `s` to at most `length` bytes without splitting a character.
+fn truncate(s: &str, length: usize) -> &str {
+    if s.len() <= length {
+        return s;
+    }
+
+    let end = (0..=length)
+        .rev()
+        .find(|x| s.is_char_boundary(*x))
+        .unwrap_or(0);
+    &s[..end]
+}
+
+/// Returns the lengths of the file, the message and the fields of an entry.
+fn lengths(header: &[u8; HEADER_LENGTH]) -> [usize; 3] {
+    [0, 2, 4].map(|x| u16::from_le_bytes([header[x], header[x + 1]]) as usize)
+}
+
+fn level(value: u8) -> Level {
+    match value {
+        1 => Level::Error,
+        2 => Level::Warn,
+        3 => Level::Info,
+        4 => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        CopyCursor, Fields, FieldsWriter, LogBuffer, LogEntry, MessageWriter, HEADER_LENGTH,
+        MAX_ENTRY_LENGTH, MAX_FIELDS_LENGTH, MAX_FILE_LENGTH, MAX_MESSAGE_LENGTH,
+    };
+    use core::fmt::Write;
+    use log::kv::Source;
+    use log::Level;
+
+    const SIZE: usize = MAX_ENTRY_LENGTH;
+
+    fn entry(message: &str) -> LogEntry<'_> {
+        LogEntry {
+            sequence: 0,
+            timestamp: Some(42),
+            cpu: 1,
+            level: Level::Info,
+            file: "modules/acpi/src/lib.rs",
+            line: 7,
+            message,
+            fields: Fields::default(),
+        }
+    }
+
+    /// Reads the messages after `cursor` and the number of lost entries.
+    fn messages<const N: usize>(buffer: &LogBuffer<N>, cursor: &mut u64) -> (Vec<String>, u64) {
+        let mut messages = Vec::new();
+        let lost = buffer.read(cursor, |x| messages.push(x.message.to_string()));
+        (messages, lost)
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut buffer = Box::new(LogBuffer::<SIZE>::new());
+        assert_eq!(buffer.push(&entry("Logging start")), 0);
+        let mut early = entry("Found ACPI Table");
+        early.timestamp = None;
+        early.level = Level::Trace;
+        assert_eq!(buffer.push(&early), 1);
+
+        let mut entries = Vec::new();
+        let mut cursor = 0;
+        buffer.read(&mut cursor, |x| {
+            entries.push((
+                x.sequence,
+                x.timestamp,
+                x.cpu,
+                x.level,
+                x.file.to_string(),
+                x.line,
+            ))
+        });
+        assert_eq!(cursor, 2);
+        assert_eq!(
+            entries,
+            [
+                (
+                    0,
+                    Some(42),
+                    1,
+                    Level::Info,
+                    "modules/acpi/src/lib.rs".to_string(),
+                    7
+                ),
+                (
+                    1,
+                    None,
+                    1,
+                    Level::Trace,
+                    "modules/acpi/src/lib.rs".to_string(),
+                    7
+                ),
+            ]
+        );
+
+        // Reading again only returns the new entries.
+        buffer.push(&entry("Logging rewired"));
+        assert_eq!(
+            messages(&buffer, &mut cursor),
+            (vec!["Logging rewired".to_string()], 0)
+        );
+    }
+
+    #[test]
+    fn test_overwrite() {
+        let mut buffer = Box::new(LogBuffer::<SIZE>::new());
+        let message = "x".repeat(200);
+        let length = (HEADER_LENGTH + "modules/acpi/src/lib.rs".len() + 200) as u64;
+        for _ in 0..10 {
+            buffer.push(&entry(&message));
+        }
+
+        // Only the entries fitting into the buffer are kept, wrapping around its end.
+        let kept = SIZE as u64 / length;
+        let mut cursor = 0;
+        let (messages, lost) = messages(&buffer, &mut cursor);
+        assert_eq!(lost, 10 - kept);
+        assert_eq!(messages.len() as u64, kept);
+        assert!(messages.iter().all(|x| *x == message));
+        assert_eq!(cursor, 10);
+    }
+
+    #[test]
+    fn test_maximal_entry() {
+        let mut writer = FieldsWriter::<MAX_FIELDS_LENGTH>::new();
+        for i in 0..MAX_FIELDS_LENGTH {
+            let _ = (i.to_string().as_str(), i).visit(&mut writer);
+        }
+        assert_eq!(writer.fields().0.len(), MAX_FIELDS_LENGTH);
+
+        let file = "f".repeat(MAX_FILE_LENGTH);
+        let message = "x".repeat(MAX_MESSAGE_LENGTH);
+        let mut entry = entry(&message);
+        entry.file = &file;
+        entry.fields = writer.fields();
+
+        // A buffer of the minimal size holds exactly one entry of maximal length.
+        let mut buffer = Box::new(LogBuffer::<MAX_ENTRY_LENGTH>::new());
+        for _ in 0..3 {
+            buffer.push(&entry);
+        }
+
+        let mut entries = Vec::new();
+        let lost = buffer.read(&mut 0, |x| {
+            entries.push((
+                x.sequence,
+                x.file.len(),
+                x.message.len(),
+                x.fields == writer.fields(),
+            ))
+        });
+        assert_eq!(lost, 2);
+        assert_eq!(entries, [(2, MAX_FILE_LENGTH, MAX_MESSAGE_LENGTH, true)]);
+    }
+
+    #[test]
+    fn test_copy() {
+        let mut buffer = Box::new(LogBuffer::<SIZE>::new());
+        let message = "x".repeat(200);
+        for _ in 0..10 {
+            buffer.push(&entry(&message));
+        }
+
+        // Copying skips the overwritten entries and stops after the newest one.
+        let mut cursor = CopyCursor::new();
+        let mut sequences = Vec::new();
+        while let Some(copy) = buffer.copy(&mut cursor) {
+            assert_eq!(copy.entry().message, message);
+            sequences.push(copy.entry().sequence);
+        }
+        assert!(sequences.len() < 10);
+        assert_eq!(
+            sequences,
+            (10 - sequences.len() as u64..10).collect::<Vec<_>>()
+        );
+        assert_eq!(cursor.sequence, 10);
+
+        buffer.push(&entry("Logging rewired"));
+        assert_eq!(
+            buffer.copy(&mut cursor).map(|x| x.entry().sequence),
+            Some(10)
+        );
+        assert!(buffer.copy(&mut cursor).is_none());
+
+        // A cursor behind the buffer resyncs to the oldest entry, even after it wrapped around.
+        let mut cursor = CopyCursor::new();
+        assert!(buffer.copy(&mut cursor).is_some());
+        for _ in 0..10 {
+            buffer.push(&entry(&message));
+        }
+        let mut resynced = Vec::new();
+        while let Some(copy) = buffer.copy(&mut cursor) {
+            assert_eq!(copy.entry().message, message);
+            resynced.push(copy.entry().sequence);
+        }
+        assert_eq!(
+            resynced,
+            sequences.iter().map(|x| x + 11).collect::<Vec<_>>()
+        );
+        assert_eq!(cursor.sequence, 21);
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut buffer = Box::new(LogBuffer::<SIZE>::new());
+        let message = "ä".repeat(MAX_MESSAGE_LENGTH);
+        buffer.push(&entry(&message));
+
+        let (messages, _) = messages(&buffer, &mut 0);
+        assert_eq!(messages[0], "ä".repeat(MAX_MESSAGE_LENGTH / 2));
+
+        // Nothing is written after a truncation.
+        let mut writer = MessageWriter::<5>::new();
+        let _ = write!(writer, "ä@{}{}", 1234, 5);
+        assert_eq!(writer.as_str(), "ä@12");
+    }
+
+    #[test]
+    fn test_fields() {
+        let mut writer = FieldsWriter::<44>::new();
+        let _ = ("table", "HPET").visit(&mut writer);
+        let _ = ("address", 0xFED0_0000u32).visit(&mut writer);
+        // Separators in values are replaced, pairs not fitting are dropped.
+        let _ = ("odd", "a\x1Eb").visit(&mut writer);
+        let _ = ("dropped", "x").visit(&mut writer);
+
+        let mut buffer = Box::new(LogBuffer::<SIZE>::new());
+        let mut entry = entry("Found ACPI Table");
+        entry.fields = writer.fields();
+        buffer.push(&entry);
+
+        let mut fields = Vec::new();
+        buffer.read(&mut 0, |x| {
+            fields.extend(x.fields.iter().map(|(k, v)| (k.to_string(), v.to_string())))
+        });
+        assert_eq!(
+            fields,
+            [
+                ("table".to_string(), "HPET".to_string()),
+                ("address".to_string(), "4275044352".to_string()),
+                ("odd".to_string(), "a?b".to_string()),
+            ]
+        );
+    }
+}
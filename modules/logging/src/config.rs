@@ -0,0 +1,121 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! # Logging Configuration
+//!
+//! The configuration is read from the `Config.toml` at compile time.
+//! Levels are given as `off`, `error`, `warn`, `info`, `debug` or `trace`.
+//! Levels not configured default to `debug` in debug builds and `info` in release builds, so `trace` is opt-in.
+//!
+//! `log.level` is the level for all modules without a filter,
+//! `log.filters` is a comma separated list of per-module levels like `acpi=debug,apic::timer=trace`.
+//! Records passing those are kept in the log buffer of `log.buffer_size` bytes
+//! and written to every output whose own level includes them, set by `serial.level` and `terminal.level`.
+//! The outputs are formatted as set by `serial.format` and `terminal.format`,
+//! being `colored`, `plain` or `json`, see [`crate::format`].
+
+#[cfg(any(all(target_arch = "x86_64", feature = "serial"), feature = "terminal"))]
+use crate::{format_colored, format_json, format_plain, Formatter};
+use log::LevelFilter;
+use microdragon_interface::macros::config;
+
+/// The I/O port of the serial port used, selected by its COM number with `serial.com`.
+#[cfg(all(target_arch = "x86_64", feature = "serial"))]
+pub const SERIAL_PORT: u16 = com_port(config!("serial.com", 1));
+
+/// The baud rate of the serial port.
+#[cfg(all(target_arch = "x86_64", feature = "serial"))]
+pub const SERIAL_BAUD_RATE: u32 = config!("serial.baud_rate", 38400);
+
+/// The most verbose level written to the serial port.
+#[cfg(all(target_arch = "x86_64", feature = "serial"))]
+pub const SERIAL_LEVEL: LevelFilter = parse_level(config!("serial.level", DEFAULT_LEVEL));
+
+/// The format of the serial port.
+#[cfg(all(target_arch = "x86_64", feature = "serial"))]
+pub const SERIAL_FORMAT: Formatter = parse_format(config!("serial.format", "colored"));
+
+/// The most verbose level written to the framebuffer terminal.
+#[cfg(feature = "terminal")]
+pub const TERMINAL_LEVEL: LevelFilter = parse_level(config!("terminal.level", "info"));
+
+/// The format of the framebuffer terminal.
+#[cfg(feature = "terminal")]
+pub const TERMINAL_FORMAT: Formatter = parse_format(config!("terminal.format", "colored"));
+
+/// The most verbose level of modules without a filter.
+pub const LEVEL: LevelFilter = parse_level(config!("log.level", DEFAULT_LEVEL));
+
+/// The level of everything not configured.
+#[cfg(debug_assertions)]
+const DEFAULT_LEVEL: &str = "debug";
+#[cfg(not(debug_assertions))]
+const DEFAULT_LEVEL: &str = "info";
+
+/// The per-module levels.
+const FILTERS: &str = config!("log.filters", "");
+
+/// The size of the log buffer in bytes, at least [`crate::buffer::MAX_ENTRY_LENGTH`].
+pub const BUFFER_SIZE: usize = config!("log.buffer_size", 65536);
+
+/// Returns the level of the module `target`, given by the filter of the longest matching module path.
+pub fn module_level(target: &str) -> LevelFilter {
+    filters()
+        .filter(|(module, _)| {
+            target
+                .strip_prefix(module)
+                .is_some_and(|x| x.is_empty() || x.starts_with("::"))
+        })
+        .max_by_key(|(module, _)| module.len())
+        .map_or(LEVEL, |(_, level)| level)
+}
+
+/// Returns the most verbose level of all modules.
+pub fn max_module_level() -> LevelFilter {
+    filters().map(|(_, level)| level).fold(LEVEL, Ord::max)
+}
+
+/// Iterates over the valid per-module filters.
+fn filters() -> impl Iterator<Item = (&'static str, LevelFilter)> {
+    FILTERS.split(',').filter_map(|x| {
+        let (module, level) = x.split_once('=')?;
+        Some((module.trim(), level.trim().parse().ok()?))
+    })
+}
+
+/// Parses a level at compile time, so invalid levels fail the build.
+const fn parse_level(level: &str) -> LevelFilter {
+    match level.as_bytes() {
+        b"off" => LevelFilter::Off,
+        b"error" => LevelFilter::Error,
+        b"warn" => LevelFilter::Warn,
+        b"info" => LevelFilter::Info,
+        b"debug" => LevelFilter::Debug,
+        b"trace" => LevelFilter::Trace,
+        _ => panic!("Invalid log level, expected off, error, warn, info, debug or trace"),
+    }
+}
+
+/// Parses a format at compile time, so invalid formats fail the build.
+#[cfg(any(all(target_arch = "x86_64", feature = "serial"), feature = "terminal"))]
+const fn parse_format(format: &str) -> Formatter {
+    match format.as_bytes() {
+        b"colored" => format_colored,
+        b"plain" => format_plain,
+        b"json" => format_json,
+        _ => panic!("Invalid log format, expected colored, plain or json"),
+    }
+}
+
+/// Returns the I/O port of the COM port `number`.
+#[cfg(all(target_arch = "x86_64", feature = "serial"))]
+const fn com_port(number: u8) -> u16 {
+    match number {
+        1 => 0x3F8,
+        2 => 0x2F8,
+        3 => 0x3E8,
+        4 => 0x2E8,
+        _ => panic!("Invalid serial port, expected a COM port from 1 to 4"),
+    }
+}
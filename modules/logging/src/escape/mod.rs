@@ -1,49 +1,155 @@
 // This Source Code Form is subject to the terms of the Mozilla Public
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
-mod mode;
+//! # ANSI Escape Sequences for Terminal Output.
+//!
+//! The [`Parser`] splits the output into chars to print, control chars and CSI sequences.
+//! The terminal executes the sequences, the SGR sequences setting the [`Style`] of the following chars.
+mod parser;
+mod sgr;
 
-pub use mode::EscapeMode;
+pub use parser::{Action, Csi, Parser};
 
 use crate::theme;
+use sgr::{Attribute, Color};
 
-pub struct EscapeSequence {
-    fg: (u8, u8, u8),
-    bg: (u8, u8, u8),
-    escape_mode: EscapeMode,
+/// The colors and the style of the printed chars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    fg: Color,
+    bg: Color,
+    bold: bool,
+    faint: bool,
+    inverse: bool,
 }
 
-impl EscapeSequence {
-    pub fn new() -> Self {
-        EscapeSequence {
-            fg: theme::DEFAULT_FG_COLOR,
-            bg: theme::DEFAULT_BG_COLOR,
-            escape_mode: EscapeMode::None,
+impl Style {
+    pub const fn new() -> Self {
+        Style {
+            fg: Color::Default,
+            bg: Color::Default,
+            bold: false,
+            faint: false,
+            inverse: false,
         }
     }
 
+    /// Applies the attributes of a SGR sequence.
+    pub fn apply(&mut self, csi: &Csi) {
+        for attribute in sgr::attributes(csi) {
+            match attribute {
+                Attribute::Reset => *self = Style::new(),
+                Attribute::Bold => self.bold = true,
+                Attribute::Faint => self.faint = true,
+                Attribute::NormalIntensity => {
+                    self.bold = false;
+                    self.faint = false;
+                }
+                Attribute::Inverse => self.inverse = true,
+                Attribute::NotInverse => self.inverse = false,
+                Attribute::Foreground(color) => self.fg = color,
+                Attribute::Background(color) => self.bg = color,
+            }
+        }
+    }
+
+    /// Whether chars are printed with the bold font.
+    pub const fn bold(&self) -> bool {
+        self.bold
+    }
+
+    /// Gets the foreground color, being halfway to the background color if faint.
+    pub const fn foreground(&self) -> (u8, u8, u8) {
+        let (fg, bg) = self.resolve();
+        if self.faint {
+            mix(fg, bg, 128)
+        } else {
+            fg
+        }
+    }
+
+    /// Gets the background color, which is also used for erasing.
+    pub const fn background(&self) -> (u8, u8, u8) {
+        self.resolve().1
+    }
+
     /// Gets a color with the given intensity between the current background and foreground color.
     /// It tries to estimate a gradient between the background and foreground color with `intensity` being the percentage.
     pub const fn apply_intensity(&self, intensity: u8) -> (u8, u8, u8) {
-        let inv = 255 - intensity;
-        let red =
-            ((self.fg.0 as u16 * intensity as u16) / 256) + ((self.bg.0 as u16 * inv as u16) / 256);
-        let green =
-            ((self.fg.1 as u16 * intensity as u16) / 256) + ((self.bg.1 as u16 * inv as u16) / 256);
-        let blue =
-            ((self.fg.2 as u16 * intensity as u16) / 256) + ((self.bg.2 as u16 * inv as u16) / 256);
+        mix(self.foreground(), self.background(), intensity)
+    }
+
+    /// Gets the foreground and background color, swapped if inverse.
+    const fn resolve(&self) -> ((u8, u8, u8), (u8, u8, u8)) {
+        let fg = match self.fg {
+            Color::Default => theme::DEFAULT_FG_COLOR,
+            color => rgb(color),
+        };
+        let bg = match self.bg {
+            Color::Default => theme::DEFAULT_BG_COLOR,
+            color => rgb(color),
+        };
 
-        (red as u8, green as u8, blue as u8)
+        if self.inverse {
+            (bg, fg)
+        } else {
+            (fg, bg)
+        }
     }
+}
+
+/// Gets the red, green and blue values of a color, which isn't [`Color::Default`].
+const fn rgb(color: Color) -> (u8, u8, u8) {
+    match color {
+        Color::Indexed(index) => theme::indexed_color(index),
+        Color::Rgb(red, green, blue) => (red, green, blue),
+        Color::Default => theme::DEFAULT_FG_COLOR,
+    }
+}
+
+/// Mixes `fg` with `intensity` and `bg` with the rest.
+const fn mix(fg: (u8, u8, u8), bg: (u8, u8, u8), intensity: u8) -> (u8, u8, u8) {
+    let inv = 255 - intensity;
+    let red = ((fg.0 as u16 * intensity as u16) / 256) + ((bg.0 as u16 * inv as u16) / 256);
+    let green = ((fg.1 as u16 * intensity as u16) / 256) + ((bg.1 as u16 * inv as u16) / 256);
+    let blue = ((fg.2 as u16 * intensity as u16) / 256) + ((bg.2 as u16 * inv as u16) / 256);
+
+    (red as u8, green as u8, blue as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Action, Parser, Style};
+    use crate::theme;
 
-    /// Tries to run the process command on the escape mode, if it isn't [`EscapeMode::None`].
-    pub fn try_process(&mut self, c: char) -> bool {
-        self.escape_mode != EscapeMode::None
-            && self.escape_mode.process(&mut self.fg, &mut self.bg, c)
+    /// Applies the SGR sequences of `input` to `style`.
+    fn apply(style: &mut Style, input: &str) {
+        let mut parser = Parser::new();
+        for c in input.chars() {
+            if let Action::Csi(csi) = parser.advance(c) {
+                style.apply(&csi);
+            }
+        }
     }
 
-    /// Sets the escape mode to [`EscapeMode::ExpectOpenBrace`] to start a new escape sequence.
-    pub fn start(&mut self) {
-        self.escape_mode = EscapeMode::ExpectOpenBrace;
+    #[test]
+    fn test_style() {
+        let mut style = Style::new();
+        apply(&mut style, "\x1B[1;38;2;255;128;0m\x1B[48;5;21m");
+        assert!(style.bold());
+        assert_eq!(style.foreground(), (255, 128, 0));
+        assert_eq!(style.background(), (0, 0, 255));
+
+        apply(&mut style, "\x1B[7m");
+        assert_eq!(style.foreground(), (0, 0, 255));
+        assert_eq!(style.background(), (255, 128, 0));
+
+        apply(&mut style, "\x1B[27;39;22m");
+        assert!(!style.bold());
+        assert_eq!(style.foreground(), theme::DEFAULT_FG_COLOR);
+
+        apply(&mut style, "\x1B[m");
+        assert_eq!(style, Style::new());
+        assert_eq!(style.background(), theme::DEFAULT_BG_COLOR);
     }
 }
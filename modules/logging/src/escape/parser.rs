@@ -0,0 +1,332 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Parsing of escape sequences for Terminal Output.
+//!
+//! The [`Parser`] is a state machine fed one char at a time, following the VT500 parser model of `vt100.net`.
+//! It turns the chars into an [`Action`]: chars to print, control chars to execute and complete
+//! Control Sequence Introducer (CSI) sequences like `\x1B[1;31m`, with their numeric parameters.
+//!
+//! Parameters are separated by `;`, or by `:` for subparameters like in `\x1B[38:2::255:0:0m`.
+//! Missing parameters are 0.
+//! Operating System Commands (OSC) like `\x1B]8;;link\x07` and all other escape sequences are skipped.
+
+/// The maximal number of parameters of a CSI sequence, further parameters are dropped.
+pub const MAX_PARAMS: usize = 16;
+
+const ESCAPE: char = '\x1B';
+const BELL: char = '\x07';
+const CANCEL: char = '\x18';
+const SUBSTITUTE: char = '\x1A';
+
+/// What to do with a char given to the [`Parser`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// The char is part of an escape sequence.
+    None,
+
+    /// Print the char.
+    Print(char),
+
+    /// Execute the control char, like `\n`.
+    Execute(char),
+
+    /// Execute the completed CSI sequence.
+    Csi(Csi),
+}
+
+/// A Control Sequence Introducer (CSI) sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Csi {
+    params: [u16; MAX_PARAMS],
+    len: usize,
+    /// Bit `i` is set if parameter `i` follows a `:`.
+    subparams: u16,
+
+    /// The private marker before the parameters, like `?` of `\x1B[?25l`.
+    pub private: Option<char>,
+
+    /// The intermediate char before the final char, like the space of `\x1B[2 q`.
+    pub intermediate: Option<char>,
+
+    /// The final char identifying the function, like `m` of `\x1B[31m`.
+    pub function: char,
+}
+
+impl Csi {
+    const fn new() -> Self {
+        Csi {
+            params: [0; MAX_PARAMS],
+            len: 0,
+            subparams: 0,
+            private: None,
+            intermediate: None,
+            function: '\0',
+        }
+    }
+
+    /// Returns the parameters, missing parameters being 0.
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.len]
+    }
+
+    /// Returns the parameter at `index`, or `default` if it's missing or 0.
+    pub fn param(&self, index: usize, default: u16) -> u16 {
+        match self.params().get(index) {
+            Some(0) | None => default,
+            Some(x) => *x,
+        }
+    }
+
+    /// Whether the parameter at `index` is a subparameter, following a `:`.
+    pub fn is_subparam(&self, index: usize) -> bool {
+        index < self.len && self.subparams & (1 << index) != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    CsiEntry,
+    CsiParam,
+    CsiIntermediate,
+    /// Skips an invalid CSI sequence until its final char.
+    CsiIgnore,
+    /// Skips an OSC string until it's terminated by a bell or an escape.
+    OscString,
+}
+
+/// State machine turning chars into [`Action`]s.
+pub struct Parser {
+    state: State,
+    csi: Csi,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Parser {
+            state: State::Ground,
+            csi: Csi::new(),
+        }
+    }
+
+    /// Advances the state machine by one char.
+    pub fn advance(&mut self, c: char) -> Action {
+        // These are handled the same in every state.
+        match c {
+            CANCEL | SUBSTITUTE => {
+                self.state = State::Ground;
+                return Action::None;
+            }
+            ESCAPE => {
+                self.state = State::Escape;
+                return Action::None;
+            }
+            _ => {}
+        }
+
+        match self.state {
+            State::Ground if c.is_control() => Action::Execute(c),
+            State::Ground => Action::Print(c),
+
+            State::OscString if c == BELL => {
+                self.state = State::Ground;
+                Action::None
+            }
+            State::OscString => Action::None,
+
+            // Control chars are executed in the middle of sequences.
+            _ if is_c0(c) => Action::Execute(c),
+
+            State::Escape => {
+                self.state = match c {
+                    '[' => {
+                        self.csi = Csi::new();
+                        State::CsiEntry
+                    }
+                    ']' => State::OscString,
+                    // An intermediate char is followed by the final char, which ends the sequence.
+                    '\x20'..='\x2F' => State::Escape,
+                    _ => State::Ground,
+                };
+                Action::None
+            }
+
+            State::CsiEntry | State::CsiParam => self.csi_param(c),
+            State::CsiIntermediate => self.csi_intermediate(c),
+
+            State::CsiIgnore => {
+                if is_final(c) {
+                    self.state = State::Ground;
+                }
+                Action::None
+            }
+        }
+    }
+
+    fn csi_param(&mut self, c: char) -> Action {
+        match c {
+            '<' | '=' | '>' | '?' if self.state == State::CsiEntry => {
+                self.csi.private = Some(c);
+                self.state = State::CsiParam;
+            }
+            '0'..='9' => {
+                if self.csi.len == 0 {
+                    self.csi.len = 1;
+                }
+                if let Some(param) = self.csi.params.get_mut(self.csi.len - 1) {
+                    let digit = c as u16 - '0' as u16;
+                    *param = param.saturating_mul(10).saturating_add(digit);
+                }
+                self.state = State::CsiParam;
+            }
+            ';' | ':' => {
+                // A leading separator follows a missing first parameter.
+                self.csi.len = (self.csi.len.max(1) + 1).min(MAX_PARAMS + 1);
+                if c == ':' && self.csi.len <= MAX_PARAMS {
+                    self.csi.subparams |= 1 << (self.csi.len - 1);
+                }
+                self.state = State::CsiParam;
+            }
+            _ => return self.csi_intermediate(c),
+        }
+
+        Action::None
+    }
+
+    fn csi_intermediate(&mut self, c: char) -> Action {
+        match c {
+            '\x20'..='\x2F' if self.csi.intermediate.is_none() => {
+                self.csi.intermediate = Some(c);
+                self.state = State::CsiIntermediate;
+                Action::None
+            }
+            _ if is_final(c) => {
+                self.state = State::Ground;
+                self.csi.len = self.csi.len.min(MAX_PARAMS);
+                self.csi.function = c;
+                Action::Csi(self.csi)
+            }
+            _ => {
+                self.state = State::CsiIgnore;
+                Action::None
+            }
+        }
+    }
+}
+
+/// Whether `c` is a C0 control char.
+fn is_c0(c: char) -> bool {
+    c < '\x20' || c == '\x7F'
+}
+
+/// Whether `c` ends a CSI sequence.
+fn is_final(c: char) -> bool {
+    ('\x40'..='\x7E').contains(&c)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Action, Csi, Parser, MAX_PARAMS};
+
+    /// Parses `input` and returns all actions except [`Action::None`].
+    fn parse(input: &str) -> Vec<Action> {
+        let mut parser = Parser::new();
+        input
+            .chars()
+            .map(|c| parser.advance(c))
+            .filter(|x| *x != Action::None)
+            .collect()
+    }
+
+    /// Parses `input`, which has to be a single CSI sequence.
+    fn csi(input: &str) -> Csi {
+        match parse(input)[..] {
+            [Action::Csi(csi)] => csi,
+            ref actions => panic!("Expected one CSI sequence, got {:?}", actions),
+        }
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(
+            parse("a\tä\n"),
+            [
+                Action::Print('a'),
+                Action::Execute('\t'),
+                Action::Print('ä'),
+                Action::Execute('\n')
+            ]
+        );
+    }
+
+    #[test]
+    fn test_params() {
+        let sequence = csi("\x1B[1;31m");
+        assert_eq!(sequence.params(), [1, 31]);
+        assert_eq!(sequence.function, 'm');
+
+        assert_eq!(csi("\x1B[m").params(), []);
+        assert_eq!(csi("\x1B[;5H").params(), [0, 5]);
+        assert_eq!(csi("\x1B[5;H").params(), [5, 0]);
+        assert_eq!(csi("\x1B[99999A").params(), [u16::MAX]);
+
+        let sequence = csi("\x1B[1;38:2::255:0:0m");
+        assert_eq!(sequence.params(), [1, 38, 2, 0, 255, 0, 0]);
+        assert!(!sequence.is_subparam(1) && (2..7).all(|x| sequence.is_subparam(x)));
+        assert!(!sequence.is_subparam(7));
+
+        let sequence = csi("\x1B[;7H");
+        assert_eq!((sequence.param(0, 1), sequence.param(1, 1)), (1, 7));
+        assert_eq!(sequence.param(2, 1), 1);
+
+        // Parameters beyond the maximum are dropped.
+        let many = format!("\x1B[{}m", ["1"; MAX_PARAMS + 4].join(";"));
+        assert_eq!(csi(&many).params(), [1; MAX_PARAMS]);
+    }
+
+    #[test]
+    fn test_private_and_intermediate() {
+        let sequence = csi("\x1B[?25l");
+        assert_eq!(
+            (sequence.private, sequence.params(), sequence.function),
+            (Some('?'), &[25][..], 'l')
+        );
+
+        let sequence = csi("\x1B[2 q");
+        assert_eq!(
+            (sequence.intermediate, sequence.params(), sequence.function),
+            (Some(' '), &[2][..], 'q')
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        // A private marker after a parameter makes the sequence invalid.
+        assert_eq!(parse("\x1B[1?5hx"), [Action::Print('x')]);
+        // Cancelled sequences and other escape sequences are skipped.
+        assert_eq!(parse("\x1B[31\x18x"), [Action::Print('x')]);
+        assert_eq!(
+            parse("\x1B(Bx\x1Bcy"),
+            [Action::Print('x'), Action::Print('y')]
+        );
+        // An escape restarts the sequence.
+        assert_eq!(csi("\x1B[31\x1B[32m").params(), [32]);
+    }
+
+    #[test]
+    fn test_control_in_sequence() {
+        let actions = parse("\x1B[3\n1m");
+        assert_eq!(actions[0], Action::Execute('\n'));
+        assert!(matches!(actions[1], Action::Csi(x) if x.params() == [31]));
+    }
+
+    #[test]
+    fn test_osc() {
+        assert_eq!(
+            parse("\x1B]8;;https://example.com\x07a\x1B]0;title\x1B\\b"),
+            [Action::Print('a'), Action::Print('b')]
+        );
+    }
+}
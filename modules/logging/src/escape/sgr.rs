@@ -0,0 +1,230 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Select Graphic Rendition (SGR)
+//!
+//! The parameters of the `m` CSI sequence set the colors and the style of the following text.
+//! Supported are reset, bold, faint, inverse and the 8 and 16 colors, 256 colors with `38;5;n`
+//! and truecolor with `38;2;r;g;b`. Other attributes like italic or underline are skipped.
+
+use super::Csi;
+
+/// A color given by a SGR sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// The default foreground or background color of the theme.
+    Default,
+
+    /// One of the 256 colors, the first 16 being the colors of the theme.
+    Indexed(u8),
+
+    /// A red, green and blue color.
+    Rgb(u8, u8, u8),
+}
+
+/// An attribute set by a SGR sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    /// Resets all attributes.
+    Reset,
+    Bold,
+    Faint,
+    /// Neither bold nor faint.
+    NormalIntensity,
+    /// Swaps the foreground and background color.
+    Inverse,
+    NotInverse,
+    Foreground(Color),
+    Background(Color),
+}
+
+/// Iterator over the [`Attribute`]s of a SGR sequence.
+pub struct Attributes<'a> {
+    csi: &'a Csi,
+    index: usize,
+}
+
+/// Returns the attributes set by a SGR sequence.
+pub fn attributes(csi: &Csi) -> Attributes<'_> {
+    Attributes { csi, index: 0 }
+}
+
+impl Iterator for Attributes<'_> {
+    type Item = Attribute;
+
+    fn next(&mut self) -> Option<Attribute> {
+        let params = self.csi.params();
+
+        // A sequence without parameters resets all attributes.
+        if params.is_empty() && self.index == 0 {
+            self.index = 1;
+            return Some(Attribute::Reset);
+        }
+
+        loop {
+            // Subparameters of unsupported attributes, like the underline style of `4:3`, are skipped.
+            while self.csi.is_subparam(self.index) {
+                self.index += 1;
+            }
+
+            let param = *params.get(self.index)?;
+            self.index += 1;
+
+            let attribute = match param {
+                0 => Attribute::Reset,
+                1 => Attribute::Bold,
+                2 => Attribute::Faint,
+                22 => Attribute::NormalIntensity,
+                7 => Attribute::Inverse,
+                27 => Attribute::NotInverse,
+                30..=37 => Attribute::Foreground(Color::Indexed(param as u8 - 30)),
+                38 => match self.extended_color() {
+                    Some(color) => Attribute::Foreground(color),
+                    None => continue,
+                },
+                39 => Attribute::Foreground(Color::Default),
+                40..=47 => Attribute::Background(Color::Indexed(param as u8 - 40)),
+                48 => match self.extended_color() {
+                    Some(color) => Attribute::Background(color),
+                    None => continue,
+                },
+                49 => Attribute::Background(Color::Default),
+                90..=97 => Attribute::Foreground(Color::Indexed(param as u8 - 90 + 8)),
+                100..=107 => Attribute::Background(Color::Indexed(param as u8 - 100 + 8)),
+                _ => continue,
+            };
+
+            return Some(attribute);
+        }
+    }
+}
+
+impl Attributes<'_> {
+    /// Reads the color following `38` or `48`, being `5;n` or `2;r;g;b`.
+    /// The subparameter form `2:id:r:g:b` has a color space id, which is commonly left empty.
+    fn extended_color(&mut self) -> Option<Color> {
+        let params = &self.csi.params()[self.index..];
+        let subparams = (self.index..self.index + params.len())
+            .take_while(|x| self.csi.is_subparam(*x))
+            .count();
+
+        let (color, len) = match *params {
+            _ if subparams > 0 => match params[..subparams] {
+                [5, index] => (indexed(index), subparams),
+                [2, red, green, blue] | [2, _, red, green, blue, ..] => {
+                    (rgb(red, green, blue), subparams)
+                }
+                _ => (None, subparams),
+            },
+            [5, index, ..] => (indexed(index), 2),
+            [2, red, green, blue, ..] => (rgb(red, green, blue), 4),
+            // The rest of the sequence can't be told apart from other attributes.
+            _ => (None, params.len()),
+        };
+
+        self.index += len;
+        color
+    }
+}
+
+fn indexed(index: u16) -> Option<Color> {
+    Some(Color::Indexed(u8::try_from(index).ok()?))
+}
+
+fn rgb(red: u16, green: u16, blue: u16) -> Option<Color> {
+    Some(Color::Rgb(
+        u8::try_from(red).ok()?,
+        u8::try_from(green).ok()?,
+        u8::try_from(blue).ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{attributes, Attribute, Color};
+    use crate::escape::{Action, Parser};
+
+    /// Parses `input`, which has to be a single CSI sequence, and returns its attributes.
+    fn parse(input: &str) -> Vec<Attribute> {
+        let mut parser = Parser::new();
+        let csi = input
+            .chars()
+            .map(|c| parser.advance(c))
+            .find_map(|x| match x {
+                Action::Csi(csi) => Some(csi),
+                _ => None,
+            })
+            .unwrap();
+        attributes(&csi).collect()
+    }
+
+    #[test]
+    fn test_basic() {
+        assert_eq!(parse("\x1B[m"), [Attribute::Reset]);
+        assert_eq!(
+            parse("\x1B[0;1;31;42m"),
+            [
+                Attribute::Reset,
+                Attribute::Bold,
+                Attribute::Foreground(Color::Indexed(1)),
+                Attribute::Background(Color::Indexed(2))
+            ]
+        );
+        assert_eq!(
+            parse("\x1B[91;107;39;49m"),
+            [
+                Attribute::Foreground(Color::Indexed(9)),
+                Attribute::Background(Color::Indexed(15)),
+                Attribute::Foreground(Color::Default),
+                Attribute::Background(Color::Default)
+            ]
+        );
+        // Unsupported attributes like italic and underline are skipped.
+        assert_eq!(
+            parse("\x1B[3;4;7;27m"),
+            [Attribute::Inverse, Attribute::NotInverse]
+        );
+    }
+
+    #[test]
+    fn test_extended() {
+        assert_eq!(
+            parse("\x1B[38;5;208;1m"),
+            [Attribute::Foreground(Color::Indexed(208)), Attribute::Bold]
+        );
+        assert_eq!(
+            parse("\x1B[48;2;255;128;0;22m"),
+            [
+                Attribute::Background(Color::Rgb(255, 128, 0)),
+                Attribute::NormalIntensity
+            ]
+        );
+        // An invalid index only skips the color.
+        assert_eq!(
+            parse("\x1B[1;38;5;256;31m"),
+            [Attribute::Bold, Attribute::Foreground(Color::Indexed(1))]
+        );
+        assert_eq!(parse("\x1B[38;2;255m"), []);
+    }
+
+    #[test]
+    fn test_subparams() {
+        assert_eq!(
+            parse("\x1B[38:2::0:255:0;1m"),
+            [
+                Attribute::Foreground(Color::Rgb(0, 255, 0)),
+                Attribute::Bold
+            ]
+        );
+        assert_eq!(
+            parse("\x1B[48:2:0:0:255m"),
+            [Attribute::Background(Color::Rgb(0, 0, 255))]
+        );
+        assert_eq!(
+            parse("\x1B[38:5:21;4:1;1m"),
+            [Attribute::Foreground(Color::Indexed(21)), Attribute::Bold]
+        );
+        // Invalid colors only skip their subparameters.
+        assert_eq!(parse("\x1B[38:5;1m"), [Attribute::Bold]);
+    }
+}
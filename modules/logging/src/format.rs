@@ -0,0 +1,168 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! # Log Formats
+//!
+//! A [`Formatter`] turns a [`LogEntry`] into a line of text, every [`crate::WriterSink`] has its own.
+//!
+//! The text formats write `[seconds.micros] cpuN LEVEL file@line message key=value`,
+//! the timestamp being `0.000000` until the first clock source is registered.
+//! [`format_json`] writes JSON lines for tools parsing the log.
+
+use crate::LogEntry;
+use core::fmt::{self, Write};
+use log::Level;
+
+/// Writes the entry as a line to the output.
+pub type Formatter = fn(&mut dyn Write, &LogEntry) -> fmt::Result;
+
+/// Nanoseconds per microsecond and second.
+const NANOSECONDS_PER_MICROSECOND: u64 = 1_000;
+const NANOSECONDS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Writes the text format, with the level colored by ANSI escape sequences.
+pub fn format_colored(output: &mut dyn Write, entry: &LogEntry) -> fmt::Result {
+    // Pre-format the level text.
+    let level = match entry.level {
+        Level::Error => "\x1B[91mERROR\x1B[39m",
+        Level::Warn => "\x1B[93m WARN\x1B[39m",
+        Level::Info => "\x1B[92m INFO\x1B[39m",
+        Level::Debug => "\x1B[94mDEBUG\x1B[39m",
+        Level::Trace => "\x1B[95mTRACE\x1B[39m",
+    };
+
+    format_text(output, entry, level)
+}
+
+/// Writes the text format, for outputs not understanding escape sequences.
+pub fn format_plain(output: &mut dyn Write, entry: &LogEntry) -> fmt::Result {
+    let level = match entry.level {
+        Level::Error => "ERROR",
+        Level::Warn => " WARN",
+        Level::Info => " INFO",
+        Level::Debug => "DEBUG",
+        Level::Trace => "TRACE",
+    };
+
+    format_text(output, entry, level)
+}
+
+/// Writes a JSON object per line, the timestamp being in nanoseconds or `null`.
+pub fn format_json(output: &mut dyn Write, entry: &LogEntry) -> fmt::Result {
+    write!(output, "{{\"sequence\":{},\"timestamp\":", entry.sequence)?;
+    match entry.timestamp {
+        Some(timestamp) => write!(output, "{}", timestamp)?,
+        None => output.write_str("null")?,
+    }
+    write!(
+        output,
+        ",\"cpu\":{},\"level\":\"{}\",\"file\":",
+        entry.cpu, entry.level
+    )?;
+    write_json_string(output, entry.file)?;
+    write!(output, ",\"line\":{},\"message\":", entry.line)?;
+    write_json_string(output, entry.message)?;
+
+    output.write_str(",\"fields\":{")?;
+    for (i, (key, value)) in entry.fields.iter().enumerate() {
+        if i > 0 {
+            output.write_char(',')?;
+        }
+        write_json_string(output, key)?;
+        output.write_char(':')?;
+        write_json_string(output, value)?;
+    }
+    output.write_str("}}\n")
+}
+
+fn format_text(output: &mut dyn Write, entry: &LogEntry, level: &str) -> fmt::Result {
+    let timestamp = entry.timestamp.unwrap_or_default();
+    write!(
+        output,
+        "[{:>5}.{:06}] cpu{} {} {}@{} {}",
+        timestamp / NANOSECONDS_PER_SECOND,
+        timestamp % NANOSECONDS_PER_SECOND / NANOSECONDS_PER_MICROSECOND,
+        entry.cpu,
+        level,
+        entry.file,
+        entry.line,
+        entry.message
+    )?;
+
+    for (key, value) in entry.fields.iter() {
+        write!(output, " {}={}", key, value)?;
+    }
+    output.write_char('\n')
+}
+
+/// Writes `s` as a quoted JSON string.
+fn write_json_string(output: &mut dyn Write, s: &str) -> fmt::Result {
+    output.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => output.write_str("\\\"")?,
+            '\\' => output.write_str("\\\\")?,
+            '\n' => output.write_str("\\n")?,
+            '\r' => output.write_str("\\r")?,
+            '\t' => output.write_str("\\t")?,
+            c if c < ' ' => write!(output, "\\u{:04x}", c as u32)?,
+            c => output.write_char(c)?,
+        }
+    }
+    output.write_char('"')
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_colored, format_json, format_plain, Formatter};
+    use crate::buffer::FieldsWriter;
+    use crate::LogEntry;
+    use log::kv::Source;
+    use log::Level;
+
+    fn format(formatter: Formatter, timestamp: Option<u64>, message: &str) -> String {
+        let mut fields = FieldsWriter::<64>::new();
+        let _ = ("address", "0xfed00000").visit(&mut fields);
+        let _ = ("comparators", 3).visit(&mut fields);
+
+        let mut output = String::new();
+        let _ = formatter(
+            &mut output,
+            &LogEntry {
+                sequence: 12,
+                timestamp,
+                cpu: 1,
+                level: Level::Info,
+                file: "modules/hpet/src/lib.rs",
+                line: 42,
+                message,
+                fields: fields.fields(),
+            },
+        );
+        output
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(
+            format(format_plain, Some(12_345_678_901), "HPET found"),
+            "[   12.345678] cpu1  INFO modules/hpet/src/lib.rs@42 HPET found address=0xfed00000 comparators=3\n"
+        );
+        assert_eq!(
+            format(format_colored, None, "HPET found"),
+            "[    0.000000] cpu1 \x1B[92m INFO\x1B[39m modules/hpet/src/lib.rs@42 HPET found address=0xfed00000 comparators=3\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(
+            format(format_json, Some(1_000), "HPET \"found\"\n"),
+            "{\"sequence\":12,\"timestamp\":1000,\"cpu\":1,\"level\":\"INFO\",\"file\":\"modules/hpet/src/lib.rs\",\"line\":42,\
+            \"message\":\"HPET \\\"found\\\"\\n\",\"fields\":{\"address\":\"0xfed00000\",\"comparators\":\"3\"}}\n"
+        );
+        assert!(format(format_json, None, "\x1B").contains("\"timestamp\":null,"));
+        assert!(format(format_json, None, "\x1B").contains("\"message\":\"\\u001b\""));
+    }
+}
@@ -81,6 +81,28 @@ impl Framebuffer {
         }
     }
 
+    /// Sets the pixels of the given rectangle to the given color, clipped to the buffer.
+    /// The color needs to be encoded with `encode_color` first.
+    pub fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        let end_x = (x + width).min(self.width);
+
+        for y in y..y + height {
+            // Stop at the first row not completely inside the buffer.
+            if (y + 1) * self.pitch > self.size {
+                break;
+            }
+
+            for x in x..end_x {
+                // Safety: write is valid since the row is inside the buffer and x is less than the width.
+                unsafe {
+                    self.buffer
+                        .byte_add((y * self.pitch) + (x * 4))
+                        .write_volatile(color)
+                };
+            }
+        }
+    }
+
     /// Copies the given amount of pixels at index `from` to index `to`.
     pub fn copy_pixels(&mut self, from_y: usize, to_y: usize, size: usize) {
         debug_assert_ne!(from_y, to_y, "Starting and ending axis are identical");
@@ -9,63 +9,102 @@
 //!
 //! `Serial Port`
 //! By default, microdragon will log to serial port 1 with colored output using ANSI escape sequences.
-//! (TODO: Make port and logging configurable)
 //!
 //! `Framebuffer Terminal`
 //! By default, microdragon will request a frame buffer from the bootloader that, if available, will be used for logging.
-//! (TODO: Make logging configurable)
-#![no_std]
+//!
+//! The serial port, its baud rate and the levels of each output and module are set in the `Config.toml`,
+//! see [`config`] for the options.
+//!
+//! Both outputs are [`LogSink`]s, other modules add their own sinks with [`register_sink`],
+//! each having its own level and formatting.
+//!
+//! Every record is kept with its timestamp and CPU in a ring buffer, see [`buffer`].
+//! Sinks replay it when registered, so nothing logged before is lost, and [`read`] drains it for userspace.
+#![cfg_attr(not(test), no_std)]
 
+mod buffer;
+mod config;
 #[cfg(feature = "terminal")]
 mod escape;
+mod format;
 #[cfg(feature = "terminal")]
 mod framebuffer;
 #[cfg(feature = "terminal")]
 mod position;
 #[cfg(all(target_arch = "x86_64", feature = "serial"))]
 mod serial;
+mod sink;
 #[cfg(feature = "terminal")]
 mod terminal;
 #[cfg(feature = "terminal")]
 mod theme;
 
+use buffer::{CopyCursor, FieldsWriter, LogBuffer, MessageWriter};
 use common::interrupts;
 use common::sync::Spinlock;
 use core::fmt::Write;
-use log::{info, Level, LevelFilter, Log, Metadata, Record};
+use log::{info, Log, Metadata, Record};
 use microdragon_interface::macros::init;
 use microdragon_interface::ModuleInterface;
+use sink::SinkList;
+
+pub use buffer::{Fields, LogEntry, MAX_FIELDS_LENGTH, MAX_FILE_LENGTH, MAX_MESSAGE_LENGTH};
+pub use format::{format_colored, format_json, format_plain, Formatter};
+pub use sink::{LogSink, SinkError, WriterSink, MAX_SINKS};
+
+/// The buffer keeping every record, for replaying them to sinks registered later and for [`read`].
+static BUFFER: Spinlock<LogBuffer<{ config::BUFFER_SIZE }>> = Spinlock::new(LogBuffer::new());
+
+/// The registered sinks, always locked after [`BUFFER`].
+static SINKS: Spinlock<SinkList> = Spinlock::new(SinkList::new());
 
 /// The central [`log::Log`] implementation.
 /// There can only be one active Log implementation,
-/// so this struct formats the messages and relays them to the outputs.
+/// so this struct formats the messages, keeps them in the log buffer and relays them to the sinks.
 struct LoggingSubsystem;
 
 impl Log for LoggingSubsystem {
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        // TODO: For now we always accept logging, but if serial logging is disable,
-        // we only want info or higher to display to the framebuffer terminal.
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= config::module_level(metadata.target())
     }
 
     fn log(&self, record: &Record) {
-        // Pre-format the level text.
-        let level = match record.level() {
-            Level::Error => "\x1B[91mERROR\x1B[39m",
-            Level::Warn => "\x1B[93m WARN\x1B[39m",
-            Level::Info => "\x1B[92m INFO\x1B[39m",
-            Level::Debug => "\x1B[94mDEBUG\x1B[39m",
-            Level::Trace => "\x1B[95mTRACE\x1B[39m",
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut message = MessageWriter::<MAX_MESSAGE_LENGTH>::new();
+        let _ = message.write_fmt(*record.args());
+        let mut fields = FieldsWriter::<MAX_FIELDS_LENGTH>::new();
+        let _ = record.key_values().visit(&mut fields);
+        let mut entry = LogEntry {
+            sequence: 0,
+            timestamp: common::time::monotonic_nanoseconds(),
+            cpu: cpu_id(),
+            level: record.level(),
+            file: record
+                .file()
+                .or_else(|| record.module_path())
+                .unwrap_or_default(),
+            line: record.line().unwrap_or_default(),
+            message: message.as_str(),
+            fields: fields.fields(),
         };
 
         // Start a critical section, since interrupts might log too.
         let _guard = interrupts::disable();
 
-        // Write to logger outputs.
-        #[cfg(all(target_arch = "x86_64", feature = "serial"))]
-        write_to_output(&serial::SERIAL_PORT_OUTPUT, level, record);
-        #[cfg(feature = "terminal")]
-        write_to_output(&terminal::TERMINAL_OUTPUT, level, record);
+        // The sinks are copied while the buffer is locked, so a sink being registered gets every entry exactly once.
+        let sinks = {
+            let mut buffer = BUFFER.lock();
+            entry.sequence = buffer.push(&entry);
+            *SINKS.lock()
+        };
+
+        for sink in sinks.iter() {
+            write_to_sink(sink, &entry);
+        }
     }
 
     fn flush(&self) {}
@@ -75,28 +114,32 @@ static INSTANCE: LoggingSubsystem = LoggingSubsystem;
 
 /// Initializes the logging module.
 /// Interrupts should still be disables while this is run.
+///
+/// The logger is set first, so the sinks replay everything logged while they were initialized.
 #[init]
 pub fn init(interface: &ModuleInterface) {
-    // Run the initialization sequence for the logging outputs.
+    // Set global Log implementation.
+    let _ = log::set_logger(&INSTANCE);
+
+    // Set global max log level, to the most verbose level any module is logged with.
+    log::set_max_level(config::max_module_level());
+
+    // Run the initialization sequence for the logging sinks.
     #[cfg(all(target_arch = "x86_64", feature = "serial"))]
-    serial::SERIAL_PORT_OUTPUT.lock().init();
+    {
+        serial::SERIAL_SINK.output().lock().init();
+        let _ = register_sink(&serial::SERIAL_SINK);
+    }
 
     #[cfg(feature = "terminal")]
     if let Some(address) = core::ptr::NonNull::new(interface.framebuffer_info.address as *mut u32) {
-        terminal::TERMINAL_OUTPUT
+        terminal::TERMINAL_SINK
+            .output()
             .lock()
             .init(&interface.framebuffer_info, address);
+        let _ = register_sink(&*terminal::TERMINAL_SINK);
     }
 
-    // Set global Log implementation.
-    let _ = log::set_logger(&INSTANCE);
-
-    // Set global max log level.
-    #[cfg(debug_assertions)]
-    log::set_max_level(LevelFilter::Trace);
-    #[cfg(not(debug_assertions))]
-    log::set_max_level(LevelFilter::Info);
-
     info!("Logging start");
 }
 
@@ -104,26 +147,79 @@ pub fn init(interface: &ModuleInterface) {
 #[cfg(feature = "terminal")]
 #[init]
 pub fn rewire(_: &ModuleInterface) {
-    // terminal::TERMINAL_OUTPUT.lock().rewire();
+    // terminal::TERMINAL_SINK.output().lock().rewire();
 
     info!("Logging rewired");
 }
 
-/// Writes the given record to `output` using pre-formatted `level`.
-fn write_to_output<T: Write>(output: &Spinlock<T>, level: &str, record: &Record) {
-    // Lock the output.
-    let mut guard = output.lock();
-
-    // write using `writeln` macro.
-    let _ = writeln!(
-        guard,
-        "{} {}@{} {}",
-        level,
-        record
-            .file()
-            .or_else(|| record.module_path())
-            .unwrap_or_default(),
-        record.line().unwrap_or_default(),
-        record.args()
-    );
+/// Registers a sink, which first gets every entry of the log buffer and then every new entry up to its level.
+///
+/// The entries are replayed one by one without holding the buffer lock, so logging isn't blocked by slow sinks.
+/// Only the final check for new entries and adding the sink happen while the buffer is locked,
+/// so the sink gets every entry exactly once.
+pub fn register_sink(sink: &'static dyn LogSink) -> Result<(), SinkError> {
+    {
+        let _guard = interrupts::disable();
+        SINKS.lock().check_add(sink)?;
+    }
+
+    let mut cursor = CopyCursor::new();
+    loop {
+        let entry = {
+            let _guard = interrupts::disable();
+            let buffer = BUFFER.lock();
+            match buffer.copy(&mut cursor) {
+                Some(entry) => entry,
+                None => return SINKS.lock().add(sink),
+            }
+        };
+
+        // Interrupts stay disabled while writing, like in `log`, since the output of the sink is locked.
+        let _guard = interrupts::disable();
+        write_to_sink(sink, &entry.entry());
+    }
+}
+
+/// Unregisters a sink, it doesn't get any entries afterwards.
+pub fn unregister_sink(sink: &'static dyn LogSink) -> Result<(), SinkError> {
+    let _guard = interrupts::disable();
+
+    let _buffer = BUFFER.lock();
+    SINKS.lock().remove(sink)
+}
+
+/// Calls `f` with every entry of the log buffer starting at the sequence number `cursor`, oldest first,
+/// and moves the cursor after the last entry. Starting with a cursor of 0 replays the whole buffer.
+/// Returns the number of entries overwritten before they could be read.
+///
+/// Used for draining the log to userspace.
+/// `f` runs with the log buffer locked and interrupts disabled, so it must not log.
+pub fn read(cursor: &mut u64, f: impl FnMut(&LogEntry)) -> u64 {
+    let _guard = interrupts::disable();
+    BUFFER.lock().read(cursor, f)
+}
+
+/// Writes the given entry to `sink`, if its level includes it.
+fn write_to_sink(sink: &dyn LogSink, entry: &LogEntry) {
+    if entry.level <= sink.level() {
+        sink.write(entry);
+    }
+}
+
+/// Returns the id of the current CPU, which is its initial APIC id.
+#[cfg(target_arch = "x86_64")]
+fn cpu_id() -> u32 {
+    use core::arch::x86_64::{__cpuid, __get_cpuid_max};
+
+    // The x2APIC id of leaf 0xB has 32 bits, the initial APIC id of leaf 1 only 8 bits.
+    if __get_cpuid_max(0).0 >= 0xB {
+        __cpuid(0xB).edx
+    } else {
+        __cpuid(1).ebx >> 24
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn cpu_id() -> u32 {
+    0
 }
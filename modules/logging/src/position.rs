@@ -49,6 +49,16 @@ impl Position {
         self.row >= self.max_rows
     }
 
+    /// Moves to the given row, clamped to the line.
+    pub fn set_row(&mut self, row: usize) {
+        self.row = row.min(self.max_rows.saturating_sub(1));
+    }
+
+    /// Moves to the given column, clamped to the screen.
+    pub fn set_column(&mut self, column: usize) {
+        self.column = column.min(self.max_columns);
+    }
+
     /// Gets the current row.
     pub fn row(&self) -> usize {
         self.row
@@ -59,6 +69,11 @@ impl Position {
         self.column
     }
 
+    /// Gets the row length.
+    pub fn max_rows(&self) -> usize {
+        self.max_rows
+    }
+
     /// Gets the column length.
     pub fn max_columns(&self) -> usize {
         self.max_columns
@@ -1,9 +1,72 @@
 // This Source Code Form is subject to the terms of the Mozilla Public
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
-use common::sync::Spinlock;
+use crate::config::{SERIAL_BAUD_RATE, SERIAL_FORMAT, SERIAL_LEVEL, SERIAL_PORT};
+use crate::WriterSink;
+use core::arch::asm;
+use core::fmt;
 
-/// For serial logging we can just use the [`SerialPort`] type from the [`uart_16550`] crate.
-/// It needs to wrapped into a spinlock.
-pub static SERIAL_PORT_OUTPUT: Spinlock<uart_16550::SerialPort> =
-    Spinlock::new(unsafe { uart_16550::SerialPort::new(0x3F8) });
+/// The sink of the serial port, using the port and baud rate of the configuration.
+pub static SERIAL_SINK: WriterSink<SerialOutput> = WriterSink::new(
+    "serial",
+    SerialOutput {
+        // Safety: The port is one of the standard COM ports.
+        port: unsafe { uart_16550::SerialPort::new(SERIAL_PORT) },
+    },
+    SERIAL_LEVEL,
+    SERIAL_FORMAT,
+);
+
+/// The UART clock divided by 16, the highest baud rate possible.
+const MAX_BAUD_RATE: u32 = 115200;
+
+/// The divisor of the UART clock resulting in the baud rate.
+const DIVISOR: u16 = {
+    assert!(
+        SERIAL_BAUD_RATE > 0 && MAX_BAUD_RATE.is_multiple_of(SERIAL_BAUD_RATE),
+        "Invalid baud rate, it has to divide 115200"
+    );
+    (MAX_BAUD_RATE / SERIAL_BAUD_RATE) as u16
+};
+
+/// Offsets of the UART registers.
+const DIVISOR_LOW: u16 = 0;
+const DIVISOR_HIGH: u16 = 1;
+const LINE_CONTROL: u16 = 3;
+
+/// Line control value for 8 data bits, no parity and one stop bit.
+const LINE_8N1: u8 = 0x03;
+
+/// Bit of the line control register mapping the divisor to the first two registers.
+const LINE_DLAB: u8 = 0x80;
+
+/// For serial logging we can just use the [`uart_16550::SerialPort`] type from the [`uart_16550`] crate,
+/// which only supports its default baud rate though.
+pub struct SerialOutput {
+    port: uart_16550::SerialPort,
+}
+
+impl SerialOutput {
+    /// Initializes the serial port with the configured baud rate.
+    pub fn init(&mut self) {
+        self.port.init();
+
+        // Safety: The port is one of the standard COM ports.
+        unsafe {
+            write(SERIAL_PORT + LINE_CONTROL, LINE_DLAB | LINE_8N1);
+            write(SERIAL_PORT + DIVISOR_LOW, DIVISOR as u8);
+            write(SERIAL_PORT + DIVISOR_HIGH, (DIVISOR >> 8) as u8);
+            write(SERIAL_PORT + LINE_CONTROL, LINE_8N1);
+        }
+    }
+}
+
+impl fmt::Write for SerialOutput {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.port.write_str(s)
+    }
+}
+
+unsafe fn write(port: u16, value: u8) {
+    asm!("OUT DX, AL", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+}
@@ -0,0 +1,206 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! # Log Sinks
+//!
+//! A [`LogSink`] is a destination of log entries, like the serial port, the framebuffer terminal,
+//! a debug console or a network connection. Sinks are added and removed at runtime with
+//! [`crate::register_sink`] and [`crate::unregister_sink`], each filters the entries by its own level.
+//!
+//! Most sinks write text to a [`Write`] implementation, which [`WriterSink`] does with a [`Formatter`].
+
+use crate::{Formatter, LogEntry};
+use common::sync::Spinlock;
+use core::fmt::Write;
+use log::LevelFilter;
+
+/// The maximal number of sinks registered at the same time.
+pub const MAX_SINKS: usize = 8;
+
+/// A destination of log entries.
+pub trait LogSink: Sync {
+    /// The name of the sink, used in logs.
+    fn name(&self) -> &'static str;
+
+    /// The most verbose level written to the sink.
+    fn level(&self) -> LevelFilter;
+
+    /// Formats and writes the entry. This must not log itself.
+    fn write(&self, entry: &LogEntry);
+}
+
+/// Error returned when registering or unregistering a sink fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkError {
+    /// [`MAX_SINKS`] sinks are registered already.
+    TooManySinks,
+
+    /// The sink is registered already.
+    AlreadyRegistered,
+
+    /// The sink isn't registered.
+    NotFound,
+}
+
+/// A [`LogSink`] formatting the entries with a [`Formatter`] and writing them to `T`.
+pub struct WriterSink<T> {
+    name: &'static str,
+    output: Spinlock<T>,
+    level: LevelFilter,
+    formatter: Formatter,
+}
+
+impl<T> WriterSink<T> {
+    pub const fn new(
+        name: &'static str,
+        output: T,
+        level: LevelFilter,
+        formatter: Formatter,
+    ) -> Self {
+        WriterSink {
+            name,
+            output: Spinlock::new(output),
+            level,
+            formatter,
+        }
+    }
+
+    /// Returns the output, for initializing it.
+    pub fn output(&self) -> &Spinlock<T> {
+        &self.output
+    }
+}
+
+impl<T: Write + Send> LogSink for WriterSink<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn write(&self, entry: &LogEntry) {
+        let mut guard = self.output.lock();
+        let _ = (self.formatter)(&mut *guard, entry);
+    }
+}
+
+/// The registered sinks, in the order of their registration.
+#[derive(Clone, Copy)]
+pub(crate) struct SinkList {
+    sinks: [Option<&'static dyn LogSink>; MAX_SINKS],
+}
+
+impl SinkList {
+    pub const fn new() -> Self {
+        SinkList {
+            sinks: [None; MAX_SINKS],
+        }
+    }
+
+    pub fn add(&mut self, sink: &'static dyn LogSink) -> Result<(), SinkError> {
+        self.check_add(sink)?;
+
+        let slot = self.sinks.iter_mut().find(|x| x.is_none()).unwrap();
+        *slot = Some(sink);
+        Ok(())
+    }
+
+    /// Checks if the sink can be added, without adding it.
+    pub fn check_add(&self, sink: &'static dyn LogSink) -> Result<(), SinkError> {
+        if self.position(sink).is_some() {
+            Err(SinkError::AlreadyRegistered)
+        } else if self.sinks.iter().all(|x| x.is_some()) {
+            Err(SinkError::TooManySinks)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Removes the sink, keeping the order of the others.
+    pub fn remove(&mut self, sink: &'static dyn LogSink) -> Result<(), SinkError> {
+        let index = self.position(sink).ok_or(SinkError::NotFound)?;
+        self.sinks[index..].rotate_left(1);
+        self.sinks[MAX_SINKS - 1] = None;
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'static dyn LogSink> + '_ {
+        self.sinks.iter().map_while(|x| *x)
+    }
+
+    fn position(&self, sink: &'static dyn LogSink) -> Option<usize> {
+        self.iter().position(|x| core::ptr::addr_eq(x, sink))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LogSink, SinkError, SinkList, WriterSink, MAX_SINKS};
+    use crate::{format_colored, format_plain, Fields, LogEntry};
+    use log::{Level, LevelFilter};
+
+    fn entry(level: Level) -> LogEntry<'static> {
+        LogEntry {
+            sequence: 0,
+            timestamp: None,
+            cpu: 0,
+            level,
+            file: "modules/acpi/src/lib.rs",
+            line: 86,
+            message: "ACPI not available",
+            fields: Fields::default(),
+        }
+    }
+
+    #[test]
+    fn test_writer_sink() {
+        let sink = WriterSink::new("test", String::new(), LevelFilter::Info, format_plain);
+        sink.write(&entry(Level::Info));
+        sink.write(&entry(Level::Warn));
+        assert_eq!(
+            *sink.output().lock(),
+            "[    0.000000] cpu0  INFO modules/acpi/src/lib.rs@86 ACPI not available\n\
+            [    0.000000] cpu0  WARN modules/acpi/src/lib.rs@86 ACPI not available\n"
+        );
+
+        let sink = WriterSink::new("test", String::new(), LevelFilter::Info, format_colored);
+        sink.write(&entry(Level::Error));
+        assert_eq!(
+            *sink.output().lock(),
+            "[    0.000000] cpu0 \x1B[91mERROR\x1B[39m modules/acpi/src/lib.rs@86 ACPI not available\n"
+        );
+    }
+
+    #[test]
+    fn test_sink_list() {
+        let sinks: &'static [WriterSink<String>] = Box::leak(
+            (0..=MAX_SINKS)
+                .map(|_| WriterSink::new("test", String::new(), LevelFilter::Trace, format_plain))
+                .collect(),
+        );
+
+        let mut list = SinkList::new();
+        for sink in &sinks[..MAX_SINKS] {
+            assert_eq!(list.add(sink), Ok(()));
+        }
+        assert_eq!(list.add(&sinks[MAX_SINKS]), Err(SinkError::TooManySinks));
+        assert_eq!(list.add(&sinks[0]), Err(SinkError::AlreadyRegistered));
+        assert_eq!(
+            list.check_add(&sinks[MAX_SINKS]),
+            Err(SinkError::TooManySinks)
+        );
+
+        // Removing keeps the order of the other sinks.
+        assert_eq!(list.remove(&sinks[1]), Ok(()));
+        assert_eq!(list.remove(&sinks[1]), Err(SinkError::NotFound));
+        assert_eq!(list.add(&sinks[MAX_SINKS]), Ok(()));
+        let order: Vec<_> = list
+            .iter()
+            .map(|x| sinks.iter().position(|y| core::ptr::addr_eq(x, y)).unwrap())
+            .collect();
+        assert_eq!(order, [0, 2, 3, 4, 5, 6, 7, 8]);
+    }
+}
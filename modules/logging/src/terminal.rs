@@ -2,10 +2,12 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use crate::escape::EscapeSequence;
+use crate::config::{TERMINAL_FORMAT, TERMINAL_LEVEL};
+use crate::escape::{Action, Csi, Parser, Style};
 use crate::framebuffer::{Framebuffer, LINE_SPACING};
 use crate::position::Position;
-use common::sync::{Spinlock, SyncLazy};
+use crate::WriterSink;
+use common::sync::SyncLazy;
 use core::fmt::Write;
 use core::ptr::NonNull;
 use microdragon_interface::framebuffer::FramebufferInfo;
@@ -13,17 +15,34 @@ use noto_sans_mono_bitmap::{
     get_raster, get_raster_width, FontWeight, RasterHeight, RasterizedChar,
 };
 
-pub static TERMINAL_OUTPUT: SyncLazy<Spinlock<TerminalOutput>> =
-    SyncLazy::new(|| Spinlock::new(TerminalOutput::new()));
+/// The sink of the framebuffer terminal.
+pub static TERMINAL_SINK: SyncLazy<WriterSink<TerminalOutput>> = SyncLazy::new(|| {
+    WriterSink::new(
+        "terminal",
+        TerminalOutput::new(),
+        TERMINAL_LEVEL,
+        TERMINAL_FORMAT,
+    )
+});
 
 const BORDER_PADDING: usize = 1;
 const RASTER_HEIGHT: RasterHeight = RasterHeight::Size16;
+const RASTER_WIDTH: usize = get_raster_width(FontWeight::Regular, RASTER_HEIGHT);
+const LINE_HEIGHT: usize = RASTER_HEIGHT.val() + LINE_SPACING;
+const TAB_WIDTH: usize = 8;
+
+// Bold chars are printed into the same cells.
+const _: () = assert!(get_raster_width(FontWeight::Bold, RASTER_HEIGHT) == RASTER_WIDTH);
 
 /// Logger output creating a write-only terminal based on a framebuffer.
+///
+/// Besides the colors of SGR sequences, it supports the cursor movement with `A`, `B`, `C`, `D`,
+/// `E`, `F`, `G`, `d` and `H` and erasing with `J` and `K`.
 pub struct TerminalOutput {
     framebuffer: Option<Framebuffer>,
     position: Position,
-    sequence: EscapeSequence,
+    parser: Parser,
+    style: Style,
 }
 
 impl TerminalOutput {
@@ -32,7 +51,8 @@ impl TerminalOutput {
         TerminalOutput {
             framebuffer: None,
             position: Position::new(),
-            sequence: EscapeSequence::new(),
+            parser: Parser::new(),
+            style: Style::new(),
         }
     }
 
@@ -69,9 +89,8 @@ impl TerminalOutput {
 
         // Calculate the max rows and columns we have.
         self.position.set_limits(
-            (info.width as usize - BORDER_PADDING * 2)
-                / get_raster_width(FontWeight::Regular, RASTER_HEIGHT),
-            (info.height as usize - BORDER_PADDING * 2) / (RASTER_HEIGHT.val() + LINE_SPACING),
+            (info.width as usize - BORDER_PADDING * 2) / RASTER_WIDTH,
+            (info.height as usize - BORDER_PADDING * 2) / LINE_HEIGHT,
         );
     }
 
@@ -84,21 +103,17 @@ impl TerminalOutput {
 
     /// Writes the given [`RasterizedChar`] into the framebuffer.
     fn write_rasterized_char(&mut self, c: RasterizedChar) {
-        const WIDTH: usize = get_raster_width(FontWeight::Regular, RASTER_HEIGHT);
-
         let Some(fb) = &mut self.framebuffer else {
             return;
         };
 
         for (y, row) in c.raster().iter().enumerate() {
             for (x, intensity) in row.iter().enumerate() {
-                let color = fb.encode_color(self.sequence.apply_intensity(*intensity));
+                let color = fb.encode_color(self.style.apply_intensity(*intensity));
 
                 fb.set_pixel(
-                    BORDER_PADDING + (self.position.row() * WIDTH) + x,
-                    BORDER_PADDING
-                        + (self.position.column() * (RASTER_HEIGHT.val() + LINE_SPACING))
-                        + y,
+                    BORDER_PADDING + (self.position.row() * RASTER_WIDTH) + x,
+                    BORDER_PADDING + (self.position.column() * LINE_HEIGHT) + y,
                     color,
                 );
             }
@@ -115,15 +130,136 @@ impl TerminalOutput {
         if self.position.newline() {
             if let Some(fb) = &mut self.framebuffer {
                 fb.copy_pixels(
-                    BORDER_PADDING + RASTER_HEIGHT.val() + LINE_SPACING,
+                    BORDER_PADDING + LINE_HEIGHT,
                     BORDER_PADDING,
-                    self.position.max_columns() * (RASTER_HEIGHT.val() + LINE_SPACING),
-                );
-                fb.clear_pixels(
-                    BORDER_PADDING
-                        + (self.position.max_columns() * (RASTER_HEIGHT.val() + LINE_SPACING)),
+                    self.position.max_columns() * LINE_HEIGHT,
                 );
+                fb.clear_pixels(BORDER_PADDING + (self.position.max_columns() * LINE_HEIGHT));
+            }
+        }
+    }
+
+    /// Prints the char with the current style, or `?` if the font doesn't have it.
+    fn print(&mut self, c: char) {
+        let weight = if self.style.bold() {
+            FontWeight::Bold
+        } else {
+            FontWeight::Regular
+        };
+
+        if let Some(c) = get_raster(c, weight, RASTER_HEIGHT) {
+            self.write_rasterized_char(c)
+        } else if let Some(c) = get_raster('?', weight, RASTER_HEIGHT) {
+            self.write_rasterized_char(c)
+        }
+    }
+
+    /// Executes a control char.
+    fn execute(&mut self, c: char) {
+        match c {
+            '\n' => self.newline(),
+            '\r' => self.position.set_row(0),
+            // Backspace
+            '\x08' => self.position.set_row(self.position.row().saturating_sub(1)),
+            '\t' => self
+                .position
+                .set_row((self.position.row() / TAB_WIDTH + 1) * TAB_WIDTH),
+            _ => {}
+        }
+    }
+
+    /// Executes a CSI sequence, setting the style, moving the cursor or erasing.
+    /// Cursor positions are 1-based and clamped to the screen, so moving never scrolls.
+    fn execute_csi(&mut self, csi: &Csi) {
+        // Sequences with a private marker or intermediate char, like `\x1B[?25l`, aren't supported.
+        if csi.private.is_some() || csi.intermediate.is_some() {
+            return;
+        }
+
+        let row = self.position.row();
+        let column = self.position.column();
+        let count = csi.param(0, 1) as usize;
+
+        match csi.function {
+            'm' => self.style.apply(csi),
+            'A' => self.position.set_column(column.saturating_sub(count)),
+            'B' => self.position.set_column(column + count),
+            'C' => self.position.set_row(row + count),
+            'D' => self.position.set_row(row.saturating_sub(count)),
+            'E' | 'F' => {
+                self.position.set_row(0);
+                self.position.set_column(if csi.function == 'E' {
+                    column + count
+                } else {
+                    column.saturating_sub(count)
+                });
+            }
+            'G' => self.position.set_row(count - 1),
+            'd' => self.position.set_column(count - 1),
+            'H' | 'f' => {
+                self.position.set_column(count - 1);
+                self.position.set_row(csi.param(1, 1) as usize - 1);
+            }
+            'J' => self.erase_display(csi.param(0, 0)),
+            'K' => self.erase_line(csi.param(0, 0)),
+            _ => {}
+        }
+    }
+
+    /// Erases from the cursor to the end (0), from the start to the cursor (1) or the whole screen (2 and 3).
+    fn erase_display(&mut self, mode: u16) {
+        let column = self.position.column();
+        let lines = self.position.max_columns() + 1;
+
+        match mode {
+            0 => {
+                self.erase_line(0);
+                self.erase_lines(column + 1, lines);
+            }
+            1 => {
+                self.erase_lines(0, column);
+                self.erase_line(1);
             }
+            2 | 3 => self.erase_lines(0, lines),
+            _ => {}
+        }
+    }
+
+    /// Erases from the cursor to the end (0), from the start to the cursor (1) or the whole line (2).
+    fn erase_line(&mut self, mode: u16) {
+        let row = self.position.row();
+        let (start, end) = match mode {
+            0 => (row, self.position.max_rows()),
+            1 => (0, row + 1),
+            2 => (0, self.position.max_rows()),
+            _ => return,
+        };
+
+        self.erase(
+            BORDER_PADDING + (start * RASTER_WIDTH),
+            BORDER_PADDING + (self.position.column() * LINE_HEIGHT),
+            (end - start) * RASTER_WIDTH,
+            LINE_HEIGHT,
+        );
+    }
+
+    /// Erases the lines from `start` to `end` (exclusive).
+    fn erase_lines(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.erase(
+                BORDER_PADDING,
+                BORDER_PADDING + (start * LINE_HEIGHT),
+                self.position.max_rows() * RASTER_WIDTH,
+                (end - start) * LINE_HEIGHT,
+            );
+        }
+    }
+
+    /// Fills the rectangle with the current background color.
+    fn erase(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        if let Some(fb) = &mut self.framebuffer {
+            let color = fb.encode_color(self.style.background());
+            fb.fill(x, y, width, height, color);
         }
     }
 }
@@ -146,22 +282,11 @@ impl Write for TerminalOutput {
             return Ok(());
         }
 
-        if self.sequence.try_process(c) {
-            return Ok(());
-        }
-
-        match c {
-            '\r' => {}
-            '\n' => self.newline(),
-            '\x1b' => self.sequence.start(),
-            _ if (c as u32) < 32 => {}
-            _ => {
-                if let Some(c) = get_raster(c, FontWeight::Regular, RASTER_HEIGHT) {
-                    self.write_rasterized_char(c)
-                } else if let Some(c) = get_raster('?', FontWeight::Regular, RASTER_HEIGHT) {
-                    self.write_rasterized_char(c)
-                }
-            }
+        match self.parser.advance(c) {
+            Action::None => {}
+            Action::Print(c) => self.print(c),
+            Action::Execute(c) => self.execute(c),
+            Action::Csi(csi) => self.execute_csi(&csi),
         }
         Ok(())
     }
@@ -6,6 +6,9 @@
 //!
 //! The theme is compose out of 8 normal or dark colors and 8 bright colors.
 //! Each being able to be set as either the foreground or background color.
+//!
+//! Together with the 6x6x6 color cube and the 24 grays of xterm, they make up the 256 colors
+//! selected with [`indexed_color`].
 
 use microdragon_interface::macros::config;
 
@@ -15,39 +18,80 @@ pub const DEFAULT_FG_COLOR: (u8, u8, u8) = split_color(config!("theme.default_fg
 /// The default background color used.
 pub const DEFAULT_BG_COLOR: (u8, u8, u8) = split_color(config!("theme.default_bg", 0x0C0C0C));
 
-/// Get a normal or dark color based on the given char.
-/// The char is the last digit of it's ansi escape sequence.
-pub const fn get_color(c: char) -> (u8, u8, u8) {
-    match c {
-        '0' => split_color(config!("theme.black", 0x0C0C0C)),
-        '1' => split_color(config!("theme.red", 0xC50F1E)),
-        '2' => split_color(config!("theme.green", 0x13A10E)),
-        '3' => split_color(config!("theme.yellow", 0xC19A00)),
-        '4' => split_color(config!("theme.blue", 0x0037DA)),
-        '5' => split_color(config!("theme.magenta", 0x891798)),
-        '6' => split_color(config!("theme.cyan", 0x3A96DD)),
-        '7' => split_color(config!("theme.white", 0xCCCCCC)),
+/// The levels of red, green and blue in the color cube.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Get a normal or dark color based on the given index.
+/// The index is the last digit of it's ansi escape sequence.
+pub const fn get_color(index: u8) -> (u8, u8, u8) {
+    match index {
+        0 => split_color(config!("theme.black", 0x0C0C0C)),
+        1 => split_color(config!("theme.red", 0xC50F1E)),
+        2 => split_color(config!("theme.green", 0x13A10E)),
+        3 => split_color(config!("theme.yellow", 0xC19A00)),
+        4 => split_color(config!("theme.blue", 0x0037DA)),
+        5 => split_color(config!("theme.magenta", 0x891798)),
+        6 => split_color(config!("theme.cyan", 0x3A96DD)),
+        7 => split_color(config!("theme.white", 0xCCCCCC)),
         _ => DEFAULT_BG_COLOR,
     }
 }
 
-/// Get a bright color based on the given char.
-/// The char is the last digit of it's ansi escape sequence.
-pub const fn get_bright_color(c: char) -> (u8, u8, u8) {
-    match c {
-        '0' => split_color(config!("theme.bright_black", 0x767676)),
-        '1' => split_color(config!("theme.bright_red", 0xE74855)),
-        '2' => split_color(config!("theme.bright_green", 0x15C60C)),
-        '3' => split_color(config!("theme.bright_yellow", 0xF9F1A5)),
-        '4' => split_color(config!("theme.bright_blue", 0x3B79FF)),
-        '5' => split_color(config!("theme.bright_magenta", 0xB4009F)),
-        '6' => split_color(config!("theme.bright_cyan", 0x61D6D6)),
-        '7' => split_color(config!("theme.bright_white", 0xF2F2F2)),
+/// Get a bright color based on the given index.
+/// The index is the last digit of it's ansi escape sequence.
+pub const fn get_bright_color(index: u8) -> (u8, u8, u8) {
+    match index {
+        0 => split_color(config!("theme.bright_black", 0x767676)),
+        1 => split_color(config!("theme.bright_red", 0xE74855)),
+        2 => split_color(config!("theme.bright_green", 0x15C60C)),
+        3 => split_color(config!("theme.bright_yellow", 0xF9F1A5)),
+        4 => split_color(config!("theme.bright_blue", 0x3B79FF)),
+        5 => split_color(config!("theme.bright_magenta", 0xB4009F)),
+        6 => split_color(config!("theme.bright_cyan", 0x61D6D6)),
+        7 => split_color(config!("theme.bright_white", 0xF2F2F2)),
         _ => DEFAULT_FG_COLOR,
     }
 }
 
+/// Get one of the 256 colors.
+/// The first 16 are the normal and bright colors of the theme, followed by the color cube and the grays.
+pub const fn indexed_color(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=7 => get_color(index),
+        8..=15 => get_bright_color(index - 8),
+        16..=231 => {
+            let index = (index - 16) as usize;
+            (
+                CUBE_LEVELS[index / 36],
+                CUBE_LEVELS[index / 6 % 6],
+                CUBE_LEVELS[index % 6],
+            )
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
 /// Slips a 32-bit hex color value into three u8 color bytes for RGB respectively.
 const fn split_color(color: u32) -> (u8, u8, u8) {
     ((color >> 16) as u8, (color >> 8) as u8, color as u8)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{get_bright_color, get_color, indexed_color};
+
+    #[test]
+    fn test_indexed_color() {
+        assert_eq!(indexed_color(1), get_color(1));
+        assert_eq!(indexed_color(15), get_bright_color(7));
+        assert_eq!(indexed_color(16), (0, 0, 0));
+        assert_eq!(indexed_color(196), (255, 0, 0));
+        assert_eq!(indexed_color(208), (255, 135, 0));
+        assert_eq!(indexed_color(231), (255, 255, 255));
+        assert_eq!(indexed_color(232), (8, 8, 8));
+        assert_eq!(indexed_color(255), (238, 238, 238));
+    }
+}
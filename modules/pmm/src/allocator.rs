@@ -0,0 +1,468 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use crate::region::{MemoryRegion, MemoryRegionKind};
+use common::addr::PhysAddr;
+use core::ptr::NonNull;
+use core::slice;
+
+/// Size of the smallest frame, every other frame size is a multiple of it.
+const FRAME_SIZE: u64 = 4096;
+
+/// Number of frames tracked by one word of the bitmap.
+const FRAMES_PER_WORD: usize = u64::BITS as usize;
+
+/// The sizes of physical frames the allocator can hand out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameSize {
+    /// A 4 KiB frame, used by level 1 page table entries.
+    Size4KiB,
+
+    /// A 2 MiB frame, used by level 2 page table entries.
+    Size2MiB,
+
+    /// A 1 GiB frame, used by level 3 page table entries.
+    Size1GiB,
+}
+
+impl FrameSize {
+    /// Returns the size of the frame in bytes.
+    pub const fn bytes(self) -> u64 {
+        match self {
+            FrameSize::Size4KiB => 4096,
+            FrameSize::Size2MiB => 2 * 1024 * 1024,
+            FrameSize::Size1GiB => 1024 * 1024 * 1024,
+        }
+    }
+
+    /// Returns how many 4 KiB frames make up a frame of this size.
+    const fn frames(self) -> usize {
+        (self.bytes() / FRAME_SIZE) as usize
+    }
+}
+
+/// Statistics about the physical memory, all values are in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Statistics {
+    /// Memory that is managed by the allocator, free or used.
+    pub usable: u64,
+
+    /// Memory that can still be allocated.
+    pub free: u64,
+
+    /// Memory that is allocated, including the allocator's own bitmap.
+    pub used: u64,
+
+    /// Memory that isn't managed by the allocator, because it's reserved by the firmware, bootloader or hardware.
+    pub reserved: u64,
+}
+
+/// Bitmap based physical frame allocator.
+/// Every 4 KiB frame up to the end of the highest usable region is tracked by one bit, set bits are in use.
+/// A second bitmap of the same size has the bits of the frames set, which can be handed out and thus freed.
+/// Both bitmaps are placed in the first usable region big enough to hold them.
+///
+/// The first frame is never handed out, so a null address never refers to an allocation.
+pub struct FrameAllocator {
+    bitmap: NonNull<u64>,
+    bitmap_address: PhysAddr,
+    words: usize,
+    frames: usize,
+    usable: u64,
+    free: u64,
+    reserved: u64,
+    next: usize,
+}
+
+impl FrameAllocator {
+    /// Creates a frame allocator managing all [`MemoryRegionKind::Usable`] regions.
+    /// `map` is used to convert the physical address of the bitmap into a pointer.
+    /// Returns `None` if no usable region can hold the bitmap.
+    ///
+    /// ## Safety
+    ///
+    /// The regions have to describe the physical memory correctly and `map` has to return a pointer,
+    /// that is valid for reads and writes to the given physical memory.
+    pub unsafe fn new(
+        regions: impl Iterator<Item = MemoryRegion> + Clone,
+        map: impl FnOnce(PhysAddr) -> *mut u64,
+    ) -> Option<Self> {
+        let mut highest = 0;
+        let mut reserved = 0;
+        for region in regions.clone() {
+            if region.kind == MemoryRegionKind::Usable {
+                highest = highest.max(align_down(region.end));
+            } else {
+                reserved += region.len();
+            }
+        }
+
+        let frames = (highest / FRAME_SIZE) as usize;
+        let words = frames.div_ceil(FRAMES_PER_WORD);
+        let bitmap_size = align_up((2 * words * 8) as u64);
+
+        let bitmap_address = regions
+            .clone()
+            .filter(|x| x.kind == MemoryRegionKind::Usable)
+            .map(|x| (align_up(x.start).max(FRAME_SIZE), align_down(x.end)))
+            .find(|(start, end)| start + bitmap_size <= *end)
+            .map(|(start, _)| PhysAddr::new(start))?;
+
+        let bitmap = NonNull::new(map(bitmap_address))?;
+        let mut allocator = FrameAllocator {
+            bitmap,
+            bitmap_address,
+            words,
+            frames,
+            usable: 0,
+            free: 0,
+            reserved,
+            next: 0,
+        };
+
+        // Start with everything in use and only release usable memory.
+        allocator.bitmap().fill(u64::MAX);
+        allocator.allocatable().fill(0);
+        for region in regions.filter(|x| x.kind == MemoryRegionKind::Usable) {
+            let start = align_up(region.start);
+            let end = align_down(region.end);
+            if start >= end {
+                continue;
+            }
+
+            let (index, count) = (frame_index(start), frame_index(end - start));
+            allocator.set_range(index, count, false);
+            set_bits(allocator.allocatable(), index, count, true);
+            allocator.usable += end - start;
+            allocator.free += end - start;
+        }
+
+        allocator.mark_used(0, 1);
+        allocator.mark_used(
+            frame_index(bitmap_address.as_u64()),
+            frame_index(bitmap_size),
+        );
+
+        Some(allocator)
+    }
+
+    /// Moves the bitmap to a new virtual address, after the mapping of physical memory changed.
+    ///
+    /// ## Safety
+    ///
+    /// `map` has to return a pointer, that is valid for reads and writes to the given physical memory.
+    pub unsafe fn rewire(&mut self, map: impl FnOnce(PhysAddr) -> *mut u64) {
+        self.bitmap = NonNull::new(map(self.bitmap_address)).expect("Bitmap mapped to null");
+    }
+
+    /// Allocates a frame of the given size, aligned to it's size.
+    /// Returns `None` if no free frame of that size is available.
+    pub fn allocate(&mut self, size: FrameSize) -> Option<PhysAddr> {
+        let index = match size {
+            FrameSize::Size4KiB => self.find_frame()?,
+            _ => self.find_chunk(size.frames() / FRAMES_PER_WORD)?,
+        };
+
+        self.set_range(index, size.frames(), true);
+        self.free -= size.bytes();
+
+        Some(PhysAddr::new(index as u64 * FRAME_SIZE))
+    }
+
+    /// Frees a frame previously returned by [`FrameAllocator::allocate`] with the same size.
+    /// Panics if the frame is misaligned, not usable memory managed by the allocator or not allocated.
+    pub fn free(&mut self, address: PhysAddr, size: FrameSize) {
+        assert!(
+            address.is_aligned(size.bytes()),
+            "Freed frame {:#x} is not aligned to it's size",
+            address
+        );
+
+        let index = frame_index(address.as_u64());
+        assert!(
+            index + size.frames() <= self.frames
+                && are_bits_set(self.allocatable(), index, size.frames()),
+            "Freed frame {:#x} is not managed by the allocator",
+            address
+        );
+        assert!(
+            self.is_range_used(index, size.frames()),
+            "Freed frame {:#x} is not allocated, double free?",
+            address
+        );
+
+        self.set_range(index, size.frames(), false);
+        self.free += size.bytes();
+        self.next = self.next.min(index / FRAMES_PER_WORD);
+    }
+
+    /// Returns statistics about the managed memory.
+    pub fn statistics(&self) -> Statistics {
+        Statistics {
+            usable: self.usable,
+            free: self.free,
+            used: self.usable - self.free,
+            reserved: self.reserved,
+        }
+    }
+
+    /// Returns the bitmap of used frames as a slice.
+    fn bitmap(&mut self) -> &mut [u64] {
+        // Safety: The bitmaps are valid for `2 * words` entries, as required by `new` and `rewire`.
+        unsafe { slice::from_raw_parts_mut(self.bitmap.as_ptr(), self.words) }
+    }
+
+    /// Returns the bitmap of frames, which can be handed out, as a slice.
+    fn allocatable(&mut self) -> &mut [u64] {
+        // Safety: The bitmaps are valid for `2 * words` entries, as required by `new` and `rewire`.
+        unsafe { slice::from_raw_parts_mut(self.bitmap.as_ptr().add(self.words), self.words) }
+    }
+
+    /// Finds a single free frame, starting at the word of the last allocation.
+    fn find_frame(&mut self) -> Option<usize> {
+        let start = self.next;
+        let bitmap = self.bitmap();
+
+        let word = (start..bitmap.len())
+            .chain(0..start)
+            .find(|x| bitmap[*x] != u64::MAX)?;
+        let index = word * FRAMES_PER_WORD + bitmap[word].trailing_ones() as usize;
+
+        self.next = word;
+        Some(index)
+    }
+
+    /// Finds `words` consecutive free words of the bitmap, aligned to `words`.
+    fn find_chunk(&mut self, words: usize) -> Option<usize> {
+        self.bitmap()
+            .chunks_exact(words)
+            .position(|x| x.iter().all(|x| *x == 0))
+            .map(|x| x * words * FRAMES_PER_WORD)
+    }
+
+    /// Marks a range of usable frames as used for good, updating the free count.
+    fn mark_used(&mut self, index: usize, count: usize) {
+        for index in index..index + count {
+            if !self.is_range_used(index, 1) {
+                self.set_range(index, 1, true);
+                self.free -= FRAME_SIZE;
+            }
+        }
+        set_bits(self.allocatable(), index, count, false);
+    }
+
+    /// Returns whenever all frames in the range are in use.
+    fn is_range_used(&mut self, index: usize, count: usize) -> bool {
+        are_bits_set(self.bitmap(), index, count)
+    }
+
+    /// Marks the range of frames as either used or free.
+    fn set_range(&mut self, index: usize, count: usize, used: bool) {
+        set_bits(self.bitmap(), index, count, used);
+    }
+}
+
+unsafe impl Send for FrameAllocator {}
+
+/// Returns whenever all bits of the range are set.
+fn are_bits_set(bitmap: &[u64], index: usize, count: usize) -> bool {
+    (index..index + count).all(|x| bitmap[x / FRAMES_PER_WORD] & (1 << (x % FRAMES_PER_WORD)) != 0)
+}
+
+/// Sets or clears the range of bits.
+fn set_bits(bitmap: &mut [u64], mut index: usize, mut count: usize, set: bool) {
+    while count > 0 {
+        let bit = index % FRAMES_PER_WORD;
+        let bits = count.min(FRAMES_PER_WORD - bit);
+        let mask = if bits == FRAMES_PER_WORD {
+            u64::MAX
+        } else {
+            ((1 << bits) - 1) << bit
+        };
+
+        if set {
+            bitmap[index / FRAMES_PER_WORD] |= mask;
+        } else {
+            bitmap[index / FRAMES_PER_WORD] &= !mask;
+        }
+
+        index += bits;
+        count -= bits;
+    }
+}
+
+/// Returns the index of the frame containing `address`.
+const fn frame_index(address: u64) -> usize {
+    (address / FRAME_SIZE) as usize
+}
+
+/// Aligns the address up to the next frame.
+const fn align_up(address: u64) -> u64 {
+    (address + FRAME_SIZE - 1) & !(FRAME_SIZE - 1)
+}
+
+/// Aligns the address down to the previous frame.
+const fn align_down(address: u64) -> u64 {
+    address & !(FRAME_SIZE - 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FrameAllocator, FrameSize};
+    use crate::region::{MemoryRegion, MemoryRegionKind};
+    use common::addr::PhysAddr;
+    use common::memory::{MemoryInfo, MEMORY_INFO};
+
+    const MIB: u64 = 1024 * 1024;
+    const GIB: u64 = 1024 * MIB;
+
+    fn region(start: u64, end: u64, kind: MemoryRegionKind) -> MemoryRegion {
+        MemoryRegion { start, end, kind }
+    }
+
+    /// Sets up the memory info needed by [`PhysAddr`].
+    fn setup() {
+        MEMORY_INFO.get_or_init(|| MemoryInfo {
+            virtual_address_bits: 48,
+            physical_address_bits: 52,
+            page_table_entry_address_mask: 0x000ffffffffff000,
+            highest_page_table_level: 4,
+        });
+    }
+
+    /// Creates an allocator over `regions`, with the bitmap placed in host memory.
+    fn allocator(regions: &[MemoryRegion]) -> FrameAllocator {
+        setup();
+
+        let buffer = vec![0u64; 1024 * 1024].leak();
+        unsafe { FrameAllocator::new(regions.iter().copied(), |_| buffer.as_mut_ptr()) }.unwrap()
+    }
+
+    #[test]
+    fn test_statistics() {
+        let allocator = allocator(&[
+            region(0, 0x9F000, MemoryRegionKind::Usable),
+            region(0x9F000, 0x100000, MemoryRegionKind::Reserved),
+            region(0x100000, 64 * MIB, MemoryRegionKind::Usable),
+            region(64 * MIB, 65 * MIB, MemoryRegionKind::AcpiNvs),
+        ]);
+
+        let stats = allocator.statistics();
+        assert_eq!(stats.usable, 0x9F000 + 63 * MIB);
+        assert_eq!(stats.reserved, 0x61000 + MIB);
+        // The null frame and one frame of bitmap are in use.
+        assert_eq!(stats.used, 2 * 4096);
+        assert_eq!(stats.free, stats.usable - stats.used);
+    }
+
+    #[test]
+    fn test_allocate_4kib() {
+        let mut allocator = allocator(&[
+            region(0, 0x4000, MemoryRegionKind::Usable),
+            region(0x4000, 0x8000, MemoryRegionKind::Kernel),
+            region(0x8000, 0xA000, MemoryRegionKind::Usable),
+        ]);
+
+        // Frame 0 is never handed out and frame 1 holds the bitmap.
+        let frames = [
+            allocator.allocate(FrameSize::Size4KiB),
+            allocator.allocate(FrameSize::Size4KiB),
+            allocator.allocate(FrameSize::Size4KiB),
+            allocator.allocate(FrameSize::Size4KiB),
+        ];
+        assert_eq!(
+            frames.map(|x| x.map(PhysAddr::as_u64)),
+            [Some(0x2000), Some(0x3000), Some(0x8000), Some(0x9000)]
+        );
+        assert_eq!(allocator.allocate(FrameSize::Size4KiB), None);
+        assert_eq!(allocator.statistics().free, 0);
+
+        allocator.free(PhysAddr::new(0x3000), FrameSize::Size4KiB);
+        assert_eq!(
+            allocator.allocate(FrameSize::Size4KiB),
+            Some(PhysAddr::new(0x3000))
+        );
+    }
+
+    #[test]
+    fn test_allocate_2mib() {
+        let mut allocator = allocator(&[
+            region(0x1000, 3 * MIB, MemoryRegionKind::Usable),
+            region(3 * MIB, 5 * MIB, MemoryRegionKind::Framebuffer),
+            region(5 * MIB, 9 * MIB, MemoryRegionKind::Usable),
+        ]);
+
+        // The first 2 MiB contain the bitmap and the range from 2 to 4 MiB isn't fully usable.
+        assert_eq!(
+            allocator.allocate(FrameSize::Size2MiB),
+            Some(PhysAddr::new(6 * MIB))
+        );
+        assert_eq!(allocator.allocate(FrameSize::Size2MiB), None);
+
+        let free = allocator.statistics().free;
+        allocator.free(PhysAddr::new(6 * MIB), FrameSize::Size2MiB);
+        assert_eq!(allocator.statistics().free, free + 2 * MIB);
+    }
+
+    #[test]
+    fn test_allocate_1gib() {
+        let mut allocator = allocator(&[
+            region(0x1000, 3 * GIB, MemoryRegionKind::Usable),
+            region(3 * GIB, 4 * GIB, MemoryRegionKind::Reserved),
+        ]);
+
+        assert_eq!(
+            allocator.allocate(FrameSize::Size1GiB),
+            Some(PhysAddr::new(GIB))
+        );
+        assert_eq!(
+            allocator.allocate(FrameSize::Size1GiB),
+            Some(PhysAddr::new(2 * GIB))
+        );
+        assert_eq!(allocator.allocate(FrameSize::Size1GiB), None);
+
+        // Smaller frames still fit into the first GiB.
+        assert!(allocator.allocate(FrameSize::Size2MiB).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "double free")]
+    fn test_double_free() {
+        let mut allocator = allocator(&[region(0, MIB, MemoryRegionKind::Usable)]);
+
+        let frame = allocator.allocate(FrameSize::Size4KiB).unwrap();
+        allocator.free(frame, FrameSize::Size4KiB);
+        allocator.free(frame, FrameSize::Size4KiB);
+    }
+
+    #[test]
+    #[should_panic(expected = "not managed")]
+    fn test_free_reserved() {
+        let mut allocator = allocator(&[
+            region(0, MIB, MemoryRegionKind::Usable),
+            region(MIB, 2 * MIB, MemoryRegionKind::Reserved),
+            region(2 * MIB, 3 * MIB, MemoryRegionKind::Usable),
+        ]);
+
+        allocator.free(PhysAddr::new(MIB), FrameSize::Size4KiB);
+    }
+
+    #[test]
+    #[should_panic(expected = "not managed")]
+    fn test_free_bitmap() {
+        let mut allocator = allocator(&[region(0, MIB, MemoryRegionKind::Usable)]);
+
+        // Frame 1 holds the bitmaps.
+        allocator.free(PhysAddr::new(0x1000), FrameSize::Size4KiB);
+    }
+
+    #[test]
+    fn test_no_space_for_bitmap() {
+        setup();
+
+        let regions = [region(0, 0x1000, MemoryRegionKind::Usable)];
+        let allocator =
+            unsafe { FrameAllocator::new(regions.iter().copied(), |_| core::ptr::null_mut()) };
+        assert!(allocator.is_none());
+    }
+}
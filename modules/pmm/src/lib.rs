@@ -0,0 +1,133 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Physical Memory Manager (PMM) Module
+//!
+//! The PMM keeps track of which physical memory is free and hands it out in frames of 4 KiB, 2 MiB or 1 GiB.
+//! It's built from the memory map supplied by the bootloader, supporting both the Limine and the Rust bootloader layout.
+//! Only memory marked as usable is ever handed out, everything else is counted as reserved.
+//! Memory used by the bootloader is not reclaimed, since the bootloader's page tables are still in use.
+//!
+#![cfg_attr(not(test), no_std)]
+
+mod allocator;
+mod region;
+
+pub use allocator::*;
+pub use region::*;
+
+use common::addr::PhysAddr;
+use common::interrupts;
+use common::memory::{physical_to_virtual, MemoryInfo, MEMORY_INFO};
+use common::paging::{self, PhysFrame};
+use common::sync::Spinlock;
+use log::{debug, info, log_enabled, warn, Level};
+use microdragon_interface::macros::init;
+use microdragon_interface::ModuleInterface;
+
+/// The global physical frame allocator, `None` until [`init`] ran.
+static PHYSICAL_MEMORY: Spinlock<Option<FrameAllocator>> = Spinlock::new(None);
+
+/// Entrypoint to the PMM module.
+#[init]
+pub fn init(interface: &ModuleInterface) {
+    // The PMM is the first part of the memory subsystem to run,
+    // so it publishes the MMU info needed to work with addresses.
+    MEMORY_INFO.get_or_init(|| MemoryInfo {
+        virtual_address_bits: interface.memory_info.virtual_address_bits,
+        physical_address_bits: interface.memory_info.physical_address_bits,
+        page_table_entry_address_mask: interface.memory_info.page_table_entry_address_mask,
+        highest_page_table_level: interface.memory_info.highest_page_table_level,
+    });
+
+    let mut guard = PHYSICAL_MEMORY.lock();
+    if guard.is_some() {
+        warn!("PMM Kernel Module already initialized");
+        return;
+    }
+
+    // Safety: We assume the memory map given by the interface is valid.
+    let regions = unsafe { MemoryRegions::new(&interface.memory_map_info) };
+    if log_enabled!(Level::Debug) {
+        for region in regions.clone() {
+            debug!(
+                "Memory Region {:#x} - {:#x} {:?}",
+                region.start, region.end, region.kind
+            );
+        }
+    }
+
+    // Safety: Until the KMM takes over, all physical memory is mapped at the offset given by the bootloader.
+    let offset = interface.memory_map_info.physical_memory_offset;
+    let Some(allocator) =
+        (unsafe { FrameAllocator::new(regions, |x| (offset + x.as_u64()) as *mut u64) })
+    else {
+        warn!("Not enough usable memory for the physical frame allocator");
+        return;
+    };
+
+    let statistics = allocator.statistics();
+    info!(
+        "Physical Memory: {} KiB usable, {} KiB free, {} KiB reserved",
+        statistics.usable / 1024,
+        statistics.free / 1024,
+        statistics.reserved / 1024
+    );
+
+    *guard = Some(allocator);
+}
+
+/// Called after the kernel memory manager (KMM) has been initialized to move the bitmap into the direct mapped memory area.
+#[init]
+pub fn rewire(_: &ModuleInterface) {
+    let _guard = interrupts::disable();
+    if let Some(allocator) = PHYSICAL_MEMORY.lock().as_mut() {
+        // Safety: The direct mapped memory area maps all physical memory.
+        unsafe { allocator.rewire(|x| physical_to_virtual(x).as_mut_ptr()) };
+    }
+
+    info!("PMM rewired");
+}
+
+/// Allocates a physical frame of the given size, aligned to it's size.
+/// Returns `None` if no frame of that size is available or the PMM isn't initialized.
+pub fn allocate(size: FrameSize) -> Option<PhysAddr> {
+    let _guard = interrupts::disable();
+    PHYSICAL_MEMORY.lock().as_mut()?.allocate(size)
+}
+
+/// Allocates a 4 KiB physical frame.
+/// Returns `None` if no frame is available or the PMM isn't initialized.
+pub fn allocate_frame() -> Option<PhysAddr> {
+    allocate(FrameSize::Size4KiB)
+}
+
+/// Hands out 4 KiB frames of the global PMM, for use with [`paging::Mapper`].
+pub struct GlobalFrameAllocator;
+
+impl paging::FrameAllocator for GlobalFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame> {
+        // Safety: 4 KiB frames are aligned to their size.
+        allocate_frame().map(|x| unsafe { PhysFrame::from_start_address_unchecked(x) })
+    }
+}
+
+/// Frees a physical frame previously returned by [`allocate`] with the same size.
+/// Panics if the frame wasn't allocated.
+pub fn free(address: PhysAddr, size: FrameSize) {
+    let _guard = interrupts::disable();
+    PHYSICAL_MEMORY
+        .lock()
+        .as_mut()
+        .expect("PMM not initialized")
+        .free(address, size)
+}
+
+/// Returns statistics about the physical memory or `None` if the PMM isn't initialized.
+pub fn statistics() -> Option<Statistics> {
+    let _guard = interrupts::disable();
+    PHYSICAL_MEMORY
+        .lock()
+        .as_ref()
+        .map(FrameAllocator::statistics)
+}
@@ -0,0 +1,266 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use core::slice;
+use microdragon_interface::memory::{MemoryMapInfo, MemoryMapType};
+
+/// A bootloader independent entry of the memory map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRegion {
+    /// Physical start address of the region.
+    pub start: u64,
+
+    /// Physical end address (exclusive) of the region.
+    pub end: u64,
+
+    /// What the region is used for.
+    pub kind: MemoryRegionKind,
+}
+
+impl MemoryRegion {
+    /// Returns the length of the region in bytes.
+    pub const fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Returns whenever the region is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// The usage of a [`MemoryRegion`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryRegionKind {
+    /// Free memory, that can be handed out by the allocator.
+    Usable,
+
+    /// Memory used by the bootloader, like it's page tables and the boot info.
+    /// The Rust bootloader also places the kernel in these regions.
+    Bootloader,
+
+    /// Memory containing the ACPI tables, that can be reclaimed once they are no longer needed.
+    AcpiReclaimable,
+
+    /// Memory reserved by ACPI.
+    AcpiNvs,
+
+    /// Memory containing the kernel and it's modules.
+    Kernel,
+
+    /// Memory of the framebuffer.
+    Framebuffer,
+
+    /// Memory that is unusable due to physical damage.
+    BadMemory,
+
+    /// Memory reserved for an unknown reason.
+    Reserved,
+}
+
+/// Memory map entry as laid out by the Limine bootloader.
+#[repr(C)]
+pub(crate) struct LimineEntry {
+    pub base: u64,
+    pub length: u64,
+    pub entry_type: u64,
+}
+
+impl LimineEntry {
+    /// Converts the entry into a [`MemoryRegion`].
+    fn region(&self) -> MemoryRegion {
+        let kind = match self.entry_type {
+            0 => MemoryRegionKind::Usable,
+            2 => MemoryRegionKind::AcpiReclaimable,
+            3 => MemoryRegionKind::AcpiNvs,
+            4 => MemoryRegionKind::BadMemory,
+            5 => MemoryRegionKind::Bootloader,
+            6 => MemoryRegionKind::Kernel,
+            7 => MemoryRegionKind::Framebuffer,
+            _ => MemoryRegionKind::Reserved,
+        };
+
+        MemoryRegion {
+            start: self.base,
+            end: self.base + self.length,
+            kind,
+        }
+    }
+}
+
+/// Memory map entry as laid out by the Rust bootloader.
+#[repr(C)]
+pub(crate) struct RustRegion {
+    pub start: u64,
+    pub end: u64,
+    pub kind: u32,
+    pub tag: u32,
+}
+
+impl RustRegion {
+    /// Converts the entry into a [`MemoryRegion`].
+    fn region(&self) -> MemoryRegion {
+        let kind = match self.kind {
+            0 => MemoryRegionKind::Usable,
+            1 => MemoryRegionKind::Bootloader,
+            _ => MemoryRegionKind::Reserved,
+        };
+
+        MemoryRegion {
+            start: self.start,
+            end: self.end,
+            kind,
+        }
+    }
+}
+
+/// Iterator over the entries of the memory map passed to the modules.
+#[derive(Clone)]
+pub struct MemoryRegions<'a>(RegionsInner<'a>);
+
+#[derive(Clone)]
+enum RegionsInner<'a> {
+    Limine(slice::Iter<'a, &'a LimineEntry>),
+    Rust(slice::Iter<'a, RustRegion>),
+}
+
+impl<'a> MemoryRegions<'a> {
+    /// Creates an iterator over the memory map described by `info`.
+    ///
+    /// ## Safety
+    ///
+    /// The memory map pointer has to point to `memory_map_count` valid entries of the given type.
+    pub unsafe fn new(info: &'a MemoryMapInfo) -> Self {
+        MemoryRegions(match info.memory_map_type {
+            MemoryMapType::Limine => RegionsInner::Limine(
+                slice::from_raw_parts(
+                    info.memory_map as *const &LimineEntry,
+                    info.memory_map_count,
+                )
+                .iter(),
+            ),
+            MemoryMapType::Rust => RegionsInner::Rust(
+                slice::from_raw_parts(info.memory_map as *const RustRegion, info.memory_map_count)
+                    .iter(),
+            ),
+        })
+    }
+}
+
+impl<'a> Iterator for MemoryRegions<'a> {
+    type Item = MemoryRegion;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            RegionsInner::Limine(x) => x.next().map(|x| x.region()),
+            RegionsInner::Rust(x) => x.next().map(|x| x.region()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LimineEntry, MemoryRegion, MemoryRegionKind, MemoryRegions, RustRegion};
+    use microdragon_interface::memory::{MemoryMapInfo, MemoryMapType};
+
+    #[test]
+    fn test_limine_memory_map() {
+        let entries = [
+            LimineEntry {
+                base: 0,
+                length: 0x9F000,
+                entry_type: 0,
+            },
+            LimineEntry {
+                base: 0x100000,
+                length: 0x100000,
+                entry_type: 6,
+            },
+            LimineEntry {
+                base: 0xFD000000,
+                length: 0x300000,
+                entry_type: 7,
+            },
+            LimineEntry {
+                base: 0xFFFC0000,
+                length: 0x40000,
+                entry_type: 1,
+            },
+        ];
+        let pointers = entries.each_ref();
+        let info = MemoryMapInfo {
+            memory_map: pointers.as_ptr() as u64,
+            memory_map_count: pointers.len(),
+            memory_map_type: MemoryMapType::Limine,
+            physical_memory_offset: 0,
+        };
+
+        let mut regions = unsafe { MemoryRegions::new(&info) };
+        assert_eq!(
+            regions.next(),
+            Some(MemoryRegion {
+                start: 0,
+                end: 0x9F000,
+                kind: MemoryRegionKind::Usable
+            })
+        );
+        assert_eq!(
+            regions.next(),
+            Some(MemoryRegion {
+                start: 0x100000,
+                end: 0x200000,
+                kind: MemoryRegionKind::Kernel
+            })
+        );
+        assert_eq!(
+            regions.next().map(|x| x.kind),
+            Some(MemoryRegionKind::Framebuffer)
+        );
+        assert_eq!(
+            regions.next().map(|x| x.kind),
+            Some(MemoryRegionKind::Reserved)
+        );
+        assert_eq!(regions.next(), None);
+    }
+
+    #[test]
+    fn test_rust_memory_map() {
+        let entries = [
+            RustRegion {
+                start: 0x1000,
+                end: 0x9F000,
+                kind: 0,
+                tag: 0,
+            },
+            RustRegion {
+                start: 0x100000,
+                end: 0x180000,
+                kind: 1,
+                tag: 0,
+            },
+            RustRegion {
+                start: 0x180000,
+                end: 0x200000,
+                kind: 2,
+                tag: 9,
+            },
+        ];
+        let info = MemoryMapInfo {
+            memory_map: entries.as_ptr() as u64,
+            memory_map_count: entries.len(),
+            memory_map_type: MemoryMapType::Rust,
+            physical_memory_offset: 0,
+        };
+
+        let regions = unsafe { MemoryRegions::new(&info) };
+        let kinds = regions.map(|x| (x.len(), x.kind)).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (0x9E000, MemoryRegionKind::Usable),
+                (0x80000, MemoryRegionKind::Bootloader),
+                (0x80000, MemoryRegionKind::Reserved),
+            ]
+        );
+    }
+}
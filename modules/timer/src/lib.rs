@@ -0,0 +1,210 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Timer Module
+//!
+//! The timer module provides kernel timers and sleeping on top of the time base of [`common::time`].
+//! All times are nanoseconds of [`common::time::monotonic_nanoseconds`].
+//!
+//! - [`start_oneshot`] and [`start_periodic`] start timers calling a callback, until they are [`cancel`]ed.
+//!   The callbacks run in the interrupt handler of the clock event, so they must not sleep.
+//! - [`sleep_until`] and [`sleep_for`] halt the processor until the deadline.
+//!   They spin instead, while interrupts are disabled or no clock event is available.
+//!
+//! All timers are kept in a single queue ordered by their deadline.
+//! The clock event is programmed for the earliest deadline and runs the expired timers on its interrupt.
+//!
+#![cfg_attr(not(test), no_std)]
+
+mod queue;
+
+use common::sync::Spinlock;
+use common::time::{clock_event, monotonic_nanoseconds};
+use core::sync::atomic::{AtomicU64, Ordering};
+use log::{info, warn};
+use microdragon_interface::macros::init;
+use microdragon_interface::ModuleInterface;
+use queue::{Entry, TimerQueue};
+
+/// The maximal number of timers started at once.
+const MAX_TIMERS: usize = 256;
+
+/// The maximal delay the clock event is programmed for, which every clock event can represent.
+/// Deadlines further away are reached by reprogramming on every event.
+const MAX_PROGRAM_DELAY: u64 = 100_000_000;
+
+/// Called when a timer expires, with the data given when starting the timer.
+pub type TimerCallback = fn(usize);
+
+/// Identifies a started timer, to cancel it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerId(u64);
+
+/// Error returned when starting or canceling a timer fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerError {
+    /// No clock source is registered yet.
+    NoClock,
+
+    /// The maximal number of timers is started.
+    QueueFull,
+
+    /// The period of a periodic timer is zero.
+    InvalidPeriod,
+
+    /// The timer already expired or was canceled.
+    NotFound,
+}
+
+static QUEUE: Spinlock<TimerQueue<MAX_TIMERS>> = Spinlock::new(TimerQueue::new());
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Entrypoint to the timer module.
+#[init]
+pub fn init(_: &ModuleInterface) {
+    if common::time::set_clock_event_handler(run_expired).is_err() {
+        warn!("Timer Kernel Module already initialized");
+        return;
+    }
+
+    match clock_event() {
+        Some(event) => info!("Timers driven by the {} clock event", event.name()),
+        None => warn!("No clock event available, timers only expire while sleeping"),
+    }
+}
+
+/// Starts a timer calling `callback` with `data` once at `deadline`.
+pub fn start_oneshot(
+    deadline: u64,
+    callback: TimerCallback,
+    data: usize,
+) -> Result<TimerId, TimerError> {
+    monotonic_nanoseconds().ok_or(TimerError::NoClock)?;
+    start(deadline, 0, callback, data)
+}
+
+/// Starts a timer calling `callback` with `data` every `period` nanoseconds, starting one period from now.
+pub fn start_periodic(
+    period: u64,
+    callback: TimerCallback,
+    data: usize,
+) -> Result<TimerId, TimerError> {
+    if period == 0 {
+        return Err(TimerError::InvalidPeriod);
+    }
+
+    let now = monotonic_nanoseconds().ok_or(TimerError::NoClock)?;
+    start(now.saturating_add(period), period, callback, data)
+}
+
+/// Cancels the timer, its callback won't be called anymore.
+/// A callback already running on another processor isn't waited for.
+pub fn cancel(id: TimerId) -> Result<(), TimerError> {
+    let _guard = common::interrupts::disable();
+    QUEUE.lock().remove(id).ok_or(TimerError::NotFound)?;
+    Ok(())
+}
+
+/// Halts the processor until `deadline`.
+/// Returns immediately if no clock source is registered.
+pub fn sleep_until(deadline: u64) {
+    let can_wait = common::interrupts::are_enabled() && clock_event().is_some();
+
+    while monotonic_nanoseconds().is_some_and(|now| now < deadline) {
+        if !can_wait {
+            run_expired();
+            core::hint::spin_loop();
+            continue;
+        }
+
+        let _guard = common::interrupts::disable();
+        // The deadline may be earlier than all timers, or the clock event belongs to another processor.
+        let next = QUEUE.lock().next_deadline().unwrap_or(u64::MAX);
+        program(deadline.min(next));
+
+        if monotonic_nanoseconds().is_some_and(|now| now < deadline) {
+            common::interrupts::enable_and_wait();
+        }
+    }
+}
+
+/// Halts the processor for `duration` nanoseconds.
+/// Returns immediately if no clock source is registered.
+pub fn sleep_for(duration: u64) {
+    if let Some(now) = monotonic_nanoseconds() {
+        sleep_until(now.saturating_add(duration));
+    }
+}
+
+fn start(
+    deadline: u64,
+    period: u64,
+    callback: TimerCallback,
+    data: usize,
+) -> Result<TimerId, TimerError> {
+    let id = TimerId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let entry = Entry {
+        id,
+        deadline,
+        period,
+        callback,
+        data,
+    };
+
+    let _guard = common::interrupts::disable();
+    let mut queue = QUEUE.lock();
+    queue.push(entry).map_err(|_| TimerError::QueueFull)?;
+    if queue.next_deadline() == Some(deadline) {
+        program(deadline);
+    }
+    Ok(id)
+}
+
+/// Runs the callbacks of all expired timers and programs the clock event for the next deadline.
+fn run_expired() {
+    loop {
+        let entry = {
+            let _guard = common::interrupts::disable();
+            let mut queue = QUEUE.lock();
+            let Some(now) = monotonic_nanoseconds() else {
+                return;
+            };
+            let Some(entry) = queue.pop_expired(now) else {
+                if let Some(next) = queue.next_deadline() {
+                    program(next);
+                }
+                return;
+            };
+
+            if entry.period != 0 {
+                // Periods missed entirely are skipped.
+                let mut deadline = entry.deadline.saturating_add(entry.period);
+                if deadline <= now {
+                    deadline = now.saturating_add(entry.period);
+                }
+                let _ = queue.push(Entry { deadline, ..entry });
+            }
+            entry
+        };
+
+        // The lock isn't held, so callbacks can start and cancel timers.
+        (entry.callback)(entry.data);
+    }
+}
+
+/// Programs the clock event to raise an event at `deadline`, or earlier if it's too far away.
+fn program(deadline: u64) {
+    let (Some(event), Some(now)) = (clock_event(), monotonic_nanoseconds()) else {
+        return;
+    };
+
+    let delay = deadline.saturating_sub(now).min(MAX_PROGRAM_DELAY);
+    if let Err(error) = event.set_oneshot(delay) {
+        warn!(
+            "Failed to program the {} clock event: {:?}",
+            event.name(),
+            error
+        );
+    }
+}
@@ -0,0 +1,181 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! A fixed capacity min-heap of timers keyed by their deadline.
+use crate::{TimerCallback, TimerId};
+
+/// A timer waiting for its deadline.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub id: TimerId,
+
+    /// The monotonic time the timer expires at, in nanoseconds.
+    pub deadline: u64,
+
+    /// The period of a periodic timer in nanoseconds, `0` for one-shot timers.
+    pub period: u64,
+
+    pub callback: TimerCallback,
+    pub data: usize,
+}
+
+/// Error returned when the queue has no space left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueFull;
+
+/// A min-heap of `N` timers, the timer with the earliest deadline is at the root.
+pub struct TimerQueue<const N: usize> {
+    entries: [Option<Entry>; N],
+    len: usize,
+}
+
+impl<const N: usize> TimerQueue<N> {
+    pub const fn new() -> Self {
+        TimerQueue {
+            entries: [None; N],
+            len: 0,
+        }
+    }
+
+    /// Returns the earliest deadline of all timers.
+    pub fn next_deadline(&self) -> Option<u64> {
+        Some(self.entry(0)?.deadline)
+    }
+
+    pub fn push(&mut self, entry: Entry) -> Result<(), QueueFull> {
+        if self.len == N {
+            return Err(QueueFull);
+        }
+
+        self.entries[self.len] = Some(entry);
+        self.len += 1;
+        self.sift_up(self.len - 1);
+        Ok(())
+    }
+
+    /// Removes the timer with the earliest deadline, if it expired at `now`.
+    pub fn pop_expired(&mut self, now: u64) -> Option<Entry> {
+        if self.next_deadline()? > now {
+            return None;
+        }
+
+        self.remove_at(0)
+    }
+
+    /// Removes the timer with the id.
+    pub fn remove(&mut self, id: TimerId) -> Option<Entry> {
+        let index = self.entries[..self.len]
+            .iter()
+            .position(|x| x.is_some_and(|x| x.id == id))?;
+        self.remove_at(index)
+    }
+
+    fn remove_at(&mut self, index: usize) -> Option<Entry> {
+        self.len -= 1;
+        self.entries.swap(index, self.len);
+        let entry = self.entries[self.len].take();
+
+        if index < self.len {
+            self.sift_down(index);
+            self.sift_up(index);
+        }
+        entry
+    }
+
+    fn sift_up(&mut self, mut index: usize) {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if self.deadline(parent) <= self.deadline(index) {
+                break;
+            }
+
+            self.entries.swap(parent, index);
+            index = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut index: usize) {
+        loop {
+            let mut smallest = index;
+            for child in [2 * index + 1, 2 * index + 2] {
+                if child < self.len && self.deadline(child) < self.deadline(smallest) {
+                    smallest = child;
+                }
+            }
+            if smallest == index {
+                break;
+            }
+
+            self.entries.swap(smallest, index);
+            index = smallest;
+        }
+    }
+
+    fn entry(&self, index: usize) -> Option<&Entry> {
+        self.entries.get(index)?.as_ref()
+    }
+
+    fn deadline(&self, index: usize) -> u64 {
+        self.entry(index).map_or(u64::MAX, |x| x.deadline)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Entry, QueueFull, TimerId, TimerQueue};
+
+    fn entry(id: u64, deadline: u64) -> Entry {
+        Entry {
+            id: TimerId(id),
+            deadline,
+            period: 0,
+            callback: |_| {},
+            data: 0,
+        }
+    }
+
+    #[test]
+    fn test_pop_in_deadline_order() {
+        let mut queue = TimerQueue::<8>::new();
+        for (id, deadline) in [(0, 50), (1, 10), (2, 40), (3, 20), (4, 30)] {
+            queue.push(entry(id, deadline)).unwrap();
+        }
+        assert_eq!(queue.next_deadline(), Some(10));
+
+        assert!(queue.pop_expired(5).is_none());
+        let expired = core::iter::from_fn(|| queue.pop_expired(35))
+            .map(|x| x.deadline)
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(expired, [10, 20, 30]);
+        assert_eq!(queue.next_deadline(), Some(40));
+        assert_eq!(queue.pop_expired(u64::MAX).map(|x| x.deadline), Some(40));
+        assert_eq!(queue.pop_expired(u64::MAX).map(|x| x.deadline), Some(50));
+        assert!(queue.next_deadline().is_none());
+    }
+
+    #[test]
+    fn test_remove_timer() {
+        let mut queue = TimerQueue::<8>::new();
+        for (id, deadline) in [(0, 50), (1, 10), (2, 40), (3, 20), (4, 30), (5, 60)] {
+            queue.push(entry(id, deadline)).unwrap();
+        }
+
+        assert_eq!(queue.remove(TimerId(1)).map(|x| x.deadline), Some(10));
+        assert_eq!(queue.remove(TimerId(2)).map(|x| x.deadline), Some(40));
+        assert!(queue.remove(TimerId(2)).is_none());
+
+        let expired = core::iter::from_fn(|| queue.pop_expired(u64::MAX))
+            .map(|x| x.id.0)
+            .collect::<std::vec::Vec<_>>();
+        assert_eq!(expired, [3, 4, 0, 5]);
+    }
+
+    #[test]
+    fn test_queue_full() {
+        let mut queue = TimerQueue::<2>::new();
+        queue.push(entry(0, 10)).unwrap();
+        queue.push(entry(1, 20)).unwrap();
+        assert_eq!(queue.push(entry(2, 5)), Err(QueueFull));
+        assert_eq!(queue.next_deadline(), Some(10));
+    }
+}
@@ -0,0 +1,151 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Time Stamp Counter (TSC) Module
+//!
+//! The TSC module registers the time stamp counter of the processor as clock source of [`common::time`],
+//! if it is invariant, meaning it runs at a constant rate in all power states.
+//!
+//! The frequency of the TSC is calibrated against the clock source registered before, usually the HPET.
+//! Without one, the frequency reported by CPUID is used, or it is calibrated against the legacy PIT.
+//!
+#![no_std]
+
+mod pit;
+
+use common::sync::SyncOnceCell;
+use common::time::ClockSource;
+use core::arch::x86_64::{__cpuid, _rdtsc};
+use log::{debug, info, warn};
+use microdragon_interface::macros::init;
+use microdragon_interface::ModuleInterface;
+
+/// Rating of the TSC clock source, it's the fastest to read.
+const RATING: u32 = 300;
+
+/// Duration of the calibration in nanoseconds.
+const CALIBRATION_NANOSECONDS: u64 = 10_000_000;
+
+/// The CPUID leaf reporting the ratio between the TSC and the core crystal clock.
+const CPUID_TSC_LEAF: u32 = 0x15;
+
+/// The CPUID leaf reporting the processor base frequency.
+const CPUID_FREQUENCY_LEAF: u32 = 0x16;
+
+/// The CPUID leaf reporting whether the TSC is invariant.
+const CPUID_POWER_MANAGEMENT_LEAF: u32 = 0x8000_0007;
+
+/// Bit in EDX of [`CPUID_POWER_MANAGEMENT_LEAF`] reporting an invariant TSC.
+const INVARIANT_TSC: u32 = 1 << 8;
+
+static TSC: SyncOnceCell<Tsc> = SyncOnceCell::new();
+
+struct Tsc {
+    /// The frequency of the TSC in Hz.
+    frequency: u64,
+}
+
+impl ClockSource for Tsc {
+    fn name(&self) -> &'static str {
+        "tsc"
+    }
+
+    fn rating(&self) -> u32 {
+        RATING
+    }
+
+    fn frequency(&self) -> u64 {
+        self.frequency
+    }
+
+    fn read(&self) -> u64 {
+        read()
+    }
+}
+
+/// Entrypoint to the TSC module.
+/// Interrupts have to be disabled while this is run.
+#[init]
+pub fn init(_: &ModuleInterface) {
+    if TSC.is_initialized() {
+        warn!("TSC Kernel Module already initialized");
+        return;
+    }
+
+    if !is_invariant() {
+        info!("TSC not invariant, not used as clock source");
+        return;
+    }
+
+    let frequency = calibrate();
+    if frequency == 0 {
+        warn!("TSC frequency couldn't be determined");
+        return;
+    }
+    info!("TSC running at {} Hz", frequency);
+
+    let tsc = TSC.get_or_init(|| Tsc { frequency });
+    if common::time::register_clock_source(tsc) {
+        info!("TSC used as clock source");
+    }
+}
+
+/// Reads the time stamp counter of the current processor.
+pub fn read() -> u64 {
+    // Safety: Reading the TSC has no side effects.
+    unsafe { _rdtsc() }
+}
+
+/// Returns whenever the TSC runs at a constant rate in all power states.
+fn is_invariant() -> bool {
+    __cpuid(0x8000_0000).eax >= CPUID_POWER_MANAGEMENT_LEAF
+        && __cpuid(CPUID_POWER_MANAGEMENT_LEAF).edx & INVARIANT_TSC != 0
+}
+
+/// Determines the frequency of the TSC in Hz.
+fn calibrate() -> u64 {
+    if let Some(start) = common::time::monotonic_nanoseconds() {
+        let source = common::time::clock_source().map_or("", |x| x.name());
+        debug!("Calibrating TSC against {}", source);
+
+        let start_tsc = read();
+        let mut now = start;
+        while now - start < CALIBRATION_NANOSECONDS {
+            core::hint::spin_loop();
+            now = common::time::monotonic_nanoseconds().unwrap_or(now);
+        }
+        let ticks = read() - start_tsc;
+        return (ticks as u128 * 1_000_000_000 / (now - start) as u128) as u64;
+    }
+
+    if let Some(frequency) = cpuid_frequency() {
+        debug!("TSC frequency reported by CPUID");
+        return frequency;
+    }
+
+    debug!("Calibrating TSC against the PIT");
+    // Safety: Nothing else uses the PIT, the kernel doesn't use it as interrupt source.
+    unsafe { pit::calibrate(CALIBRATION_NANOSECONDS, read) }
+}
+
+/// Returns the TSC frequency reported by CPUID, if the processor reports it.
+fn cpuid_frequency() -> Option<u64> {
+    let max_leaf = __cpuid(0).eax;
+    if max_leaf >= CPUID_TSC_LEAF {
+        let leaf = __cpuid(CPUID_TSC_LEAF);
+        // EBX/EAX is the ratio of the TSC to the crystal clock, ECX the crystal clock frequency.
+        if leaf.eax != 0 && leaf.ebx != 0 && leaf.ecx != 0 {
+            return Some(leaf.ecx as u64 * leaf.ebx as u64 / leaf.eax as u64);
+        }
+    }
+
+    if max_leaf >= CPUID_FREQUENCY_LEAF {
+        // The base frequency in MHz, which the TSC runs at.
+        let base = __cpuid(CPUID_FREQUENCY_LEAF).eax & 0xFFFF;
+        if base != 0 {
+            return Some(base as u64 * 1_000_000);
+        }
+    }
+
+    None
+}
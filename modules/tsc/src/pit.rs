@@ -0,0 +1,62 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! Calibration against channel 2 of the legacy Programmable Interval Timer (PIT).
+use core::arch::asm;
+
+/// The frequency of the PIT in Hz.
+const FREQUENCY: u64 = 1_193_182;
+
+const CHANNEL_2_DATA: u16 = 0x42;
+const COMMAND: u16 = 0x43;
+
+/// Port controlling the gate of channel 2 and reporting its output.
+const CHANNEL_2_CONTROL: u16 = 0x61;
+
+/// Bit in [`CHANNEL_2_CONTROL`] enabling the gate of channel 2.
+const GATE: u8 = 1 << 0;
+
+/// Bit in [`CHANNEL_2_CONTROL`] connecting channel 2 to the PC speaker.
+const SPEAKER: u8 = 1 << 1;
+
+/// Bit in [`CHANNEL_2_CONTROL`] reporting the output of channel 2.
+const OUTPUT: u8 = 1 << 5;
+
+/// Command selecting channel 2, low then high byte access and mode 0, raising the output after the count.
+const CHANNEL_2_ONE_SHOT: u8 = 0b1011_0000;
+
+/// Counts how often `counter` increments during `nanoseconds` measured by the PIT, and returns its frequency in Hz.
+///
+/// ## Safety
+///
+/// The system has to have a PIT and nothing else may use channel 2.
+pub unsafe fn calibrate(nanoseconds: u64, counter: fn() -> u64) -> u64 {
+    let count = (FREQUENCY * nanoseconds / 1_000_000_000).min(u16::MAX as u64) as u16;
+
+    let control = read(CHANNEL_2_CONTROL) & !(SPEAKER | GATE);
+    write(CHANNEL_2_CONTROL, control);
+    write(COMMAND, CHANNEL_2_ONE_SHOT);
+    write(CHANNEL_2_DATA, count as u8);
+    write(CHANNEL_2_DATA, (count >> 8) as u8);
+
+    // The count starts when the gate is enabled.
+    write(CHANNEL_2_CONTROL, control | GATE);
+    let start = counter();
+    while read(CHANNEL_2_CONTROL) & OUTPUT == 0 {
+        core::hint::spin_loop();
+    }
+    let ticks = counter() - start;
+    write(CHANNEL_2_CONTROL, control);
+
+    ticks * FREQUENCY / count as u64
+}
+
+unsafe fn read(port: u16) -> u8 {
+    let value;
+    asm!("IN AL, DX", in("dx") port, out("al") value, options(nomem, nostack, preserves_flags));
+    value
+}
+
+unsafe fn write(port: u16, value: u8) {
+    asm!("OUT DX, AL", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+}
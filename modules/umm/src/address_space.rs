@@ -0,0 +1,218 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+use common::addr::{PhysAddr, VirtAddr};
+use common::memory::{
+    get_memory_info, DIRECT_MAPPING_LEVEL_3_PAGE_TABLE, DIRECT_MAPPING_START, KERNEL_DYNAMIC_START,
+    KERNEL_LEVEL_3_PAGE_TABLE,
+};
+use common::paging::{self, Mapper, Page, PageTable, PageTableFlags, PhysFrame};
+use kmm::MapError;
+use pmm::{FrameSize, GlobalFrameAllocator};
+
+/// Access permissions of a userspace page.
+/// Pages are never writable and executable at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protection {
+    /// The page can only be read.
+    Read,
+
+    /// The page can be read and written.
+    ReadWrite,
+
+    /// The page can be read and executed.
+    ReadExecute,
+}
+
+impl Protection {
+    /// Returns the page table flags for this protection.
+    fn flags(self) -> PageTableFlags {
+        match self {
+            Protection::Read => PageTableFlags::USER,
+            Protection::ReadWrite => PageTableFlags::USER | PageTableFlags::WRITABLE,
+            Protection::ReadExecute => PageTableFlags::USER | PageTableFlags::EXECUTABLE,
+        }
+    }
+}
+
+/// An address space, owning the page tables of the userspace area.
+/// The kernel areas are shared with the kernel page tables and mapped in every address space.
+///
+/// Only the page tables are owned, the frames mapped into the address space have to be managed by the caller.
+pub struct AddressSpace {
+    /// Physical address of the top-level page table.
+    root: PhysAddr,
+}
+
+impl AddressSpace {
+    /// Creates a new address space with an empty userspace area.
+    /// Returns `None` if there is not enough physical memory for the page tables.
+    ///
+    /// Panics if the kernel memory manager (KMM) isn't initialized.
+    pub fn new() -> Option<Self> {
+        let direct = *DIRECT_MAPPING_LEVEL_3_PAGE_TABLE
+            .get()
+            .expect("KMM not initialized");
+        let kernel = *KERNEL_LEVEL_3_PAGE_TABLE
+            .get()
+            .expect("KMM not initialized");
+
+        let root = create_table()?;
+        let address_space = AddressSpace { root };
+
+        // With 5 levels, the kernel areas share the last level 4 table with the top of the userspace area.
+        let level_4 = if get_memory_info().highest_page_table_level == 5 {
+            let level_4 = create_table()?;
+            // Safety: The root table was just created and isn't referenced anywhere else.
+            let root = unsafe { table(root) };
+            root[DIRECT_MAPPING_START.p5_index()].set_table(level_4, PageTableFlags::USER);
+            level_4
+        } else {
+            root
+        };
+
+        // Safety: The level 4 table was just created and isn't referenced anywhere else.
+        let level_4 = unsafe { table(level_4) };
+        level_4[DIRECT_MAPPING_START.p4_index()].set_table(direct, PageTableFlags::empty());
+        level_4[KERNEL_DYNAMIC_START.p4_index()].set_table(kernel, PageTableFlags::empty());
+
+        Some(address_space)
+    }
+
+    /// Returns the physical address of the top-level page table.
+    pub fn page_table(&self) -> PhysAddr {
+        self.root
+    }
+
+    /// Returns whenever this is the active address space.
+    pub fn is_active(&self) -> bool {
+        paging::active_page_table() == self.root
+    }
+
+    /// Switches to this address space.
+    ///
+    /// ## Safety
+    ///
+    /// The address space has to stay alive while it's active.
+    pub unsafe fn activate(&self) {
+        if !self.is_active() {
+            paging::set_active_page_table(self.root);
+        }
+    }
+
+    /// Maps a 4 KiB page inside the userspace area to the given frame.
+    pub fn map(
+        &mut self,
+        page: Page,
+        frame: PhysFrame,
+        protection: Protection,
+    ) -> Result<(), MapError> {
+        assert_user_page(page);
+        self.mapper()
+            .map(page, frame, protection.flags(), &mut GlobalFrameAllocator)
+    }
+
+    /// Unmaps a 4 KiB page inside the userspace area.
+    /// Returns the frame the page was mapped to or `None` if it wasn't mapped.
+    pub fn unmap(&mut self, page: Page) -> Option<PhysFrame> {
+        assert_user_page(page);
+
+        let frame = self.mapper().unmap(page).ok()?;
+        self.flush(page);
+        Some(frame)
+    }
+
+    /// Changes the protection of a mapped 4 KiB page inside the userspace area.
+    pub fn protect(&mut self, page: Page, protection: Protection) -> Result<(), MapError> {
+        assert_user_page(page);
+
+        self.mapper().update_flags(page, protection.flags())?;
+        self.flush(page);
+        Ok(())
+    }
+
+    /// Returns the physical address `virt` is mapped to in this address space.
+    pub fn translate(&self, virt: VirtAddr) -> Option<PhysAddr> {
+        if virt >= DIRECT_MAPPING_START {
+            return None;
+        }
+
+        self.mapper().translate(virt)
+    }
+
+    /// Returns a mapper for the page tables of this address space.
+    fn mapper(&self) -> Mapper {
+        let levels = get_memory_info().highest_page_table_level;
+        // Safety: The tables of the userspace area are owned by this address space
+        // and the kernel areas are never modified through it.
+        unsafe { Mapper::new(self.root, levels, DIRECT_MAPPING_START.as_u64()) }
+    }
+
+    /// Flushes the TLB entry of `page` if this address space is active.
+    fn flush(&self, page: Page) {
+        if self.is_active() {
+            paging::flush(page.start_address());
+        }
+    }
+}
+
+impl Drop for AddressSpace {
+    fn drop(&mut self) {
+        assert!(!self.is_active(), "Dropping the active address space");
+
+        let levels = get_memory_info().highest_page_table_level;
+        // Safety: The address space isn't active, so nothing references the tables of the userspace area anymore.
+        unsafe { free_table(self.root, levels, true) };
+    }
+}
+
+/// Allocates an empty page table.
+fn create_table() -> Option<PhysAddr> {
+    let address = pmm::allocate_frame()?;
+    // Safety: The frame was just allocated, so nothing else references it.
+    unsafe { table(address) }.zero();
+    Some(address)
+}
+
+/// Returns the page table at the physical address, accessed through the direct mapping area.
+///
+/// ## Safety
+///
+/// `address` has to point to a page table, that isn't referenced anywhere else at the same time.
+unsafe fn table<'a>(address: PhysAddr) -> &'a mut PageTable {
+    PageTable::from_physical(address, DIRECT_MAPPING_START.as_u64())
+}
+
+/// Frees the page table of the given level and all lower level tables referenced by it.
+/// The tables of the kernel areas are skipped, if `kernel` is set and the table contains them.
+///
+/// ## Safety
+///
+/// The table can't be in use anymore.
+unsafe fn free_table(address: PhysAddr, level: u8, kernel: bool) {
+    if level > 1 {
+        let kernel_index = DIRECT_MAPPING_START.page_table_index(level);
+        for (index, entry) in table(address).iter().enumerate() {
+            if !entry.is_table(level) {
+                continue;
+            }
+
+            let contains_kernel = kernel && index >= kernel_index;
+            if contains_kernel && level == 4 {
+                continue;
+            }
+            free_table(entry.address(), level - 1, contains_kernel);
+        }
+    }
+
+    pmm::free(address, FrameSize::Size4KiB);
+}
+
+/// Panics if `page` isn't a page inside the userspace area.
+fn assert_user_page(page: Page) {
+    assert!(
+        page.start_address() < DIRECT_MAPPING_START,
+        "{:?} is not a page inside the userspace area",
+        page
+    );
+}
@@ -0,0 +1,55 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//! # Userspace Memory Manager (UMM) Module
+//!
+//! The UMM manages the userspace area described in [`common::memory`].
+//! Every [`AddressSpace`] owns the page tables of the userspace area,
+//! while the page tables of the kernel areas are shared with the kernel memory manager (KMM).
+//!
+//! Switching to an address space drops the remaining mappings of the bootloader,
+//! so only the kernel areas are accessible afterwards.
+//!
+#![no_std]
+
+mod address_space;
+
+pub use address_space::*;
+pub use kmm::MapError;
+
+use common::addr::{PhysAddr, VirtAddr};
+use common::memory::{get_memory_info, DIRECT_MAPPING_START, KERNEL_LEVEL_3_PAGE_TABLE};
+use common::paging::{self, Mapper};
+use log::{info, warn};
+use microdragon_interface::macros::init;
+use microdragon_interface::ModuleInterface;
+
+/// Entrypoint to the UMM module.
+#[init]
+pub fn init(_: &ModuleInterface) {
+    if !KERNEL_LEVEL_3_PAGE_TABLE.is_initialized() {
+        warn!("UMM Kernel Module needs the KMM Kernel Module to be initialized");
+        return;
+    }
+
+    info!("UMM ready");
+}
+
+/// Converts a virtual address inside the userspace area to a physical address, by walking the page tables of the active address space.
+#[export_name = "__internal_virtual_to_physical_user"]
+pub fn virtual_to_physical_user(virt: VirtAddr) -> Option<PhysAddr> {
+    if virt >= DIRECT_MAPPING_START || !KERNEL_LEVEL_3_PAGE_TABLE.is_initialized() {
+        return None;
+    }
+
+    let levels = get_memory_info().highest_page_table_level;
+    // Safety: The mapper is only used to read the active page tables.
+    let mapper: Mapper = unsafe {
+        Mapper::new(
+            paging::active_page_table(),
+            levels,
+            DIRECT_MAPPING_START.as_u64(),
+        )
+    };
+    mapper.translate(virt)
+}
@@ -30,6 +30,11 @@ pub struct BuildArguments {
     #[arg(short, long, default_value_t)]
     pub release: bool,
 
+    /// Shuts the machine down once the kernel booted and reboots it on panics, so automated runs exit.
+    /// On x86_64, `run` fails if the kernel reported a panic through QEMU's `isa-debug-exit` device.
+    #[arg(long)]
+    pub exit: bool,
+
     /// List of built-in modules to include.
     #[arg(short, long, default_values_t = modules::default_modules())]
     pub modules: Vec<ModuleInfo>,
@@ -59,12 +64,15 @@ impl BuildArguments {
             None
         };
 
-        cmd!(
+        let mut command = cmd!(
             ctx.shell(),
             "cargo rustc --target {target} --package {bootloader} {release...} -- {args...}"
         )
-        .env("MICRODRAGON_RUNNER", runner)
-        .run()?;
+        .env("MICRODRAGON_RUNNER", runner);
+        if self.exit {
+            command = command.env("MICRODRAGON_EXIT", "1");
+        }
+        command.run()?;
 
         Ok(())
     }
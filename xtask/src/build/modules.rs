@@ -38,5 +38,17 @@ pub fn build_modules(build: &BuildArguments, ctx: &CommandContext) -> Result<Vec
 }
 
 pub fn default_modules() -> Vec<ModuleInfo> {
-    vec![ModuleInfo::new("acpi"), ModuleInfo::new("logging")]
+    vec![
+        ModuleInfo::new("acpi"),
+        ModuleInfo::new("logging"),
+        ModuleInfo::new("idt"),
+        ModuleInfo::new("pmm"),
+        ModuleInfo::new("kmm"),
+        ModuleInfo::new("umm"),
+        ModuleInfo::new("heap"),
+        ModuleInfo::new("apic"),
+        ModuleInfo::new("hpet_module"),
+        ModuleInfo::new("tsc"),
+        ModuleInfo::new("timer"),
+    ]
 }
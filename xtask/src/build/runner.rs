@@ -16,10 +16,16 @@ const PREAMBLE: &str = "// This Source Code Form is subject to the terms of the
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-fn run_modules(interface: &ModuleInterface) {
 ";
 
-const APPENDIX: &str = "}
+const MODULES_START: &str = "fn run_modules(interface: &ModuleInterface) {
+";
+
+const CPU_MODULES_START: &str = "
+fn run_cpu_modules(interface: &ModuleInterface, cpu: &::microdragon_interface::smp::CpuInfo) {
+";
+
+const FUNCTION_END: &str = "}
 ";
 
 pub fn generate_runner(build: &BuildArguments, ctx: &CommandContext) -> Result<PathBuf> {
@@ -35,63 +41,70 @@ pub fn generate_runner(build: &BuildArguments, ctx: &CommandContext) -> Result<P
         .collect::<HashSet<String>>();
 
     let mut infos = Vec::new();
+    let mut cpu_infos = Vec::new();
     for package in metadata
         .packages
         .iter()
         .filter(|x| modules.contains(&x.name))
     {
-        let Some(constructors) = package
+        let Some(metadata) = package
             .metadata
             .as_object()
             .and_then(|x| x.get("microdragon"))
             .and_then(|x| x.as_object())
-            .and_then(|x| x.get("constructors"))
-            .and_then(|x| x.as_array())
         else {
             continue;
         };
 
-        for constructor in constructors.iter().filter_map(|x| x.as_object()) {
-            let Some(path) = constructor.get("path").and_then(|x| x.as_str()) else {
-                warn!(
-                    "Constructor in package {} is missing it's path",
-                    package.name
-                );
-                continue;
-            };
-
-            let Some(order) = constructor.get("order").and_then(|x| x.as_u64()) else {
-                warn!(
-                    "Constructor {} in package {} is missing it's order",
-                    path, package.name
-                );
+        for (key, infos) in [
+            ("constructors", &mut infos),
+            ("cpu_constructors", &mut cpu_infos),
+        ] {
+            let Some(constructors) = metadata.get(key).and_then(|x| x.as_array()) else {
                 continue;
             };
 
-            let cfg = constructor
-                .get("cfg")
-                .and_then(|x| x.as_str())
-                .map(|x| x.to_string());
-
-            infos.push(ConstructorInfo {
-                function: format!("::{}::{}", package.name, path),
-                order,
-                cfg,
-            });
+            for constructor in constructors.iter().filter_map(|x| x.as_object()) {
+                let Some(path) = constructor.get("path").and_then(|x| x.as_str()) else {
+                    warn!(
+                        "Constructor in package {} is missing it's path",
+                        package.name
+                    );
+                    continue;
+                };
+
+                let Some(order) = constructor.get("order").and_then(|x| x.as_u64()) else {
+                    warn!(
+                        "Constructor {} in package {} is missing it's order",
+                        path, package.name
+                    );
+                    continue;
+                };
+
+                let cfg = constructor
+                    .get("cfg")
+                    .and_then(|x| x.as_str())
+                    .map(|x| x.to_string());
+
+                infos.push(ConstructorInfo {
+                    function: format!("::{}::{}", package.name, path),
+                    order,
+                    cfg,
+                });
+            }
         }
     }
 
     let mut runner = String::from(PREAMBLE);
 
-    infos.sort_by_key(|x| x.order);
-    for info in &infos {
-        if let Some(cfg) = &info.cfg {
-            writeln!(&mut runner, "#[cfg({})]", cfg)?;
-        }
-        writeln!(&mut runner, "    {}(interface);", info.function)?;
-    }
+    runner.push_str(MODULES_START);
+    write_constructors(&mut runner, &mut infos, "interface")?;
+    runner.push_str(FUNCTION_END);
 
-    runner.push_str(APPENDIX);
+    // Constructors run on each application processor, once all constructors ran on the bootstrap processor.
+    runner.push_str(CPU_MODULES_START);
+    write_constructors(&mut runner, &mut cpu_infos, "interface, cpu")?;
+    runner.push_str(FUNCTION_END);
 
     let path = ctx.target_directory().join("runner.rs");
     fs::write(&path, runner)?;
@@ -99,6 +112,22 @@ pub fn generate_runner(build: &BuildArguments, ctx: &CommandContext) -> Result<P
     Ok(path)
 }
 
+fn write_constructors(
+    runner: &mut String,
+    infos: &mut [ConstructorInfo],
+    arguments: &str,
+) -> Result<()> {
+    infos.sort_by_key(|x| x.order);
+    for info in infos.iter() {
+        if let Some(cfg) = &info.cfg {
+            writeln!(runner, "#[cfg({})]", cfg)?;
+        }
+        writeln!(runner, "    {}({});", info.function, arguments)?;
+    }
+
+    Ok(())
+}
+
 struct ConstructorInfo {
     function: String,
     order: u64,
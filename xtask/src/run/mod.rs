@@ -25,6 +25,10 @@ pub struct RunArguments {
     #[arg(short, long, default_value_t)]
     firmware: Firmware,
 
+    /// Number of processors of the VM.
+    #[arg(long, default_value_t = 4)]
+    cpus: u32,
+
     /// Does not launch the debugger.
     #[arg(long)]
     no_debug: bool,
@@ -54,6 +58,20 @@ impl RunArguments {
         };
         let extra = &self.args;
         let sysroot = ctx.sysroot_directory();
+        let cpus = self.cpus.to_string();
+        default_args.push("-smp");
+        default_args.push(&cpus);
+
+        if self.build.exit {
+            // Rebooting on panics exits QEMU as well.
+            default_args.push("-no-reboot");
+        }
+        // The kernel reports panics through the `isa-debug-exit` device, which only exists on x86_64.
+        // QEMU then exits with a status other than 0, failing the command, while shutting down exits with 0.
+        if self.build.exit && self.build.target == Target::X86_64 {
+            default_args.push("-device");
+            default_args.push("isa-debug-exit,iobase=0xf4,iosize=0x04");
+        }
 
         if !self.no_debug {
             default_args.push("-gdb");